@@ -0,0 +1,213 @@
+//! Dry run of a fire sequence definition on the build machine:
+//!
+//! ```text
+//! cd host && cargo run --bin simulate_sequence -- ../sequence.txt
+//! ```
+//!
+//! The definition goes through the firmware's own parser and validation,
+//! with the limits of `config.rs` (servos at their `SERVOS` calibration), so
+//! it is accepted or rejected exactly as `cmd/sequence/load` would. Prints
+//! the timeline from the FIRE command with the servos, fire trigger and
+//! signal light after every step; exits with status 1 if the stand would
+//! reject the definition. `-` reads it from stdin.
+
+use std::io::{self, Read as _};
+use std::process::ExitCode;
+use std::{env, fs};
+
+use mainboard_host::config::{FIRE_COUNTDOWN_S, SERVOS};
+use mainboard_host::mqtt::commands::servo::ServoCommand;
+use mainboard_host::sequence::limits;
+use mainboard_host::sequence::script::{Action, Sequence};
+use mainboard_host::servo::calibration::ServoCalibration;
+use mainboard_host::servo::profile::Profile;
+use mainboard_host::servo::ServoConfig;
+use mainboard_host::signal_light::SignalLightConfig;
+
+/// A servo and its last move, which starts at `start_ms` relative to T-0.
+struct Servo {
+    config: &'static ServoConfig,
+    start_ms: i32,
+    profile: Profile,
+}
+
+impl Servo {
+    /// At rest where the stand leaves it, closed.
+    fn new(config: &'static ServoConfig) -> Self {
+        let closed = config.calibration.closed_degrees;
+        Self {
+            config,
+            start_ms: i32::MIN,
+            profile: Profile::new(closed, closed, 1, 1),
+        }
+    }
+
+    fn position(&self, at_ms: i32) -> u16 {
+        self.profile.position(at_ms.abs_diff(self.start_ms))
+    }
+
+    fn end_ms(&self) -> i64 {
+        self.start_ms as i64 + self.profile.duration_ms() as i64
+    }
+
+    fn command(&mut self, command: ServoCommand, at_ms: i32) {
+        let from = self.position(at_ms);
+        self.profile = self.config.profile(&self.config.calibration, command, from);
+        self.start_ms = at_ms;
+    }
+
+    fn describe(&self, at_ms: i32) -> String {
+        let calibration = &self.config.calibration;
+        let target = position_name(calibration, self.profile.target());
+        if self.end_ms() <= at_ms as i64 {
+            format!("{} {}", self.config.name, target)
+        } else {
+            format!("{} to {} by T{:+}", self.config.name, target, self.end_ms())
+        }
+    }
+}
+
+fn position_name(calibration: &ServoCalibration, position: u16) -> String {
+    if position == calibration.open_degrees {
+        String::from("OPEN")
+    } else if position == calibration.closed_degrees {
+        String::from("CLOSED")
+    } else {
+        position.to_string()
+    }
+}
+
+fn lamps(light: SignalLightConfig) -> String {
+    let lamps = [
+        (light.green, "GREEN"),
+        (light.red, "RED"),
+        (light.yellow, "YELLOW"),
+        (light.blue, "BLUE"),
+        (light.white, "WHITE"),
+        (light.buzzer, "BUZZER"),
+    ];
+    let on: Vec<_> = lamps
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect();
+    if on.is_empty() {
+        String::from("off")
+    } else {
+        on.join(" ")
+    }
+}
+
+fn channels(mask: u8) -> String {
+    let channels: Vec<_> = (0..8)
+        .filter(|channel| mask & (1 << channel) != 0)
+        .map(|channel| channel.to_string())
+        .collect();
+    channels.join(" ")
+}
+
+/// The action as it would be written, channels and lamps spelled out.
+fn describe(action: Action) -> String {
+    match action {
+        Action::Servo { servo, command } => {
+            let name = SERVOS[servo as usize].name;
+            match command {
+                ServoCommand::Open => format!("SERVO {name} OPEN"),
+                ServoCommand::Close => format!("SERVO {name} CLOSE"),
+                ServoCommand::Move {
+                    position,
+                    rate: None,
+                } => format!("SERVO {name} MOVE {position}"),
+                ServoCommand::Move {
+                    position,
+                    rate: Some(rate),
+                } => format!("SERVO {name} MOVE {position} {rate}"),
+            }
+        }
+        Action::Trigger { mask, .. } => format!("{} {}", action.as_str(), channels(mask)),
+        Action::Light(light) => format!("LIGHT {}", lamps(light)),
+        _ => String::from(action.as_str()),
+    }
+}
+
+fn simulate(sequence: &Sequence) {
+    let fire_ms = -(sequence.countdown_s(FIRE_COUNTDOWN_S) as i32 * 1000);
+    let mut servos: Vec<_> = SERVOS.iter().map(Servo::new).collect();
+    let mut trigger = 0u8;
+    let mut light = None;
+    let mut manual_servo = false;
+    let mut camera_presses = 0;
+
+    println!("FIRE at T{fire_ms:+}, countdown to T-0");
+    for (index, step) in sequence.steps().iter().enumerate() {
+        match step.action {
+            Action::Servo { servo, command } => {
+                servos[servo as usize].command(command, step.at_ms);
+            }
+            Action::ServoManual => manual_servo = true,
+            Action::Trigger { mask, on: true } => trigger |= mask,
+            Action::Trigger { mask, on: false } => trigger &= !mask,
+            Action::Camera => camera_presses += 1,
+            Action::Light(config) => light = Some(config),
+            Action::End => {}
+        }
+
+        let servo_states: Vec<_> = servos
+            .iter()
+            .map(|servo| servo.describe(step.at_ms))
+            .collect();
+        println!(
+            "Step {:>2} {:>7} ({:>6} ms after FIRE) {:<28} servo {} | trigger {:#04x} | light {}",
+            index + 1,
+            format!("T{:+}", step.at_ms),
+            step.at_ms - fire_ms,
+            describe(step.action),
+            servo_states.join(", "),
+            trigger,
+            light.map_or(String::from("state pattern"), lamps),
+        );
+    }
+
+    let end_ms = sequence.last_ms().max(0);
+    println!(
+        "POSTFIRE at T{:+}, {} ms after FIRE: trigger released, camera shots: {}",
+        end_ms,
+        end_ms - fire_ms,
+        camera_presses
+    );
+    if manual_servo {
+        println!("cmd/servo/<name> moves the servos from the SERVO MANUAL step until POSTFIRE");
+    }
+}
+
+fn main() -> ExitCode {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: simulate_sequence <definition, - for stdin>");
+        return ExitCode::from(2);
+    };
+    let mut text = Vec::new();
+    let read = match path.as_str() {
+        "-" => io::stdin().read_to_end(&mut text).map(|_| ()),
+        path => fs::read(path).map(|bytes| text = bytes),
+    };
+    if let Err(error) = read {
+        eprintln!("{path}: {error}");
+        return ExitCode::from(2);
+    }
+
+    let limits = limits::configured(|index| SERVOS[index].calibration);
+    match Sequence::parse(&text, &limits) {
+        Ok(sequence) => {
+            simulate(&sequence);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!(
+                "Sequence rejected: line {}: {}",
+                error.line,
+                error.kind.as_str()
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Host build of the hardware-free modules of `mainboard` and the test
+//! stand, so their unit tests run on the build machine:
+//!
+//! ```text
+//! cd host && cargo test
+//! ```
+//!
+//! The firmware only builds for the ESP32-C6. This crate compiles the pure
+//! modules from the firmware sources, with `std`, under the module paths
+//! the firmware gives them, so their `crate::` and `mainboard::` imports
+//! resolve unchanged. Only modules that touch no hardware belong here.
+
+extern crate alloc;
+extern crate self as mainboard;
+
+// ============================================================================
+// MAINBOARD
+// ============================================================================
+
+#[path = "../../src/analog"]
+pub mod analog {
+    pub mod channel;
+    pub mod filter;
+
+    pub use channel::{AnalogChannel, CHANNEL_COUNT};
+}
+
+#[path = "../../src/crash"]
+pub mod crash {
+    pub mod record;
+}
+
+#[path = "../../src/debounce"]
+pub mod debounce {
+    pub mod filter;
+}
+
+#[path = "../../src/fire_trigger.rs"]
+pub mod fire_trigger;
+
+#[path = "../../src/ota"]
+pub mod ota {
+    pub mod error;
+    pub mod image;
+
+    pub use error::OtaError;
+    pub use image::{parse_sha256_hex, SHA256_LEN};
+}
+
+#[path = "../../src/signal_light.rs"]
+pub mod signal_light;
+
+#[path = "../../src/sntp"]
+pub mod sntp {
+    pub mod filter;
+}
+
+#[path = "../../src/watchdog"]
+pub mod watchdog {
+    pub mod registry;
+}
+
+#[path = "../../src/wifi"]
+pub mod wifi {
+    pub mod mdns {
+        #[allow(clippy::new_without_default)]
+        pub mod packet;
+    }
+}
+
+// ============================================================================
+// TEST STAND
+// ============================================================================
+
+#[path = "../../src/bin/test_stand_controller/camera"]
+pub mod camera {
+    pub mod shot;
+}
+
+#[path = "../../src/bin/test_stand_controller/config.rs"]
+pub mod config;
+
+#[path = "../../src/bin/test_stand_controller/heartbeat"]
+pub mod heartbeat {
+    pub mod monitor;
+}
+
+#[path = "../../src/bin/test_stand_controller/interlock"]
+pub mod interlock {
+    pub mod rules;
+}
+
+#[path = "../../src/bin/test_stand_controller/light"]
+pub mod light {
+    #[allow(clippy::new_without_default)]
+    pub mod pattern;
+}
+
+#[path = "../../src/bin/test_stand_controller/mqtt"]
+pub mod mqtt {
+    pub mod codec;
+    pub mod commands {
+        pub mod calibrate;
+        pub mod servo;
+        pub mod state;
+    }
+    pub mod sensors {
+        // `CommandStatusPacket::from_str` predates this crate
+        #[allow(clippy::should_implement_trait)]
+        pub mod status;
+    }
+}
+
+#[path = "../../src/bin/test_stand_controller/sequence"]
+pub mod sequence {
+    pub mod limits;
+    pub mod script;
+}
+
+#[path = "../../src/bin/test_stand_controller/sequencer"]
+pub mod sequencer {
+    pub mod challenge;
+    pub mod machine;
+}
+
+#[path = "../../src/bin/test_stand_controller/servo"]
+pub mod servo {
+    pub mod calibration;
+    pub mod config;
+    #[allow(clippy::new_without_default)]
+    pub mod feedback;
+    pub mod profile;
+
+    pub use config::{ServoConfig, ServoPin};
+}
@@ -0,0 +1,107 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Two-step FIRE: publishes ARM_REQUEST, waits for the ARM_NONCE answer on
+# status/cmd and publishes FIRE with that nonce, plus its HMAC when
+# FIRE_HMAC_KEY is set.
+#
+# The stand rejects FIRE, and aborts the countdown or the burn, without
+# operator heartbeats. A background loop publishes cmd/heartbeat every
+# HEARTBEAT_INTERVAL_S (default 1) from before ARM_REQUEST until status/state
+# leaves COUNTDOWN, HOLD and FIRE, or the FIRE is rejected. Ctrl-C stops the
+# heartbeats, which aborts the burn.
+
+for tool in mosquitto_pub mosquitto_sub; do
+  if ! command -v "$tool" >/dev/null 2>&1; then
+    echo "error: $tool not found in PATH" >&2
+    exit 1
+  fi
+done
+
+HOST="${MQTT_HOST:-${1:-localhost}}"
+
+PORT="${MQTT_PORT:-1883}"
+TIMEOUT_S="${ARM_TIMEOUT_S:-5}"
+HEARTBEAT_INTERVAL_S="${HEARTBEAT_INTERVAL_S:-1}"
+
+auth=()
+
+if [[ -n "${MQTT_USER:-}" ]]; then
+  auth+=( -u "$MQTT_USER" )
+fi
+
+if [[ -n "${MQTT_PASSWORD:-}" ]]; then
+  auth+=( -P "$MQTT_PASSWORD" )
+fi
+
+answer="$(mktemp)"
+sub=""
+heartbeat=""
+watch=""
+trap 'rm -f "$answer"; kill $sub $heartbeat $watch 2>/dev/null || true' EXIT
+
+while true; do
+  mosquitto_pub -h "$HOST" -p "$PORT" ${auth[@]+"${auth[@]}"} -t cmd/heartbeat -m 1 || true
+  sleep "$HEARTBEAT_INTERVAL_S"
+done &
+heartbeat=$!
+# Let the stand see a heartbeat before FIRE is checked
+sleep "$HEARTBEAT_INTERVAL_S"
+
+mosquitto_sub -h "$HOST" -p "$PORT" ${auth[@]+"${auth[@]}"} -t status/cmd -W "$TIMEOUT_S" \
+  | grep --line-buffered -m1 '^ARM_NONCE ' >"$answer" &
+sub=$!
+sleep 0.5
+
+mosquitto_pub -h "$HOST" -p "$PORT" ${auth[@]+"${auth[@]}"} -t cmd/state -m ARM_REQUEST
+
+for _ in $(seq $((TIMEOUT_S * 10))); do
+  [[ -s "$answer" ]] && break
+  sleep 0.1
+done
+
+if [[ ! -s "$answer" ]]; then
+  echo "error: no ARM_NONCE on status/cmd, is the stand ARMED?" >&2
+  exit 1
+fi
+
+nonce="$(cut -d' ' -f2 <"$answer")"
+payload="FIRE $nonce"
+
+if [[ -n "${FIRE_HMAC_KEY:-}" ]]; then
+  mac="$(printf '%s' "$payload" | openssl dgst -sha256 -hmac "$FIRE_HMAC_KEY" | awk '{print $NF}')"
+  payload="$payload $mac"
+fi
+
+# Follow the burn, from the retained state on, to know when to stop
+coproc watch_fd {
+  mosquitto_sub -h "$HOST" -p "$PORT" ${auth[@]+"${auth[@]}"} -v -t status/state -t status/cmd
+}
+watch=$watch_fd_PID
+sleep 0.5
+
+mosquitto_pub -h "$HOST" -p "$PORT" ${auth[@]+"${auth[@]}"} -t cmd/state -m "$payload"
+
+echo "published '$payload' to 'cmd/state' on $HOST:$PORT, sending heartbeats until the burn ends"
+
+started=false
+while read -r topic message <&"${watch_fd[0]}"; do
+  case "$topic $message" in
+    "status/cmd FIRE rejected"*)
+      echo "error: $message" >&2
+      exit 1
+      ;;
+    "status/state COUNTDOWN"* | "status/state HOLD"* | "status/state FIRE"*)
+      started=true
+      ;;
+    status/state*)
+      if $started; then
+        echo "stand in $message, heartbeats stopped"
+        exit 0
+      fi
+      ;;
+  esac
+done
+
+echo "error: lost status/state, heartbeats stopped" >&2
+exit 1
@@ -0,0 +1,55 @@
+//! The analog inputs of the board.
+
+pub const CHANNEL_COUNT: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum AnalogChannel {
+    A0,
+    A1,
+    A2,
+    A3,
+    A4,
+    BatVol,
+    BoostVol,
+}
+
+impl AnalogChannel {
+    pub const ALL: [AnalogChannel; CHANNEL_COUNT] = [
+        AnalogChannel::A0,
+        AnalogChannel::A1,
+        AnalogChannel::A2,
+        AnalogChannel::A3,
+        AnalogChannel::A4,
+        AnalogChannel::BatVol,
+        AnalogChannel::BoostVol,
+    ];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// ADC1 channel, equal to the GPIO number of the input.
+    pub const fn adc_channel(self) -> u8 {
+        match self {
+            AnalogChannel::A0 => 4,
+            AnalogChannel::A1 => 5,
+            AnalogChannel::A2 => 6,
+            AnalogChannel::A3 => 0,
+            AnalogChannel::A4 => 1,
+            AnalogChannel::BatVol => 2,
+            AnalogChannel::BoostVol => 3,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            AnalogChannel::A0 => "a0",
+            AnalogChannel::A1 => "a1",
+            AnalogChannel::A2 => "a2",
+            AnalogChannel::A3 => "a3",
+            AnalogChannel::A4 => "a4",
+            AnalogChannel::BatVol => "battery",
+            AnalogChannel::BoostVol => "boost",
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Continuous ADC1 conversion with the digital controller and GDMA.
+//!
+//! The controller walks the pattern table at a fixed rate and streams type 2
+//! results into a circular list of GDMA descriptors. The driver is polled:
+//! [`ContinuousAdc::drain`] hands out every descriptor the DMA has released
+//! and gives it back. When the DMA finds no free descriptor (the consumer
+//! fell behind) conversion stops with a descriptor error; the driver reports
+//! an [`Overrun`] and restarts from a clean ring.
+//!
+//! The Espressif HAL has no driver for the digital controller yet, so this
+//! programs `APB_SARADC`, `PCR` and the GDMA channel registers directly,
+//! following the ESP-IDF `adc_ll` sequence for the ESP32-C6.
+
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+use esp_hal::analog::adc::Attenuation;
+use esp_hal::efuse::{AdcCalibUnit, Efuse};
+use esp_hal::peripherals::{APB_SARADC, DMA, DMA_CH2, PCR};
+
+use super::pattern::{
+    conversion_period_ns, encode_tables, LineCalibration, PatternEntry, PatternError, CLKM_DIV_NUM,
+    SAMPLE_BYTES,
+};
+
+pub const DESCRIPTOR_COUNT: usize = 8;
+pub const SAMPLES_PER_DESCRIPTOR: usize = 128;
+
+/// GDMA channel reserved for the ADC, matches [`DMA_CH2`].
+const DMA_CHANNEL: usize = 2;
+/// `PERI_IN_SEL` value of the ADC on the ESP32-C6.
+const GDMA_PERIPHERAL_ADC: u8 = 8;
+/// `SARADC_CLKM_SEL` value of PLL_F80M.
+const CLKM_SEL_PLL_F80M: u8 = 1;
+
+const DESCRIPTOR_OWNER_DMA: u32 = 1 << 31;
+const DESCRIPTOR_SIZE_MASK: u32 = 0xFFF;
+const DESCRIPTOR_LENGTH_SHIFT: u32 = 12;
+
+/// The DMA ran out of free descriptors and samples were lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Overrun;
+
+/// GDMA linked list item.
+#[repr(C, align(4))]
+struct Descriptor {
+    flags: u32,
+    buffer: *mut u8,
+    next: *mut Descriptor,
+}
+
+/// Descriptors and sample buffers; must live in internal RAM.
+pub struct DmaMemory {
+    descriptors: [Descriptor; DESCRIPTOR_COUNT],
+    data: [[u32; SAMPLES_PER_DESCRIPTOR]; DESCRIPTOR_COUNT],
+}
+
+// SAFETY: the raw pointers only point into the same struct
+unsafe impl Send for DmaMemory {}
+
+impl DmaMemory {
+    pub const fn new() -> Self {
+        const EMPTY: Descriptor = Descriptor {
+            flags: 0,
+            buffer: core::ptr::null_mut(),
+            next: core::ptr::null_mut(),
+        };
+        Self {
+            descriptors: [EMPTY; DESCRIPTOR_COUNT],
+            data: [[0; SAMPLES_PER_DESCRIPTOR]; DESCRIPTOR_COUNT],
+        }
+    }
+
+    /// Hand every descriptor to the DMA and close the ring.
+    fn link(&mut self) {
+        let base = addr_of_mut!(self.descriptors) as *mut Descriptor;
+        for index in 0..DESCRIPTOR_COUNT {
+            let descriptor = &mut self.descriptors[index];
+            descriptor.buffer = self.data[index].as_mut_ptr() as *mut u8;
+            // SAFETY: index and (index + 1) % N are within the array
+            descriptor.next = unsafe { base.add((index + 1) % DESCRIPTOR_COUNT) };
+            // SAFETY: plain store to memory the DMA reads
+            unsafe { write_volatile(&mut descriptor.flags, Self::released_flags()) };
+        }
+        fence(Ordering::Release);
+    }
+
+    const fn released_flags() -> u32 {
+        DESCRIPTOR_OWNER_DMA | (SAMPLES_PER_DESCRIPTOR * SAMPLE_BYTES) as u32
+    }
+}
+
+impl Default for DmaMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ContinuousAdc {
+    _dma: DMA_CH2<'static>,
+    memory: &'static mut DmaMemory,
+    next: usize,
+    conversion_period_ns: u32,
+    calibration: LineCalibration,
+}
+
+impl ContinuousAdc {
+    /// Configure the controller for `pattern`, converting every
+    /// `timer_target * 400 ns`. ADC1 must already be powered and calibrated
+    /// for `attenuation` (any oneshot read through the HAL does both).
+    pub fn new(
+        dma: DMA_CH2<'static>,
+        memory: &'static mut DmaMemory,
+        pattern: &[PatternEntry],
+        attenuation: Attenuation,
+        timer_target: u32,
+    ) -> Result<Self, PatternError> {
+        let tables = encode_tables(pattern)?;
+
+        let pcr = PCR::regs();
+        pcr.saradc_clkm_conf().modify(|_, w| unsafe {
+            w.saradc_clkm_sel()
+                .bits(CLKM_SEL_PLL_F80M)
+                .saradc_clkm_div_num()
+                .bits(CLKM_DIV_NUM as u8)
+                .saradc_clkm_div_a()
+                .bits(0)
+                .saradc_clkm_div_b()
+                .bits(1)
+                .saradc_clkm_en()
+                .set_bit()
+        });
+        pcr.gdma_conf()
+            .modify(|_, w| w.gdma_clk_en().set_bit().gdma_rst_en().clear_bit());
+
+        let adc = APB_SARADC::regs();
+        adc.ctrl2().modify(|_, w| w.timer_en().clear_bit());
+        adc.ctrl().modify(|_, w| unsafe {
+            w.xpd_sar_force()
+                .bits(0b11)
+                .wait_arb_cycle()
+                .bits(1)
+                .sar_patt_len()
+                .bits(pattern.len() as u8 - 1)
+        });
+        adc.sar_patt_tab1()
+            .write(|w| unsafe { w.sar_patt_tab1().bits(tables[0]) });
+        adc.sar_patt_tab2()
+            .write(|w| unsafe { w.sar_patt_tab2().bits(tables[1]) });
+        adc.ctrl().modify(|_, w| w.sar_patt_p_clear().set_bit());
+        adc.ctrl().modify(|_, w| w.sar_patt_p_clear().clear_bit());
+        adc.ctrl2().modify(|_, w| unsafe {
+            w.meas_num_limit()
+                .clear_bit()
+                .sar1_inv()
+                .clear_bit()
+                .timer_target()
+                .bits(timer_target as u16)
+        });
+        adc.dma_conf().modify(|_, w| unsafe {
+            w.apb_adc_eof_num()
+                .bits(SAMPLES_PER_DESCRIPTOR as u16)
+                .apb_adc_trans()
+                .set_bit()
+        });
+
+        let mut driver = Self {
+            _dma: dma,
+            memory,
+            next: 0,
+            conversion_period_ns: conversion_period_ns(timer_target),
+            calibration: line_calibration(attenuation),
+        };
+        driver.restart();
+        Ok(driver)
+    }
+
+    /// Exact time between two conversions.
+    pub fn conversion_period_ns(&self) -> u32 {
+        self.conversion_period_ns
+    }
+
+    /// Converts raw codes to millivolts at the pin.
+    pub fn calibration(&self) -> LineCalibration {
+        self.calibration
+    }
+
+    /// Pass every completed DMA word to `sink`, in conversion order. Returns
+    /// the number of words, or [`Overrun`] after restarting conversion;
+    /// words delivered before the overrun are valid.
+    pub fn drain(&mut self, mut sink: impl FnMut(u32)) -> Result<usize, Overrun> {
+        let status = DMA::regs().in_int_ch(DMA_CHANNEL).raw().read();
+        let overrun = status.in_dscr_err().bit_is_set() || status.infifo_ovf().bit_is_set();
+
+        let mut drained = 0;
+        loop {
+            let descriptor = &mut self.memory.descriptors[self.next];
+            // SAFETY: the DMA writes the flags back when it releases the descriptor
+            let flags = unsafe { read_volatile(&descriptor.flags) };
+            if flags & DESCRIPTOR_OWNER_DMA != 0 {
+                break;
+            }
+            fence(Ordering::Acquire);
+
+            let bytes = ((flags >> DESCRIPTOR_LENGTH_SHIFT) & DESCRIPTOR_SIZE_MASK) as usize;
+            let words = (bytes / SAMPLE_BYTES).min(SAMPLES_PER_DESCRIPTOR);
+            for word in &self.memory.data[self.next][..words] {
+                // SAFETY: written by the DMA before it released the descriptor
+                sink(unsafe { read_volatile(word) });
+            }
+            drained += words;
+
+            // SAFETY: see above
+            unsafe { write_volatile(&mut descriptor.flags, DmaMemory::released_flags()) };
+            self.next = (self.next + 1) % DESCRIPTOR_COUNT;
+        }
+
+        if overrun {
+            self.restart();
+            return Err(Overrun);
+        }
+        Ok(drained)
+    }
+
+    /// Stop the timer, reset the controller and the DMA channel, and start
+    /// over with every descriptor free.
+    fn restart(&mut self) {
+        let adc = APB_SARADC::regs();
+        let channel = DMA::regs().ch(DMA_CHANNEL);
+        let interrupts = DMA::regs().in_int_ch(DMA_CHANNEL);
+
+        adc.ctrl2().modify(|_, w| w.timer_en().clear_bit());
+        channel.in_link().modify(|_, w| w.inlink_stop().set_bit());
+        channel.in_conf0().modify(|_, w| w.in_rst().set_bit());
+        channel.in_conf0().modify(|_, w| w.in_rst().clear_bit());
+        adc.dma_conf()
+            .modify(|_, w| w.apb_adc_reset_fsm().set_bit());
+        adc.dma_conf()
+            .modify(|_, w| w.apb_adc_reset_fsm().clear_bit());
+
+        self.memory.link();
+        self.next = 0;
+
+        channel
+            .in_conf1()
+            .modify(|_, w| w.in_check_owner().set_bit());
+        channel
+            .in_peri_sel()
+            .write(|w| unsafe { w.peri_in_sel().bits(GDMA_PERIPHERAL_ADC) });
+        interrupts.clr().write(|w| {
+            w.in_dscr_err()
+                .clear_bit_by_one()
+                .infifo_ovf()
+                .clear_bit_by_one()
+                .in_suc_eof()
+                .clear_bit_by_one()
+                .in_done()
+                .clear_bit_by_one()
+        });
+
+        let first = self.memory.descriptors.as_ptr() as u32;
+        channel
+            .in_link()
+            .modify(|_, w| unsafe { w.inlink_addr().bits(first & 0xF_FFFF) });
+        channel.in_link().modify(|_, w| w.inlink_start().set_bit());
+        adc.ctrl2().modify(|_, w| w.timer_en().set_bit());
+    }
+}
+
+impl Drop for ContinuousAdc {
+    fn drop(&mut self) {
+        APB_SARADC::regs()
+            .ctrl2()
+            .modify(|_, w| w.timer_en().clear_bit());
+        DMA::regs()
+            .ch(DMA_CHANNEL)
+            .in_link()
+            .modify(|_, w| w.inlink_stop().set_bit());
+    }
+}
+
+fn line_calibration(attenuation: Attenuation) -> LineCalibration {
+    match Efuse::rtc_calib_cal_code(AdcCalibUnit::ADC1, attenuation) {
+        Some(cal_code) => LineCalibration {
+            cal_code,
+            cal_mv: Efuse::rtc_calib_cal_mv(AdcCalibUnit::ADC1, attenuation),
+        },
+        None => LineCalibration::NOMINAL[attenuation as usize],
+    }
+}
@@ -0,0 +1,319 @@
+//! Per-channel filter pipeline in fixed point.
+//!
+//! Every frame of a filtered channel goes through, in order:
+//! 1. oversampling: `oversample` consecutive frames are averaged into one
+//!    value;
+//! 2. the median of the last `median` values, which rejects single spikes;
+//! 3. a first order IIR low pass with cutoff `low_pass_mhz`.
+//!
+//! The channel's decimation then keeps the filter output of one frame out of
+//! `decimation`. Values are pin millivolts in Q16.16, rounded on output.
+
+pub const MAX_MEDIAN: u8 = 9;
+
+const FRACTION_BITS: u32 = 16;
+const ONE: u64 = 1 << FRACTION_BITS;
+/// 2π in Q16.16
+const TWO_PI: u64 = 411_775;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterConfig {
+    /// Frames averaged into one value; must divide the channel decimation.
+    pub oversample: u16,
+    /// Window of the median filter in values, odd; 1 disables.
+    pub median: u8,
+    /// Cutoff of the low pass in millihertz; 0 disables.
+    pub low_pass_mhz: u32,
+}
+
+impl FilterConfig {
+    /// Passes every frame through unchanged.
+    pub const NONE: Self = Self {
+        oversample: 1,
+        median: 1,
+        low_pass_mhz: 0,
+    };
+
+    pub const fn is_none(&self) -> bool {
+        self.oversample == 1 && self.median == 1 && self.low_pass_mhz == 0
+    }
+
+    pub const fn is_valid(&self) -> bool {
+        self.oversample > 0 && self.median % 2 == 1 && self.median <= MAX_MEDIAN
+    }
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Smoothing factor of a first order low pass in Q16.16, for values spaced
+/// by `period_ns`: `alpha = w dt / (1 + w dt)` with `w = 2π cutoff`, the
+/// backward Euler discretization of an RC filter. Never 0, so the output
+/// always follows the input eventually.
+pub fn low_pass_alpha(cutoff_mhz: u32, period_ns: u64) -> u32 {
+    // 2π [Q16] * mHz * ns = w dt [Q16] * 1e12
+    let w_dt = TWO_PI as u128 * cutoff_mhz as u128 * period_ns as u128;
+    let one = ONE as u128 * 1_000_000_000_000;
+    ((w_dt << FRACTION_BITS) / (one + w_dt)).max(1) as u32
+}
+
+/// Filter state of one channel.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelFilter {
+    oversample: u16,
+    median: u8,
+    /// Q16.16, `None` disables the low pass
+    alpha: Option<u32>,
+    sum: u32,
+    count: u16,
+    window: [u32; MAX_MEDIAN as usize],
+    window_len: u8,
+    window_next: u8,
+    /// Q16.16 output, `None` until the first value
+    state: Option<u32>,
+    last_input: u16,
+}
+
+impl ChannelFilter {
+    /// `frame_period_ns` is the spacing of the frames pushed in.
+    pub fn new(config: &FilterConfig, frame_period_ns: u32) -> Self {
+        let value_period_ns = frame_period_ns as u64 * config.oversample.max(1) as u64;
+        Self {
+            oversample: config.oversample.max(1),
+            median: config.median.clamp(1, MAX_MEDIAN),
+            alpha: (config.low_pass_mhz > 0)
+                .then(|| low_pass_alpha(config.low_pass_mhz, value_period_ns)),
+            sum: 0,
+            count: 0,
+            window: [0; MAX_MEDIAN as usize],
+            window_len: 0,
+            window_next: 0,
+            state: None,
+            last_input: 0,
+        }
+    }
+
+    /// Start over, e.g. after samples were lost.
+    pub fn reset(&mut self) {
+        self.sum = 0;
+        self.count = 0;
+        self.window_len = 0;
+        self.window_next = 0;
+        self.state = None;
+    }
+
+    pub fn push(&mut self, millivolts: u16) {
+        self.last_input = millivolts;
+        self.sum += millivolts as u32;
+        self.count += 1;
+        if self.count < self.oversample {
+            return;
+        }
+
+        let mean = ((self.sum as u64) << FRACTION_BITS) / self.oversample as u64;
+        self.sum = 0;
+        self.count = 0;
+
+        let value = self.median(mean as u32);
+        self.state = Some(match (self.state, self.alpha) {
+            (Some(previous), Some(alpha)) => {
+                let step = (value as i64 - previous as i64) * alpha as i64;
+                // Rounded to nearest either way; a shift floors, which
+                // leaves the output short of a rising input
+                let step = (step + step.signum() * (ONE as i64 / 2)) / ONE as i64;
+                (previous as i64 + step) as u32
+            }
+            _ => value,
+        });
+    }
+
+    /// Filtered millivolts; the last input until the first value is
+    /// complete.
+    pub fn output(&self) -> u16 {
+        match self.state {
+            Some(state) => ((state as u64 + ONE / 2) >> FRACTION_BITS).min(u16::MAX as u64) as u16,
+            None => self.last_input,
+        }
+    }
+
+    fn median(&mut self, value: u32) -> u32 {
+        if self.median == 1 {
+            return value;
+        }
+
+        self.window[self.window_next as usize] = value;
+        self.window_next = (self.window_next + 1) % self.median;
+        self.window_len = (self.window_len + 1).min(self.median);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..self.window_len as usize];
+        sorted.sort_unstable();
+        sorted[sorted.len() / 2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1 kHz frames
+    const FRAME_NS: u32 = 1_000_000;
+
+    fn filter(oversample: u16, median: u8, low_pass_mhz: u32) -> ChannelFilter {
+        let config = FilterConfig {
+            oversample,
+            median,
+            low_pass_mhz,
+        };
+        assert!(config.is_valid());
+        ChannelFilter::new(&config, FRAME_NS)
+    }
+
+    fn push_all(filter: &mut ChannelFilter, millivolts: u16, count: usize) {
+        for _ in 0..count {
+            filter.push(millivolts);
+        }
+    }
+
+    #[test]
+    fn alpha_follows_cutoff() {
+        // w dt = 2π 1 Hz 1 ms, alpha = w dt / (1 + w dt) = 0.006244
+        assert_eq!(low_pass_alpha(1_000, 1_000_000), 409);
+        // Far above the sample rate the output follows the input
+        assert!(low_pass_alpha(1_000_000_000, 1_000_000) > ONE as u32 - 20);
+        // Far below it never stops moving
+        assert_eq!(low_pass_alpha(1, 1_000_000), 1);
+        assert!(low_pass_alpha(2_000, 1_000_000) > low_pass_alpha(1_000, 1_000_000));
+    }
+
+    #[test]
+    fn none_passes_through() {
+        let mut filter = ChannelFilter::new(&FilterConfig::NONE, FRAME_NS);
+        for millivolts in [0, 1, 1234, 3300, 1] {
+            filter.push(millivolts);
+            assert_eq!(filter.output(), millivolts);
+        }
+    }
+
+    #[test]
+    fn step_reaches_63_percent_after_one_time_constant() {
+        // 1 Hz cutoff: time constant 1 / 2π s, 159 frames
+        let mut filter = filter(1, 1, 1_000);
+        filter.push(0);
+        push_all(&mut filter, 1000, 159);
+        let output = filter.output();
+        assert!((625..=637).contains(&output), "{output}");
+    }
+
+    #[test]
+    fn ramp_lags_by_time_constant() {
+        let mut filter = filter(1, 1, 1_000);
+        for millivolts in 0..2000 {
+            filter.push(millivolts);
+        }
+        let lag = 1999 - filter.output();
+        assert!((155..=162).contains(&lag), "{lag}");
+    }
+
+    #[test]
+    fn settles_on_rising_and_falling_steps() {
+        // Slowest possible low pass: every step is a fraction of a millivolt
+        let mut filter = filter(1, 1, 1);
+        filter.push(1000);
+        push_all(&mut filter, 2000, 2_000_000);
+        assert_eq!(filter.output(), 2000);
+        push_all(&mut filter, 1000, 2_000_000);
+        assert_eq!(filter.output(), 1000);
+    }
+
+    #[test]
+    fn median_rejects_spikes() {
+        let mut filter = filter(1, 3, 0);
+        push_all(&mut filter, 1000, 3);
+        filter.push(4095);
+        assert_eq!(filter.output(), 1000);
+        filter.push(1000);
+        filter.push(0);
+        assert_eq!(filter.output(), 1000);
+
+        // Two in a row need a window of five
+        let mut filter = self::filter(1, 5, 0);
+        push_all(&mut filter, 1000, 5);
+        push_all(&mut filter, 4095, 2);
+        assert_eq!(filter.output(), 1000);
+        filter.push(4095);
+        assert_eq!(filter.output(), 4095);
+    }
+
+    #[test]
+    fn median_passes_steps_late() {
+        let mut filter = filter(1, 3, 0);
+        push_all(&mut filter, 1000, 3);
+        filter.push(2000);
+        assert_eq!(filter.output(), 1000);
+        filter.push(2000);
+        assert_eq!(filter.output(), 2000);
+    }
+
+    #[test]
+    fn oversampling_averages_frames() {
+        let mut filter = filter(4, 1, 0);
+        filter.push(1000);
+        filter.push(1001);
+        // The last input until the first value is complete
+        assert_eq!(filter.output(), 1001);
+        filter.push(1002);
+        filter.push(1003);
+        // 1001.5 rounds up
+        assert_eq!(filter.output(), 1002);
+
+        // Holds between values
+        filter.push(3000);
+        assert_eq!(filter.output(), 1002);
+        push_all(&mut filter, 3000, 3);
+        assert_eq!(filter.output(), 3000);
+    }
+
+    #[test]
+    fn oversampling_slows_the_low_pass_clock() {
+        // Same cutoff at a quarter of the value rate: same time constant in
+        // frames
+        let mut plain = filter(1, 1, 1_000);
+        let mut oversampled = filter(4, 1, 1_000);
+        plain.push(0);
+        push_all(&mut oversampled, 0, 4);
+        push_all(&mut plain, 1000, 160);
+        push_all(&mut oversampled, 1000, 160);
+        assert!(plain.output().abs_diff(oversampled.output()) <= 10);
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut filter = filter(2, 3, 1_000);
+        push_all(&mut filter, 1000, 10);
+        filter.push(3000);
+        filter.reset();
+        assert_eq!(filter.output(), 3000);
+        push_all(&mut filter, 2000, 2);
+        assert_eq!(filter.output(), 2000);
+    }
+
+    #[test]
+    fn config_validity() {
+        assert!(FilterConfig::NONE.is_none());
+        assert!(FilterConfig::default().is_valid());
+        let even_median = FilterConfig {
+            median: 4,
+            ..FilterConfig::NONE
+        };
+        assert!(!even_median.is_valid());
+        let no_frames = FilterConfig {
+            oversample: 0,
+            ..FilterConfig::NONE
+        };
+        assert!(!no_frames.is_valid());
+    }
+}
@@ -0,0 +1,740 @@
+//! Analog sampling service.
+//!
+//! Owns ADC1 and every analog input of the board (`A0`–`A4`, `BatVol`,
+//! `BoostVol`). Enabled channels are sampled together in frames, with the
+//! eFuse line calibration, so raw readings are millivolts at the ADC pin;
+//! [`Scaling`] turns them into physical units per channel.
+//!
+//! Two [`SamplingMode`]s are available:
+//! - `Oneshot` reads the channels one by one on a `Ticker`. Cheap and good
+//!   for slow signals, but timing jitters with the executor load.
+//! - `Continuous` lets the ADC digital controller convert the channels back
+//!   to back at a fixed hardware rate and stream the results over GDMA (see
+//!   [`continuous`]). Sample periods are exact, so sample timestamps are
+//!   derived from the sample index rather than measured.
+//!
+//! A channel keeps every `decimation`-th frame, which gives each channel its
+//! own rate out of a single conversion stream. A [`FilterConfig`] per channel
+//! averages, median filters and low passes every frame before decimation, so
+//! slow channels report a clean value instead of one noisy sample.
+//!
+//! Readings are published two ways:
+//! - [`AnalogBlock`]s of `samples_per_block` samples per channel on a
+//!   pub/sub channel, for consumers that need every sample;
+//! - the last sample of each block as an [`AnalogSnapshot`] on a `Watch`,
+//!   for consumers that only need the current value.
+
+pub mod channel;
+pub mod continuous;
+pub mod filter;
+pub mod pattern;
+pub mod scaling;
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::warn;
+use embassy_executor::Spawner;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::{pubsub, watch};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use esp_hal::analog::adc::{Adc, AdcCalLine, AdcConfig, AdcPin, Attenuation};
+use esp_hal::peripherals::{ADC1, DMA_CH2};
+use esp_hal::{Async, Blocking};
+use static_cell::StaticCell;
+
+use crate::board::{A0Pin, A1Pin, A2Pin, A3Pin, A4Pin, BatVolPin, BoostVolPin};
+
+use continuous::{ContinuousAdc, DmaMemory, SAMPLES_PER_DESCRIPTOR};
+use filter::ChannelFilter;
+use pattern::{decode_sample, timer_target, FrameAssembler, PatternEntry};
+
+pub use channel::{AnalogChannel, CHANNEL_COUNT};
+pub use continuous::Overrun;
+pub use filter::FilterConfig;
+pub use scaling::{Calibration, Scaling, POLY_TERMS};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Upper bound of [`AnalogConfig::samples_per_block`].
+pub const BLOCK_MAX_SAMPLES: usize = 128;
+/// Upper bound of [`ChannelConfig::decimation`].
+pub const MAX_DECIMATION: u16 = BLOCK_MAX_SAMPLES as u16;
+
+const MAX_SNAPSHOT_RECEIVERS: usize = 4;
+const BLOCK_QUEUE_LEN: usize = 2;
+const MAX_BLOCK_SUBSCRIBERS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelConfig {
+    pub enabled: bool,
+    pub attenuation: Attenuation,
+    pub scaling: Scaling,
+    /// Unit of the scaled value, e.g. "V", "N", "bar".
+    pub unit: &'static str,
+    /// Keep one frame out of `decimation`; 1 keeps every frame.
+    pub decimation: u16,
+    /// Applied to every frame before decimation.
+    pub filter: FilterConfig,
+}
+
+impl ChannelConfig {
+    pub const DISABLED: Self = Self {
+        enabled: false,
+        attenuation: Attenuation::_0dB,
+        scaling: Scaling::VOLTS,
+        unit: "V",
+        decimation: 1,
+        filter: FilterConfig::NONE,
+    };
+
+    /// Input voltage behind a divider of ratio `divider`, at 0 dB.
+    pub const fn volts(divider: f32) -> Self {
+        Self {
+            enabled: true,
+            attenuation: Attenuation::_0dB,
+            scaling: Scaling::divider(divider),
+            unit: "V",
+            decimation: 1,
+            filter: FilterConfig::NONE,
+        }
+    }
+
+    pub const fn with_decimation(self, decimation: u16) -> Self {
+        Self { decimation, ..self }
+    }
+
+    pub const fn with_filter(self, filter: FilterConfig) -> Self {
+        Self { filter, ..self }
+    }
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self::DISABLED
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SamplingMode {
+    /// Read every enabled channel once per `interval`.
+    Oneshot { interval: Duration },
+    /// Convert continuously at `conversion_rate_hz` conversions per second,
+    /// shared by all enabled channels in turn. The rate is rounded to a
+    /// multiple of 400 ns per conversion. Needs [`AnalogIo::dma`] and one
+    /// attenuation for all enabled channels.
+    Continuous { conversion_rate_hz: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnalogConfig {
+    /// Indexed by [`AnalogChannel::index`].
+    pub channels: [ChannelConfig; CHANNEL_COUNT],
+    pub mode: SamplingMode,
+    /// Frames per block; a multiple of every channel's decimation.
+    pub samples_per_block: usize,
+}
+
+impl AnalogConfig {
+    pub fn channel(&self, channel: AnalogChannel) -> &ChannelConfig {
+        &self.channels[channel.index()]
+    }
+
+    pub fn channel_mut(&mut self, channel: AnalogChannel) -> &mut ChannelConfig {
+        &mut self.channels[channel.index()]
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = AnalogChannel> + '_ {
+        AnalogChannel::ALL
+            .into_iter()
+            .filter(|&channel| self.channel(channel).enabled)
+    }
+}
+
+impl Default for AnalogConfig {
+    fn default() -> Self {
+        Self {
+            channels: [ChannelConfig::volts(1.0); CHANNEL_COUNT],
+            mode: SamplingMode::Oneshot {
+                interval: Duration::from_millis(10),
+            },
+            samples_per_block: 100,
+        }
+    }
+}
+
+/// Analog inputs handed over to the service.
+pub struct AnalogIo {
+    pub adc: ADC1<'static>,
+    pub a0: A0Pin,
+    pub a1: A1Pin,
+    pub a2: A2Pin,
+    pub a3: A3Pin,
+    pub a4: A4Pin,
+    pub bat_vol: BatVolPin,
+    pub boost_vol: BoostVolPin,
+    /// Required by [`SamplingMode::Continuous`].
+    pub dma: Option<DMA_CH2<'static>>,
+}
+
+/// One reading of every enabled channel.
+#[derive(Debug, Clone, Copy)]
+pub struct AnalogSnapshot {
+    pub timestamp_ms: u32,
+    millivolts: [u16; CHANNEL_COUNT],
+    channels: [ChannelConfig; CHANNEL_COUNT],
+}
+
+impl AnalogSnapshot {
+    /// Millivolts at the ADC pin, `None` for disabled channels.
+    pub fn millivolts(&self, channel: AnalogChannel) -> Option<u16> {
+        let index = channel.index();
+        self.channels[index]
+            .enabled
+            .then_some(self.millivolts[index])
+    }
+
+    /// Scaled value in the unit of the channel.
+    pub fn value(&self, channel: AnalogChannel) -> Option<f32> {
+        let scaling = self.channels[channel.index()].scaling;
+        self.millivolts(channel).map(|mv| scaling.apply(mv))
+    }
+
+    pub fn unit(&self, channel: AnalogChannel) -> &'static str {
+        self.channels[channel.index()].unit
+    }
+}
+
+/// Consecutive samples of every enabled channel, taken at a fixed rate.
+///
+/// Every channel starts at `first_timestamp_ms`; a decimated channel holds
+/// fewer samples, spaced by its own [`AnalogBlock::sample_period_ns`].
+#[derive(Debug, Clone)]
+pub struct AnalogBlock {
+    pub sequence: u32,
+    pub first_timestamp_ms: u32,
+    /// Timestamp of the last frame.
+    pub last_timestamp_ms: u32,
+    /// Overruns of the continuous conversion since the service started;
+    /// samples were lost right before this block whenever it changes.
+    pub overruns: u32,
+    frames: usize,
+    lens: [usize; CHANNEL_COUNT],
+    periods_ns: [u32; CHANNEL_COUNT],
+    millivolts: [[u16; BLOCK_MAX_SAMPLES]; CHANNEL_COUNT],
+    channels: [ChannelConfig; CHANNEL_COUNT],
+}
+
+impl AnalogBlock {
+    fn new(sequence: u32, overruns: u32, config: &AnalogConfig, frame_period_ns: u32) -> Self {
+        let mut periods_ns = [0; CHANNEL_COUNT];
+        for (period, channel) in periods_ns.iter_mut().zip(&config.channels) {
+            *period = frame_period_ns.saturating_mul(channel.decimation as u32);
+        }
+        Self {
+            sequence,
+            first_timestamp_ms: 0,
+            last_timestamp_ms: 0,
+            overruns,
+            frames: 0,
+            lens: [0; CHANNEL_COUNT],
+            periods_ns,
+            millivolts: [[0; BLOCK_MAX_SAMPLES]; CHANNEL_COUNT],
+            channels: config.channels,
+        }
+    }
+
+    /// Add one frame; `sample` is only read for channels due in this frame.
+    fn push(&mut self, timestamp_ms: u32, sample: &[u16; CHANNEL_COUNT]) {
+        if self.frames == 0 {
+            self.first_timestamp_ms = timestamp_ms;
+        }
+        self.last_timestamp_ms = timestamp_ms;
+        for channel in AnalogChannel::ALL {
+            if self.is_due(channel) {
+                let index = channel.index();
+                self.millivolts[index][self.lens[index]] = sample[index];
+                self.lens[index] += 1;
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Whether `channel` is sampled in the next frame.
+    fn is_due(&self, channel: AnalogChannel) -> bool {
+        let config = &self.channels[channel.index()];
+        config.enabled && self.frames % config.decimation as usize == 0
+    }
+
+    fn last_snapshot(&self) -> AnalogSnapshot {
+        let mut millivolts = [0; CHANNEL_COUNT];
+        for ((out, channel), &len) in millivolts.iter_mut().zip(&self.millivolts).zip(&self.lens) {
+            if len > 0 {
+                *out = channel[len - 1];
+            }
+        }
+        AnalogSnapshot {
+            timestamp_ms: self.last_timestamp_ms,
+            millivolts,
+            channels: self.channels,
+        }
+    }
+
+    /// Frames in the block, equal to the samples of undecimated channels.
+    pub fn len(&self) -> usize {
+        self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    pub fn channel_config(&self, channel: AnalogChannel) -> &ChannelConfig {
+        &self.channels[channel.index()]
+    }
+
+    /// Time between two samples of `channel`. Exact in continuous mode, the
+    /// nominal ticker interval in oneshot mode.
+    pub fn sample_period_ns(&self, channel: AnalogChannel) -> u32 {
+        self.periods_ns[channel.index()]
+    }
+
+    /// Timestamp of the last sample of `channel`.
+    pub fn last_timestamp_ms(&self, channel: AnalogChannel) -> u32 {
+        let index = channel.index();
+        let elapsed_ns = self.lens[index].saturating_sub(1) as u64 * self.periods_ns[index] as u64;
+        self.first_timestamp_ms
+            .wrapping_add((elapsed_ns / 1_000_000) as u32)
+    }
+
+    /// Millivolts at the ADC pin, `None` for disabled channels.
+    pub fn millivolts(&self, channel: AnalogChannel) -> Option<&[u16]> {
+        let index = channel.index();
+        self.channels[index]
+            .enabled
+            .then(|| &self.millivolts[index][..self.lens[index]])
+    }
+
+    /// Scaled values in the unit of the channel.
+    pub fn values(&self, channel: AnalogChannel) -> Option<impl Iterator<Item = f32> + '_> {
+        let scaling = self.channels[channel.index()].scaling;
+        self.millivolts(channel)
+            .map(move |samples| samples.iter().map(move |&mv| scaling.apply(mv)))
+    }
+
+    /// Mean scaled value over the block.
+    pub fn mean(&self, channel: AnalogChannel) -> Option<f32> {
+        let len = self.lens[channel.index()];
+        if len == 0 {
+            return None;
+        }
+        let sum: f32 = self.values(channel)?.sum();
+        Some(sum / len as f32)
+    }
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static ANALOG_SNAPSHOT: watch::Watch<
+    CriticalSectionRawMutex,
+    AnalogSnapshot,
+    MAX_SNAPSHOT_RECEIVERS,
+> = watch::Watch::new();
+
+pub type AnalogSnapshotReceiver =
+    watch::Receiver<'static, CriticalSectionRawMutex, AnalogSnapshot, MAX_SNAPSHOT_RECEIVERS>;
+
+static ANALOG_BLOCKS: pubsub::PubSubChannel<
+    CriticalSectionRawMutex,
+    AnalogBlock,
+    BLOCK_QUEUE_LEN,
+    MAX_BLOCK_SUBSCRIBERS,
+    1,
+> = pubsub::PubSubChannel::new();
+
+pub type AnalogBlockSubscriber = pubsub::Subscriber<
+    'static,
+    CriticalSectionRawMutex,
+    AnalogBlock,
+    BLOCK_QUEUE_LEN,
+    MAX_BLOCK_SUBSCRIBERS,
+    1,
+>;
+
+/// Calibration changes, applied from the next block on.
+static CALIBRATION_UPDATES: Channel<
+    CriticalSectionRawMutex,
+    (AnalogChannel, Calibration, &'static str),
+    CHANNEL_COUNT,
+> = Channel::new();
+
+static ANALOG_STARTED: AtomicBool = AtomicBool::new(false);
+
+static DMA_MEMORY: StaticCell<DmaMemory> = StaticCell::new();
+
+// ============================================================================
+// SPAWN METHOD
+// ============================================================================
+
+pub fn spawn_analog_service(spawner: &Spawner, io: AnalogIo, config: AnalogConfig) -> AnalogHandle {
+    if ANALOG_STARTED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        panic!("Analog service already started");
+    }
+    assert!(
+        config.samples_per_block > 0 && config.samples_per_block <= BLOCK_MAX_SAMPLES,
+        "samples_per_block out of range"
+    );
+    for channel in config.enabled() {
+        let decimation = config.channel(channel).decimation;
+        assert!(
+            decimation > 0
+                && decimation <= MAX_DECIMATION
+                && config.samples_per_block % decimation as usize == 0,
+            "decimation must divide samples_per_block"
+        );
+        let filter = config.channel(channel).filter;
+        assert!(
+            filter.is_valid() && decimation % filter.oversample == 0,
+            "invalid filter: oversample must divide decimation, median must be odd"
+        );
+    }
+    if let SamplingMode::Continuous { .. } = config.mode {
+        assert!(io.dma.is_some(), "Continuous sampling needs a DMA channel");
+        assert!(
+            shared_attenuation(&config).is_some(),
+            "Continuous sampling needs one attenuation for all enabled channels"
+        );
+    }
+
+    spawner
+        .spawn(analog_task(io, config))
+        .expect("spawn analog service failed");
+
+    AnalogHandle { _priv: PhantomData }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+type CalScheme = AdcCalLine<ADC1<'static>>;
+
+struct AnalogPins {
+    a0: AdcPin<A0Pin, ADC1<'static>, CalScheme>,
+    a1: AdcPin<A1Pin, ADC1<'static>, CalScheme>,
+    a2: AdcPin<A2Pin, ADC1<'static>, CalScheme>,
+    a3: AdcPin<A3Pin, ADC1<'static>, CalScheme>,
+    a4: AdcPin<A4Pin, ADC1<'static>, CalScheme>,
+    bat_vol: AdcPin<BatVolPin, ADC1<'static>, CalScheme>,
+    boost_vol: AdcPin<BoostVolPin, ADC1<'static>, CalScheme>,
+}
+
+impl AnalogPins {
+    fn new(io: AnalogIo, config: &AnalogConfig) -> (Adc<'static, ADC1<'static>, Blocking>, Self) {
+        let mut adc_config = AdcConfig::new();
+        let attenuation = |channel: AnalogChannel| config.channel(channel).attenuation;
+
+        let pins = Self {
+            a0: adc_config.enable_pin_with_cal(io.a0, attenuation(AnalogChannel::A0)),
+            a1: adc_config.enable_pin_with_cal(io.a1, attenuation(AnalogChannel::A1)),
+            a2: adc_config.enable_pin_with_cal(io.a2, attenuation(AnalogChannel::A2)),
+            a3: adc_config.enable_pin_with_cal(io.a3, attenuation(AnalogChannel::A3)),
+            a4: adc_config.enable_pin_with_cal(io.a4, attenuation(AnalogChannel::A4)),
+            bat_vol: adc_config.enable_pin_with_cal(io.bat_vol, attenuation(AnalogChannel::BatVol)),
+            boost_vol: adc_config
+                .enable_pin_with_cal(io.boost_vol, attenuation(AnalogChannel::BoostVol)),
+        };
+
+        (Adc::new(io.adc, adc_config), pins)
+    }
+
+    async fn read(
+        &mut self,
+        adc: &mut Adc<'static, ADC1<'static>, Async>,
+        channel: AnalogChannel,
+    ) -> u16 {
+        match channel {
+            AnalogChannel::A0 => adc.read_oneshot(&mut self.a0).await,
+            AnalogChannel::A1 => adc.read_oneshot(&mut self.a1).await,
+            AnalogChannel::A2 => adc.read_oneshot(&mut self.a2).await,
+            AnalogChannel::A3 => adc.read_oneshot(&mut self.a3).await,
+            AnalogChannel::A4 => adc.read_oneshot(&mut self.a4).await,
+            AnalogChannel::BatVol => adc.read_oneshot(&mut self.bat_vol).await,
+            AnalogChannel::BoostVol => adc.read_oneshot(&mut self.boost_vol).await,
+        }
+    }
+
+    /// One blocking read, which powers ADC1 up and loads the hardware
+    /// calibration for the attenuation of `channel`.
+    fn prime(&mut self, adc: &mut Adc<'static, ADC1<'static>, Blocking>, channel: AnalogChannel) {
+        let _ = match channel {
+            AnalogChannel::A0 => nb::block!(adc.read_oneshot(&mut self.a0)),
+            AnalogChannel::A1 => nb::block!(adc.read_oneshot(&mut self.a1)),
+            AnalogChannel::A2 => nb::block!(adc.read_oneshot(&mut self.a2)),
+            AnalogChannel::A3 => nb::block!(adc.read_oneshot(&mut self.a3)),
+            AnalogChannel::A4 => nb::block!(adc.read_oneshot(&mut self.a4)),
+            AnalogChannel::BatVol => nb::block!(adc.read_oneshot(&mut self.bat_vol)),
+            AnalogChannel::BoostVol => nb::block!(adc.read_oneshot(&mut self.boost_vol)),
+        };
+    }
+}
+
+/// The attenuation of every enabled channel, if there is exactly one.
+fn shared_attenuation(config: &AnalogConfig) -> Option<Attenuation> {
+    let mut channels = config
+        .enabled()
+        .map(|channel| config.channel(channel).attenuation);
+    let first = channels.next()?;
+    channels
+        .all(|attenuation| attenuation as u8 == first as u8)
+        .then_some(first)
+}
+
+/// Collects frames into blocks and publishes the full ones.
+struct BlockBuilder {
+    config: AnalogConfig,
+    frame_period_ns: u32,
+    sequence: u32,
+    overruns: u32,
+    block: AnalogBlock,
+    filters: [ChannelFilter; CHANNEL_COUNT],
+    snapshot_sender:
+        watch::Sender<'static, CriticalSectionRawMutex, AnalogSnapshot, MAX_SNAPSHOT_RECEIVERS>,
+    publisher: pubsub::Publisher<
+        'static,
+        CriticalSectionRawMutex,
+        AnalogBlock,
+        BLOCK_QUEUE_LEN,
+        MAX_BLOCK_SUBSCRIBERS,
+        1,
+    >,
+}
+
+impl BlockBuilder {
+    fn new(config: AnalogConfig, frame_period_ns: u32) -> Self {
+        Self {
+            config,
+            frame_period_ns,
+            sequence: 0,
+            overruns: 0,
+            block: AnalogBlock::new(0, 0, &config, frame_period_ns),
+            filters: config
+                .channels
+                .map(|channel| ChannelFilter::new(&channel.filter, frame_period_ns)),
+            snapshot_sender: ANALOG_SNAPSHOT.sender(),
+            publisher: ANALOG_BLOCKS
+                .publisher()
+                .expect("Analog block publisher unavailable"),
+        }
+    }
+
+    /// Whether `channel` has to be read in the next frame: when it is due,
+    /// or every frame when filtered.
+    fn needs_sample(&self, channel: AnalogChannel) -> bool {
+        let config = self.config.channel(channel);
+        self.block.is_due(channel) || (config.enabled && !config.filter.is_none())
+    }
+
+    fn push(&mut self, timestamp_ms: u32, sample: &[u16; CHANNEL_COUNT]) {
+        let mut filtered = *sample;
+        for channel in self.config.enabled() {
+            let index = channel.index();
+            if !self.config.channels[index].filter.is_none() {
+                self.filters[index].push(sample[index]);
+                filtered[index] = self.filters[index].output();
+            }
+        }
+
+        self.block.push(timestamp_ms, &filtered);
+        if self.block.len() < self.config.samples_per_block {
+            return;
+        }
+
+        self.snapshot_sender.send(self.block.last_snapshot());
+        self.sequence = self.sequence.wrapping_add(1);
+        let next = self.next_block();
+        let full = core::mem::replace(&mut self.block, next);
+        self.publisher.publish_immediate(full);
+    }
+
+    /// Samples were lost: drop the partial block so every block stays
+    /// evenly spaced.
+    fn overrun(&mut self) {
+        self.overruns = self.overruns.wrapping_add(1);
+        self.block = self.next_block();
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+    }
+
+    /// Empty block with the calibration updates received so far.
+    fn next_block(&mut self) -> AnalogBlock {
+        while let Ok((channel, calibration, unit)) = CALIBRATION_UPDATES.try_receive() {
+            let config = self.config.channel_mut(channel);
+            config.scaling.calibration = calibration;
+            config.unit = unit;
+        }
+        AnalogBlock::new(
+            self.sequence,
+            self.overruns,
+            &self.config,
+            self.frame_period_ns,
+        )
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+async fn analog_task(mut io: AnalogIo, config: AnalogConfig) {
+    match config.mode {
+        SamplingMode::Oneshot { interval } => {
+            run_oneshot(io, config, interval).await;
+        }
+        SamplingMode::Continuous { conversion_rate_hz } => {
+            let dma = io
+                .dma
+                .take()
+                .expect("Continuous sampling needs a DMA channel");
+            run_continuous(io, dma, config, conversion_rate_hz).await;
+        }
+    }
+}
+
+async fn run_oneshot(io: AnalogIo, config: AnalogConfig, interval: Duration) -> ! {
+    let (adc, mut pins) = AnalogPins::new(io, &config);
+    let mut adc = adc.into_async();
+
+    let frame_period_ns = interval.as_micros().saturating_mul(1000) as u32;
+    let mut blocks = BlockBuilder::new(config, frame_period_ns);
+    let mut sample = [0u16; CHANNEL_COUNT];
+    let mut ticker = Ticker::every(interval);
+
+    loop {
+        let timestamp_ms = Instant::now().as_millis() as u32;
+        for channel in AnalogChannel::ALL {
+            if blocks.needs_sample(channel) {
+                sample[channel.index()] = pins.read(&mut adc, channel).await;
+            }
+        }
+        blocks.push(timestamp_ms, &sample);
+
+        ticker.next().await;
+    }
+}
+
+async fn run_continuous(
+    io: AnalogIo,
+    dma: DMA_CH2<'static>,
+    config: AnalogConfig,
+    conversion_rate_hz: u32,
+) -> ! {
+    let attenuation = shared_attenuation(&config).expect("attenuation checked at spawn");
+    let channels: Vec<AnalogChannel> = config.enabled().collect();
+    let adc_channels: Vec<u8> = channels
+        .iter()
+        .map(|channel| channel.adc_channel())
+        .collect();
+    let pattern: Vec<PatternEntry> = adc_channels
+        .iter()
+        .map(|&adc_channel| PatternEntry {
+            adc_channel,
+            attenuation: attenuation as u8,
+        })
+        .collect();
+
+    // The HAL driver stays alive to keep ADC1 clocked and powered.
+    let (mut adc, mut pins) = AnalogPins::new(io, &config);
+    pins.prime(&mut adc, channels[0]);
+
+    let target = timer_target(conversion_rate_hz);
+    let memory = DMA_MEMORY.init_with(DmaMemory::new);
+    let mut converter = ContinuousAdc::new(dma, memory, &pattern, attenuation, target)
+        .expect("pattern checked at spawn");
+    let calibration = converter.calibration();
+    let mut frames = FrameAssembler::new(&adc_channels).expect("pattern checked at spawn");
+
+    let frame_period_ns = converter.conversion_period_ns() * pattern.len() as u32;
+    let mut blocks = BlockBuilder::new(config, frame_period_ns);
+
+    // Poll twice per descriptor so the ring never gets close to full.
+    let descriptor_ns = converter.conversion_period_ns() as u64 * SAMPLES_PER_DESCRIPTOR as u64;
+    let poll_interval = Duration::from_micros((descriptor_ns / 2_000).max(1_000));
+
+    let mut start_ms = Instant::now().as_millis();
+    let mut frame_index: u64 = 0;
+    let mut sample = [0u16; CHANNEL_COUNT];
+
+    loop {
+        let drained = converter.drain(|word| {
+            let Some(raw) = decode_sample(word) else {
+                return;
+            };
+            let Some(codes) = frames.push(raw) else {
+                return;
+            };
+            for (channel, &code) in channels.iter().zip(codes) {
+                sample[channel.index()] = calibration.millivolts(code);
+            }
+            let offset_ms = frame_index * frame_period_ns as u64 / 1_000_000;
+            blocks.push((start_ms + offset_ms) as u32, &sample);
+            frame_index += 1;
+        });
+
+        if let Err(Overrun) = drained {
+            blocks.overrun();
+            frames.reset();
+            frame_index = 0;
+            start_ms = Instant::now().as_millis();
+            warn!("Analog DMA overrun ({} so far)", blocks.overruns);
+        }
+
+        Timer::after(poll_interval).await;
+    }
+}
+
+// ============================================================================
+// HANDLE
+// ============================================================================
+
+#[derive(Clone, Copy)]
+pub struct AnalogHandle {
+    _priv: PhantomData<()>,
+}
+
+impl AnalogHandle {
+    /// Latest snapshot, updated once per block.
+    pub fn latest(&self) -> Option<AnalogSnapshot> {
+        ANALOG_SNAPSHOT.try_get()
+    }
+
+    pub fn snapshot_receiver(&self) -> Option<AnalogSnapshotReceiver> {
+        ANALOG_SNAPSHOT.receiver()
+    }
+
+    pub fn block_subscriber(&self) -> Option<AnalogBlockSubscriber> {
+        ANALOG_BLOCKS.subscriber().ok()
+    }
+
+    /// Replace the calibration and unit of `channel`, keeping its divider.
+    /// Takes effect from the next block on.
+    pub async fn set_calibration(
+        &self,
+        channel: AnalogChannel,
+        calibration: Calibration,
+        unit: &'static str,
+    ) {
+        CALIBRATION_UPDATES.send((channel, calibration, unit)).await;
+    }
+}
@@ -0,0 +1,187 @@
+//! Pattern table, timing and DMA sample format of the ESP32-C6 ADC digital
+//! controller.
+
+/// Entries of the pattern table (`SAR_PATT_TAB1`/`TAB2`, 4 per register).
+pub const MAX_PATTERN_LEN: usize = 8;
+
+/// Controller clock: PLL_F80M divided by `CLKM_DIV_NUM + 1`.
+pub const CLKM_SOURCE_HZ: u32 = 80_000_000;
+pub const CLKM_DIV_NUM: u32 = 15;
+
+/// One conversion every `timer_target * CONVERSION_TICK_NS`.
+pub const CONVERSION_TICK_NS: u32 = 1_000_000_000 / (CLKM_SOURCE_HZ / (CLKM_DIV_NUM + 1) / 2);
+
+/// Limits of `SARADC_TIMER_TARGET`; the lower one keeps the rate within
+/// what the SAR can convert (about 100 kHz).
+pub const TIMER_TARGET_MIN: u32 = 25;
+pub const TIMER_TARGET_MAX: u32 = 0xFFF;
+
+/// Bytes per conversion result in DMA memory.
+pub const SAMPLE_BYTES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PatternError {
+    Empty,
+    TooLong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternEntry {
+    /// ADC1 channel, equal to the GPIO number on the ESP32-C6.
+    pub adc_channel: u8,
+    /// Attenuation code, 0 (0 dB) to 3 (11 dB).
+    pub attenuation: u8,
+}
+
+impl PatternEntry {
+    const fn bits(self) -> u32 {
+        ((self.adc_channel as u32 & 0x7) << 2) | (self.attenuation as u32 & 0x3)
+    }
+}
+
+/// Values for `SAR_PATT_TAB1` and `SAR_PATT_TAB2`: 6 bits per entry, first
+/// entry in the top bits of TAB1.
+pub fn encode_tables(entries: &[PatternEntry]) -> Result<[u32; 2], PatternError> {
+    if entries.is_empty() {
+        return Err(PatternError::Empty);
+    }
+    if entries.len() > MAX_PATTERN_LEN {
+        return Err(PatternError::TooLong);
+    }
+
+    let mut tables = [0u32; 2];
+    for (index, entry) in entries.iter().enumerate() {
+        let shift = (3 - index % 4) * 6;
+        tables[index / 4] |= entry.bits() << shift;
+    }
+    Ok(tables)
+}
+
+/// `SARADC_TIMER_TARGET` for the closest achievable conversion rate.
+pub fn timer_target(conversion_rate_hz: u32) -> u32 {
+    let tick_hz = 1_000_000_000 / CONVERSION_TICK_NS;
+    let target = (tick_hz + conversion_rate_hz / 2) / conversion_rate_hz.max(1);
+    target.clamp(TIMER_TARGET_MIN, TIMER_TARGET_MAX)
+}
+
+/// Exact time between two conversions for `target`.
+pub const fn conversion_period_ns(target: u32) -> u32 {
+    target * CONVERSION_TICK_NS
+}
+
+/// Conversion result as written by the controller (type 2 format).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawSample {
+    pub adc_channel: u8,
+    pub code: u16,
+}
+
+/// Decode one DMA word: data in bits 0..12, channel in 13..16, unit in 16.
+/// Results of another unit are rejected.
+pub const fn decode_sample(word: u32) -> Option<RawSample> {
+    if (word >> 16) & 0x1 != 0 {
+        return None;
+    }
+    Some(RawSample {
+        adc_channel: ((word >> 13) & 0x7) as u8,
+        code: (word & 0xFFF) as u16,
+    })
+}
+
+/// Two-point line through zero and the eFuse reference reading, the same
+/// reference `AdcCalLine` uses for oneshot reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCalibration {
+    /// Raw code read at `cal_mv`.
+    pub cal_code: u16,
+    pub cal_mv: u16,
+}
+
+impl LineCalibration {
+    /// Approximate line for chips without calibration eFuses, indexed by
+    /// attenuation code.
+    pub const NOMINAL: [LineCalibration; 4] = [
+        LineCalibration {
+            cal_code: 4095,
+            cal_mv: 950,
+        },
+        LineCalibration {
+            cal_code: 4095,
+            cal_mv: 1250,
+        },
+        LineCalibration {
+            cal_code: 4095,
+            cal_mv: 1750,
+        },
+        LineCalibration {
+            cal_code: 4095,
+            cal_mv: 3100,
+        },
+    ];
+
+    pub const fn millivolts(&self, code: u16) -> u16 {
+        if self.cal_code == 0 {
+            return 0;
+        }
+        let mv = code as u32 * self.cal_mv as u32 / self.cal_code as u32;
+        if mv > u16::MAX as u32 {
+            u16::MAX
+        } else {
+            mv as u16
+        }
+    }
+}
+
+/// Groups the result stream into frames, one result per pattern entry.
+/// A missing or unexpected result drops the partial frame and waits for the
+/// start of the next one.
+#[derive(Debug, Clone)]
+pub struct FrameAssembler {
+    channels: [u8; MAX_PATTERN_LEN],
+    len: usize,
+    position: usize,
+    codes: [u16; MAX_PATTERN_LEN],
+}
+
+impl FrameAssembler {
+    /// `channels` are the ADC1 channels of the pattern, in table order.
+    pub fn new(channels: &[u8]) -> Result<Self, PatternError> {
+        if channels.is_empty() {
+            return Err(PatternError::Empty);
+        }
+        if channels.len() > MAX_PATTERN_LEN {
+            return Err(PatternError::TooLong);
+        }
+        let mut pattern = [0; MAX_PATTERN_LEN];
+        pattern[..channels.len()].copy_from_slice(channels);
+        Ok(Self {
+            channels: pattern,
+            len: channels.len(),
+            position: 0,
+            codes: [0; MAX_PATTERN_LEN],
+        })
+    }
+
+    /// Add one result; returns the codes of a completed frame in pattern
+    /// order.
+    pub fn push(&mut self, sample: RawSample) -> Option<&[u16]> {
+        if sample.adc_channel != self.channels[self.position] {
+            self.position = 0;
+            if sample.adc_channel != self.channels[0] {
+                return None;
+            }
+        }
+        self.codes[self.position] = sample.code;
+        self.position += 1;
+        if self.position < self.len {
+            return None;
+        }
+        self.position = 0;
+        Some(&self.codes[..self.len])
+    }
+
+    /// Forget the partial frame, e.g. after the conversion was restarted.
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+}
@@ -0,0 +1,83 @@
+//! Conversion of calibrated ADC readings to physical units.
+
+/// Number of coefficients of [`Calibration::Polynomial`].
+pub const POLY_TERMS: usize = 4;
+
+/// Maps the voltage at the board input (after the divider) to a physical
+/// value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Calibration {
+    /// `gain * x + offset`
+    Linear { gain: f32, offset: f32 },
+    /// `c[0] + c[1] * x + c[2] * x^2 + c[3] * x^3`
+    Polynomial([f32; POLY_TERMS]),
+}
+
+impl Calibration {
+    /// Reports the input voltage itself.
+    pub const IDENTITY: Self = Calibration::Linear {
+        gain: 1.0,
+        offset: 0.0,
+    };
+
+    pub fn apply(&self, x: f32) -> f32 {
+        match *self {
+            Calibration::Linear { gain, offset } => gain * x + offset,
+            Calibration::Polynomial(coefficients) => coefficients
+                .iter()
+                .rev()
+                .fold(0.0, |acc, &coefficient| acc * x + coefficient),
+        }
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Full conversion of one channel: ADC pin millivolts, times the input
+/// divider ratio, through the calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scaling {
+    /// Input voltage / pin voltage, e.g. 4.9 for a 39k/10k divider.
+    pub divider: f32,
+    pub calibration: Calibration,
+}
+
+impl Scaling {
+    /// Pin voltage in volts.
+    pub const VOLTS: Self = Self::divider(1.0);
+
+    /// Input voltage in volts behind a divider of ratio `divider`.
+    pub const fn divider(divider: f32) -> Self {
+        Self {
+            divider,
+            calibration: Calibration::IDENTITY,
+        }
+    }
+
+    pub fn input_volts(&self, pin_mv: u16) -> f32 {
+        pin_mv as f32 * self.divider / 1000.0
+    }
+
+    pub fn apply(&self, pin_mv: u16) -> f32 {
+        self.calibration.apply(self.input_volts(pin_mv))
+    }
+
+    /// `(gain, offset)` such that the value is `gain * pin_mv + offset`, if
+    /// the calibration is linear.
+    pub fn linear_per_mv(&self) -> Option<(f32, f32)> {
+        match self.calibration {
+            Calibration::Linear { gain, offset } => Some((gain * self.divider / 1000.0, offset)),
+            Calibration::Polynomial(_) => None,
+        }
+    }
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Self::VOLTS
+    }
+}
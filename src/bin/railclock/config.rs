@@ -3,6 +3,7 @@ use alloc::string::String;
 use lazy_static::lazy_static;
 
 pub static BUTTON_DELAY_MS: u64 = 1000;
+/// Comma separated SNTP servers, tried in order.
 pub static NTP_SERVER: &str = env!("NTP_SERVER");
 pub static MQTT_HOST: &str = env!("MQTT_HOST");
 pub const MQTT_PORT: u16 = 1883;
@@ -12,11 +13,20 @@ pub static MQTT_CLIENT_ID: &str = match option_env!("MQTT_CLIENT_ID") {
     Some(id) => id,
     None => "esp32-railclock",
 };
-pub static BATTRY_CALIBRATION: u32 = 5780;
+/// Browse mDNS for `_mqtt._tcp` when `MQTT_HOST` does not resolve.
+pub const MQTT_MDNS_DISCOVERY: bool = option_env!("MQTT_MDNS_DISCOVERY").is_some();
+/// Battery input divider ratio, calibrated
+pub static BATTERY_DIVIDER: f32 = 5.78;
 
 // Battery publish interval (seconds) - configurable
 pub static BATTERY_PUBLISH_INTERVAL_SECS: u64 = 120;
 
+// Longest a supervised task may go without checking in, and how often idle tasks check in
+pub const TASK_DEADLINE_SECS: u64 = 10;
+pub const TASK_IDLE_CHECK_IN_SECS: u64 = 1;
+// Longest a supervised task waits for the power task or the RTC to answer
+pub const TASK_REQUEST_TIMEOUT_SECS: u64 = 3;
+
 lazy_static! {
     pub static ref MQTT_BATTERY_SENSOR_TOPIC: String =
         format!("homeassistant/sensor/{MQTT_CLIENT_ID}/battery");
@@ -30,6 +40,11 @@ lazy_static! {
         format!("homeassistant/button/{MQTT_CLIENT_ID}/ntp_sync/config");
     pub static ref MQTT_NTP_SYNC_TOPIC: String =
         format!("homeassistant/button/{MQTT_CLIENT_ID}/button/ntp_sync");
+    pub static ref MQTT_CRASH_SENSOR_TOPIC: String =
+        format!("homeassistant/sensor/{MQTT_CLIENT_ID}/crash");
+    pub static ref MQTT_CRASH_SENSOR_CONFIG_TOPIC: String =
+        format!("homeassistant/sensor/{MQTT_CLIENT_ID}/crash/config");
+    pub static ref MQTT_LOG_TOPIC: String = format!("log/{MQTT_CLIENT_ID}");
 }
 
 lazy_static! {
@@ -51,6 +66,23 @@ lazy_static! {
         )
     };
 
+    /// Discovery JSON payload for the last crash report entity
+    pub static ref MQTT_CRASH_SENSOR_DISCOVERY: String = {
+        let crash_topic = MQTT_CRASH_SENSOR_TOPIC.as_str();
+        format!(
+            r#"{{
+                "name": "Last crash",
+                "state_topic": "{crash_topic}",
+                "unique_id": "{MQTT_CLIENT_ID}_crash_sensor",
+                "entity_category": "diagnostic",
+                "device": {{
+                    "identifiers": ["{MQTT_CLIENT_ID}-device"],
+                    "name": "{MQTT_CLIENT_ID}"
+                }}
+            }}"#,
+        )
+    };
+
     /// Discovery JSON payload for the push button entity
     pub static ref MQTT_PUSH_BUTTON_DISCOVERY: String = {
         let button_topic = MQTT_BUTTON_TOPIC.as_str();
@@ -1,20 +1,27 @@
-use alloc::format;
-use defmt::{error, info, Format};
+use core::future::Future;
+
+use defmt::{info, Format};
 use embassy_executor::Spawner;
 use embassy_sync::{
     blocking_mutex::raw::CriticalSectionRawMutex,
     semaphore::{GreedySemaphore, Semaphore},
 };
-use embassy_time::Timer;
+use embassy_time::{with_timeout, Duration, Timer};
 use esp_hal::gpio::{Output, OutputConfig};
 use mainboard::{
     board::{Motor0Pin, Motor1Pin},
+    net_error,
     tasks::PowerHandle,
+    watchdog::{self, TaskWatch},
 };
 use mcp794xx::Timelike;
 use rkyv::{rancor::Error, Archive, Deserialize, Serialize};
 
-use crate::{rtc::RTC, CLOCK_DRIVER};
+use crate::{
+    config::{TASK_DEADLINE_SECS, TASK_IDLE_CHECK_IN_SECS, TASK_REQUEST_TIMEOUT_SECS},
+    rtc::RTC,
+    CLOCK_DRIVER,
+};
 
 pub(crate) struct ClockDriver {
     semaphore: GreedySemaphore<CriticalSectionRawMutex>,
@@ -75,10 +82,7 @@ async fn read_rtc_state() -> ClockDriverState {
             state
         }
         Err(e) => {
-            error!(
-                "Failed to read the rtc sram memory {}",
-                format!("{:?}", e).as_str()
-            );
+            net_error!("Failed to read the rtc sram memory {:?}", e);
             Default::default()
         }
     }
@@ -89,10 +93,7 @@ async fn write_rtc_state(state: &ClockDriverState) {
     let data = match rkyv::to_bytes::<Error>(state) {
         Ok(v) => v,
         Err(e) => {
-            error!(
-                "Failed to serialzie to json, {}",
-                format!("{:?}", e).as_str()
-            );
+            net_error!("Failed to serialzie to json, {:?}", e);
             return;
         }
     };
@@ -100,17 +101,28 @@ async fn write_rtc_state(state: &ClockDriverState) {
     match RTC.write_nonvolatile(0x20u8, data.as_ref()).await {
         Ok(()) => {}
         Err(e) => {
-            error!(
-                "Failed to write to rtc sram {}",
-                format!("{:?}", e).as_str()
-            );
+            net_error!("Failed to write to rtc sram {:?}", e);
         }
     }
 }
 
+/// Wait for the power task or the RTC to answer, at most
+/// `TASK_REQUEST_TIMEOUT_SECS`; `None` if it did not.
+async fn request<F: Future>(watch: &TaskWatch, what: &str, fut: F) -> Option<F::Output> {
+    let response = with_timeout(Duration::from_secs(TASK_REQUEST_TIMEOUT_SECS), fut).await;
+    watch.check_in();
+    if response.is_err() {
+        net_error!("No answer to {}", what);
+    }
+    response.ok()
+}
+
 #[embassy_executor::task]
 async fn clock_task(motor_pin0: Motor0Pin, motor_pin1: Motor1Pin, power: PowerHandle) {
-    let mut state = read_rtc_state().await;
+    let watch = watchdog::register("clock", Duration::from_secs(TASK_DEADLINE_SECS));
+    let mut state = request(&watch, "RTC state read", read_rtc_state())
+        .await
+        .unwrap_or_default();
 
     if state.pin == 0 || state.pin == 3 {
         state.pin = 1;
@@ -131,7 +143,11 @@ async fn clock_task(motor_pin0: Motor0Pin, motor_pin1: Motor1Pin, power: PowerHa
     let driver = CLOCK_DRIVER.get().await;
 
     if let Some(last_update) = state.time.as_ref() {
-        let current_time = RTC.get_datetime().await.unwrap_or_default().second() as i64;
+        let current_time = request(&watch, "RTC time read", RTC.get_datetime())
+            .await
+            .and_then(Result::ok)
+            .unwrap_or_default()
+            .second() as i64;
         if current_time > *last_update {
             let diff = (current_time - last_update) / 60;
             driver.push_forward(diff as usize);
@@ -139,10 +155,15 @@ async fn clock_task(motor_pin0: Motor0Pin, motor_pin1: Motor1Pin, power: PowerHa
     }
 
     loop {
-        let n = driver.acquire().await;
+        let n = watch
+            .idle(
+                Duration::from_secs(TASK_IDLE_CHECK_IN_SECS),
+                driver.acquire(),
+            )
+            .await;
         info!("Acquired {} pushes", n);
 
-        power.set_boost_converter(true).await;
+        request(&watch, "boost enable", power.set_boost_converter(true)).await;
         Timer::after_millis(100).await;
 
         for _ in 0..n {
@@ -151,13 +172,15 @@ async fn clock_task(motor_pin0: Motor0Pin, motor_pin1: Motor1Pin, power: PowerHa
             state.pin ^= 0x3;
             info!("Clock phase switched");
             Timer::after_secs(1).await;
+            watch.check_in();
         }
 
-        power.set_boost_converter(false).await;
+        request(&watch, "boost disable", power.set_boost_converter(false)).await;
 
-        if let Ok(time) = RTC.get_datetime().await {
+        let time = request(&watch, "RTC time read", RTC.get_datetime()).await;
+        if let Some(Ok(time)) = time {
             state.time = Some(time.and_utc().timestamp());
-            write_rtc_state(&state).await;
+            request(&watch, "RTC state write", write_rtc_state(&state)).await;
         }
     }
 }
@@ -0,0 +1,54 @@
+use alloc::string::String;
+
+use embassy_time::Instant;
+use mainboard::netlog::{Level, LogReader, LogRecord, RateLimiter, ReadResult};
+
+use crate::config::MQTT_LOG_TOPIC;
+use crate::mqtt_queue::mqtt_publish_log;
+
+/// Records per second forwarded to MQTT on average.
+const LOG_RATE_PER_SECOND: u32 = 2;
+const LOG_BURST: u32 = 10;
+
+fn publish_record(record: &LogRecord) -> Result<(), ()> {
+    let mut line = String::new();
+    let _ = record.write_line(&mut line);
+    mqtt_publish_log(MQTT_LOG_TOPIC.as_str(), &line)
+}
+
+/// Forwards network log records to `log/<client-id>`.
+#[embassy_executor::task]
+pub(crate) async fn log_forward_task(mut reader: LogReader) {
+    let mut limiter = RateLimiter::new(LOG_RATE_PER_SECOND, LOG_BURST);
+    let mut dropped = 0u32;
+
+    loop {
+        let record = match reader.next().await {
+            ReadResult::Record(record) => record,
+            ReadResult::Dropped(count) => {
+                dropped = dropped.saturating_add(count);
+                continue;
+            }
+        };
+        if !limiter.allow(Instant::now().as_millis()) {
+            continue;
+        }
+
+        dropped = dropped.saturating_add(limiter.take_suppressed());
+        if dropped > 0 {
+            let notice = LogRecord::new(
+                Level::Warn,
+                record.uptime_ms,
+                module_path!(),
+                format_args!("{} log records dropped", dropped),
+            );
+            if publish_record(&notice).is_ok() {
+                dropped = 0;
+            }
+        }
+
+        if publish_record(&record).is_err() {
+            dropped = dropped.saturating_add(1);
+        }
+    }
+}
@@ -10,37 +10,48 @@
 mod battery;
 mod config;
 mod driver;
+mod log_forward;
 mod mqtt;
 mod mqtt_queue;
 mod ntp;
 mod rtc;
 
-use alloc::format;
-use defmt::{error, info};
+use defmt::info;
 use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::once_lock::OnceLock;
 use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Timer};
-use embassy_futures::select::{select, Either};
-use esp_hal::rtc_cntl::Rtc;
 use esp_hal::clock::CpuClock;
 use esp_hal::gpio::{Input, InputConfig};
+use esp_hal::rtc_cntl::Rtc;
 use esp_hal::timer::timg::TimerGroup;
 use mcp794xx::AlarmDateTime;
-use panic_rtt_target as _;
 use static_cell::StaticCell;
 
-use crate::battery::BatteryCalibration;
-use crate::config::{BATTRY_CALIBRATION, BUTTON_DELAY_MS, MQTT_BATTERY_SENSOR_TOPIC};
+use crate::config::{
+    BATTERY_DIVIDER, BUTTON_DELAY_MS, MQTT_BATTERY_SENSOR_TOPIC, NTP_SERVER, TASK_DEADLINE_SECS,
+};
 use crate::driver::{spawn_clock_task, ClockDriver};
 use crate::mqtt::mqtt_task;
 use crate::ntp::sync_time_with_ntp;
 use crate::rtc::{rtc_handler, RTC};
+use mainboard::analog::{
+    spawn_analog_service, AnalogChannel, AnalogConfig, AnalogIo, ChannelConfig, SamplingMode,
+    CHANNEL_COUNT,
+};
 use mainboard::board::{acquire_i2c_bus, init_i2c_bus, Board, D0Pin};
 use mainboard::create_board;
+use mainboard::flash::init_flash;
+use mainboard::net_error;
+use mainboard::netlog::{self, spawn_syslog_sink, SyslogConfig};
+use mainboard::ota::{spawn_ota_service, OtaConfig};
 use mainboard::power::PowerControllerIO;
+use mainboard::sntp::{spawn_sntp_service, SntpConfig};
 use mainboard::tasks::{spawn_ext_interrupt_task, spawn_power_controller, PowerStateReceiver};
+use mainboard::watchdog::{self, spawn_watchdog, WatchdogConfig};
+use mainboard::wifi::mdns::{spawn_mdns_responder, MdnsConfig};
 use mainboard::wifi::{initialize_wifi_sta, WifiResourceSta};
 
 extern crate alloc;
@@ -68,6 +79,7 @@ async fn main(spawner: Spawner) -> ! {
 
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
+    mainboard::crash::init();
 
     esp_alloc::heap_allocator!(#[esp_hal::ram(reclaimed)] size: 65536);
 
@@ -82,6 +94,9 @@ async fn main(spawner: Spawner) -> ! {
 
     init_i2c_bus(peripherals.I2C0, board.Sda, board.Scl).expect("Failed to initialize I2C bus");
 
+    init_flash(peripherals.FLASH);
+    let ota = spawn_ota_service(&spawner, OtaConfig::default());
+
     info!("Initializing WiFi...");
     let mut rng = esp_hal::rng::Rng::new();
     let radio_init =
@@ -90,6 +105,32 @@ async fn main(spawner: Spawner) -> ! {
         .init(initialize_wifi_sta(spawner, radio_init, peripherals.WIFI, &mut rng).await);
     info!("WiFi initialized!");
 
+    spawn_mdns_responder(
+        &spawner,
+        *wifi_res,
+        MdnsConfig {
+            hostname: config::MQTT_CLIENT_ID,
+            services: &[],
+        },
+    );
+
+    if let Some(server) = mainboard::config::SYSLOG_SERVER {
+        spawn_syslog_sink(
+            &spawner,
+            *wifi_res,
+            SyslogConfig {
+                server,
+                hostname: config::MQTT_CLIENT_ID,
+                app_name: "railclock",
+                ..Default::default()
+            },
+        );
+    }
+    let log_reader = netlog::reader().expect("Failed to get network log reader");
+    spawner
+        .spawn(log_forward::log_forward_task(log_reader))
+        .expect("Failed to spawn log forwarder");
+
     CLOCK_DRIVER.get_or_init(|| ClockDriver::new());
 
     let power_config = Default::default();
@@ -108,8 +149,16 @@ async fn main(spawner: Spawner) -> ! {
 
     spawn_ext_interrupt_task(&spawner, board.GlobalInt, power, Some(&RTC_INT_SIGNAL));
 
+    let sntp = spawn_sntp_service(
+        &spawner,
+        *wifi_res,
+        SntpConfig {
+            servers: NTP_SERVER,
+            ..Default::default()
+        },
+    );
     spawner
-        .spawn(sync_time_with_ntp(wifi_res))
+        .spawn(sync_time_with_ntp(sntp))
         .expect("Failed to start ntp sync task");
 
     spawner
@@ -126,28 +175,49 @@ async fn main(spawner: Spawner) -> ! {
         .spawn(listen_on_tick())
         .expect("Failed to spawn task awaiting RTC interrupts");
 
-    // Spawn battery monitor (ADC) which will publish its readings via MQTT helper
-    let adc_config = esp_hal::analog::adc::AdcConfig::new();
-    let battery_cal: battery::BatteryCalibration = BatteryCalibration {
-        battery_voltage_calibration: BATTRY_CALIBRATION
+    // Only the battery input is wired; average 8 readings per block
+    let mut analog_config = AnalogConfig {
+        channels: [ChannelConfig::DISABLED; CHANNEL_COUNT],
+        mode: SamplingMode::Oneshot {
+            interval: Duration::from_millis(100),
+        },
+        samples_per_block: 8,
+    };
+    *analog_config.channel_mut(AnalogChannel::BatVol) = ChannelConfig::volts(BATTERY_DIVIDER);
+    let analog_io = AnalogIo {
+        adc: peripherals.ADC1,
+        a0: board.A0,
+        a1: board.A1,
+        a2: board.A2,
+        a3: board.A3,
+        a4: board.A4,
+        bat_vol: board.BatVol,
+        boost_vol: board.BoostVol,
+        dma: None,
     };
+    let analog = spawn_analog_service(&spawner, analog_io, analog_config);
+
+    // Spawn battery monitor (ADC) which will publish its readings via MQTT helper
     let _battery = battery::spawn_battery_task(
         &spawner,
-        peripherals.ADC1,
-        adc_config,
-        battery_cal,
-        board.BatVol,
+        analog,
         Some(crate::config::BATTERY_PUBLISH_INTERVAL_SECS),
         Some(MQTT_BATTERY_SENSOR_TOPIC.as_str()),
     );
 
     spawner
-        .spawn(mqtt_task(wifi_res))
+        .spawn(mqtt_task(wifi_res, ota))
         .expect("Failed to spawn mqtt task");
 
+    // Feed the hardware watchdog only while every supervised task checks in
+    let main_watch = watchdog::register("main", Duration::from_secs(TASK_DEADLINE_SECS));
+    let timg1 = TimerGroup::new(peripherals.TIMG1);
+    spawn_watchdog(&spawner, timg1.wdt, WatchdogConfig::default());
+
     loop {
         match select(Timer::after(Duration::from_secs(1)), SHUTDOWN_SIGNAL.wait()).await {
             Either::First(_) => {
+                main_watch.check_in();
                 info!("Idle task...");
             }
             Either::Second(_) => {
@@ -161,7 +231,9 @@ async fn main(spawner: Spawner) -> ! {
     info!("Executing shutdown sequence: disable boost, set charger to Charging, float GPIOs");
     match power.set_boost_converter(false).await {
         mainboard::tasks::PowerResponse::Ok => info!("Boost converter disabled"),
-        mainboard::tasks::PowerResponse::Err(e) => info!("Failed to disable boost converter: {:?}", e),
+        mainboard::tasks::PowerResponse::Err(e) => {
+            info!("Failed to disable boost converter: {:?}", e)
+        }
     }
 
     match power.enter_shipping_mode().await {
@@ -233,10 +305,7 @@ async fn listen_on_tick() {
             .unwrap_or(false)
         {
             if let Err(e) = RTC.clear_alarm_matched_flag(mcp794xx::Alarm::Zero).await {
-                error!(
-                    "Failed to reset RTC alarm {:?}",
-                    format!("{:?}", e).as_str()
-                );
+                net_error!("Failed to reset RTC alarm {:?}", e);
             }
 
             info!("RTC fired advancing clock");
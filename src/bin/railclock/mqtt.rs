@@ -1,13 +1,15 @@
 use crate::{
     config::{
         MQTT_BATTERY_SENSOR_CONFIG_TOPIC, MQTT_BATTERY_SENSOR_DISCOVERY, MQTT_BATTERY_SENSOR_TOPIC,
-        MQTT_BUTTON_CONFIG_TOPIC, MQTT_PUSH_BUTTON_DISCOVERY, MQTT_BUTTON_TOPIC,
-        MQTT_NTP_SYNC_CONFIG_TOPIC, MQTT_NTP_SYNC_DISCOVERY, MQTT_NTP_SYNC_TOPIC,
-            MQTT_SHUTDOWN_CONFIG_TOPIC, MQTT_SHUTDOWN_TOPIC, MQTT_SHUTDOWN_DISCOVERY,
+        MQTT_BUTTON_CONFIG_TOPIC, MQTT_BUTTON_TOPIC, MQTT_CRASH_SENSOR_CONFIG_TOPIC,
+        MQTT_CRASH_SENSOR_DISCOVERY, MQTT_CRASH_SENSOR_TOPIC, MQTT_NTP_SYNC_CONFIG_TOPIC,
+        MQTT_NTP_SYNC_DISCOVERY, MQTT_NTP_SYNC_TOPIC, MQTT_PUSH_BUTTON_DISCOVERY,
+        MQTT_SHUTDOWN_CONFIG_TOPIC, MQTT_SHUTDOWN_DISCOVERY, MQTT_SHUTDOWN_TOPIC,
     },
     mqtt_queue::{OutgoingMessage, OUTGOING_CH},
 };
 use alloc::format;
+use alloc::string::String;
 use defmt::{error, info, warn};
 use embassy_futures::select::{select, Either};
 use embassy_net::tcp::TcpSocket;
@@ -25,14 +27,23 @@ use rust_mqtt::{
 use smoltcp::wire::{DnsQueryType, IpAddress};
 use static_cell::StaticCell;
 
-use crate::config::{MQTT_CLIENT_ID, MQTT_HOST, MQTT_PASSWORD, MQTT_PORT, MQTT_USER};
+use crate::config::{
+    MQTT_CLIENT_ID, MQTT_HOST, MQTT_MDNS_DISCOVERY, MQTT_PASSWORD, MQTT_PORT, MQTT_USER,
+};
 use crate::CLOCK_DRIVER;
+use mainboard::ota::{BootState, OtaHandle, OtaResponse};
+use mainboard::wifi::mdns::browse_service;
 use mainboard::wifi::WifiResourceSta;
+use mainboard::{net_error, net_warn};
 // battery handle removed; battery task moved into binary and publishes via mqtt_queue
 
 const RECONNECT_DELAY_MS: u64 = 5000;
 const MQTT_KEEPALIVE_SECS: u16 = 10;
 const BUFFER_SIZE: usize = 4096;
+const MQTT_MDNS_SERVICE: &str = "_mqtt._tcp";
+const MQTT_MDNS_TIMEOUT: Duration = Duration::from_secs(3);
+/// Home Assistant drops sensor states longer than this.
+const HA_STATE_MAX_LEN: usize = 255;
 
 // Static buffers for MQTT - allocated once, reused across reconnections
 static TCP_RX_BUF: StaticCell<[u8; 4096]> = StaticCell::new();
@@ -113,8 +124,37 @@ async fn publish_discovery(
     Ok(())
 }
 
+async fn publish_crash_report(
+    client: &mut AppClient<'_, '_>,
+    report: &mainboard::crash::CrashRecord,
+) -> Result<(), AppMqttError> {
+    let mut summary = String::new();
+    let _ = report.write_summary(&mut summary);
+    let mut len = summary.len().min(HA_STATE_MAX_LEN);
+    while !summary.is_char_boundary(len) {
+        len -= 1;
+    }
+    summary.truncate(len);
+    net_warn!("Publishing crash report from previous run: {}", summary);
+
+    let topic = make_topic_name(MQTT_CRASH_SENSOR_TOPIC.as_str())
+        .ok_or(AppMqttError::StringConversionError)?;
+    let options = PublicationOptions {
+        retain: true,
+        topic,
+        qos: QoS::AtMostOnce,
+    };
+
+    client
+        .publish(&options, summary.as_bytes().into())
+        .await
+        .map_err(|_| AppMqttError::MqttError)?;
+
+    Ok(())
+}
+
 #[embassy_executor::task]
-pub(crate) async fn mqtt_task(sta_stack: &'static WifiResourceSta) {
+pub(crate) async fn mqtt_task(sta_stack: &'static WifiResourceSta, ota: OtaHandle) {
     // Initialize static buffers once
     let tcp_rx_buf = TCP_RX_BUF.init([0u8; 4096]);
     let tcp_tx_buf = TCP_TX_BUF.init([0u8; 4096]);
@@ -128,8 +168,9 @@ pub(crate) async fn mqtt_task(sta_stack: &'static WifiResourceSta) {
     info!("MQTT: Network configured");
 
     loop {
-        if let Err(e) = mqtt_connection_loop(sta_stack, tcp_rx_buf, tcp_tx_buf, mqtt_buf).await {
-            error!("MQTT connection error: {:?}", &e);
+        if let Err(e) = mqtt_connection_loop(sta_stack, ota, tcp_rx_buf, tcp_tx_buf, mqtt_buf).await
+        {
+            net_error!("MQTT connection error: {:?}", e);
         }
         warn!("MQTT: Reconnecting in {} ms...", RECONNECT_DELAY_MS);
         Timer::after(Duration::from_millis(RECONNECT_DELAY_MS)).await;
@@ -144,28 +185,42 @@ enum AppMqttError {
     MqttError,
 }
 
+async fn resolve_mqtt_endpoint(
+    sta_stack: &embassy_net::Stack<'static>,
+) -> Result<(smoltcp::wire::Ipv4Address, u16), AppMqttError> {
+    info!("MQTT: Resolving host: {}", MQTT_HOST);
+    let first = sta_stack
+        .dns_query(MQTT_HOST, DnsQueryType::A)
+        .await
+        .ok()
+        .and_then(|addrs| addrs.first().copied());
+
+    match first {
+        Some(IpAddress::Ipv4(ip)) => Ok((ip, MQTT_PORT)),
+        None if MQTT_MDNS_DISCOVERY => {
+            warn!("MQTT: DNS lookup failed, browsing mDNS for a broker");
+            browse_service(*sta_stack, MQTT_MDNS_SERVICE, MQTT_MDNS_TIMEOUT)
+                .await
+                .map(|found| (found.address, found.port))
+                .ok_or(AppMqttError::DnsLookupFailed)
+        }
+        None => Err(AppMqttError::DnsLookupFailed),
+    }
+}
+
 async fn mqtt_connection_loop(
     sta_stack: &embassy_net::Stack<'static>,
+    ota: OtaHandle,
     tcp_rx_buf: &mut [u8; 4096],
     tcp_tx_buf: &mut [u8; 4096],
     mqtt_buf: &mut [u8; BUFFER_SIZE],
 ) -> Result<(), AppMqttError> {
-    info!("MQTT: Resolving host: {}", MQTT_HOST);
-    let mqtt_addrs = sta_stack
-        .dns_query(MQTT_HOST, DnsQueryType::A)
-        .await
-        .map_err(|_| AppMqttError::DnsLookupFailed)?;
-
-    let mqtt_ip = mqtt_addrs.first().ok_or(AppMqttError::DnsLookupFailed)?;
-    info!("MQTT: Resolved to {:?}", mqtt_ip);
-
-    let remote_endpoint = match mqtt_ip {
-        IpAddress::Ipv4(ip) => (*ip, MQTT_PORT),
-    };
+    let remote_endpoint = resolve_mqtt_endpoint(sta_stack).await?;
+    info!("MQTT: Resolved to {:?}", remote_endpoint);
 
     let mut socket = TcpSocket::new(*sta_stack, tcp_rx_buf, tcp_tx_buf);
 
-    info!("MQTT: Connecting TCP to port {}", MQTT_PORT);
+    info!("MQTT: Connecting TCP to port {}", remote_endpoint.1);
     socket
         .connect(remote_endpoint)
         .await
@@ -264,6 +319,25 @@ async fn mqtt_connection_loop(
     )
     .await?;
 
+    publish_discovery(
+        &mut client,
+        &MQTT_CRASH_SENSOR_CONFIG_TOPIC,
+        MQTT_CRASH_SENSOR_DISCOVERY.as_str(),
+    )
+    .await?;
+
+    if let Some(report) = mainboard::crash::pending_report() {
+        publish_crash_report(&mut client, &report).await?;
+        mainboard::crash::clear_report();
+    }
+
+    // Reaching the broker proves a freshly updated image works
+    if ota.boot_state() == BootState::PendingVerify {
+        if let OtaResponse::Ok(_) = ota.confirm_healthy().await {
+            info!("OTA image confirmed after MQTT connect");
+        }
+    }
+
     loop {
         match select(client.poll_header(), OUTGOING_CH.receive()).await {
             Either::First(poll_header_res) => match poll_header_res {
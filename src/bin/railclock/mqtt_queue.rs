@@ -13,6 +13,10 @@ pub enum OutgoingMessage {
 
 pub static OUTGOING_CH: Channel<CriticalSectionRawMutex, OutgoingMessage, 16> = Channel::new();
 
+/// Log lines are refused once fewer slots than this are free, so a log storm
+/// cannot delay battery and button updates.
+const LOG_RESERVED_SLOTS: usize = 8;
+
 /// Try to publish a message by enqueuing it on the outgoing channel.
 /// Returns `Ok(())` if enqueued, `Err(())` if the queue is full.
 pub fn mqtt_publish(topic: &'static str, payload: &str, retain: bool) -> Result<(), ()> {
@@ -24,3 +28,11 @@ pub fn mqtt_publish(topic: &'static str, payload: &str, retain: bool) -> Result<
         })
         .map_err(|_| ())
 }
+
+/// Like [`mqtt_publish`], but gives up while the queue is half full.
+pub fn mqtt_publish_log(topic: &'static str, payload: &str) -> Result<(), ()> {
+    if OUTGOING_CH.free_capacity() < LOG_RESERVED_SLOTS {
+        return Err(());
+    }
+    mqtt_publish(topic, payload, false)
+}
@@ -1,107 +1,58 @@
-use core::net::{IpAddr, SocketAddr};
-
-use alloc::format;
-use defmt::{error, info};
-use embassy_futures::select;
-use embassy_net::udp::{PacketMetadata, UdpSocket};
-use embassy_time::{Duration, Instant, Timer};
+use defmt::info;
+use embassy_futures::select::{select, Either};
+use embassy_time::Instant;
 use mcp794xx::NaiveDateTime;
-use smoltcp::wire::DnsQueryType;
-use sntpc::{get_time, NtpContext, NtpTimestampGenerator};
 
-use crate::NTP_TRIGGER;
-use crate::config::NTP_SERVER;
 use crate::rtc::RTC;
-use mainboard::wifi::WifiResourceSta;
-
-#[derive(Copy, Clone)]
-struct Timestamp {
-    instant: Instant,
-}
-
-impl Default for Timestamp {
-    fn default() -> Self {
-        Timestamp {
-            instant: Instant::now(),
-        }
-    }
-}
-
-impl NtpTimestampGenerator for Timestamp {
-    fn init(&mut self) {
-        self.instant = Instant::now();
-    }
-
-    fn timestamp_sec(&self) -> u64 {
-        self.instant.as_secs()
-    }
-
-    fn timestamp_subsec_micros(&self) -> u32 {
-        (self.instant.as_millis() - self.instant.as_secs() * 1000) as u32
-    }
-}
+use crate::NTP_TRIGGER;
+use mainboard::sntp::{SntpHandle, SyncQuality};
+use mainboard::{net_error, net_info};
 
+/// Bridges the library SNTP service and the MCP794xx RTC: seeds the time
+/// mapping from the RTC at boot, forwards manual sync requests and writes
+/// every network sync back into the RTC.
 #[embassy_executor::task]
-pub(crate) async fn sync_time_with_ntp(sta_stack: &'static WifiResourceSta) {
-    let mut rx_meta = [PacketMetadata::EMPTY; 16];
-    let mut tx_meta = [PacketMetadata::EMPTY; 16];
-    let mut rx_buf = [0u8; 4096];
-    let mut tx_buf = [0u8; 4096];
-
-    // Wait for network to be ready
-    info!("NTP: Waiting for network link...");
-    sta_stack.wait_link_up().await;
-    info!("NTP: Link is up, waiting for IP configuration...");
-    sta_stack.wait_config_up().await;
-    info!("NTP: Network configured");
-
-    // Create UDP socket bound to ephemeral port
-    let mut socket = UdpSocket::new(
-        *sta_stack,
-        &mut rx_meta,
-        &mut rx_buf,
-        &mut tx_meta,
-        &mut tx_buf,
-    );
-
-    socket.bind(0).unwrap();
-
-    let ctx = NtpContext::new(Timestamp::default());
-
-    let ntp_addrs = sta_stack.dns_query(NTP_SERVER, DnsQueryType::A).await;
-    let ntp_addrs = ntp_addrs.unwrap();
-
-    if ntp_addrs.is_empty() {
-        error!("Failed to resolve DNS");
-        return;
+pub(crate) async fn sync_time_with_ntp(sntp: SntpHandle) {
+    let mut receiver = sntp.receiver().expect("Failed to get SNTP state receiver");
+
+    match RTC.get_datetime().await {
+        Ok(datetime) => {
+            let utc_us =
+                datetime.timestamp() * 1_000_000 + datetime.timestamp_subsec_micros() as i64;
+            sntp.seed_from_rtc(utc_us);
+            info!("NTP: Seeded time from RTC");
+        }
+        Err(e) => net_error!("Failed to read RTC time, reason: {:?}", e),
     }
 
     loop {
-        let addr: IpAddr = ntp_addrs[0].into();
-        let result = get_time(SocketAddr::from((addr, 123)), &socket, ctx).await;
+        match select(NTP_TRIGGER.wait(), receiver.changed()).await {
+            Either::First(()) => {
+                info!("NTP: Manual sync requested");
+                sntp.request_sync();
+            }
+            Either::Second(mapping) => {
+                if mapping.quality < SyncQuality::Coarse {
+                    continue;
+                }
 
-        match result {
-            Ok(time) => {
+                let utc_us = mapping.utc_micros_at(Instant::now().as_micros());
                 let datetime = NaiveDateTime::from_timestamp(
-                    time.sec() as i64,
-                    time.sec_fraction() * (1_000_000_000 / u32::MAX),
+                    utc_us.div_euclid(1_000_000),
+                    (utc_us.rem_euclid(1_000_000) * 1_000) as u32,
                 );
                 if let Err(e) = RTC.set_datetime(datetime).await {
-                    error!(
-                        "Failed to set RTC time, reason: {}",
-                        format!("{:?}", e).as_str()
-                    );
+                    net_error!("Failed to set RTC time, reason: {:?}", e);
                 }
 
-                info!("Time: {:?}", time);
-            }
-            Err(e) => {
-                error!("Error getting time: {:?}", e);
+                net_info!(
+                    "Time: {} (offset {} us, delay {} us, {:?})",
+                    datetime,
+                    mapping.offset_us,
+                    mapping.delay_us,
+                    mapping.quality
+                );
             }
         }
-
-        let timeout = Timer::after(Duration::from_secs(1200));
-        let trigger = NTP_TRIGGER.wait();
-        let _ = select::select(timeout, trigger).await;
     }
 }
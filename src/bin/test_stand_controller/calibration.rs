@@ -0,0 +1,295 @@
+//! Tare and span calibration of the tensometer and pressure channels.
+//!
+//! `cmd/calibrate` measures the input voltage of a channel, averaged over
+//! `CALIBRATION_AVERAGE_BLOCKS` analog blocks, and turns it into a linear
+//! [`Calibration`]: `TARE` records the zero point, `SPAN` with a known load or
+//! pressure sets the gain through the zero point. The result goes to the
+//! analog service, so every consumer of scaled values sees it, is stored in
+//! flash and reported on the retained `status/calibration` topic.
+
+use alloc::format;
+use core::cell::Cell;
+
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use mainboard::analog::{AnalogChannel, AnalogHandle, Calibration};
+use mainboard::storage::{self, StorageError, StorageKey};
+use mainboard::{net_info, net_warn};
+
+use crate::config::CALIBRATION_AVERAGE_BLOCKS;
+use crate::mqtt::commands::calibrate::{CalibrateCommand, CalibrationChannel};
+use crate::mqtt::queue;
+use crate::mqtt::sensors::calibration::CalibrationStatusPacket;
+use crate::sensor_collection::{PRESSURE_COMBUSTION, PRESSURE_TANK, TENSOMETER};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+const CALIBRATION_KEY: StorageKey = StorageKey(0);
+const STORED_VERSION: u8 = 1;
+const STORED_CHANNEL_LEN: usize = 9;
+const STORED_LEN: usize = 1 + STORED_CHANNEL_LEN * CalibrationChannel::ALL.len();
+/// Smallest input change between tare and span that gives a usable gain.
+const MIN_SPAN_VOLTS: f32 = 0.005;
+
+/// Calibration of one channel, relative to the input voltage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelCalibration {
+    /// Input voltage at zero load.
+    pub zero_volts: f32,
+    /// Units per volt above `zero_volts`.
+    pub gain: f32,
+    /// A span was recorded, so values are in engineering units.
+    pub spanned: bool,
+}
+
+impl ChannelCalibration {
+    pub const RESET: Self = Self {
+        zero_volts: 0.0,
+        gain: 1.0,
+        spanned: false,
+    };
+
+    pub fn offset(&self) -> f32 {
+        -self.gain * self.zero_volts
+    }
+
+    pub fn calibration(&self) -> Calibration {
+        Calibration::Linear {
+            gain: self.gain,
+            offset: self.offset(),
+        }
+    }
+
+    pub fn unit(&self, channel: CalibrationChannel) -> &'static str {
+        match (self.spanned, channel) {
+            (false, _) => "V",
+            (true, CalibrationChannel::Tensometer) => "N",
+            (true, _) => "bar",
+        }
+    }
+
+    fn tare(&mut self, volts: f32) {
+        self.zero_volts = volts;
+    }
+
+    fn span(&mut self, volts: f32, value: f32) -> bool {
+        let delta = volts - self.zero_volts;
+        if delta.abs() < MIN_SPAN_VOLTS {
+            return false;
+        }
+        self.gain = value / delta;
+        self.spanned = true;
+        true
+    }
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static CALIBRATE_COMMANDS: Channel<CriticalSectionRawMutex, CalibrateCommand, 2> = Channel::new();
+/// Applied calibration, `None` until loaded from flash
+static ACTIVE: Mutex<CriticalSectionRawMutex, Cell<Option<[ChannelCalibration; 3]>>> =
+    Mutex::new(Cell::new(None));
+
+pub fn submit_calibrate_command(command: CalibrateCommand) {
+    if CALIBRATE_COMMANDS.try_send(command).is_err() {
+        warn!("Calibration command dropped: busy");
+        queue::publish_command_log("Calibration rejected: busy");
+    }
+}
+
+pub fn republish_calibration() {
+    if let Some(calibrations) = ACTIVE.lock(|active| active.get()) {
+        publish_status(&calibrations);
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+const fn analog_channel(channel: CalibrationChannel) -> AnalogChannel {
+    match channel {
+        CalibrationChannel::Tensometer => TENSOMETER,
+        CalibrationChannel::PressureTank => PRESSURE_TANK,
+        CalibrationChannel::PressureCombustion => PRESSURE_COMBUSTION,
+    }
+}
+
+/// Mean input voltage of `channel` over the next blocks.
+async fn measure_volts(analog: &AnalogHandle, channel: CalibrationChannel) -> Option<f32> {
+    let mut blocks = analog.block_subscriber()?;
+    let channel = analog_channel(channel);
+
+    let mut sum_mv = 0u64;
+    let mut count = 0u64;
+    let mut divider = 1.0;
+    for _ in 0..CALIBRATION_AVERAGE_BLOCKS {
+        let block = blocks.next_message_pure().await;
+        let samples = block.millivolts(channel)?;
+        sum_mv += samples.iter().map(|&mv| mv as u64).sum::<u64>();
+        count += samples.len() as u64;
+        divider = block.channel_config(channel).scaling.divider;
+    }
+
+    if count == 0 {
+        return None;
+    }
+    Some(sum_mv as f32 / count as f32 * divider / 1000.0)
+}
+
+fn encode(calibrations: &[ChannelCalibration; 3]) -> [u8; STORED_LEN] {
+    let mut out = [0u8; STORED_LEN];
+    out[0] = STORED_VERSION;
+    for (chunk, calibration) in out[1..]
+        .chunks_exact_mut(STORED_CHANNEL_LEN)
+        .zip(calibrations)
+    {
+        chunk[0..4].copy_from_slice(&calibration.zero_volts.to_le_bytes());
+        chunk[4..8].copy_from_slice(&calibration.gain.to_le_bytes());
+        chunk[8] = calibration.spanned as u8;
+    }
+    out
+}
+
+fn decode(bytes: &[u8]) -> Option<[ChannelCalibration; 3]> {
+    if bytes.len() != STORED_LEN || bytes[0] != STORED_VERSION {
+        return None;
+    }
+
+    let mut calibrations = [ChannelCalibration::RESET; 3];
+    for (calibration, chunk) in calibrations
+        .iter_mut()
+        .zip(bytes[1..].chunks_exact(STORED_CHANNEL_LEN))
+    {
+        let zero_volts = f32::from_le_bytes(chunk[0..4].try_into().ok()?);
+        let gain = f32::from_le_bytes(chunk[4..8].try_into().ok()?);
+        if !zero_volts.is_finite() || !gain.is_finite() {
+            return None;
+        }
+        *calibration = ChannelCalibration {
+            zero_volts,
+            gain,
+            spanned: chunk[8] != 0,
+        };
+    }
+    Some(calibrations)
+}
+
+async fn load() -> [ChannelCalibration; 3] {
+    let mut bytes = [0u8; STORED_LEN];
+    match storage::load(CALIBRATION_KEY, &mut bytes).await {
+        Ok(len) => decode(&bytes[..len]).unwrap_or_else(|| {
+            net_warn!("Stored calibration invalid, using defaults");
+            [ChannelCalibration::RESET; 3]
+        }),
+        Err(StorageError::NotFound) => [ChannelCalibration::RESET; 3],
+        Err(e) => {
+            net_warn!("Failed to load calibration: {:?}", e);
+            [ChannelCalibration::RESET; 3]
+        }
+    }
+}
+
+/// Push every channel to the analog service and report the set.
+async fn apply(analog: &AnalogHandle, calibrations: &[ChannelCalibration; 3]) {
+    for channel in CalibrationChannel::ALL {
+        let calibration = &calibrations[channel.index()];
+        analog
+            .set_calibration(
+                analog_channel(channel),
+                calibration.calibration(),
+                calibration.unit(channel),
+            )
+            .await;
+    }
+    ACTIVE.lock(|active| active.set(Some(*calibrations)));
+    publish_status(calibrations);
+}
+
+fn publish_status(calibrations: &[ChannelCalibration; 3]) {
+    let packet = CalibrationStatusPacket {
+        channels: *calibrations,
+    };
+    if queue::publish_calibration_status(packet).is_err() {
+        warn!("Calibration status not queued: outbound queue full");
+    }
+}
+
+async fn handle_command(
+    analog: &AnalogHandle,
+    calibrations: &mut [ChannelCalibration; 3],
+    command: CalibrateCommand,
+) {
+    let (channel, updated) = match command {
+        CalibrateCommand::Tare(channel) => {
+            let Some(volts) = measure_volts(analog, channel).await else {
+                queue::publish_command_log("Calibration failed: no samples");
+                return;
+            };
+            let mut updated = calibrations[channel.index()];
+            updated.tare(volts);
+            net_info!("Calibration: {} tared at {} V", channel.name(), volts);
+            (channel, updated)
+        }
+        CalibrateCommand::Span { channel, value } => {
+            let Some(volts) = measure_volts(analog, channel).await else {
+                queue::publish_command_log("Calibration failed: no samples");
+                return;
+            };
+            let mut updated = calibrations[channel.index()];
+            if !updated.span(volts, value) {
+                net_warn!(
+                    "Calibration: {} span rejected, input {} V is at the zero point",
+                    channel.name(),
+                    volts
+                );
+                queue::publish_command_log("Calibration failed: span too small");
+                return;
+            }
+            net_info!(
+                "Calibration: {} gain {} {}/V",
+                channel.name(),
+                updated.gain,
+                updated.unit(channel)
+            );
+            (channel, updated)
+        }
+        CalibrateCommand::Reset(channel) => {
+            net_info!("Calibration: {} reset", channel.name());
+            (channel, ChannelCalibration::RESET)
+        }
+    };
+
+    calibrations[channel.index()] = updated;
+    apply(analog, calibrations).await;
+
+    match storage::store(CALIBRATION_KEY, &encode(calibrations)).await {
+        Ok(()) => queue::publish_command_log(&format!("Calibration: {} updated", channel.name())),
+        Err(e) => {
+            net_warn!("Failed to store calibration: {:?}", e);
+            queue::publish_command_log("Calibration applied, not stored");
+        }
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn calibration_task(analog: AnalogHandle) {
+    let mut calibrations = load().await;
+    apply(&analog, &calibrations).await;
+    info!("Calibration loaded");
+
+    loop {
+        let command = CALIBRATE_COMMANDS.receive().await;
+        handle_command(&analog, &mut calibrations, command).await;
+    }
+}
@@ -0,0 +1,249 @@
+//! Camera remote: half-press (wake and focus) on `D3`, full press on `D4`.
+//!
+//! Runs one [`Job`] at a time; shots asked for meanwhile queue up behind it.
+//! A time-lapse [`Interval`] runs in the background and takes its shots
+//! whenever the camera is free. Every change of the outputs is published on
+//! `sensor/camera` with its boot timestamp.
+
+pub mod shot;
+
+use alloc::format;
+
+use defmt::{info, warn};
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::Output;
+use mainboard::{net_warn, watchdog};
+
+use crate::config::{
+    CAMERA_BULB_MAX_MS, CAMERA_FIRE_INTERVAL_MS, CAMERA_PRESS_MS, TASK_DEADLINE_MS,
+    TASK_IDLE_CHECK_IN_MS,
+};
+use crate::mqtt::commands::camera::CameraCommand;
+use crate::mqtt::queue;
+use crate::mqtt::sensors::camera::CameraPacket;
+use shot::{Interval, Job, Levels, TIMING};
+
+pub use shot::SHOT_CYCLE_MS;
+
+const SHOT: Job = Job::Shots {
+    count: 1,
+    hold_ms: CAMERA_PRESS_MS,
+};
+/// Longest time-lapse period.
+const INTERVAL_MAX_MS: u32 = 3_600_000;
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+enum CameraRequest {
+    Start(Job),
+    Interval { period_ms: u32, count: Option<u16> },
+    EndInterval,
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static CAMERA_REQUESTS: Channel<CriticalSectionRawMutex, CameraRequest, 8> = Channel::new();
+/// Cuts the current job short, ends the interval and drops queued requests
+static CAMERA_STOP: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// One ordinary shot.
+pub fn shoot() {
+    request(CameraRequest::Start(SHOT));
+}
+
+/// Time-lapse from T-0 until FIRE ends, when `CAMERA_FIRE_INTERVAL_MS` sets one.
+pub fn start_fire_interval() {
+    if CAMERA_FIRE_INTERVAL_MS != 0 {
+        request(CameraRequest::Interval {
+            period_ms: CAMERA_FIRE_INTERVAL_MS,
+            count: None,
+        });
+    }
+}
+
+/// End the time-lapse, letting a shot in progress finish.
+pub fn end_interval() {
+    request(CameraRequest::EndInterval);
+}
+
+pub fn send_camera_command(command: CameraCommand) {
+    let request = match command {
+        CameraCommand::Shoot => CameraRequest::Start(SHOT),
+        CameraCommand::Burst(count) => CameraRequest::Start(Job::Shots {
+            count,
+            hold_ms: CAMERA_PRESS_MS,
+        }),
+        CameraCommand::Bulb(hold_ms) if hold_ms > CAMERA_BULB_MAX_MS => {
+            return reject(&format!("bulb longer than {} ms", CAMERA_BULB_MAX_MS));
+        }
+        CameraCommand::Bulb(hold_ms) => CameraRequest::Start(Job::Shots { count: 1, hold_ms }),
+        CameraCommand::Interval { period_ms, .. }
+            if !(SHOT_CYCLE_MS..=INTERVAL_MAX_MS).contains(&period_ms) =>
+        {
+            return reject(&format!(
+                "interval outside {} to {} ms",
+                SHOT_CYCLE_MS, INTERVAL_MAX_MS
+            ));
+        }
+        CameraCommand::Interval { period_ms, count } => {
+            CameraRequest::Interval { period_ms, count }
+        }
+        CameraCommand::Focus if CAMERA_FOCUS_MS == 0 => {
+            return reject("half-press disabled");
+        }
+        CameraCommand::Focus => CameraRequest::Start(Job::Focus),
+        CameraCommand::Stop => {
+            CAMERA_STOP.signal(());
+            return;
+        }
+    };
+    request(request);
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn request(request: CameraRequest) {
+    if CAMERA_REQUESTS.try_send(request).is_err() {
+        warn!("Camera channel full, dropping request");
+    }
+}
+
+fn reject(reason: &str) {
+    let message = format!("Camera rejected: {}", reason);
+    net_warn!("{}", message);
+    queue::publish_command_log(&message);
+}
+
+fn timestamp_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+/// Drive the outputs to `wanted` and publish the change, counting full
+/// presses in `shots`. The half-press goes on before and off after the full
+/// press.
+fn set_levels(
+    focus_pin: &mut Output<'static>,
+    shutter_pin: &mut Output<'static>,
+    current: &mut Levels,
+    wanted: Levels,
+    shots: &mut u32,
+) {
+    if wanted == *current {
+        return;
+    }
+    if wanted.focus {
+        focus_pin.set_high();
+    }
+    shutter_pin.set_level(wanted.shutter.into());
+    if !wanted.focus {
+        focus_pin.set_low();
+    }
+    let timestamp_ms = timestamp_ms();
+
+    if wanted.shutter && !current.shutter {
+        *shots += 1;
+        info!("Camera shot {}", *shots);
+    }
+    *current = wanted;
+    let packet = CameraPacket {
+        timestamp_ms,
+        focus: wanted.focus,
+        shutter: wanted.shutter,
+        shot: *shots,
+    };
+    if queue::publish_camera_event(packet).is_err() {
+        warn!("Outbound queue full, dropping camera event");
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn camera_task(mut focus_pin: Output<'static>, mut shutter_pin: Output<'static>) {
+    info!("Camera task started");
+    let watch = watchdog::register("camera", Duration::from_millis(TASK_DEADLINE_MS));
+    let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+    let mut job: Option<(Job, u32)> = None;
+    let mut interval: Option<Interval> = None;
+    let mut levels = Levels::RELEASED;
+    let mut shots = 0;
+
+    loop {
+        let now = timestamp_ms();
+        if job.is_none() {
+            if let Some(schedule) = &mut interval {
+                if schedule.take(now) {
+                    job = Some((SHOT, now));
+                }
+                if schedule.finished() {
+                    interval = None;
+                }
+            }
+        }
+
+        let (wanted, next_change) = match job {
+            Some((current, start_ms)) => {
+                let elapsed_ms = now.wrapping_sub(start_ms);
+                match current.next_change(&TIMING, elapsed_ms) {
+                    Some(ms) => (
+                        current.levels(&TIMING, elapsed_ms),
+                        Instant::now() + Duration::from_millis(ms as u64),
+                    ),
+                    None => {
+                        job = None;
+                        continue;
+                    }
+                }
+            }
+            None => (
+                Levels::RELEASED,
+                interval.map_or(Instant::MAX, |schedule| {
+                    Instant::now() + Duration::from_millis(schedule.until_next(now) as u64)
+                }),
+            ),
+        };
+        set_levels(
+            &mut focus_pin,
+            &mut shutter_pin,
+            &mut levels,
+            wanted,
+            &mut shots,
+        );
+
+        // Requests wait for the current job
+        let busy = job.is_some();
+        let next_request = async {
+            if busy {
+                core::future::pending().await
+            } else {
+                CAMERA_REQUESTS.receive().await
+            }
+        };
+        let event = select3(next_request, Timer::at(next_change), CAMERA_STOP.wait());
+        match watch.idle(idle_period, event).await {
+            Either3::First(CameraRequest::Start(next)) => job = Some((next, timestamp_ms())),
+            Either3::First(CameraRequest::Interval { period_ms, count }) => {
+                interval = Some(Interval::new(period_ms, count, timestamp_ms()));
+            }
+            Either3::First(CameraRequest::EndInterval) => interval = None,
+            Either3::Second(()) => {}
+            Either3::Third(()) => {
+                job = None;
+                interval = None;
+                CAMERA_REQUESTS.clear();
+            }
+        }
+    }
+}
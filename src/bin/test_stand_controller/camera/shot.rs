@@ -0,0 +1,163 @@
+//! Camera jobs as data, the timeline that turns them into half-press and
+//! full-press levels, and the time-lapse schedule.
+//!
+//! Times are milliseconds and may wrap.
+
+use crate::config::{CAMERA_FOCUS_MS, CAMERA_PRESS_MS, CAMERA_RELEASE_MS};
+
+/// The configured timing.
+pub const TIMING: Timing = Timing {
+    focus_ms: CAMERA_FOCUS_MS,
+    press_ms: CAMERA_PRESS_MS,
+    release_ms: CAMERA_RELEASE_MS,
+};
+/// One shot, press and release; shots closer than this queue up.
+pub const SHOT_CYCLE_MS: u32 = TIMING.shot_ms();
+
+/// How long each part of a shot lasts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Timing {
+    /// Half-press ahead of the first full press; 0 leaves the half-press
+    /// output off
+    pub focus_ms: u32,
+    /// Full press of an ordinary shot
+    pub press_ms: u32,
+    /// Release after every full press before the next may start
+    pub release_ms: u32,
+}
+
+impl Timing {
+    /// One ordinary shot, release included.
+    pub const fn shot_ms(&self) -> u32 {
+        self.focus_ms + self.press_ms + self.release_ms
+    }
+}
+
+/// Levels of the two outputs, `true` pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Levels {
+    pub focus: bool,
+    pub shutter: bool,
+}
+
+impl Levels {
+    pub const RELEASED: Self = Self {
+        focus: false,
+        shutter: false,
+    };
+}
+
+/// One run of the outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Job {
+    /// `count` full presses of `hold_ms` each, under one half-press
+    Shots { count: u16, hold_ms: u32 },
+    /// Half-press only, to wake the camera
+    Focus,
+}
+
+impl Job {
+    /// From the start until the outputs are free again.
+    pub const fn duration_ms(self, timing: &Timing) -> u32 {
+        match self {
+            Self::Shots { count, hold_ms } => {
+                timing.focus_ms + count as u32 * (hold_ms + timing.release_ms)
+            }
+            Self::Focus => timing.focus_ms + timing.release_ms,
+        }
+    }
+
+    /// Levels `elapsed_ms` after the job started.
+    pub fn levels(self, timing: &Timing, elapsed_ms: u32) -> Levels {
+        match self {
+            Self::Shots { count, hold_ms } => {
+                let slot = hold_ms + timing.release_ms;
+                let last_release = timing.focus_ms + (count.max(1) as u32 - 1) * slot + hold_ms;
+                let shutter = elapsed_ms
+                    .checked_sub(timing.focus_ms)
+                    .is_some_and(|pressing| {
+                        pressing / slot < count as u32 && pressing % slot < hold_ms
+                    });
+                Levels {
+                    focus: timing.focus_ms != 0 && elapsed_ms < last_release,
+                    shutter,
+                }
+            }
+            Self::Focus => Levels {
+                focus: elapsed_ms < timing.focus_ms,
+                shutter: false,
+            },
+        }
+    }
+
+    /// Milliseconds from `elapsed_ms` to the next time [`Job::levels`] may
+    /// change or the job ends, `None` once it has ended.
+    pub fn next_change(self, timing: &Timing, elapsed_ms: u32) -> Option<u32> {
+        let end = self.duration_ms(timing);
+        if elapsed_ms >= end {
+            return None;
+        }
+        let edge = match self {
+            _ if elapsed_ms < timing.focus_ms => timing.focus_ms,
+            Self::Shots { hold_ms, .. } => {
+                let slot = hold_ms + timing.release_ms;
+                let pressing = elapsed_ms - timing.focus_ms;
+                let start = timing.focus_ms + pressing / slot * slot;
+                if pressing % slot < hold_ms {
+                    start + hold_ms
+                } else {
+                    start + slot
+                }
+            }
+            Self::Focus => end,
+        };
+        Some(edge.min(end) - elapsed_ms)
+    }
+}
+
+/// Time-lapse schedule: a shot every `period_ms`, a number of times or
+/// without end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Interval {
+    period_ms: u32,
+    /// Shots still to take, `None` without end
+    remaining: Option<u16>,
+    next_ms: u32,
+}
+
+impl Interval {
+    /// The first shot is due at `now_ms`.
+    pub const fn new(period_ms: u32, count: Option<u16>, now_ms: u32) -> Self {
+        Self {
+            period_ms,
+            remaining: count,
+            next_ms: now_ms,
+        }
+    }
+
+    /// Whether a shot is due at `now_ms`, moving on to the next one. Shots
+    /// that fell while the camera was busy are skipped, not counted.
+    pub fn take(&mut self, now_ms: u32) -> bool {
+        let behind = now_ms.wrapping_sub(self.next_ms);
+        if self.finished() || (behind as i32) < 0 {
+            return false;
+        }
+        let periods = behind / self.period_ms + 1;
+        self.next_ms = self
+            .next_ms
+            .wrapping_add(periods.wrapping_mul(self.period_ms));
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        true
+    }
+
+    /// Milliseconds from `now_ms` until the next shot is due.
+    pub fn until_next(&self, now_ms: u32) -> u32 {
+        (self.next_ms.wrapping_sub(now_ms) as i32).max(0) as u32
+    }
+
+    pub fn finished(&self) -> bool {
+        self.remaining == Some(0)
+    }
+}
@@ -0,0 +1,383 @@
+//! Fire capture: full-resolution windows of the fast channels around a
+//! trigger, uploaded over MQTT once the window is complete.
+//!
+//! The live `sensor/adc/fast/*` stream is best effort and drops batches when
+//! the outbound queue fills. This keeps the last seconds of every fast
+//! sample in RAM instead; entering FIRE, a threshold crossing or
+//! `cmd/capture/trigger` freezes `CAPTURE_PRE_TRIGGER_MS` of history plus
+//! `CAPTURE_POST_TRIGGER_MS` after the trigger. The window is then sent one
+//! message at a time, each resent until `cmd/capture/ack` confirms it, and
+//! dropped once a message goes unacknowledged `CAPTURE_MAX_RESENDS` times.
+//! Threshold and manual triggers are ignored while it uploads; entering FIRE
+//! drops the upload and captures the fire instead.
+
+mod window;
+
+use defmt::warn;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant};
+use mainboard::analog::{AnalogBlock, AnalogBlockSubscriber};
+use mainboard::watchdog::{self, TaskWatch};
+use mainboard::{net_info, net_warn};
+use static_cell::ConstStaticCell;
+
+use crate::config::{
+    CAPTURE_ACK_TIMEOUT_MS, CAPTURE_MAX_RESENDS, CAPTURE_POST_TRIGGER_MS, CAPTURE_PRE_TRIGGER_MS,
+    CAPTURE_THRESHOLD_MV, TASK_DEADLINE_MS,
+};
+use crate::mqtt::codec::U12_MAX;
+use crate::mqtt::commands::capture::CaptureCommand;
+use crate::mqtt::queue;
+use crate::mqtt::sensors::capture::{
+    CaptureChunkPacket, CaptureMetaPacket, CaptureTrigger, CAPTURE_CHUNK_SAMPLES, CAPTURE_FLAG_GAP,
+    NO_TRIGGER_CHANNEL,
+};
+use crate::sensor_collection::{PRESSURE_COMBUSTION, PRESSURE_TANK, TENSOMETER};
+use window::{CaptureRing, Window};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Tensometer, tank pressure, combustion pressure
+const CAPTURE_CHANNELS: usize = 3;
+/// About 5 s at the 2 kHz fast channel rate
+const CAPTURE_MAX_FRAMES: usize = 10_240;
+
+type Ring = CaptureRing<CAPTURE_CHANNELS, CAPTURE_MAX_FRAMES>;
+
+#[derive(Debug, Clone, Copy)]
+struct TriggerRequest {
+    trigger: CaptureTrigger,
+    channel: u8,
+    timestamp_ms: u32,
+}
+
+enum AckResult {
+    Acked,
+    TimedOut,
+    Aborted,
+    /// FIRE was entered; the request is captured once the upload is dropped
+    Preempted(TriggerRequest),
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static RING: ConstStaticCell<Ring> = ConstStaticCell::new(Ring::new());
+static TRIGGER: Signal<CriticalSectionRawMutex, TriggerRequest> = Signal::new();
+static CAPTURE_COMMANDS: Channel<CriticalSectionRawMutex, CaptureCommand, 4> = Channel::new();
+
+/// Freeze a window around now, unless one is already being captured.
+pub fn trigger_capture(trigger: CaptureTrigger) {
+    TRIGGER.signal(TriggerRequest {
+        trigger,
+        channel: NO_TRIGGER_CHANNEL,
+        timestamp_ms: Instant::now().as_millis() as u32,
+    });
+}
+
+pub fn submit_capture_command(command: CaptureCommand) {
+    if command == CaptureCommand::Trigger {
+        trigger_capture(CaptureTrigger::Manual);
+        return;
+    }
+    if CAPTURE_COMMANDS.try_send(command).is_err() {
+        warn!("Capture command dropped: no upload in progress");
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Feeds blocks into the ring and keeps what the upload metadata needs.
+struct Recorder {
+    request: Option<TriggerRequest>,
+    previous: Option<[u16; CAPTURE_CHANNELS]>,
+    sample_period_ns: u32,
+    /// Frame number and timestamp of the first frame of the last block
+    anchor: (u64, u32),
+    last_sequence: Option<u32>,
+    overruns: u32,
+    /// Frame number right after the last discontinuity
+    gap_frame: Option<u64>,
+}
+
+impl Recorder {
+    const fn new() -> Self {
+        Self {
+            request: None,
+            previous: None,
+            sample_period_ns: 0,
+            anchor: (0, 0),
+            last_sequence: None,
+            overruns: 0,
+            gap_frame: None,
+        }
+    }
+
+    fn rearm(&mut self) {
+        *self = Self {
+            overruns: self.overruns,
+            ..Self::new()
+        };
+    }
+
+    fn record(&mut self, ring: &mut Ring, block: &AnalogBlock) {
+        let continuous = self
+            .last_sequence
+            .is_none_or(|sequence| block.sequence == sequence.wrapping_add(1));
+        if !continuous || block.overruns != self.overruns {
+            self.gap_frame = Some(ring.frame_number());
+        }
+        self.last_sequence = Some(block.sequence);
+        self.overruns = block.overruns;
+        self.sample_period_ns = block.sample_period_ns(TENSOMETER);
+        self.anchor = (ring.frame_number(), block.first_timestamp_ms);
+
+        if let Some(request) = TRIGGER.try_take() {
+            self.fire(ring, request);
+        }
+
+        let columns = [TENSOMETER, PRESSURE_TANK, PRESSURE_COMBUSTION]
+            .map(|channel| block.millivolts(channel).unwrap_or_default());
+        for index in 0..block.len() {
+            let frame = columns.map(|column| column.get(index).copied().unwrap_or(0));
+            if !ring.push(&frame) {
+                break;
+            }
+            if let Some(channel) = self.crossing(&frame) {
+                let request = TriggerRequest {
+                    trigger: CaptureTrigger::Threshold,
+                    channel: channel as u8,
+                    timestamp_ms: self.frame_timestamp_ms(ring.frame_number() - 1),
+                };
+                self.fire(ring, request);
+            }
+            self.previous = Some(frame);
+        }
+    }
+
+    /// First channel that rose through its threshold with this frame.
+    fn crossing(&self, frame: &[u16; CAPTURE_CHANNELS]) -> Option<usize> {
+        let previous = self.previous?;
+        (0..CAPTURE_CHANNELS).find(|&channel| {
+            CAPTURE_THRESHOLD_MV[channel].is_some_and(|threshold| {
+                previous[channel] < threshold && frame[channel] >= threshold
+            })
+        })
+    }
+
+    fn fire(&mut self, ring: &mut Ring, request: TriggerRequest) {
+        let pre = self.frames_in(CAPTURE_PRE_TRIGGER_MS);
+        let post = self.frames_in(CAPTURE_POST_TRIGGER_MS);
+        if ring.trigger(pre, post) {
+            self.request = Some(request);
+            net_info!(
+                "Capture triggered by {:?}, recording {} ms",
+                request.trigger,
+                CAPTURE_POST_TRIGGER_MS
+            );
+        }
+    }
+
+    fn frames_in(&self, duration_ms: u32) -> usize {
+        if self.sample_period_ns == 0 {
+            return 0;
+        }
+        (duration_ms as u64 * 1_000_000 / self.sample_period_ns as u64) as usize
+    }
+
+    fn frame_timestamp_ms(&self, frame: u64) -> u32 {
+        let (anchor_frame, anchor_ms) = self.anchor;
+        let offset_ms =
+            (frame as i64 - anchor_frame as i64) * self.sample_period_ns as i64 / 1_000_000;
+        anchor_ms.wrapping_add(offset_ms as u32)
+    }
+
+    fn meta(&self, capture_id: u32, window: Window) -> CaptureMetaPacket {
+        let chunks = window.frames().div_ceil(CAPTURE_CHUNK_SAMPLES);
+        let request = self.request.unwrap_or(TriggerRequest {
+            trigger: CaptureTrigger::Manual,
+            channel: NO_TRIGGER_CHANNEL,
+            timestamp_ms: self.frame_timestamp_ms(window.trigger),
+        });
+        let gap = self
+            .gap_frame
+            .is_some_and(|frame| frame > window.start && frame < window.end);
+
+        CaptureMetaPacket {
+            capture_id,
+            message_count: (1 + CAPTURE_CHANNELS * chunks) as u16,
+            trigger: request.trigger,
+            trigger_channel: request.channel,
+            flags: if gap { CAPTURE_FLAG_GAP } else { 0 },
+            trigger_timestamp_ms: request.timestamp_ms,
+            first_timestamp_ms: self.frame_timestamp_ms(window.start),
+            sample_period_ns: self.sample_period_ns,
+            frames: window.frames() as u32,
+            pre_frames: window.pre_frames() as u32,
+            channels: CAPTURE_CHANNELS as u8,
+        }
+    }
+}
+
+/// Upload message `sequence` (1-based), channel by channel.
+fn chunk(ring: &Ring, meta: &CaptureMetaPacket, sequence: u16) -> Option<CaptureChunkPacket> {
+    let chunks = (meta.frames as usize).div_ceil(CAPTURE_CHUNK_SAMPLES);
+    let index = sequence as usize - 1;
+    let channel = index / chunks;
+    let offset = (index % chunks) * CAPTURE_CHUNK_SAMPLES;
+
+    let mut samples = [0u16; CAPTURE_CHUNK_SAMPLES];
+    let count = ring.read(channel, offset, &mut samples);
+    let samples = &mut samples[..count];
+    for sample in samples.iter_mut() {
+        *sample = (*sample).min(U12_MAX);
+    }
+
+    CaptureChunkPacket::from_slice(
+        meta.capture_id,
+        sequence,
+        channel as u8,
+        offset as u32,
+        samples,
+    )
+    .ok()
+}
+
+async fn wait_for_ack(capture_id: u32, sequence: u16) -> AckResult {
+    let ack = async {
+        loop {
+            match CAPTURE_COMMANDS.receive().await {
+                CaptureCommand::Ack {
+                    capture_id: id,
+                    sequence: acked,
+                } if id == capture_id && acked == sequence => return AckResult::Acked,
+                CaptureCommand::Abort { capture_id: id } if id == capture_id => {
+                    return AckResult::Aborted
+                }
+                _ => {}
+            }
+        }
+    };
+
+    let fire = async {
+        loop {
+            let request = TRIGGER.wait().await;
+            if matches!(request.trigger, CaptureTrigger::Fire) {
+                return request;
+            }
+        }
+    };
+
+    let wait = select(ack, fire);
+    match with_timeout(Duration::from_millis(CAPTURE_ACK_TIMEOUT_MS), wait).await {
+        Ok(Either::First(result)) => result,
+        Ok(Either::Second(request)) => AckResult::Preempted(request),
+        Err(_) => AckResult::TimedOut,
+    }
+}
+
+/// Returns a FIRE trigger that cut the upload short.
+async fn upload(ring: &Ring, meta: CaptureMetaPacket, watch: &TaskWatch) -> Option<TriggerRequest> {
+    CAPTURE_COMMANDS.clear();
+    net_info!(
+        "Capture {}: uploading {} frames in {} messages",
+        meta.capture_id,
+        meta.frames,
+        meta.message_count
+    );
+
+    let mut sequence = 0u16;
+    let mut resends = 0u32;
+    let mut unacked = 0u32;
+    while sequence < meta.message_count {
+        let queued = if sequence == 0 {
+            queue::publish_capture_meta(meta).is_ok()
+        } else {
+            match chunk(ring, &meta, sequence) {
+                Some(packet) => queue::publish_capture_chunk(packet).is_ok(),
+                None => {
+                    net_warn!("Capture {}: chunk {} invalid", meta.capture_id, sequence);
+                    return None;
+                }
+            }
+        };
+        if !queued {
+            warn!("Capture chunk not queued: outbound queue full");
+        }
+
+        match wait_for_ack(meta.capture_id, sequence).await {
+            AckResult::Acked => {
+                sequence += 1;
+                unacked = 0;
+            }
+            AckResult::TimedOut if unacked >= CAPTURE_MAX_RESENDS => {
+                net_warn!(
+                    "Capture {}: message {} not acked, upload dropped",
+                    meta.capture_id,
+                    sequence
+                );
+                return None;
+            }
+            AckResult::TimedOut => {
+                if resends == 0 {
+                    net_warn!("Capture {}: ack timeout, resending", meta.capture_id);
+                }
+                resends += 1;
+                unacked += 1;
+            }
+            AckResult::Aborted => {
+                net_warn!("Capture {}: upload aborted", meta.capture_id);
+                return None;
+            }
+            AckResult::Preempted(request) => {
+                net_warn!("Capture {}: upload dropped for FIRE", meta.capture_id);
+                return Some(request);
+            }
+        }
+        watch.check_in();
+    }
+
+    net_info!(
+        "Capture {}: upload complete ({} resends)",
+        meta.capture_id,
+        resends
+    );
+    None
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn capture_task(mut blocks: AnalogBlockSubscriber) {
+    let ring = RING.take();
+    let watch = watchdog::register("capture", Duration::from_millis(TASK_DEADLINE_MS));
+    let mut recorder = Recorder::new();
+    let mut capture_id = 0u32;
+
+    loop {
+        let block = blocks.next_message_pure().await;
+        watch.check_in();
+        recorder.record(ring, &block);
+
+        if let Some(window) = ring.window() {
+            capture_id = capture_id.wrapping_add(1);
+            let fire = upload(ring, recorder.meta(capture_id, window), &watch).await;
+            ring.release();
+            recorder.rearm();
+            TRIGGER.reset();
+            if let Some(request) = fire {
+                TRIGGER.signal(request);
+            }
+        }
+    }
+}
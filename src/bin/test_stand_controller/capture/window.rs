@@ -0,0 +1,157 @@
+//! Rolling sample buffer that freezes a window around a trigger.
+//!
+//! Frames (one sample per channel) are numbered from 0 since the last
+//! release. While recording, the newest `FRAMES` frames are kept. A trigger
+//! fixes the window to the pre-trigger frames still in the buffer plus the
+//! requested post-trigger frames; once those are in, the buffer freezes
+//! and ignores new frames until [`CaptureRing::release`].
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Recording,
+    Triggered { start: u64, trigger: u64, end: u64 },
+    Frozen { start: u64, trigger: u64, end: u64 },
+}
+
+/// Frozen frames, as frame numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub start: u64,
+    pub trigger: u64,
+    pub end: u64,
+}
+
+impl Window {
+    pub const fn frames(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    pub const fn pre_frames(&self) -> usize {
+        (self.trigger - self.start) as usize
+    }
+}
+
+pub struct CaptureRing<const CH: usize, const FRAMES: usize> {
+    samples: [[u16; FRAMES]; CH],
+    written: u64,
+    state: State,
+}
+
+impl<const CH: usize, const FRAMES: usize> CaptureRing<CH, FRAMES> {
+    pub const fn new() -> Self {
+        Self {
+            samples: [[0; FRAMES]; CH],
+            written: 0,
+            state: State::Recording,
+        }
+    }
+
+    /// Number the next frame will get.
+    pub const fn frame_number(&self) -> u64 {
+        self.written
+    }
+
+    pub const fn is_recording(&self) -> bool {
+        matches!(self.state, State::Recording)
+    }
+
+    /// Store one frame; returns false once frozen.
+    pub fn push(&mut self, frame: &[u16; CH]) -> bool {
+        if let State::Frozen { .. } = self.state {
+            return false;
+        }
+
+        let slot = (self.written % FRAMES as u64) as usize;
+        for (channel, &sample) in self.samples.iter_mut().zip(frame) {
+            channel[slot] = sample;
+        }
+        self.written += 1;
+
+        if let State::Triggered {
+            start,
+            trigger,
+            end,
+        } = self.state
+        {
+            if self.written >= end {
+                self.state = State::Frozen {
+                    start,
+                    trigger,
+                    end,
+                };
+            }
+        }
+        true
+    }
+
+    /// Trigger at the next frame. `pre_frames` is cut to what the buffer
+    /// holds next to `post_frames`. Ignored unless recording.
+    pub fn trigger(&mut self, pre_frames: usize, post_frames: usize) -> bool {
+        if !self.is_recording() {
+            return false;
+        }
+
+        let post = post_frames.min(FRAMES) as u64;
+        let pre = (pre_frames as u64)
+            .min(self.written)
+            .min(FRAMES as u64 - post);
+        let trigger = self.written;
+        let window = (trigger - pre, trigger, trigger + post);
+        self.state = if post == 0 {
+            State::Frozen {
+                start: window.0,
+                trigger: window.1,
+                end: window.2,
+            }
+        } else {
+            State::Triggered {
+                start: window.0,
+                trigger: window.1,
+                end: window.2,
+            }
+        };
+        true
+    }
+
+    pub fn window(&self) -> Option<Window> {
+        match self.state {
+            State::Frozen {
+                start,
+                trigger,
+                end,
+            } => Some(Window {
+                start,
+                trigger,
+                end,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Copy samples of `channel` from `offset` frames into the frozen
+    /// window; returns how many were copied.
+    pub fn read(&self, channel: usize, offset: usize, out: &mut [u16]) -> usize {
+        let Some(window) = self.window() else {
+            return 0;
+        };
+        let available = window.frames().saturating_sub(offset);
+        let count = available.min(out.len());
+        for (index, sample) in out[..count].iter_mut().enumerate() {
+            let frame = window.start + (offset + index) as u64;
+            *sample = self.samples[channel][(frame % FRAMES as u64) as usize];
+        }
+        count
+    }
+
+    /// Drop the window and start recording from scratch.
+    pub fn release(&mut self) {
+        self.written = 0;
+        self.state = State::Recording;
+    }
+}
+
+impl<const CH: usize, const FRAMES: usize> Default for CaptureRing<CH, FRAMES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
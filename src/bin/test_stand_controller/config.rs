@@ -1,3 +1,9 @@
+use mainboard::fire_trigger::CHANNELS;
+
+use crate::interlock::rules::Rule;
+use crate::servo::calibration::ServoCalibration;
+use crate::servo::{ServoConfig, ServoPin};
+
 // =============================================
 //                    MQTT
 // =============================================
@@ -10,6 +16,8 @@ pub static MQTT_CLIENT_ID: &str = match option_env!("MQTT_CLIENT_ID") {
     Some(id) => id,
     None => "esp32-test-stand",
 };
+/// Browse mDNS for `_mqtt._tcp` when `MQTT_HOST` does not resolve.
+pub const MQTT_MDNS_DISCOVERY: bool = option_env!("MQTT_MDNS_DISCOVERY").is_some();
 
 // =============================================
 //              Temperature (TMP107)
@@ -21,16 +29,228 @@ pub const TEMP_BATCH_SIZE: usize = 20;
 pub const TEMP_UART_BOUDRATE: u32 = 115200;
 
 // =============================================
-//                    SERVO
+//                   CAPTURE
+// =============================================
+
+/// Fast channel history kept in front of a trigger
+pub const CAPTURE_PRE_TRIGGER_MS: u32 = 1000;
+/// Recording after a trigger; pre + post must fit the capture buffer (5 s)
+pub const CAPTURE_POST_TRIGGER_MS: u32 = 4000;
+/// Pin millivolts whose upward crossing triggers a capture, per fast channel
+/// (tensometer, tank pressure, combustion pressure); `None` disables
+pub const CAPTURE_THRESHOLD_MV: [Option<u16>; 3] = [None, None, None];
+/// Resend an upload message when its ack takes longer than this
+pub const CAPTURE_ACK_TIMEOUT_MS: u64 = 2000;
+/// Resends of one upload message without an ack before the upload is
+/// dropped, so a receiver that went away gives up after about 10 s
+pub const CAPTURE_MAX_RESENDS: u32 = 5;
+
+// =============================================
+//                 CALIBRATION
+// =============================================
+
+/// Analog blocks (100 samples each) averaged for a tare or span reading
+pub const CALIBRATION_AVERAGE_BLOCKS: usize = 10;
+/// Append the calibrated scale (`gain f32, offset f32`) to every
+/// `sensor/adc/fast/*` payload
+pub const CALIBRATED_SENSOR_PAYLOADS: bool = option_env!("CALIBRATED_SENSOR_PAYLOADS").is_some();
+
+// =============================================
+//                  INTERLOCKS
+// =============================================
+
+/// Rules that reject FIRE while violated and abort it when violated during
+/// FIRE. Analog values are block means in the calibrated unit of the channel
+/// (input volts until spanned), temperatures in °C, servos 1 while stalled
+/// or faulted.
+///
+/// None by default: a rule that does not hold on the stand blocks FIRE, so
+/// only add rules for sensors that are fitted. For example, with a spanned
+/// tank transducer, TMP107 probe 1 on the tank and servo 0 of `SERVOS` on the
+/// fuel valve:
+///
+/// ```ignore
+/// Rule {
+///     name: "tank_overpressure",
+///     source: Source::Analog(crate::sensor_collection::PRESSURE_TANK),
+///     condition: Condition::Above(60.0), // bar
+/// },
+/// Rule {
+///     name: "tank_pressure_rise",
+///     source: Source::Analog(crate::sensor_collection::PRESSURE_TANK),
+///     condition: Condition::RateAbove(50.0), // bar/s
+/// },
+/// Rule {
+///     name: "probe1_overheat",
+///     source: Source::Temperature(1),
+///     condition: Condition::Above(90.0),
+/// },
+/// // Blocks FIRE on a stand without the probe
+/// Rule {
+///     name: "probe1_missing",
+///     source: Source::Temperature(1),
+///     condition: Condition::Missing { timeout_ms: 2000 },
+/// },
+/// Rule {
+///     name: "fuel_servo_fault",
+///     source: Source::Servo(0),
+///     condition: Condition::Above(0.5),
+/// },
+/// ```
+pub const INTERLOCK_RULES: &[Rule] = &[];
+/// Consecutive readings that must agree before a rule trips or clears
+pub const INTERLOCK_CONFIRM_SAMPLES: u8 = 2;
+/// Period of the `Missing` check and of the abort check during FIRE
+pub const INTERLOCK_CHECK_INTERVAL_MS: u64 = 100;
+
+// =============================================
+//                   ARMING
+// =============================================
+
+/// How long the nonce of an ARM_REQUEST answers FIRE
+pub const ARM_NONCE_VALIDITY_MS: u32 = 10_000;
+/// Pre-shared key; when set, FIRE must also carry the HMAC-SHA256 of
+/// `FIRE <nonce>` under it
+pub static FIRE_HMAC_KEY: Option<&str> = option_env!("FIRE_HMAC_KEY");
+/// How long an armed switch contact must hold a level before it counts
+pub const ARMED_DEBOUNCE_MS: u64 = 30;
+/// Second armed switch contact on `Motor1`, which then drives no servo; the
+/// stand is armed only while both contacts are closed
+pub const ARMED_SECOND_CONTACT: bool = false;
+/// Longest the two contacts may disagree, as while the key turns, before it
+/// is reported
+pub const ARMED_DISAGREE_MS: u64 = 500;
+
+// =============================================
+//                FIRE TRIGGER
+// =============================================
+
+/// Expander pins wired to igniters, channel N on pin PN (active low); must
+/// leave out the continuity sense pin
+pub const FIRE_CHANNEL_MASK: u8 = 0xFF;
+/// Longest a channel stays on: the fire trigger releases it after this, and
+/// sequences holding it longer are rejected
+pub const FIRE_CHANNEL_MAX_ON_MS: u32 = 10_000;
+/// Pause between attempts to release channels whose release did not read
+/// back; after `TASK_DEADLINE_MS` of failures the watchdog resets the board
+pub const FIRE_RELEASE_RETRY_MS: u64 = 50;
+/// When FIRE without a loaded sequence turns each channel on, ms after T-0;
+/// `None` leaves the channel off
+pub const FIRE_CHANNEL_OFFSETS_MS: [Option<u32>; CHANNELS] = [
+    Some(0),
+    Some(0),
+    Some(0),
+    Some(0),
+    Some(0),
+    Some(0),
+    Some(0),
+    Some(0),
+];
+/// Fire trigger expander pin pulled low through the igniter; FIRE needs it
+/// low. 0 when no continuity sense is fitted. Fitting one takes its pin from
+/// the igniters, e.g. `1 << 7` with `FIRE_CHANNEL_MASK` 0x7F
+pub const FIRE_CONTINUITY_SENSE_MASK: u8 = 0;
+/// How often the igniter continuity is read while no countdown runs
+pub const CONTINUITY_CHECK_INTERVAL_MS: u64 = 500;
+
+// =============================================
+//                  COUNTDOWN
+// =============================================
+
+/// Seconds from the FIRE command to T-0, counted down on `status/countdown`;
+/// 0 fires at once
+pub const FIRE_COUNTDOWN_S: u16 = 10;
+
+// =============================================
+//                   CAMERA
 // =============================================
 
-// Servo pulse width range (MCPWM ticks mapping physical 0-180 degrees)
-pub const SERVO_MIN_PULSE_TICKS: u16 = 500;
-pub const SERVO_MAX_PULSE_TICKS: u16 = 2500;
+/// Half-press on `D3` ahead of every shot, to wake and focus the camera;
+/// 0 leaves `D3` off
+pub const CAMERA_FOCUS_MS: u32 = 300;
+/// Full press on `D4` of an ordinary shot
+pub const CAMERA_PRESS_MS: u32 = 200;
+/// Release after every full press before the next may start
+pub const CAMERA_RELEASE_MS: u32 = 200;
+/// Longest full press of `cmd/camera` `BULB`
+pub const CAMERA_BULB_MAX_MS: u32 = 60_000;
+/// Time-lapse period from T-0 until FIRE ends; 0 takes no time-lapse
+pub const CAMERA_FIRE_INTERVAL_MS: u32 = 0;
 
-// Operational positions (degrees within the 0-1800 range)
-pub const SERVO_OPEN_DEGREES: u16 = 975;
-pub const SERVO_CLOSED_DEGREES: u16 = 1800;
+// =============================================
+//                  SEQUENCES
+// =============================================
+
+/// Earliest step of an uploaded fire sequence before T-0
+pub const SEQUENCE_MAX_COUNTDOWN_MS: u32 = 60_000;
+/// Latest step of an uploaded fire sequence after T-0
+pub const SEQUENCE_MAX_DURATION_MS: u32 = 120_000;
+
+// =============================================
+//                  HEARTBEAT
+// =============================================
+
+/// Longest gap between operator heartbeats on `cmd/heartbeat` before FIRE
+/// aborts; 0 disables the dead-man switch
+pub const HEARTBEAT_TIMEOUT_MS: u32 = 3000;
+/// Period of the heartbeat check
+pub const HEARTBEAT_CHECK_INTERVAL_MS: u64 = 100;
+
+// =============================================
+//                  WATCHDOG
+// =============================================
+
+/// Longest a supervised task may go without checking in
+pub const TASK_DEADLINE_MS: u64 = 10_000;
+/// Check-in period of tasks idling on an event
+pub const TASK_IDLE_CHECK_IN_MS: u64 = 1000;
+
+// =============================================
+//                    SERVO
+// =============================================
 
-// Time for full 0-180 degree travel
-pub const SERVO_FULL_RANGE_MS: u64 = 5000;
+/// One per MCPWM operator at most, each on its own pin. Positions are tenths
+/// of a degree; pulse widths are MCPWM ticks mapping physical 0-180 degrees.
+/// A servo with a position potentiometer names the free analog input it is
+/// wired to and its calibration in `feedback`. `calibration` holds until
+/// `cmd/calibrate/servo/<name>` stores another.
+///
+/// A second valve on `Motor0`, added after `fuel`:
+///
+/// ```ignore
+/// ServoConfig {
+///     name: "oxidiser",
+///     pin: ServoPin::Motor0,
+///     calibration: ServoCalibration {
+///         min_pulse_ticks: 500,
+///         max_pulse_ticks: 2500,
+///         open_degrees: 975,
+///         closed_degrees: 1800,
+///         full_range_ms: 5000,
+///     },
+///     min_degrees: 0,
+///     max_degrees: 1800,
+///     accel_degrees_per_s2: 3600,
+///     feedback: None,
+/// },
+/// ```
+pub const SERVOS: &[ServoConfig] = &[ServoConfig {
+    name: "fuel",
+    pin: ServoPin::D1,
+    calibration: ServoCalibration {
+        min_pulse_ticks: 500,
+        max_pulse_ticks: 2500,
+        open_degrees: 975,
+        closed_degrees: 1800,
+        full_range_ms: 5000,
+    },
+    min_degrees: 0,
+    max_degrees: 1800,
+    accel_degrees_per_s2: 3600,
+    feedback: None,
+}];
+/// Pulse widths a jog may reach, beyond the calibrated ends
+pub const SERVO_JOG_MIN_PULSE_TICKS: u16 = 400;
+pub const SERVO_JOG_MAX_PULSE_TICKS: u16 = 2600;
+/// Longest each leg of a full-range measurement may take
+pub const SERVO_MEASURE_TIMEOUT_MS: u32 = 15_000;
@@ -0,0 +1,100 @@
+//! Operator dead-man switch.
+//!
+//! The operator console publishes `cmd/heartbeat` at least every
+//! `HEARTBEAT_TIMEOUT_MS`. Without a fresh heartbeat in the current MQTT
+//! session FIRE is rejected; when the heartbeats stop or the session drops
+//! during the countdown or FIRE the sequencer aborts, and in ARMED the servo
+//! closes.
+
+pub mod monitor;
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Ticker};
+use mainboard::watchdog;
+use mainboard::{net_info, net_warn};
+
+use crate::config::{HEARTBEAT_CHECK_INTERVAL_MS, HEARTBEAT_TIMEOUT_MS, TASK_DEADLINE_MS};
+use crate::mqtt::queue;
+use crate::sequencer;
+use monitor::{Event, LossReason, Monitor};
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static MONITOR: Mutex<CriticalSectionRawMutex, RefCell<Monitor>> =
+    Mutex::new(RefCell::new(Monitor::new(HEARTBEAT_TIMEOUT_MS)));
+
+pub fn beat() {
+    let now = timestamp_ms();
+    MONITOR.lock(|monitor| monitor.borrow_mut().beat(now));
+}
+
+/// Called once the MQTT session is subscribed to the commands.
+pub fn session_up() {
+    MONITOR.lock(|monitor| monitor.borrow_mut().session_up());
+}
+
+pub fn session_down() {
+    MONITOR.lock(|monitor| monitor.borrow_mut().session_down());
+}
+
+/// Whether the operator link is alive now.
+pub fn status() -> Result<(), LossReason> {
+    let now = timestamp_ms();
+    MONITOR.lock(|monitor| monitor.borrow().status(now))
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn timestamp_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+/// Reports link changes and makes the sequencer react to a lost link: on
+/// every check during the countdown or FIRE, so a dropped message cannot keep it burning, and
+/// once per loss otherwise.
+#[embassy_executor::task]
+pub async fn heartbeat_task() {
+    let watch = watchdog::register("heartbeat", Duration::from_millis(TASK_DEADLINE_MS));
+    let mut ticker = Ticker::every(Duration::from_millis(HEARTBEAT_CHECK_INTERVAL_MS));
+
+    loop {
+        ticker.next().await;
+        watch.check_in();
+
+        let now = timestamp_ms();
+        let (event, status) = MONITOR.lock(|monitor| {
+            let mut monitor = monitor.borrow_mut();
+            (monitor.poll(now), monitor.status(now))
+        });
+
+        match event {
+            Some(Event::Lost(reason)) => {
+                net_warn!("Operator link lost: {}", reason.as_str());
+                queue::publish_command_log("Operator link lost");
+            }
+            Some(Event::Restored) => {
+                net_info!("Operator link restored");
+                queue::publish_command_log("Operator link restored");
+            }
+            None => {}
+        }
+
+        if let Err(reason) = status {
+            let lost_now = matches!(event, Some(Event::Lost(_)));
+            if lost_now || sequencer::load_state().is_active() {
+                sequencer::report_operator_lost(reason);
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Operator link state from heartbeats and the MQTT session.
+
+/// Why the operator link counts as lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum LossReason {
+    /// No heartbeat within the timeout, or none yet in this session
+    Timeout,
+    /// The MQTT session is down
+    Disconnected,
+}
+
+impl LossReason {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Timeout => "heartbeat timeout",
+            Self::Disconnected => "MQTT session lost",
+        }
+    }
+}
+
+/// Change of the link state, reported once per change by
+/// [`Monitor::poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Event {
+    Lost(LossReason),
+    Restored,
+}
+
+/// Dead-man switch over operator heartbeats.
+///
+/// The link is alive while an MQTT session is up and the last heartbeat of
+/// that session is at most `timeout_ms` old. A new session starts without
+/// heartbeats, so it only counts once the console sends one. A timeout of 0
+/// disables the monitor: the link is always alive. Timestamps are
+/// milliseconds and may wrap.
+#[derive(Debug, Clone, Copy)]
+pub struct Monitor {
+    timeout_ms: u32,
+    session_up: bool,
+    last_beat_ms: Option<u32>,
+    /// Last state reported by `poll`
+    alive: bool,
+}
+
+impl Monitor {
+    pub const fn new(timeout_ms: u32) -> Self {
+        Self {
+            timeout_ms,
+            session_up: false,
+            last_beat_ms: None,
+            alive: timeout_ms == 0,
+        }
+    }
+
+    pub fn session_up(&mut self) {
+        self.session_up = true;
+        self.last_beat_ms = None;
+    }
+
+    pub fn session_down(&mut self) {
+        self.session_up = false;
+        self.last_beat_ms = None;
+    }
+
+    pub fn beat(&mut self, now_ms: u32) {
+        if self.session_up {
+            self.last_beat_ms = Some(now_ms);
+        }
+    }
+
+    pub fn status(&self, now_ms: u32) -> Result<(), LossReason> {
+        if self.timeout_ms == 0 {
+            return Ok(());
+        }
+        if !self.session_up {
+            return Err(LossReason::Disconnected);
+        }
+        match self.last_beat_ms {
+            Some(last_ms) if now_ms.wrapping_sub(last_ms) <= self.timeout_ms => Ok(()),
+            _ => Err(LossReason::Timeout),
+        }
+    }
+
+    /// The change since the last poll, if any.
+    pub fn poll(&mut self, now_ms: u32) -> Option<Event> {
+        match (self.alive, self.status(now_ms)) {
+            (true, Err(reason)) => {
+                self.alive = false;
+                Some(Event::Lost(reason))
+            }
+            (false, Ok(())) => {
+                self.alive = true;
+                Some(Event::Restored)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT_MS: u32 = 3000;
+
+    /// Session up with a heartbeat at `now_ms`, reported alive.
+    fn alive_at(now_ms: u32) -> Monitor {
+        let mut monitor = Monitor::new(TIMEOUT_MS);
+        monitor.session_up();
+        monitor.beat(now_ms);
+        assert_eq!(monitor.poll(now_ms), Some(Event::Restored));
+        monitor
+    }
+
+    #[test]
+    fn starts_lost() {
+        let mut monitor = Monitor::new(TIMEOUT_MS);
+        assert_eq!(monitor.status(0), Err(LossReason::Disconnected));
+        assert_eq!(monitor.poll(0), None);
+    }
+
+    #[test]
+    fn new_session_needs_a_beat() {
+        let mut monitor = Monitor::new(TIMEOUT_MS);
+        monitor.session_up();
+        assert_eq!(monitor.status(100), Err(LossReason::Timeout));
+        assert_eq!(monitor.poll(100), None);
+        monitor.beat(200);
+        assert_eq!(monitor.poll(200), Some(Event::Restored));
+        assert_eq!(monitor.poll(300), None);
+    }
+
+    #[test]
+    fn times_out() {
+        let mut monitor = alive_at(1000);
+        assert_eq!(monitor.poll(1000 + TIMEOUT_MS), None);
+        assert_eq!(
+            monitor.poll(1001 + TIMEOUT_MS),
+            Some(Event::Lost(LossReason::Timeout))
+        );
+        assert_eq!(monitor.poll(1002 + TIMEOUT_MS), None);
+    }
+
+    #[test]
+    fn beats_keep_it_alive() {
+        let mut monitor = alive_at(0);
+        for now_ms in (1000..20_000).step_by(1000) {
+            monitor.beat(now_ms);
+            assert_eq!(monitor.poll(now_ms + TIMEOUT_MS), None);
+        }
+    }
+
+    #[test]
+    fn late_beat_restores() {
+        let mut monitor = alive_at(0);
+        assert_eq!(monitor.poll(5000), Some(Event::Lost(LossReason::Timeout)));
+        monitor.beat(6000);
+        assert_eq!(monitor.poll(6000), Some(Event::Restored));
+    }
+
+    #[test]
+    fn session_loss_drops_the_link() {
+        let mut monitor = alive_at(0);
+        monitor.session_down();
+        assert_eq!(
+            monitor.poll(10),
+            Some(Event::Lost(LossReason::Disconnected))
+        );
+        // No session, no heartbeats
+        monitor.beat(20);
+        assert_eq!(monitor.status(20), Err(LossReason::Disconnected));
+
+        // A beat of the old session does not carry over
+        monitor.session_up();
+        assert_eq!(monitor.status(30), Err(LossReason::Timeout));
+        monitor.beat(40);
+        assert_eq!(monitor.poll(40), Some(Event::Restored));
+    }
+
+    #[test]
+    fn clock_wrap() {
+        let mut monitor = alive_at(u32::MAX - 1000);
+        assert_eq!(monitor.poll(1000), None);
+        assert_eq!(
+            monitor.poll(TIMEOUT_MS),
+            Some(Event::Lost(LossReason::Timeout))
+        );
+    }
+
+    #[test]
+    fn disabled_is_always_alive() {
+        let mut monitor = Monitor::new(0);
+        assert_eq!(monitor.status(0), Ok(()));
+        assert_eq!(monitor.poll(0), None);
+        monitor.session_up();
+        assert_eq!(monitor.poll(1_000_000), None);
+        monitor.session_down();
+        assert_eq!(monitor.status(2_000_000), Ok(()));
+        assert_eq!(monitor.poll(2_000_000), None);
+    }
+}
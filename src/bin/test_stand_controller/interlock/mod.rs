@@ -0,0 +1,133 @@
+//! Safety interlocks over the stand's sensors.
+//!
+//! The rules in [`INTERLOCK_RULES`] are evaluated on every analog block,
+//! every TMP107 reading and every feedback check of a servo. A violated rule
+//! rejects FIRE, and during the countdown or FIRE it makes the sequencer
+//! abort: the fire trigger is released, the servos close and the light shows
+//! red with the buzzer. Rule changes are logged; the
+//! abort and its rule are reported on `status/cmd`.
+
+pub mod rules;
+
+use core::cell::RefCell;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Ticker};
+use mainboard::analog::{AnalogBlock, AnalogChannel};
+use mainboard::tmp107::raw_to_millicelsius;
+use mainboard::watchdog;
+use mainboard::{net_info, net_warn};
+
+use crate::config::{
+    INTERLOCK_CHECK_INTERVAL_MS, INTERLOCK_CONFIRM_SAMPLES, INTERLOCK_RULES, TASK_DEADLINE_MS,
+};
+use crate::sequencer;
+use rules::{Event, Interlocks, Rule, Source};
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static INTERLOCKS: Mutex<CriticalSectionRawMutex, RefCell<Interlocks>> = Mutex::new(RefCell::new(
+    Interlocks::new(INTERLOCK_RULES, INTERLOCK_CONFIRM_SAMPLES),
+));
+static INTERLOCK_EVENTS: Channel<CriticalSectionRawMutex, Event, 8> = Channel::new();
+
+/// Evaluate the rules on the analog channels against the block means.
+pub fn feed_analog(block: &AnalogBlock) {
+    for channel in AnalogChannel::ALL {
+        let source = Source::Analog(channel);
+        if !INTERLOCKS.lock(|interlocks| interlocks.borrow().watches(source)) {
+            continue;
+        }
+        if let Some(mean) = block.mean(channel) {
+            update(source, block.last_timestamp_ms(channel), mean);
+        }
+    }
+}
+
+/// Evaluate the rules on TMP107 probe `sensor_id` (1-based).
+pub fn feed_temperature(sensor_id: u8, raw: u16, timestamp_ms: u32) {
+    let celsius = raw_to_millicelsius(raw) as f32 / 1000.0;
+    update(Source::Temperature(sensor_id), timestamp_ms, celsius);
+}
+
+/// Evaluate the rules on servo `servo` (index in `SERVOS`).
+pub fn feed_servo(servo: usize, timestamp_ms: u32, faulted: bool) {
+    update(
+        Source::Servo(servo as u8),
+        timestamp_ms,
+        faulted as u8 as f32,
+    );
+}
+
+/// A rule currently violated, if any.
+pub fn active_violation() -> Option<&'static Rule> {
+    INTERLOCKS.lock(|interlocks| interlocks.borrow().active())
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn update(source: Source, timestamp_ms: u32, value: f32) {
+    INTERLOCKS.lock(|interlocks| {
+        interlocks
+            .borrow_mut()
+            .update(source, timestamp_ms, value, queue_event)
+    });
+}
+
+fn queue_event(event: Event) {
+    if INTERLOCK_EVENTS.try_send(event).is_err() {
+        defmt::warn!("Interlock event dropped: queue full");
+    }
+}
+
+fn log_event(event: Event) {
+    match event {
+        Event::Violated { rule, value } => {
+            net_warn!(
+                "Interlock {} violated: {:?} at {}",
+                rule.name,
+                rule.condition,
+                value
+            );
+        }
+        Event::Cleared { rule } => net_info!("Interlock {} cleared", rule.name),
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+/// Logs rule changes, trips `Missing` rules and aborts the countdown or FIRE
+/// while a rule is violated. Events are only logged here; the abort itself depends on the
+/// rule state, so a dropped event cannot hide a violation.
+#[embassy_executor::task]
+pub async fn interlock_task() {
+    let watch = watchdog::register("interlock", Duration::from_millis(TASK_DEADLINE_MS));
+    let mut ticker = Ticker::every(Duration::from_millis(INTERLOCK_CHECK_INTERVAL_MS));
+
+    loop {
+        match select(INTERLOCK_EVENTS.receive(), ticker.next()).await {
+            Either::First(event) => log_event(event),
+            Either::Second(()) => {
+                let now = Instant::now().as_millis() as u32;
+                INTERLOCKS
+                    .lock(|interlocks| interlocks.borrow_mut().check_missing(now, queue_event));
+            }
+        }
+        watch.check_in();
+
+        if sequencer::load_state().is_active() {
+            if let Some(rule) = active_violation() {
+                sequencer::report_interlock(rule);
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Interlock rules and their evaluation.
+
+use mainboard::analog::AnalogChannel;
+
+/// Most rules one engine tracks.
+pub const MAX_RULES: usize = 16;
+
+/// Sensor a rule watches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Source {
+    /// Block mean of an analog channel, in its calibrated unit.
+    Analog(AnalogChannel),
+    /// TMP107 probe by its 1-based chain position, in °C.
+    Temperature(u8),
+    /// Servo by its index in `SERVOS`: 1 while stalled or faulted, else 0.
+    Servo(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+#[allow(
+    dead_code,
+    reason = "only built by INTERLOCK_RULES, which is empty by default"
+)]
+pub enum Condition {
+    Above(f32),
+    Below(f32),
+    /// Rising faster than this many units per second.
+    RateAbove(f32),
+    /// Falling faster than this many units per second, given as a positive
+    /// value.
+    RateBelow(f32),
+    /// No reading for longer than `timeout_ms`.
+    Missing {
+        timeout_ms: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Rule {
+    pub name: &'static str,
+    pub source: Source,
+    pub condition: Condition,
+}
+
+/// Change of a rule's state.
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum Event {
+    /// The reading that confirmed the violation; for `Missing` the time
+    /// without a reading in milliseconds.
+    Violated {
+        rule: &'static Rule,
+        value: f32,
+    },
+    Cleared {
+        rule: &'static Rule,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RuleState {
+    /// Timestamp and value of the last reading
+    last: Option<(u32, f32)>,
+    /// Consecutive readings disagreeing with `violated`
+    strikes: u8,
+    violated: bool,
+}
+
+impl RuleState {
+    const NEW: Self = Self {
+        last: None,
+        strikes: 0,
+        violated: false,
+    };
+}
+
+/// Evaluates a fixed set of rules against incoming readings.
+///
+/// A threshold or rate rule changes state after `confirm` consecutive
+/// readings agree, so a single outlier neither trips nor clears it. A
+/// `Missing` rule trips once its source stays silent past the timeout,
+/// counted from the first [`Interlocks::check_missing`] if the source never
+/// reported, and clears with the next reading. Timestamps are milliseconds
+/// and may wrap.
+pub struct Interlocks {
+    rules: &'static [Rule],
+    states: [RuleState; MAX_RULES],
+    confirm: u8,
+}
+
+impl Interlocks {
+    pub const fn new(rules: &'static [Rule], confirm: u8) -> Self {
+        assert!(rules.len() <= MAX_RULES, "too many interlock rules");
+        Self {
+            rules,
+            states: [RuleState::NEW; MAX_RULES],
+            confirm: if confirm == 0 { 1 } else { confirm },
+        }
+    }
+
+    /// Some rule watches `source`.
+    pub fn watches(&self, source: Source) -> bool {
+        self.rules.iter().any(|rule| rule.source == source)
+    }
+
+    /// First violated rule.
+    pub fn active(&self) -> Option<&'static Rule> {
+        let rules = self.rules;
+        rules
+            .iter()
+            .zip(&self.states)
+            .find(|(_, state)| state.violated)
+            .map(|(rule, _)| rule)
+    }
+
+    /// Feed a reading of `source` taken at `timestamp_ms`.
+    pub fn update(
+        &mut self,
+        source: Source,
+        timestamp_ms: u32,
+        value: f32,
+        mut on_event: impl FnMut(Event),
+    ) {
+        let rules = self.rules;
+        for (rule, state) in rules.iter().zip(&mut self.states) {
+            if rule.source != source {
+                continue;
+            }
+
+            let previous = state.last.replace((timestamp_ms, value));
+            let violating = match rule.condition {
+                Condition::Above(limit) => value > limit,
+                Condition::Below(limit) => value < limit,
+                Condition::RateAbove(limit) => match rate(previous, timestamp_ms, value) {
+                    Some(rate) => rate > limit,
+                    None => continue,
+                },
+                Condition::RateBelow(limit) => match rate(previous, timestamp_ms, value) {
+                    Some(rate) => rate < -limit,
+                    None => continue,
+                },
+                Condition::Missing { .. } => {
+                    if state.violated {
+                        state.violated = false;
+                        on_event(Event::Cleared { rule });
+                    }
+                    continue;
+                }
+            };
+
+            if violating == state.violated {
+                state.strikes = 0;
+                continue;
+            }
+            state.strikes += 1;
+            if state.strikes < self.confirm {
+                continue;
+            }
+
+            state.strikes = 0;
+            state.violated = violating;
+            on_event(match violating {
+                true => Event::Violated { rule, value },
+                false => Event::Cleared { rule },
+            });
+        }
+    }
+
+    /// Trip `Missing` rules whose source has been silent for too long.
+    pub fn check_missing(&mut self, now_ms: u32, mut on_event: impl FnMut(Event)) {
+        let rules = self.rules;
+        for (rule, state) in rules.iter().zip(&mut self.states) {
+            let Condition::Missing { timeout_ms } = rule.condition else {
+                continue;
+            };
+            let Some((last_ms, _)) = state.last else {
+                // Start the timeout now
+                state.last = Some((now_ms, 0.0));
+                continue;
+            };
+
+            let silent_ms = now_ms.wrapping_sub(last_ms);
+            if !state.violated && silent_ms > timeout_ms {
+                state.violated = true;
+                on_event(Event::Violated {
+                    rule,
+                    value: silent_ms as f32,
+                });
+            }
+        }
+    }
+}
+
+/// Units per second between the previous reading and this one.
+fn rate(previous: Option<(u32, f32)>, timestamp_ms: u32, value: f32) -> Option<f32> {
+    let (previous_ms, previous_value) = previous?;
+    let elapsed_ms = timestamp_ms.wrapping_sub(previous_ms);
+    if elapsed_ms == 0 {
+        return None;
+    }
+    Some((value - previous_value) * 1000.0 / elapsed_ms as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TANK: Source = Source::Analog(AnalogChannel::A1);
+    const PROBE: Source = Source::Temperature(1);
+
+    static RULES: [Rule; 4] = [
+        Rule {
+            name: "overpressure",
+            source: TANK,
+            condition: Condition::Above(60.0),
+        },
+        Rule {
+            name: "pressure_rise",
+            source: TANK,
+            condition: Condition::RateAbove(50.0),
+        },
+        Rule {
+            name: "probe_missing",
+            source: PROBE,
+            condition: Condition::Missing { timeout_ms: 2000 },
+        },
+        Rule {
+            name: "probe_cold",
+            source: PROBE,
+            condition: Condition::Below(5.0),
+        },
+    ];
+
+    fn update(interlocks: &mut Interlocks, source: Source, at_ms: u32, value: f32) -> Vec<Event> {
+        let mut events = Vec::new();
+        interlocks.update(source, at_ms, value, |event| events.push(event));
+        events
+    }
+
+    fn check_missing(interlocks: &mut Interlocks, now_ms: u32) -> Vec<Event> {
+        let mut events = Vec::new();
+        interlocks.check_missing(now_ms, |event| events.push(event));
+        events
+    }
+
+    #[test]
+    fn threshold_needs_confirmation() {
+        let mut interlocks = Interlocks::new(&RULES[..1], 2);
+        assert!(update(&mut interlocks, TANK, 0, 61.0).is_empty());
+        // A single outlier is forgotten
+        assert!(update(&mut interlocks, TANK, 100, 59.0).is_empty());
+        assert!(update(&mut interlocks, TANK, 200, 61.0).is_empty());
+        assert_eq!(
+            update(&mut interlocks, TANK, 300, 62.0),
+            [Event::Violated {
+                rule: &RULES[0],
+                value: 62.0
+            }]
+        );
+        assert_eq!(interlocks.active(), Some(&RULES[0]));
+
+        assert!(update(&mut interlocks, TANK, 400, 50.0).is_empty());
+        assert_eq!(
+            update(&mut interlocks, TANK, 500, 50.0),
+            [Event::Cleared { rule: &RULES[0] }]
+        );
+        assert_eq!(interlocks.active(), None);
+    }
+
+    #[test]
+    fn zero_confirm_trips_at_once() {
+        let mut interlocks = Interlocks::new(&RULES[..1], 0);
+        assert_eq!(update(&mut interlocks, TANK, 0, 61.0).len(), 1);
+    }
+
+    #[test]
+    fn rate_uses_the_previous_reading() {
+        let mut interlocks = Interlocks::new(&RULES[1..2], 1);
+        // No rate from a first reading
+        assert!(update(&mut interlocks, TANK, 0, 10.0).is_empty());
+        // 4 units in 100 ms is 40/s
+        assert!(update(&mut interlocks, TANK, 100, 14.0).is_empty());
+        // 6 units in 100 ms is 60/s
+        assert_eq!(
+            update(&mut interlocks, TANK, 200, 20.0),
+            [Event::Violated {
+                rule: &RULES[1],
+                value: 20.0
+            }]
+        );
+        // Same timestamp again gives no rate and changes nothing
+        assert!(update(&mut interlocks, TANK, 200, 0.0).is_empty());
+    }
+
+    #[test]
+    fn rate_across_timestamp_wrap() {
+        let mut interlocks = Interlocks::new(&RULES[1..2], 1);
+        update(&mut interlocks, TANK, u32::MAX - 49, 10.0);
+        assert_eq!(update(&mut interlocks, TANK, 50, 20.0).len(), 1);
+    }
+
+    #[test]
+    fn missing_counts_from_first_check() {
+        let mut interlocks = Interlocks::new(&RULES[2..3], 2);
+        assert!(check_missing(&mut interlocks, 1000).is_empty());
+        assert!(check_missing(&mut interlocks, 3000).is_empty());
+        assert_eq!(
+            check_missing(&mut interlocks, 3001),
+            [Event::Violated {
+                rule: &RULES[2],
+                value: 2001.0
+            }]
+        );
+        // Reported once
+        assert!(check_missing(&mut interlocks, 4000).is_empty());
+
+        // The next reading clears it without confirmation
+        assert_eq!(
+            update(&mut interlocks, PROBE, 4100, 20.0),
+            [Event::Cleared { rule: &RULES[2] }]
+        );
+        assert!(check_missing(&mut interlocks, 6000).is_empty());
+    }
+
+    #[test]
+    fn rules_of_other_sources_are_untouched() {
+        let mut interlocks = Interlocks::new(&RULES, 1);
+        assert!(interlocks.watches(PROBE));
+        assert!(!interlocks.watches(Source::Servo(0)));
+        assert!(update(&mut interlocks, TANK, 0, 0.0).is_empty());
+        assert_eq!(
+            update(&mut interlocks, PROBE, 0, 1.0),
+            [Event::Violated {
+                rule: &RULES[3],
+                value: 1.0
+            }]
+        );
+        assert_eq!(interlocks.active(), Some(&RULES[3]));
+    }
+}
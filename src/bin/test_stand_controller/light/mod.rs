@@ -0,0 +1,103 @@
+//! Signal light tower.
+//!
+//! Plays [`Pattern`]s in the background: each [`Priority`] holds one
+//! pattern and the tower shows the highest one playing, so an ABORT pattern
+//! covers the countdown until it is stopped.
+
+pub mod pattern;
+
+use defmt::warn;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Timer};
+use mainboard::board::I2cType;
+use mainboard::net_warn;
+use mainboard::signal_light::SignalLight;
+use mainboard::watchdog;
+
+use crate::config::{TASK_DEADLINE_MS, TASK_IDLE_CHECK_IN_MS};
+use pattern::{Pattern, Player, Priority};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+enum LightCommand {
+    Play(Priority, Pattern),
+    Stop(Priority),
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static LIGHT_COMMANDS: Channel<CriticalSectionRawMutex, LightCommand, 8> = Channel::new();
+
+pub fn play(priority: Priority, pattern: Pattern) {
+    if LIGHT_COMMANDS
+        .try_send(LightCommand::Play(priority, pattern))
+        .is_err()
+    {
+        warn!("Signal light channel full, dropping pattern");
+    }
+}
+
+pub fn stop(priority: Priority) {
+    if LIGHT_COMMANDS
+        .try_send(LightCommand::Stop(priority))
+        .is_err()
+    {
+        warn!("Signal light channel full, dropping stop");
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn timestamp_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn signal_light_task(signal_light_i2c: I2cType) {
+    let address = pcf857x::SlaveAddr::Alternative(false, false, true);
+    let mut light = match SignalLight::new(signal_light_i2c, address) {
+        Ok(light) => light,
+        Err(_e) => {
+            net_warn!("Failed to initialize signal light");
+            return;
+        }
+    };
+    let watch = watchdog::register("signal_light", Duration::from_millis(TASK_DEADLINE_MS));
+    let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+    let mut player = Player::new();
+
+    loop {
+        let now = timestamp_ms();
+        let output = player.output(now);
+        if output != light.current() && light.set(output).is_err() {
+            warn!("Failed to set signal light");
+        }
+
+        let next_change = match player.next_change(now) {
+            // Retry a failed write soon even if the pattern is steady
+            _ if output != light.current() => Instant::now() + idle_period,
+            Some(ms) => Instant::now() + Duration::from_millis(ms as u64),
+            None => Instant::MAX,
+        };
+        let event = select(LIGHT_COMMANDS.receive(), Timer::at(next_change));
+        match watch.idle(idle_period, event).await {
+            Either::First(LightCommand::Play(priority, pattern)) => {
+                player.play(priority, pattern, timestamp_ms());
+            }
+            Either::First(LightCommand::Stop(priority)) => player.stop(priority),
+            Either::Second(()) => {}
+        }
+    }
+}
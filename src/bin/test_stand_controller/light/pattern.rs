@@ -0,0 +1,415 @@
+//! Signal light patterns as data, and the timeline that turns them into
+//! output states.
+//!
+//! Times are milliseconds and may wrap.
+
+use mainboard::signal_light::SignalLightConfig;
+
+/// How one output of the tower behaves over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Wave {
+    Off,
+    On,
+    /// On for the first `on_ms` of every `period_ms`
+    Blink {
+        period_ms: u16,
+        on_ms: u16,
+    },
+    /// `count` beeps of `on_ms`, `gap_ms` apart, repeated every `period_ms`;
+    /// a `period_ms` of 0 plays them once
+    Beeps {
+        count: u8,
+        on_ms: u16,
+        gap_ms: u16,
+        period_ms: u16,
+    },
+}
+
+impl Wave {
+    /// Whether the output is on `elapsed_ms` after the pattern started.
+    pub fn level(self, elapsed_ms: u32) -> bool {
+        match self {
+            Self::Off => false,
+            Self::On => true,
+            Self::Blink { period_ms, on_ms } => {
+                on_ms != 0 && (on_ms >= period_ms || elapsed_ms % (period_ms as u32) < on_ms as u32)
+            }
+            Self::Beeps {
+                count,
+                on_ms,
+                gap_ms,
+                period_ms,
+            } => {
+                let phase = beeps_phase(elapsed_ms, period_ms);
+                let slot = on_ms as u32 + gap_ms as u32;
+                slot != 0 && phase / slot < count as u32 && phase % slot < on_ms as u32
+            }
+        }
+    }
+
+    /// Milliseconds from `elapsed_ms` to the next time [`Wave::level`] may
+    /// change, `None` if it never does.
+    pub fn next_change(self, elapsed_ms: u32) -> Option<u32> {
+        match self {
+            Self::Off | Self::On => None,
+            Self::Blink { period_ms, on_ms } => {
+                if on_ms == 0 || on_ms >= period_ms {
+                    return None;
+                }
+                let phase = elapsed_ms % period_ms as u32;
+                let edge = if phase < on_ms as u32 {
+                    on_ms
+                } else {
+                    period_ms
+                };
+                Some(edge as u32 - phase)
+            }
+            Self::Beeps {
+                count,
+                on_ms,
+                gap_ms,
+                period_ms,
+            } => {
+                let slot = on_ms as u32 + gap_ms as u32;
+                if count == 0 || on_ms == 0 || slot == 0 {
+                    return None;
+                }
+                let phase = beeps_phase(elapsed_ms, period_ms);
+                let beep = phase / slot;
+                let edge = if beep >= count as u32 {
+                    // Silent until the next period
+                    if period_ms == 0 {
+                        return None;
+                    }
+                    period_ms as u32
+                } else if phase % slot < on_ms as u32 {
+                    beep * slot + on_ms as u32
+                } else if beep + 1 < count as u32 {
+                    (beep + 1) * slot
+                } else if period_ms == 0 {
+                    return None;
+                } else {
+                    period_ms as u32
+                };
+                Some(edge - phase)
+            }
+        }
+    }
+}
+
+/// Position within the current beep period.
+fn beeps_phase(elapsed_ms: u32, period_ms: u16) -> u32 {
+    match period_ms {
+        0 => elapsed_ms,
+        period_ms => elapsed_ms % period_ms as u32,
+    }
+}
+
+/// A [`Wave`] for every output of the tower.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Pattern {
+    pub green: Wave,
+    pub red: Wave,
+    pub yellow: Wave,
+    pub blue: Wave,
+    pub white: Wave,
+    pub buzzer: Wave,
+}
+
+impl Pattern {
+    /// The outputs of `config` on, the others off.
+    pub const fn steady(config: SignalLightConfig) -> Self {
+        const fn wave(on: bool) -> Wave {
+            if on {
+                Wave::On
+            } else {
+                Wave::Off
+            }
+        }
+        Self {
+            green: wave(config.green),
+            red: wave(config.red),
+            yellow: wave(config.yellow),
+            blue: wave(config.blue),
+            white: wave(config.white),
+            buzzer: wave(config.buzzer),
+        }
+    }
+
+    fn waves(&self) -> [Wave; 6] {
+        [
+            self.green,
+            self.red,
+            self.yellow,
+            self.blue,
+            self.white,
+            self.buzzer,
+        ]
+    }
+
+    pub fn output(&self, elapsed_ms: u32) -> SignalLightConfig {
+        SignalLightConfig {
+            green: self.green.level(elapsed_ms),
+            red: self.red.level(elapsed_ms),
+            yellow: self.yellow.level(elapsed_ms),
+            blue: self.blue.level(elapsed_ms),
+            white: self.white.level(elapsed_ms),
+            buzzer: self.buzzer.level(elapsed_ms),
+        }
+    }
+
+    pub fn next_change(&self, elapsed_ms: u32) -> Option<u32> {
+        self.waves()
+            .into_iter()
+            .filter_map(|wave| wave.next_change(elapsed_ms))
+            .min()
+    }
+}
+
+/// Who asked for a pattern; the light shows the highest priority playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Priority {
+    /// The stand state
+    State,
+    /// `LIGHT` steps of a running sequence
+    Sequence,
+    /// ABORT, over everything else
+    Alert,
+}
+
+const PRIORITIES: usize = 3;
+
+/// One pattern per [`Priority`], each timed from when it started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Player {
+    /// Pattern and start time
+    layers: [Option<(Pattern, u32)>; PRIORITIES],
+}
+
+impl Player {
+    pub const fn new() -> Self {
+        Self {
+            layers: [None; PRIORITIES],
+        }
+    }
+
+    /// Start `pattern` at `priority`. Playing the pattern already there
+    /// keeps it running, so repeating a request does not restart a blink.
+    pub fn play(&mut self, priority: Priority, pattern: Pattern, now_ms: u32) {
+        let layer = &mut self.layers[priority as usize];
+        if !layer.is_some_and(|(playing, _)| playing == pattern) {
+            *layer = Some((pattern, now_ms));
+        }
+    }
+
+    pub fn stop(&mut self, priority: Priority) {
+        self.layers[priority as usize] = None;
+    }
+
+    /// The pattern shown and how long it has played.
+    fn top(&self, now_ms: u32) -> Option<(Pattern, u32)> {
+        self.layers
+            .iter()
+            .rev()
+            .flatten()
+            .next()
+            .map(|&(pattern, start_ms)| (pattern, now_ms.wrapping_sub(start_ms)))
+    }
+
+    /// The outputs at `now_ms`, all off when nothing plays.
+    pub fn output(&self, now_ms: u32) -> SignalLightConfig {
+        self.top(now_ms)
+            .map_or(SignalLightConfig::default(), |(pattern, elapsed_ms)| {
+                pattern.output(elapsed_ms)
+            })
+    }
+
+    /// Milliseconds from `now_ms` to the next change of [`Player::output`]
+    /// without new requests, `None` if it stays as it is.
+    pub fn next_change(&self, now_ms: u32) -> Option<u32> {
+        let (pattern, elapsed_ms) = self.top(now_ms)?;
+        pattern.next_change(elapsed_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLINK: Wave = Wave::Blink {
+        period_ms: 1000,
+        on_ms: 250,
+    };
+    const BEEPS: Wave = Wave::Beeps {
+        count: 3,
+        on_ms: 100,
+        gap_ms: 50,
+        period_ms: 2000,
+    };
+    const RED: SignalLightConfig = SignalLightConfig {
+        green: false,
+        red: true,
+        yellow: false,
+        blue: false,
+        white: false,
+        buzzer: false,
+    };
+    const GREEN: SignalLightConfig = SignalLightConfig {
+        green: true,
+        red: false,
+        yellow: false,
+        blue: false,
+        white: false,
+        buzzer: false,
+    };
+
+    /// Times in `from_ms..to_ms` where the level of `wave` changes.
+    fn edges(wave: Wave, from_ms: u32, to_ms: u32) -> Vec<u32> {
+        (from_ms..to_ms)
+            .filter(|&ms| wave.level(ms) != wave.level(ms.wrapping_sub(1)))
+            .collect()
+    }
+
+    /// `next_change` lands on every edge and on nothing that is not one.
+    fn check_next_change(wave: Wave, to_ms: u32) {
+        let edges = edges(wave, 1, to_ms);
+        for elapsed_ms in 0..to_ms {
+            let next = edges.iter().find(|&&edge| edge > elapsed_ms);
+            match wave.next_change(elapsed_ms) {
+                Some(ms) => {
+                    let at = elapsed_ms + ms;
+                    assert!(ms > 0, "{wave:?} at {elapsed_ms}");
+                    assert!(
+                        next.is_none_or(|&edge| at <= edge),
+                        "{wave:?} at {elapsed_ms} skips the edge at {next:?}"
+                    );
+                }
+                None => assert_eq!(next, None, "{wave:?} at {elapsed_ms}"),
+            }
+        }
+    }
+
+    #[test]
+    fn blink_timeline() {
+        assert!(BLINK.level(0));
+        assert!(BLINK.level(249));
+        assert!(!BLINK.level(250));
+        assert!(!BLINK.level(999));
+        assert!(BLINK.level(1000));
+        assert_eq!(edges(BLINK, 1, 2500), [250, 1000, 1250, 2000, 2250]);
+        assert_eq!(BLINK.next_change(0), Some(250));
+        assert_eq!(BLINK.next_change(600), Some(400));
+        check_next_change(BLINK, 3000);
+    }
+
+    #[test]
+    fn degenerate_blinks_are_steady() {
+        for (on_ms, level) in [(0, false), (1000, true), (2000, true)] {
+            let wave = Wave::Blink {
+                period_ms: 1000,
+                on_ms,
+            };
+            assert_eq!(wave.level(123), level);
+            assert_eq!(wave.next_change(123), None);
+        }
+    }
+
+    #[test]
+    fn beeps_timeline() {
+        assert_eq!(
+            edges(BEEPS, 1, 4000),
+            [100, 150, 250, 300, 400, 2000, 2100, 2150, 2250, 2300, 2400]
+        );
+        assert_eq!(BEEPS.next_change(0), Some(100));
+        assert_eq!(BEEPS.next_change(120), Some(30));
+        // After the last beep, silent until the next period
+        assert_eq!(BEEPS.next_change(400), Some(1600));
+        check_next_change(BEEPS, 5000);
+    }
+
+    #[test]
+    fn beeps_once() {
+        let once = Wave::Beeps {
+            count: 2,
+            on_ms: 100,
+            gap_ms: 100,
+            period_ms: 0,
+        };
+        assert_eq!(edges(once, 1, 10_000), [100, 200, 300]);
+        assert_eq!(once.next_change(250), Some(50));
+        assert_eq!(once.next_change(300), None);
+        check_next_change(once, 1000);
+    }
+
+    #[test]
+    fn steady_pattern() {
+        let pattern = Pattern::steady(RED);
+        assert_eq!(pattern.output(0), RED);
+        assert_eq!(pattern.output(123_456), RED);
+        assert_eq!(pattern.next_change(0), None);
+    }
+
+    #[test]
+    fn frames_at_time() {
+        let pattern = Pattern {
+            red: BLINK,
+            buzzer: BEEPS,
+            ..Pattern::steady(GREEN)
+        };
+        let frame = pattern.output(120);
+        assert!(frame.green && frame.red && !frame.buzzer);
+        let frame = pattern.output(310);
+        assert!(frame.green && !frame.red && frame.buzzer);
+        // The nearest edge of any output
+        assert_eq!(pattern.next_change(0), Some(100));
+        assert_eq!(pattern.next_change(240), Some(10));
+    }
+
+    #[test]
+    fn player_shows_highest_priority() {
+        let mut player = Player::new();
+        assert_eq!(player.output(0), SignalLightConfig::default());
+        assert_eq!(player.next_change(0), None);
+
+        player.play(Priority::State, Pattern::steady(GREEN), 0);
+        player.play(Priority::Alert, Pattern::steady(RED), 10);
+        assert_eq!(player.output(20), RED);
+        player.stop(Priority::Alert);
+        assert_eq!(player.output(30), GREEN);
+    }
+
+    #[test]
+    fn player_times_each_pattern_from_its_start() {
+        let blink = Pattern {
+            red: BLINK,
+            ..Pattern::steady(SignalLightConfig::default())
+        };
+        let mut player = Player::new();
+        player.play(Priority::Sequence, blink, 5000);
+        assert!(player.output(5000).red);
+        assert!(!player.output(5250).red);
+        assert_eq!(player.next_change(5600), Some(400));
+
+        // Asking again does not restart it
+        player.play(Priority::Sequence, blink, 5300);
+        assert!(!player.output(5300).red);
+    }
+
+    #[test]
+    fn player_across_clock_wrap() {
+        let blink = Pattern {
+            red: BLINK,
+            ..Pattern::steady(SignalLightConfig::default())
+        };
+        let start_ms = u32::MAX - 99;
+        let mut player = Player::new();
+        player.play(Priority::State, blink, start_ms);
+        assert!(player.output(start_ms).red);
+        // 150 ms in, past the wrap
+        assert!(player.output(50).red);
+        assert_eq!(player.next_change(50), Some(100));
+        assert!(!player.output(150).red);
+        // 1000 ms in: the next period
+        assert!(player.output(900).red);
+    }
+}
@@ -0,0 +1,51 @@
+use defmt::info;
+use embassy_time::Instant;
+use mainboard::netlog::{Level, LogReader, LogRecord, RateLimiter, ReadResult};
+
+use crate::mqtt::queue;
+use crate::mqtt::sensors::log::LogPacket;
+
+/// Records per second forwarded to MQTT on average.
+const LOG_RATE_PER_SECOND: u32 = 5;
+const LOG_BURST: u32 = 20;
+
+/// Forwards network log records to `log/<client-id>`. Rate limited, and the
+/// queue refuses log lines once it is half full, so a log storm cannot
+/// crowd out sensor data.
+#[embassy_executor::task]
+pub async fn log_forward_task(mut reader: LogReader) {
+    let mut limiter = RateLimiter::new(LOG_RATE_PER_SECOND, LOG_BURST);
+    let mut dropped = 0u32;
+    info!("Log forwarder started");
+
+    loop {
+        let record = match reader.next().await {
+            ReadResult::Record(record) => record,
+            ReadResult::Dropped(count) => {
+                dropped = dropped.saturating_add(count);
+                continue;
+            }
+        };
+        if !limiter.allow(Instant::now().as_millis()) {
+            continue;
+        }
+
+        dropped = dropped.saturating_add(limiter.take_suppressed());
+        if dropped > 0 {
+            let notice = LogRecord::new(
+                Level::Warn,
+                record.uptime_ms,
+                module_path!(),
+                format_args!("{} log records dropped", dropped),
+            );
+            // Keep the count if even the notice does not fit
+            if queue::publish_log(LogPacket::from_record(&notice)).is_ok() {
+                dropped = 0;
+            }
+        }
+
+        if queue::publish_log(LogPacket::from_record(&record)).is_err() {
+            dropped = dropped.saturating_add(1);
+        }
+    }
+}
@@ -7,20 +7,38 @@
     holding buffers for the duration of a data transfer."
 )]
 
-mod camera_shutter;
+extern crate alloc;
+
+mod calibration;
+mod camera;
+mod capture;
 mod config;
+mod heartbeat;
+mod interlock;
+mod light;
+mod log_forward;
 mod mqtt;
+mod ota_bridge;
 mod sensor_collection;
+mod sequence;
 mod sequencer;
 mod servo;
 mod temperature_collection;
+mod time_sync;
 
+use mainboard::analog::{spawn_analog_service, AnalogIo};
 use mainboard::board::{acquire_i2c_bus, init_i2c_bus, Board};
 use mainboard::create_board;
+use mainboard::flash::init_flash;
+use mainboard::netlog::{self, spawn_syslog_sink, SyslogConfig};
+use mainboard::ota::{spawn_ota_service, OtaConfig};
 use mainboard::power::PowerControllerIO;
+use mainboard::sntp::{spawn_sntp_service, SntpConfig};
 use mainboard::tasks::{
     spawn_ext_interrupt_task, spawn_power_controller, PowerResponse, PowerStateReceiver,
 };
+use mainboard::watchdog::{spawn_watchdog, WatchdogConfig};
+use mainboard::wifi::mdns::{spawn_mdns_responder, MdnsConfig};
 use mainboard::wifi::{initialize_wifi_sta, WifiResourceSta};
 
 use defmt::info;
@@ -29,7 +47,6 @@ use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal}
 use esp_hal::clock::CpuClock;
 use esp_hal::rtc_cntl::Rtc;
 use esp_hal::timer::timg::TimerGroup;
-use panic_rtt_target as _;
 use static_cell::StaticCell;
 
 // StaticCell for WiFi controller
@@ -54,6 +71,7 @@ async fn main(spawner: Spawner) {
     // Configure and initialize hardware
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
+    mainboard::crash::init();
 
     // Initialize heap allocator
     // Not #[esp_hal::ram(reclaimed)] because its too small XD
@@ -71,6 +89,13 @@ async fn main(spawner: Spawner) {
 
     init_i2c_bus(peripherals.I2C0, board.Sda, board.Scl).expect("Failed to initialize I2C bus");
 
+    // Roll back early if the previous OTA image never confirmed itself
+    init_flash(peripherals.FLASH);
+    let ota = spawn_ota_service(&spawner, OtaConfig::default());
+    spawner
+        .spawn(ota_bridge::ota_bridge_task(ota))
+        .expect("Failed to spawn ota_bridge_task");
+
     // Initialize RNG for WiFi
     let mut rng = esp_hal::rng::Rng::new();
 
@@ -78,16 +103,18 @@ async fn main(spawner: Spawner) {
     let radio_init =
         ESP_RADIO_INIT.init(esp_radio::init().expect("Failed to initialize Wi-Fi/BLE controller"));
 
-    let sensor_collection_io = sensor_collection::SensorCollectionIo {
+    let analog_io = AnalogIo {
         adc: peripherals.ADC1,
-        tensometer: board.A0,
-        pressure_tank: board.A1,
-        pressure_combustion: board.A2,
-        starter_sense: board.A3,
-        battery_stand: board.A4,
-        battery_computer: board.BatVol,
-        boost_voltage: board.BoostVol,
+        a0: board.A0,
+        a1: board.A1,
+        a2: board.A2,
+        a3: board.A3,
+        a4: board.A4,
+        bat_vol: board.BatVol,
+        boost_vol: board.BoostVol,
+        dma: Some(peripherals.DMA_CH2),
     };
+    let analog = spawn_analog_service(&spawner, analog_io, sensor_collection::analog_config());
 
     let power_config = Default::default();
     let power_io = PowerControllerIO {
@@ -121,6 +148,38 @@ async fn main(spawner: Spawner) {
     // Store wifi resources in static cell for mqtt_task
     let wifi_resources = WIFI_RESOURCES.init(wifi_resources);
 
+    // Answer `<client-id>.local` so the stand can be reached without a DHCP reservation
+    spawn_mdns_responder(
+        &spawner,
+        *wifi_resources,
+        MdnsConfig {
+            hostname: config::MQTT_CLIENT_ID,
+            services: &[],
+        },
+    );
+
+    if let Some(server) = mainboard::config::SYSLOG_SERVER {
+        spawn_syslog_sink(
+            &spawner,
+            *wifi_resources,
+            SyslogConfig {
+                server,
+                hostname: config::MQTT_CLIENT_ID,
+                app_name: "test_stand_controller",
+                ..Default::default()
+            },
+        );
+    }
+    let log_reader = netlog::reader().expect("Failed to get network log reader");
+    spawner
+        .spawn(log_forward::log_forward_task(log_reader))
+        .expect("Failed to spawn log_forward_task");
+
+    let sntp = spawn_sntp_service(&spawner, *wifi_resources, SntpConfig::default());
+    spawner
+        .spawn(time_sync::time_sync_publish_task(sntp))
+        .expect("Failed to spawn time_sync_publish_task");
+
     // Spawn MQTT task
     spawner
         .spawn(mqtt::mqtt_task(wifi_resources, &SHUTDOWN_SIGNAL))
@@ -129,11 +188,25 @@ async fn main(spawner: Spawner) {
 
     spawner
         .spawn(sensor_collection::sensor_collection_task(
-            sensor_collection_io,
+            analog
+                .block_subscriber()
+                .expect("Failed to get analog block subscriber"),
         ))
         .expect("Failed to spawn sensor_collection_task");
     info!("Sensor collection task spawned");
 
+    spawner
+        .spawn(capture::capture_task(
+            analog
+                .block_subscriber()
+                .expect("Failed to get analog block subscriber"),
+        ))
+        .expect("Failed to spawn capture_task");
+
+    spawner
+        .spawn(calibration::calibration_task(analog))
+        .expect("Failed to spawn calibration_task");
+
     let temp_io = temperature_collection::TemperatureCollectionIo {
         uart: peripherals.UART0,
         tx_pin: board.U0Tx,
@@ -146,36 +219,69 @@ async fn main(spawner: Spawner) {
         .expect("Failed to spawn temperature_collection_task");
     info!("Temperature collection task spawned");
 
+    let pull_up = esp_hal::gpio::InputConfig::default().with_pull(esp_hal::gpio::Pull::Up);
+    let (armed_second_pin, servo_motor1) = if config::ARMED_SECOND_CONTACT {
+        let pin = esp_hal::gpio::Input::new(board.Motor1, pull_up);
+        (Some(pin), None)
+    } else {
+        (None, Some(board.Motor1))
+    };
     spawner
-        .spawn(servo::servo_controller_task(peripherals.MCPWM0, board.D1))
+        .spawn(servo::servo_controller_task(
+            peripherals.MCPWM0,
+            servo::ServoPins {
+                d1: board.D1,
+                motor0: board.Motor0,
+                motor1: servo_motor1,
+            },
+        ))
         .expect("Failed to spawn servo_controller_task");
     info!("Servo controller task spawned");
 
+    let focus_pin = esp_hal::gpio::Output::new(
+        board.D3,
+        esp_hal::gpio::Level::Low,
+        esp_hal::gpio::OutputConfig::default(),
+    );
     let shutter_pin = esp_hal::gpio::Output::new(
         board.D4,
         esp_hal::gpio::Level::Low,
         esp_hal::gpio::OutputConfig::default(),
     );
     spawner
-        .spawn(camera_shutter::camera_shutter_task(shutter_pin))
-        .expect("Failed to spawn camera_shutter_task");
-    info!("Camera shutter task spawned");
+        .spawn(camera::camera_task(focus_pin, shutter_pin))
+        .expect("Failed to spawn camera_task");
+    info!("Camera task spawned");
 
-    let armed_pin = esp_hal::gpio::Input::new(
-        board.D2,
-        esp_hal::gpio::InputConfig::default().with_pull(esp_hal::gpio::Pull::Up),
-    );
-    sequencer::init_armed_state(&armed_pin);
+    let armed_pin = esp_hal::gpio::Input::new(board.D2, pull_up);
+    let armed_switch = sequencer::armed::ArmedSwitch::new(armed_pin, armed_second_pin);
+    sequencer::init_armed_state(&armed_switch);
     let signal_light_i2c = acquire_i2c_bus();
     let fire_trigger_i2c = acquire_i2c_bus();
+    spawner
+        .spawn(sequence::sequence_task())
+        .expect("Failed to spawn sequence_task");
+    spawner
+        .spawn(heartbeat::heartbeat_task())
+        .expect("Failed to spawn heartbeat_task");
+    spawner
+        .spawn(interlock::interlock_task())
+        .expect("Failed to spawn interlock_task");
     spawner
         .spawn(sequencer::fire_sequencer_task(fire_trigger_i2c))
         .expect("Failed to spawn fire_sequencer_task");
     spawner
-        .spawn(sequencer::state_sequencer_task(armed_pin, signal_light_i2c))
+        .spawn(light::signal_light_task(signal_light_i2c))
+        .expect("Failed to spawn signal_light_task");
+    spawner
+        .spawn(sequencer::state_sequencer_task(armed_switch, rng))
         .expect("Failed to spawn state_sequencer_task");
     info!("State sequencer task spawned");
 
+    // Feed the hardware watchdog only while every supervised task checks in
+    let timg1 = TimerGroup::new(peripherals.TIMG1);
+    spawn_watchdog(&spawner, timg1.wdt, WatchdogConfig::default());
+
     SHUTDOWN_SIGNAL.wait().await;
     info!("Shutdown signal received");
 
@@ -1,8 +1,12 @@
-use defmt::{debug, error, info, warn};
+use alloc::format;
+
+use defmt::{debug, info, warn};
 use embassy_futures::select::{select, Either};
 use embassy_net::tcp::TcpSocket;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
 use embassy_time::{Duration, Timer};
+use mainboard::wifi::mdns::browse_service;
+use mainboard::{net_error, net_info, net_warn};
 use rust_mqtt::buffer::BumpBuffer;
 use rust_mqtt::client::event::Event;
 use rust_mqtt::client::options::{
@@ -14,20 +18,33 @@ use rust_mqtt::types::{MqttBinary, MqttString, QoS};
 use smoltcp::wire::{DnsQueryType, IpAddress};
 use static_cell::StaticCell;
 
-use crate::config::{MQTT_CLIENT_ID, MQTT_HOST, MQTT_PASSWORD, MQTT_PORT, MQTT_USER};
+use crate::config::{
+    MQTT_CLIENT_ID, MQTT_HOST, MQTT_MDNS_DISCOVERY, MQTT_PASSWORD, MQTT_PORT, MQTT_USER,
+};
 use crate::mqtt::codec::EncodeError;
+use crate::mqtt::commands::calibrate::{CalibrateCommand, ServoCalibrateCommand};
+use crate::mqtt::commands::camera::CameraCommand;
+use crate::mqtt::commands::capture::CaptureCommand;
+use crate::mqtt::commands::heartbeat::HeartbeatCommand;
+use crate::mqtt::commands::ota::OtaCommand;
+use crate::mqtt::commands::sequence::SequenceCommand;
 use crate::mqtt::commands::servo::ServoCommand;
 use crate::mqtt::commands::shutdown::ShutdownCommand;
 use crate::mqtt::commands::state::StateCommand;
+use crate::mqtt::commands::trigger::TriggerCommand;
 use crate::mqtt::commands::{
-    CommandDispatcher, ServoCommandHandler, ShutdownCommandHandler, StateCommandHandler,
+    CalibrateCommandHandler, CameraCommandHandler, CaptureCommandHandler, CommandDispatcher,
+    CommandHandlers, HeartbeatCommandHandler, OtaCommandHandler, SequenceCommandHandler,
+    ServoCalibrateCommandHandler, ServoCommandHandler, ShutdownCommandHandler, StateCommandHandler,
+    TriggerCommandHandler,
 };
 use crate::mqtt::queue::{self, OutboundMessage};
-use crate::mqtt::sensors::status::StateStatus;
+use crate::mqtt::sensors::crash::CrashReportPacket;
 use crate::mqtt::sensors::EncodablePayload;
 use crate::mqtt::topics::{
-    self, TopicBuildError, COMMAND_TOPICS, TEMP_TOPIC_BUFFER_LEN, TOPIC_STATUS_CMD,
-    TOPIC_STATUS_SERVO, TOPIC_STATUS_STATE,
+    self, TopicBuildError, COMMAND_TOPICS, TEMP_TOPIC_BUFFER_LEN, TOPIC_SENSOR_SERVO_PREFIX,
+    TOPIC_STATUS_CALIBRATION_SERVO_PREFIX, TOPIC_STATUS_CMD, TOPIC_STATUS_COUNTDOWN,
+    TOPIC_STATUS_OTA, TOPIC_STATUS_SEQUENCE, TOPIC_STATUS_SERVO_PREFIX, TOPIC_STATUS_STATE,
 };
 use mainboard::wifi::WifiResourceSta;
 
@@ -36,12 +53,14 @@ const MQTT_KEEPALIVE_SECS: u16 = 10;
 const MQTT_BUFFER_SIZE: usize = 4096;
 const TCP_BUFFER_SIZE: usize = 4096;
 const MQTT_PAYLOAD_BUFFER_SIZE: usize = 256;
+const MQTT_MDNS_SERVICE: &str = "_mqtt._tcp";
+const MQTT_MDNS_TIMEOUT: Duration = Duration::from_secs(3);
 
 static TCP_RX_BUF: StaticCell<[u8; TCP_BUFFER_SIZE]> = StaticCell::new();
 static TCP_TX_BUF: StaticCell<[u8; TCP_BUFFER_SIZE]> = StaticCell::new();
 static MQTT_BUF: StaticCell<[u8; MQTT_BUFFER_SIZE]> = StaticCell::new();
 
-type AppClient<'a, 'b> = Client<'a, TcpSocket<'b>, BumpBuffer<'a>, 5, 2, 2>;
+type AppClient<'a, 'b> = Client<'a, TcpSocket<'b>, BumpBuffer<'a>, 9, 2, 2>;
 
 #[derive(Debug, Clone, Copy, defmt::Format)]
 enum AppMqttError {
@@ -62,26 +81,40 @@ impl AppCommandHandlers {
     }
 }
 
+/// Rejects a command that must wait until the stand is not firing.
+fn rejected_while_active(command: &str) -> bool {
+    let state = crate::sequencer::load_state();
+    if !state.is_active() {
+        return false;
+    }
+    net_warn!("{} rejected: {} state", command, state.as_str());
+    queue::publish_command_log(&format!("{} rejected: {} state", command, state.as_str()));
+    true
+}
+
 impl StateCommandHandler for AppCommandHandlers {
     fn handle_state_command(&mut self, command: StateCommand) {
         crate::sequencer::send_state_command(command);
-        info!("MQTT command: state -> {:?}", command);
+        net_info!("MQTT command: state -> {:?}", command);
     }
 }
 
 impl ServoCommandHandler for AppCommandHandlers {
-    fn handle_servo_command(&mut self, command: ServoCommand) {
-        if crate::sequencer::load_state() == StateStatus::Fire {
-            warn!("MQTT command ignored: cmd/servo in FIRE state");
-            queue::publish_command_log("Servo command rejected: FIRE state");
+    fn handle_servo_command(&mut self, servo: usize, command: ServoCommand) {
+        // A running sequence may hand the servo to the operator
+        if !crate::sequencer::manual_servo_allowed() && rejected_while_active("Servo command") {
             return;
         }
 
+        let name = crate::servo::name(servo);
         match command {
-            ServoCommand::Open => info!("MQTT command: OPEN"),
-            ServoCommand::Close => info!("MQTT command: CLOSE"),
+            ServoCommand::Open => info!("MQTT command: {} OPEN", name),
+            ServoCommand::Close => info!("MQTT command: {} CLOSE", name),
+            ServoCommand::Move { position, rate } => {
+                info!("MQTT command: {} MOVE {} rate {}", name, position, rate)
+            }
         }
-        crate::servo::send_servo_command(command);
+        crate::servo::send_servo_command(servo, command);
     }
 }
 
@@ -89,12 +122,10 @@ impl ShutdownCommandHandler for AppCommandHandlers {
     fn handle_shutdown_command(&mut self, command: ShutdownCommand) {
         match command {
             ShutdownCommand::Shutdown => {
-                if crate::sequencer::load_state() == StateStatus::Fire {
-                    warn!("MQTT command ignored: SHUTDOWN in FIRE state");
-                    queue::publish_command_log("Shutdown rejected: FIRE state");
+                if rejected_while_active("Shutdown") {
                     return;
                 }
-                info!("MQTT command: SHUTDOWN");
+                net_info!("MQTT command: SHUTDOWN");
                 queue::publish_command_log("Shutdown");
                 self.shutdown_signal.signal(());
             }
@@ -102,6 +133,76 @@ impl ShutdownCommandHandler for AppCommandHandlers {
     }
 }
 
+impl OtaCommandHandler for AppCommandHandlers {
+    fn handle_ota_command(&mut self, command: OtaCommand) {
+        if crate::sequencer::load_state().is_active() {
+            net_warn!("MQTT command ignored: OTA while firing");
+            queue::publish_ota_log("ERR FIRE_STATE");
+            return;
+        }
+        crate::ota_bridge::submit_ota_command(command);
+    }
+}
+
+impl CaptureCommandHandler for AppCommandHandlers {
+    fn handle_capture_command(&mut self, command: CaptureCommand) {
+        crate::capture::submit_capture_command(command);
+    }
+}
+
+impl CalibrateCommandHandler for AppCommandHandlers {
+    fn handle_calibrate_command(&mut self, command: CalibrateCommand) {
+        if rejected_while_active("Calibration") {
+            return;
+        }
+        net_info!("MQTT command: calibrate {:?}", command);
+        crate::calibration::submit_calibrate_command(command);
+    }
+}
+
+impl ServoCalibrateCommandHandler for AppCommandHandlers {
+    fn handle_servo_calibrate_command(&mut self, servo: usize, command: ServoCalibrateCommand) {
+        if rejected_while_active("Servo calibration") {
+            return;
+        }
+        net_info!(
+            "MQTT command: calibrate servo {} {:?}",
+            crate::servo::name(servo),
+            command
+        );
+        crate::servo::send_calibrate_command(servo, command);
+    }
+}
+
+impl SequenceCommandHandler for AppCommandHandlers {
+    fn handle_sequence_command(&mut self, command: SequenceCommand) {
+        if rejected_while_active("Sequence") {
+            return;
+        }
+        crate::sequence::submit_sequence_command(command);
+    }
+}
+
+impl HeartbeatCommandHandler for AppCommandHandlers {
+    fn handle_heartbeat_command(&mut self, _command: HeartbeatCommand) {
+        crate::heartbeat::beat();
+    }
+}
+
+impl TriggerCommandHandler for AppCommandHandlers {
+    fn handle_trigger_command(&mut self, command: TriggerCommand) {
+        net_info!("MQTT command: trigger {:?}", command);
+        crate::sequencer::send_trigger_command(command);
+    }
+}
+
+impl CameraCommandHandler for AppCommandHandlers {
+    fn handle_camera_command(&mut self, command: CameraCommand) {
+        net_info!("MQTT command: camera {:?}", command);
+        crate::camera::send_camera_command(command);
+    }
+}
+
 #[embassy_executor::task]
 pub async fn mqtt_task(
     wifi: &'static WifiResourceSta,
@@ -118,8 +219,9 @@ pub async fn mqtt_task(
         if let Err(error) =
             mqtt_connection_loop(&wifi, tcp_rx_buf, tcp_tx_buf, mqtt_buf, shutdown_signal).await
         {
-            error!("MQTT session ended: {:?}", &error);
+            net_error!("MQTT session ended: {:?}", error);
         }
+        crate::heartbeat::session_down();
 
         queue::clear_outbound_queue();
         Timer::after(Duration::from_millis(RECONNECT_DELAY_MS)).await;
@@ -143,7 +245,7 @@ async fn mqtt_connection_loop(
 
     mqtt_buf.fill(0);
     let mut buffer = BumpBuffer::new(mqtt_buf);
-    let mut client = Client::<_, _, 5, 2, 2>::new(&mut buffer);
+    let mut client = Client::<_, _, 9, 2, 2>::new(&mut buffer);
 
     let connect_options = build_connect_options()?;
     let client_id =
@@ -155,6 +257,7 @@ async fn mqtt_connection_loop(
         .map_err(|_| AppMqttError::MqttError)?;
 
     subscribe_to_commands(&mut client).await?;
+    crate::heartbeat::session_up();
     publish_state_on_connect();
     run_session_loop(&mut client, shutdown_signal).await
 }
@@ -163,7 +266,14 @@ fn publish_state_on_connect() {
     crate::sequencer::republish_sequencer_state();
     crate::servo::republish_servo_state();
     crate::sequencer::republish_armed_state();
+    crate::calibration::republish_calibration();
+    crate::sequence::republish_sequence();
     queue::publish_command_log("Connected");
+    crate::ota_bridge::report_healthy();
+    if let Some(record) = mainboard::crash::pending_report() {
+        net_warn!("Publishing crash report from previous run");
+        let _ = queue::publish_crash_report(CrashReportPacket { record });
+    }
     info!("Published current state on connect");
 }
 
@@ -171,14 +281,22 @@ async fn resolve_mqtt_endpoint(
     sta_stack: &embassy_net::Stack<'static>,
 ) -> Result<(smoltcp::wire::Ipv4Address, u16), AppMqttError> {
     info!("MQTT resolving host: {}", MQTT_HOST);
-    let addrs = sta_stack
+    let first = sta_stack
         .dns_query(MQTT_HOST, DnsQueryType::A)
         .await
-        .map_err(|_| AppMqttError::DnsLookupFailed)?;
+        .ok()
+        .and_then(|addrs| addrs.first().copied());
 
-    let first = addrs.first().ok_or(AppMqttError::DnsLookupFailed)?;
     match first {
-        IpAddress::Ipv4(ip) => Ok((*ip, MQTT_PORT)),
+        Some(IpAddress::Ipv4(ip)) => Ok((ip, MQTT_PORT)),
+        None if MQTT_MDNS_DISCOVERY => {
+            warn!("MQTT DNS lookup failed, browsing mDNS for a broker");
+            browse_service(*sta_stack, MQTT_MDNS_SERVICE, MQTT_MDNS_TIMEOUT)
+                .await
+                .map(|found| (found.address, found.port))
+                .ok_or(AppMqttError::DnsLookupFailed)
+        }
+        None => Err(AppMqttError::DnsLookupFailed),
     }
 }
 
@@ -264,15 +382,21 @@ async fn run_session_loop(
 
 fn handle_incoming_event<H>(event: Event<'_>, dispatcher: &mut CommandDispatcher<H>)
 where
-    H: StateCommandHandler + ServoCommandHandler + ShutdownCommandHandler,
+    H: CommandHandlers,
 {
-    if let Event::Publish(publish) = event {
-        let topic: &str = publish.topic.as_ref();
-        let payload: &[u8] = publish.message.as_ref();
+    match event {
+        Event::Publish(publish) => {
+            let topic: &str = publish.topic.as_ref();
+            let payload: &[u8] = publish.message.as_ref();
 
-        if let Err(error) = dispatcher.dispatch(topic, payload) {
-            warn!("MQTT command rejected topic='{}': {:?}", topic, &error);
+            if let Err(error) = dispatcher.dispatch(topic, payload) {
+                warn!("MQTT command rejected topic='{}': {:?}", topic, &error);
+            }
         }
+        // Only the crash report goes out at QoS 1, so this is the broker
+        // taking it
+        Event::PublishAcknowledged(_) => mainboard::crash::clear_report(),
+        _ => {}
     }
 }
 
@@ -287,19 +411,26 @@ async fn publish_outbound_message(
 
     let retain = matches!(
         message,
-        OutboundMessage::Armed(_)
+        OutboundMessage::Digital(_)
             | OutboundMessage::ServoSensor(_)
-            | OutboundMessage::ServoStatus(_)
+            | OutboundMessage::ServoStatus(..)
             | OutboundMessage::StateStatus(_)
+            | OutboundMessage::TimeSync(_)
+            | OutboundMessage::CrashReport(_)
+            | OutboundMessage::CalibrationStatus(_)
+            | OutboundMessage::ServoCalibrationStatus(_)
+            | OutboundMessage::SequenceStatus(_)
     );
 
+    // The crash report is kept across reconnects until the broker acks it
+    let qos = match message {
+        OutboundMessage::CrashReport(_) => QoS::AtLeastOnce,
+        _ => QoS::AtMostOnce,
+    };
+
     let topic =
         topics::make_topic_name(encoded.topic).ok_or(AppMqttError::StringConversionError)?;
-    let options = PublicationOptions {
-        retain,
-        topic,
-        qos: QoS::AtMostOnce,
-    };
+    let options = PublicationOptions { retain, topic, qos };
 
     client
         .publish(&options, encoded.payload.into())
@@ -338,7 +469,16 @@ fn encode_outbound_message<'a>(
                 payload: &payload_buffer[..written],
             }
         }
-        OutboundMessage::Armed(packet) => {
+        OutboundMessage::Digital(packet) => {
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic: packet.topic(),
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::Camera(packet) => {
             let written = packet
                 .encode_payload(payload_buffer)
                 .map_err(EncodeErrorWithTopic::Codec)?;
@@ -359,11 +499,15 @@ fn encode_outbound_message<'a>(
             }
         }
         OutboundMessage::ServoSensor(packet) => {
+            let name = crate::servo::name(packet.servo() as usize);
+            let topic =
+                topics::format_named_topic(TOPIC_SENSOR_SERVO_PREFIX, name, temp_topic_buffer)
+                    .map_err(EncodeErrorWithTopic::Topic)?;
             let written = packet
                 .encode_payload(payload_buffer)
                 .map_err(EncodeErrorWithTopic::Codec)?;
             EncodedMessage {
-                topic: packet.topic(),
+                topic,
                 payload: &payload_buffer[..written],
             }
         }
@@ -371,14 +515,104 @@ fn encode_outbound_message<'a>(
             topic: TOPIC_STATUS_STATE,
             payload: status.as_bytes(),
         },
-        OutboundMessage::ServoStatus(status) => EncodedMessage {
-            topic: TOPIC_STATUS_SERVO,
-            payload: status.as_bytes(),
-        },
+        OutboundMessage::ServoStatus(servo, status) => {
+            let name = crate::servo::name(*servo as usize);
+            let topic =
+                topics::format_named_topic(TOPIC_STATUS_SERVO_PREFIX, name, temp_topic_buffer)
+                    .map_err(EncodeErrorWithTopic::Topic)?;
+            EncodedMessage {
+                topic,
+                payload: status.as_bytes(),
+            }
+        }
         OutboundMessage::CommandStatus(status) => EncodedMessage {
             topic: TOPIC_STATUS_CMD,
             payload: status.as_bytes(),
         },
+        OutboundMessage::OtaStatus(status) => EncodedMessage {
+            topic: TOPIC_STATUS_OTA,
+            payload: status.as_bytes(),
+        },
+        OutboundMessage::SequenceStatus(status) => EncodedMessage {
+            topic: TOPIC_STATUS_SEQUENCE,
+            payload: status.as_bytes(),
+        },
+        OutboundMessage::CountdownStatus(status) => EncodedMessage {
+            topic: TOPIC_STATUS_COUNTDOWN,
+            payload: status.as_bytes(),
+        },
+        OutboundMessage::CrashReport(packet) => {
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic: packet.topic(),
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::Log(packet) => {
+            let topic = topics::format_log_topic(MQTT_CLIENT_ID, temp_topic_buffer)
+                .map_err(EncodeErrorWithTopic::Topic)?;
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic,
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::TimeSync(packet) => {
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic: packet.topic(),
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::CaptureMeta(packet) => {
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic: packet.topic(),
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::CaptureChunk(packet) => {
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic: packet.topic(),
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::CalibrationStatus(packet) => {
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic: packet.topic(),
+                payload: &payload_buffer[..written],
+            }
+        }
+        OutboundMessage::ServoCalibrationStatus(packet) => {
+            let name = crate::servo::name(packet.servo as usize);
+            let topic = topics::format_named_topic(
+                TOPIC_STATUS_CALIBRATION_SERVO_PREFIX,
+                name,
+                temp_topic_buffer,
+            )
+            .map_err(EncodeErrorWithTopic::Topic)?;
+            let written = packet
+                .encode_payload(payload_buffer)
+                .map_err(EncodeErrorWithTopic::Codec)?;
+            EncodedMessage {
+                topic,
+                payload: &payload_buffer[..written],
+            }
+        }
     };
 
     Ok(encoded)
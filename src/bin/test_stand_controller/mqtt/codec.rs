@@ -16,6 +16,14 @@ pub fn write_u32_le(out: &mut [u8], value: u32) -> Result<(), EncodeError> {
     Ok(())
 }
 
+pub fn write_u64_le(out: &mut [u8], value: u64) -> Result<(), EncodeError> {
+    if out.len() < 8 {
+        return Err(EncodeError::BufferTooSmall);
+    }
+    out[..8].copy_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
 pub fn write_u16_le(out: &mut [u8], value: u16) -> Result<(), EncodeError> {
     if out.len() < 2 {
         return Err(EncodeError::BufferTooSmall);
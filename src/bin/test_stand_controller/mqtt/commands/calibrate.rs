@@ -0,0 +1,144 @@
+use core::str;
+
+/// Analog channel a calibration command applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CalibrationChannel {
+    Tensometer,
+    PressureTank,
+    PressureCombustion,
+}
+
+impl CalibrationChannel {
+    pub const ALL: [Self; 3] = [
+        Self::Tensometer,
+        Self::PressureTank,
+        Self::PressureCombustion,
+    ];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Tensometer => "tensometer",
+            Self::PressureTank => "tank",
+            Self::PressureCombustion => "combustion",
+        }
+    }
+
+    fn from_name(name: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|channel| channel.name().as_bytes() == name)
+    }
+}
+
+/// `cmd/calibrate` payloads:
+///
+/// - `TARE <channel>`: the current input reads as zero
+/// - `SPAN <channel> <value>`: the current input reads as `value` (N or bar)
+/// - `RESET <channel>`: back to plain input volts
+///
+/// `<channel>` is `tensometer`, `tank` or `combustion`.
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum CalibrateCommand {
+    Tare(CalibrationChannel),
+    Span {
+        channel: CalibrationChannel,
+        value: f32,
+    },
+    Reset(CalibrationChannel),
+}
+
+impl CalibrateCommand {
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let mut parts = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|part| !part.is_empty());
+
+        let keyword = parts.next()?;
+        let channel = CalibrationChannel::from_name(parts.next()?)?;
+        let command = match keyword {
+            b"TARE" => Self::Tare(channel),
+            b"SPAN" => {
+                let value: f32 = str::from_utf8(parts.next()?).ok()?.parse().ok()?;
+                if !value.is_finite() {
+                    return None;
+                }
+                Self::Span { channel, value }
+            }
+            b"RESET" => Self::Reset(channel),
+            _ => return None,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(command)
+    }
+}
+
+/// Servo calibration point a `SET` records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ServoPoint {
+    Open,
+    Closed,
+    /// Pulse width of position 0
+    Min,
+    /// Pulse width of position 1800
+    Max,
+}
+
+impl ServoPoint {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "OPEN",
+            Self::Closed => "CLOSED",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+        }
+    }
+}
+
+/// `cmd/calibrate/servo/<name>` payloads:
+///
+/// - `JOG <ticks>`: move the pulse width by `ticks`, either sign, past the
+///   calibrated ends if need be
+/// - `SET OPEN|CLOSED|MIN|MAX`: the current pulse width is that point
+/// - `MEASURE`: time a full-range move against the position feedback
+/// - `RESET`: back to the `SERVOS` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ServoCalibrateCommand {
+    Jog(i16),
+    Set(ServoPoint),
+    Measure,
+    Reset,
+}
+
+impl ServoCalibrateCommand {
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let mut parts = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|part| !part.is_empty());
+
+        let command = match parts.next()? {
+            b"JOG" => Self::Jog(str::from_utf8(parts.next()?).ok()?.parse().ok()?),
+            b"SET" => Self::Set(match parts.next()? {
+                b"OPEN" => ServoPoint::Open,
+                b"CLOSED" => ServoPoint::Closed,
+                b"MIN" => ServoPoint::Min,
+                b"MAX" => ServoPoint::Max,
+                _ => return None,
+            }),
+            b"MEASURE" => Self::Measure,
+            b"RESET" => Self::Reset,
+            _ => return None,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(command)
+    }
+}
@@ -0,0 +1,52 @@
+use core::str;
+
+/// `cmd/camera` payloads:
+///
+/// - `SHOOT`: one shot
+/// - `BURST <count>`: `count` shots back to back under one half-press
+/// - `BULB <ms>`: one shot holding the full press for `ms`
+/// - `INTERVAL <period_ms> [<count>]`: a shot every `period_ms`, `count`
+///   times or until `STOP`
+/// - `FOCUS`: half-press only, to wake the camera
+/// - `STOP`: release both outputs, end the interval and drop queued shots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CameraCommand {
+    Shoot,
+    Burst(u16),
+    Bulb(u32),
+    Interval { period_ms: u32, count: Option<u16> },
+    Focus,
+    Stop,
+}
+
+impl CameraCommand {
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let mut words = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|word| !word.is_empty());
+        let command = match words.next()? {
+            b"SHOOT" => Self::Shoot,
+            b"BURST" => Self::Burst(parse_nonzero(words.next()?)?),
+            b"BULB" => Self::Bulb(parse_nonzero(words.next()?)?),
+            b"INTERVAL" => Self::Interval {
+                period_ms: parse_nonzero(words.next()?)?,
+                count: match words.next() {
+                    Some(count) => Some(parse_nonzero(count)?),
+                    None => None,
+                },
+            },
+            b"FOCUS" => Self::Focus,
+            b"STOP" => Self::Stop,
+            _ => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(command)
+    }
+}
+
+fn parse_nonzero<T: str::FromStr + Default + PartialEq>(word: &[u8]) -> Option<T> {
+    let value: T = str::from_utf8(word).ok()?.parse().ok()?;
+    (value != T::default()).then_some(value)
+}
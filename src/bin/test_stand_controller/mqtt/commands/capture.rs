@@ -0,0 +1,47 @@
+use core::str;
+
+use crate::mqtt::topics::{
+    TOPIC_CMD_CAPTURE_ABORT, TOPIC_CMD_CAPTURE_ACK, TOPIC_CMD_CAPTURE_TRIGGER,
+};
+
+/// Control of fire captures. The stand sends one upload message at a time
+/// and waits for its ack before sending the next.
+///
+/// - `cmd/capture/ack`: `<capture id> <sequence>`
+/// - `cmd/capture/abort`: `<capture id>`, drops the capture and re-arms
+/// - `cmd/capture/trigger`: empty, freezes a window now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CaptureCommand {
+    Ack { capture_id: u32, sequence: u16 },
+    Abort { capture_id: u32 },
+    Trigger,
+}
+
+impl CaptureCommand {
+    pub fn decode(topic: &str, payload: &[u8]) -> Option<Self> {
+        let mut parts = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|part| !part.is_empty());
+
+        let command = match topic {
+            TOPIC_CMD_CAPTURE_ACK => Self::Ack {
+                capture_id: parse(parts.next()?)?,
+                sequence: parse(parts.next()?)?,
+            },
+            TOPIC_CMD_CAPTURE_ABORT => Self::Abort {
+                capture_id: parse(parts.next()?)?,
+            },
+            TOPIC_CMD_CAPTURE_TRIGGER => Self::Trigger,
+            _ => return None,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(command)
+    }
+}
+
+fn parse<T: str::FromStr>(part: &[u8]) -> Option<T> {
+    str::from_utf8(part).ok()?.parse().ok()
+}
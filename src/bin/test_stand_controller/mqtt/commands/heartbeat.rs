@@ -0,0 +1,11 @@
+/// `cmd/heartbeat` from the operator console; the payload is not
+/// interpreted, so a counter or timestamp can be sent for the console's own
+/// use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct HeartbeatCommand;
+
+impl HeartbeatCommand {
+    pub fn decode(_payload: &[u8]) -> Option<Self> {
+        Some(Self)
+    }
+}
@@ -1,14 +1,32 @@
+pub mod calibrate;
+pub mod camera;
+pub mod capture;
+pub mod heartbeat;
+pub mod ota;
+pub mod sequence;
 pub mod servo;
 pub mod shutdown;
 pub mod state;
+pub mod trigger;
 
-use defmt::{info, warn};
+use defmt::{debug, info, warn};
 
+use crate::mqtt::commands::calibrate::{CalibrateCommand, ServoCalibrateCommand};
+use crate::mqtt::commands::camera::CameraCommand;
+use crate::mqtt::commands::capture::CaptureCommand;
+use crate::mqtt::commands::heartbeat::HeartbeatCommand;
+use crate::mqtt::commands::ota::OtaCommand;
+use crate::mqtt::commands::sequence::SequenceCommand;
 use crate::mqtt::commands::servo::ServoCommand;
 use crate::mqtt::commands::shutdown::ShutdownCommand;
 use crate::mqtt::commands::state::StateCommand;
+use crate::mqtt::commands::trigger::TriggerCommand;
 use crate::mqtt::sensors::status::StateStatus;
-use crate::mqtt::topics::{TOPIC_CMD_SERVO, TOPIC_CMD_SHUTDOWN, TOPIC_CMD_STATE};
+use crate::mqtt::topics::{
+    TOPIC_CMD_CALIBRATE, TOPIC_CMD_CALIBRATE_SERVO_PREFIX, TOPIC_CMD_CAMERA,
+    TOPIC_CMD_CAPTURE_PREFIX, TOPIC_CMD_HEARTBEAT, TOPIC_CMD_OTA_PREFIX, TOPIC_CMD_SEQUENCE_PREFIX,
+    TOPIC_CMD_SERVO_PREFIX, TOPIC_CMD_SHUTDOWN, TOPIC_CMD_STATE, TOPIC_CMD_TRIGGER,
+};
 
 #[derive(Debug, Clone, Copy, defmt::Format)]
 pub enum CommandError {
@@ -21,19 +39,82 @@ pub trait StateCommandHandler {
 }
 
 pub trait ServoCommandHandler {
-    fn handle_servo_command(&mut self, command: ServoCommand);
+    /// `servo` is an index into `SERVOS`.
+    fn handle_servo_command(&mut self, servo: usize, command: ServoCommand);
 }
 
 pub trait ShutdownCommandHandler {
     fn handle_shutdown_command(&mut self, command: ShutdownCommand);
 }
 
-pub struct CommandDispatcher<H: StateCommandHandler + ServoCommandHandler + ShutdownCommandHandler>
+pub trait OtaCommandHandler {
+    fn handle_ota_command(&mut self, command: OtaCommand);
+}
+
+pub trait CaptureCommandHandler {
+    fn handle_capture_command(&mut self, command: CaptureCommand);
+}
+
+pub trait CalibrateCommandHandler {
+    fn handle_calibrate_command(&mut self, command: CalibrateCommand);
+}
+
+pub trait ServoCalibrateCommandHandler {
+    /// `servo` is an index into `SERVOS`.
+    fn handle_servo_calibrate_command(&mut self, servo: usize, command: ServoCalibrateCommand);
+}
+
+pub trait SequenceCommandHandler {
+    fn handle_sequence_command(&mut self, command: SequenceCommand);
+}
+
+pub trait HeartbeatCommandHandler {
+    fn handle_heartbeat_command(&mut self, command: HeartbeatCommand);
+}
+
+pub trait TriggerCommandHandler {
+    fn handle_trigger_command(&mut self, command: TriggerCommand);
+}
+
+pub trait CameraCommandHandler {
+    fn handle_camera_command(&mut self, command: CameraCommand);
+}
+
+pub trait CommandHandlers:
+    StateCommandHandler
+    + ServoCommandHandler
+    + ShutdownCommandHandler
+    + OtaCommandHandler
+    + CaptureCommandHandler
+    + CalibrateCommandHandler
+    + ServoCalibrateCommandHandler
+    + SequenceCommandHandler
+    + HeartbeatCommandHandler
+    + TriggerCommandHandler
+    + CameraCommandHandler
 {
+}
+
+impl<H> CommandHandlers for H where
+    H: StateCommandHandler
+        + ServoCommandHandler
+        + ShutdownCommandHandler
+        + OtaCommandHandler
+        + CaptureCommandHandler
+        + CalibrateCommandHandler
+        + ServoCalibrateCommandHandler
+        + SequenceCommandHandler
+        + HeartbeatCommandHandler
+        + TriggerCommandHandler
+        + CameraCommandHandler
+{
+}
+
+pub struct CommandDispatcher<H: CommandHandlers> {
     handlers: H,
 }
 
-impl<H: StateCommandHandler + ServoCommandHandler + ShutdownCommandHandler> CommandDispatcher<H> {
+impl<H: CommandHandlers> CommandDispatcher<H> {
     pub const fn new(handlers: H) -> Self {
         Self { handlers }
     }
@@ -45,9 +126,10 @@ impl<H: StateCommandHandler + ServoCommandHandler + ShutdownCommandHandler> Comm
             return Ok(());
         }
 
-        if topic == TOPIC_CMD_SERVO {
+        if let Some(name) = topic.strip_prefix(TOPIC_CMD_SERVO_PREFIX) {
+            let servo = crate::servo::find(name).ok_or(CommandError::UnknownTopic)?;
             let command = ServoCommand::decode(payload).ok_or(CommandError::InvalidPayload)?;
-            self.handlers.handle_servo_command(command);
+            self.handlers.handle_servo_command(servo, command);
             return Ok(());
         }
 
@@ -57,6 +139,58 @@ impl<H: StateCommandHandler + ServoCommandHandler + ShutdownCommandHandler> Comm
             return Ok(());
         }
 
+        if topic.starts_with(TOPIC_CMD_OTA_PREFIX) {
+            let command = OtaCommand::decode(topic, payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_ota_command(command);
+            return Ok(());
+        }
+
+        if topic.starts_with(TOPIC_CMD_CAPTURE_PREFIX) {
+            let command =
+                CaptureCommand::decode(topic, payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_capture_command(command);
+            return Ok(());
+        }
+
+        if topic == TOPIC_CMD_CALIBRATE {
+            let command = CalibrateCommand::decode(payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_calibrate_command(command);
+            return Ok(());
+        }
+
+        if let Some(name) = topic.strip_prefix(TOPIC_CMD_CALIBRATE_SERVO_PREFIX) {
+            let servo = crate::servo::find(name).ok_or(CommandError::UnknownTopic)?;
+            let command =
+                ServoCalibrateCommand::decode(payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_servo_calibrate_command(servo, command);
+            return Ok(());
+        }
+
+        if topic.starts_with(TOPIC_CMD_SEQUENCE_PREFIX) {
+            let command =
+                SequenceCommand::decode(topic, payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_sequence_command(command);
+            return Ok(());
+        }
+
+        if topic == TOPIC_CMD_HEARTBEAT {
+            let command = HeartbeatCommand::decode(payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_heartbeat_command(command);
+            return Ok(());
+        }
+
+        if topic == TOPIC_CMD_TRIGGER {
+            let command = TriggerCommand::decode(payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_trigger_command(command);
+            return Ok(());
+        }
+
+        if topic == TOPIC_CMD_CAMERA {
+            let command = CameraCommand::decode(payload).ok_or(CommandError::InvalidPayload)?;
+            self.handlers.handle_camera_command(command);
+            return Ok(());
+        }
+
         Err(CommandError::UnknownTopic)
     }
 }
@@ -76,7 +210,8 @@ impl MockCommandHandlers {
 impl StateCommandHandler for MockCommandHandlers {
     fn handle_state_command(&mut self, command: StateCommand) {
         match command {
-            StateCommand::Fire => {
+            StateCommand::ArmRequest => info!("MQTT command: ARM_REQUEST"),
+            StateCommand::Fire(_) => {
                 self.state = StateStatus::Fire;
                 info!("MQTT command: FIRE");
             }
@@ -88,20 +223,32 @@ impl StateCommandHandler for MockCommandHandlers {
                 self.state = StateStatus::Armed;
                 info!("MQTT command: FIRE_RESET");
             }
+            StateCommand::Abort => {
+                self.state = StateStatus::Abort;
+                info!("MQTT command: ABORT");
+            }
+            StateCommand::Hold => info!("MQTT command: HOLD"),
+            StateCommand::Resume => info!("MQTT command: RESUME"),
         }
     }
 }
 
 impl ServoCommandHandler for MockCommandHandlers {
-    fn handle_servo_command(&mut self, command: ServoCommand) {
-        if self.state == StateStatus::Fire {
+    fn handle_servo_command(&mut self, servo: usize, command: ServoCommand) {
+        if self.state.is_active() {
             warn!("MQTT command ignored: cmd/servo in FIRE state");
             return;
         }
 
         match command {
-            ServoCommand::Open => info!("MQTT command: OPEN"),
-            ServoCommand::Close => info!("MQTT command: CLOSE"),
+            ServoCommand::Open => info!("MQTT command: servo {} OPEN", servo),
+            ServoCommand::Close => info!("MQTT command: servo {} CLOSE", servo),
+            ServoCommand::Move { position, rate } => {
+                info!(
+                    "MQTT command: servo {} MOVE {} rate {}",
+                    servo, position, rate
+                )
+            }
         }
     }
 }
@@ -113,3 +260,73 @@ impl ShutdownCommandHandler for MockCommandHandlers {
         }
     }
 }
+
+impl OtaCommandHandler for MockCommandHandlers {
+    fn handle_ota_command(&mut self, command: OtaCommand) {
+        match command {
+            OtaCommand::Begin { size, .. } => info!("MQTT command: OTA BEGIN {}", size),
+            OtaCommand::Chunk { offset, data } => {
+                info!("MQTT command: OTA CHUNK {} (+{})", offset, data.len())
+            }
+            OtaCommand::Finish => info!("MQTT command: OTA FINISH"),
+            OtaCommand::Abort => info!("MQTT command: OTA ABORT"),
+        }
+    }
+}
+
+impl CaptureCommandHandler for MockCommandHandlers {
+    fn handle_capture_command(&mut self, command: CaptureCommand) {
+        info!("MQTT command: capture {:?}", command);
+    }
+}
+
+impl CalibrateCommandHandler for MockCommandHandlers {
+    fn handle_calibrate_command(&mut self, command: CalibrateCommand) {
+        info!("MQTT command: calibrate {:?}", command);
+    }
+}
+
+impl ServoCalibrateCommandHandler for MockCommandHandlers {
+    fn handle_servo_calibrate_command(&mut self, servo: usize, command: ServoCalibrateCommand) {
+        info!("MQTT command: calibrate servo {} {:?}", servo, command);
+    }
+}
+
+impl SequenceCommandHandler for MockCommandHandlers {
+    fn handle_sequence_command(&mut self, command: SequenceCommand) {
+        match command {
+            SequenceCommand::Load(Ok(sequence)) => {
+                info!(
+                    "MQTT command: sequence LOAD {} steps",
+                    sequence.steps().len()
+                )
+            }
+            SequenceCommand::Load(Err(error)) => {
+                warn!("MQTT command: sequence LOAD rejected: {:?}", error)
+            }
+            SequenceCommand::Clear => info!("MQTT command: sequence CLEAR"),
+        }
+    }
+}
+
+impl HeartbeatCommandHandler for MockCommandHandlers {
+    fn handle_heartbeat_command(&mut self, _command: HeartbeatCommand) {
+        debug!("MQTT command: heartbeat");
+    }
+}
+
+impl TriggerCommandHandler for MockCommandHandlers {
+    fn handle_trigger_command(&mut self, command: TriggerCommand) {
+        if self.state != StateStatus::Fire {
+            warn!("MQTT command ignored: cmd/trigger outside FIRE state");
+            return;
+        }
+        info!("MQTT command: trigger {:?}", command);
+    }
+}
+
+impl CameraCommandHandler for MockCommandHandlers {
+    fn handle_camera_command(&mut self, command: CameraCommand) {
+        info!("MQTT command: camera {:?}", command);
+    }
+}
@@ -0,0 +1,71 @@
+use alloc::vec::Vec;
+use core::str;
+
+use mainboard::ota::{parse_sha256_hex, SHA256_LEN};
+
+use crate::mqtt::topics::{
+    TOPIC_CMD_OTA_ABORT, TOPIC_CMD_OTA_BEGIN, TOPIC_CMD_OTA_CHUNK, TOPIC_CMD_OTA_FINISH,
+};
+
+/// Chunked firmware transfer. The sender waits for the `status/ota` ack of
+/// each message before sending the next one.
+///
+/// - `cmd/ota/begin`: `<size> <sha256 hex>`
+/// - `cmd/ota/chunk`: `<offset: u32 LE><data>`, sequential offsets
+/// - `cmd/ota/finish`: empty, verifies and reboots into the new image
+/// - `cmd/ota/abort`: empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OtaCommand {
+    Begin { size: u32, sha256: [u8; SHA256_LEN] },
+    Chunk { offset: u32, data: Vec<u8> },
+    Finish,
+    Abort,
+}
+
+impl OtaCommand {
+    pub fn decode(topic: &str, payload: &[u8]) -> Option<Self> {
+        match topic {
+            TOPIC_CMD_OTA_BEGIN => decode_begin(trim_ascii(payload)),
+            TOPIC_CMD_OTA_CHUNK => {
+                if payload.len() <= 4 {
+                    return None;
+                }
+                let offset = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                Some(Self::Chunk {
+                    offset,
+                    data: payload[4..].to_vec(),
+                })
+            }
+            TOPIC_CMD_OTA_FINISH => Some(Self::Finish),
+            TOPIC_CMD_OTA_ABORT => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
+fn decode_begin(payload: &[u8]) -> Option<OtaCommand> {
+    let mut parts = payload
+        .split(|value| value.is_ascii_whitespace())
+        .filter(|part| !part.is_empty());
+    let size = str::from_utf8(parts.next()?).ok()?.parse().ok()?;
+    let sha256 = parse_sha256_hex(parts.next()?)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(OtaCommand::Begin { size, sha256 })
+}
+
+fn trim_ascii(input: &[u8]) -> &[u8] {
+    let start = input
+        .iter()
+        .position(|value| !value.is_ascii_whitespace())
+        .unwrap_or(input.len());
+
+    let end = input
+        .iter()
+        .rposition(|value| !value.is_ascii_whitespace())
+        .map(|index| index + 1)
+        .unwrap_or(start);
+
+    &input[start..end]
+}
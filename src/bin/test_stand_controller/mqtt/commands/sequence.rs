@@ -0,0 +1,27 @@
+use crate::mqtt::topics::{TOPIC_CMD_SEQUENCE_CLEAR, TOPIC_CMD_SEQUENCE_LOAD};
+use crate::sequence;
+use crate::sequence::script::{ScriptError, Sequence};
+
+/// Fire sequence definitions.
+///
+/// - `cmd/sequence/load`: the definition text (see [`crate::sequence::script`]),
+///   replaces the stored sequence
+/// - `cmd/sequence/clear`: empty, back to the manual FIRE sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SequenceCommand {
+    /// Parsed definition, or why it was rejected
+    Load(Result<Sequence, ScriptError>),
+    Clear,
+}
+
+impl SequenceCommand {
+    pub fn decode(topic: &str, payload: &[u8]) -> Option<Self> {
+        match topic {
+            TOPIC_CMD_SEQUENCE_LOAD => {
+                Some(Self::Load(Sequence::parse(payload, &sequence::limits())))
+            }
+            TOPIC_CMD_SEQUENCE_CLEAR => Some(Self::Clear),
+            _ => None,
+        }
+    }
+}
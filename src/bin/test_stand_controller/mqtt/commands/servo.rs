@@ -1,30 +1,56 @@
+use core::str;
+
+/// Highest position, tenths of a degree.
+pub const SERVO_MAX_POSITION: u16 = 1800;
+/// Servos the MCPWM peripheral can drive, one per operator.
+pub const MAX_SERVOS: usize = 3;
+
+/// `cmd/servo/<name>` payloads:
+///
+/// - `OPEN`, `CLOSE`: the configured positions at full rate
+/// - `MOVE <position> [<rate>]`: `position` in tenths of a degree, 0 to 1800,
+///   at most `rate` tenths of a degree per second
 #[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
 pub enum ServoCommand {
     Open,
     Close,
+    Move { position: u16, rate: Option<u16> },
 }
 
 impl ServoCommand {
     pub fn decode(payload: &[u8]) -> Option<Self> {
-        match trim_ascii(payload) {
-            b"OPEN" => Some(Self::Open),
-            b"CLOSE" => Some(Self::Close),
-            _ => None,
+        let mut words = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|word| !word.is_empty());
+        let command = Self::parse(words.next()?, &mut words)?;
+        if words.next().is_some() {
+            return None;
         }
+        Some(command)
     }
-}
 
-fn trim_ascii(input: &[u8]) -> &[u8] {
-    let start = input
-        .iter()
-        .position(|value| !value.is_ascii_whitespace())
-        .unwrap_or(input.len());
-
-    let end = input
-        .iter()
-        .rposition(|value| !value.is_ascii_whitespace())
-        .map(|index| index + 1)
-        .unwrap_or(start);
+    /// The command named by `keyword`, taking its arguments from `words`;
+    /// shared with `SERVO` sequence steps.
+    pub fn parse<'a>(keyword: &[u8], words: &mut impl Iterator<Item = &'a [u8]>) -> Option<Self> {
+        Some(match keyword {
+            b"OPEN" => Self::Open,
+            b"CLOSE" => Self::Close,
+            b"MOVE" => {
+                let position = parse_number(words.next()?)?;
+                let rate = match words.next() {
+                    Some(word) => Some(parse_number(word).filter(|&rate| rate != 0)?),
+                    None => None,
+                };
+                if position > SERVO_MAX_POSITION {
+                    return None;
+                }
+                Self::Move { position, rate }
+            }
+            _ => return None,
+        })
+    }
+}
 
-    &input[start..end]
+fn parse_number(word: &[u8]) -> Option<u16> {
+    str::from_utf8(word).ok()?.parse().ok()
 }
@@ -1,32 +1,77 @@
+use core::str;
+
+use mainboard::ota::{parse_sha256_hex, SHA256_LEN};
+
+/// Length of the FIRE HMAC.
+pub const MAC_LEN: usize = SHA256_LEN;
+
+/// Answer to an ARM_REQUEST carried by FIRE, `FIRE <nonce hex> [<hmac hex>]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct FireToken {
+    pub nonce: u64,
+    pub mac: Option<[u8; MAC_LEN]>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
 pub enum StateCommand {
-    Fire,
+    /// Ask for the nonce FIRE has to quote
+    ArmRequest,
+    Fire(Option<FireToken>),
     FireEnd,
     FireReset,
+    /// Pause the countdown
+    Hold,
+    Resume,
+    /// Stop the countdown or FIRE like a tripped interlock
+    Abort,
 }
 
 impl StateCommand {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::ArmRequest => "ARM_REQUEST",
+            Self::Fire(_) => "FIRE",
+            Self::FireEnd => "FIRE_END",
+            Self::FireReset => "FIRE_RESET",
+            Self::Hold => "HOLD",
+            Self::Resume => "RESUME",
+            Self::Abort => "ABORT",
+        }
+    }
+
     pub fn decode(payload: &[u8]) -> Option<Self> {
-        match trim_ascii(payload) {
-            b"FIRE" => Some(Self::Fire),
-            b"FIRE_END" => Some(Self::FireEnd),
-            b"FIRE_RESET" => Some(Self::FireReset),
-            _ => None,
+        let mut words = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|word| !word.is_empty());
+        let command = match words.next()? {
+            b"ARM_REQUEST" => Self::ArmRequest,
+            b"FIRE" => Self::Fire(decode_fire_token(&mut words)?),
+            b"FIRE_END" => Self::FireEnd,
+            b"FIRE_RESET" => Self::FireReset,
+            b"HOLD" => Self::Hold,
+            b"RESUME" => Self::Resume,
+            b"ABORT" => Self::Abort,
+            _ => return None,
+        };
+        if words.next().is_some() {
+            return None;
         }
+        Some(command)
     }
 }
 
-fn trim_ascii(input: &[u8]) -> &[u8] {
-    let start = input
-        .iter()
-        .position(|value| !value.is_ascii_whitespace())
-        .unwrap_or(input.len());
-
-    let end = input
-        .iter()
-        .rposition(|value| !value.is_ascii_whitespace())
-        .map(|index| index + 1)
-        .unwrap_or(start);
-
-    &input[start..end]
+/// `None` for a bare FIRE, which the stand rejects with a reason.
+fn decode_fire_token<'a>(words: &mut impl Iterator<Item = &'a [u8]>) -> Option<Option<FireToken>> {
+    let Some(nonce) = words.next() else {
+        return Some(None);
+    };
+    if nonce.len() != 16 || !nonce.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let nonce = u64::from_str_radix(str::from_utf8(nonce).ok()?, 16).ok()?;
+    let mac = match words.next() {
+        Some(mac) => Some(parse_sha256_hex(mac)?),
+        None => None,
+    };
+    Some(Some(FireToken { nonce, mac }))
 }
@@ -0,0 +1,66 @@
+use core::str;
+
+/// `cmd/trigger` payloads, accepted in FIRE only:
+///
+/// - `FIRE <channel>`: turn the channel on
+/// - `MASK <mask>`: turn the channels in `mask` on, decimal or `0x` hex
+/// - `PULSE <channel> <ms>`: turn the channel on for `ms`
+/// - `RELEASE [<channel>]`: turn the channel off, every channel without one
+///
+/// Channels are 0 to 7; none stays on longer than `FIRE_CHANNEL_MAX_ON_MS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TriggerCommand {
+    Fire(u8),
+    Pulse { mask: u8, duration_ms: u32 },
+    Release(u8),
+    ReleaseAll,
+}
+
+impl TriggerCommand {
+    /// Channels the command acts on.
+    pub const fn mask(self) -> u8 {
+        match self {
+            Self::Fire(mask) | Self::Pulse { mask, .. } | Self::Release(mask) => mask,
+            Self::ReleaseAll => 0,
+        }
+    }
+
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let mut words = payload
+            .split(|value| value.is_ascii_whitespace())
+            .filter(|word| !word.is_empty());
+        let command = match words.next()? {
+            b"FIRE" => Self::Fire(parse_channel(words.next()?)?),
+            b"MASK" => Self::Fire(parse_mask(words.next()?)?),
+            b"PULSE" => Self::Pulse {
+                mask: parse_channel(words.next()?)?,
+                duration_ms: str::from_utf8(words.next()?).ok()?.parse().ok()?,
+            },
+            b"RELEASE" => match words.next() {
+                Some(channel) => Self::Release(parse_channel(channel)?),
+                None => Self::ReleaseAll,
+            },
+            _ => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(command)
+    }
+}
+
+/// A channel number as a mask.
+fn parse_channel(word: &[u8]) -> Option<u8> {
+    match word {
+        [digit @ b'0'..=b'7'] => Some(1 << (digit - b'0')),
+        _ => None,
+    }
+}
+
+fn parse_mask(word: &[u8]) -> Option<u8> {
+    let mask = match word.strip_prefix(b"0x") {
+        Some(hex) => u8::from_str_radix(str::from_utf8(hex).ok()?, 16).ok()?,
+        None => str::from_utf8(word).ok()?.parse().ok()?,
+    };
+    (mask != 0).then_some(mask)
+}
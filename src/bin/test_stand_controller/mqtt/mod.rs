@@ -16,6 +16,6 @@ pub use client::mqtt_task;
     reason = "Public API is re-exported for the upcoming data collection integration."
 )]
 pub use queue::{
-    publish_armed_sensor, publish_fast_sensors, publish_slow_sensors, publish_temperature_sensor,
+    publish_digital_sensor, publish_fast_sensors, publish_slow_sensors, publish_temperature_sensor,
     FastSensorsBatch, SlowSensorsBatch,
 };
@@ -1,13 +1,22 @@
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::{Channel, TrySendError};
 
-use crate::mqtt::sensors::digital::ArmedPacket;
+use crate::mqtt::sensors::calibration::{CalibrationStatusPacket, ServoCalibrationPacket};
+use crate::mqtt::sensors::camera::CameraPacket;
+use crate::mqtt::sensors::capture::{CaptureChunkPacket, CaptureMetaPacket};
+use crate::mqtt::sensors::crash::CrashReportPacket;
+use crate::mqtt::sensors::digital::DigitalPacket;
 use crate::mqtt::sensors::fast::{FastAdcChannel, FastAdcPacket};
+use crate::mqtt::sensors::log::LogPacket;
 use crate::mqtt::sensors::slow::{ServoSensorPacket, SlowAdcChannel, SlowAdcPacket};
 use crate::mqtt::sensors::status::{CommandStatusPacket, ServoStatus, StateStatus};
 use crate::mqtt::sensors::temp::TempPacket;
+use crate::mqtt::sensors::time::TimeSyncPacket;
 
 pub const OUTBOUND_QUEUE_CAPACITY: usize = 256;
+/// Log lines are refused once fewer slots than this are free, keeping the
+/// rest of the queue for sensor data.
+pub const LOG_RESERVED_CAPACITY: usize = OUTBOUND_QUEUE_CAPACITY / 2;
 
 static OUTBOUND_QUEUE: Channel<CriticalSectionRawMutex, OutboundMessage, OUTBOUND_QUEUE_CAPACITY> =
     Channel::new();
@@ -16,12 +25,24 @@ static OUTBOUND_QUEUE: Channel<CriticalSectionRawMutex, OutboundMessage, OUTBOUN
 pub enum OutboundMessage {
     FastAdc(FastAdcPacket),
     SlowAdc(SlowAdcPacket),
-    Armed(ArmedPacket),
+    Digital(DigitalPacket),
+    Camera(CameraPacket),
     Temp(TempPacket),
     ServoSensor(ServoSensorPacket),
     StateStatus(StateStatus),
-    ServoStatus(ServoStatus),
+    /// Servo index and its status
+    ServoStatus(u8, ServoStatus),
     CommandStatus(CommandStatusPacket),
+    TimeSync(TimeSyncPacket),
+    OtaStatus(CommandStatusPacket),
+    CrashReport(CrashReportPacket),
+    Log(LogPacket),
+    CaptureMeta(CaptureMetaPacket),
+    CaptureChunk(CaptureChunkPacket),
+    CalibrationStatus(CalibrationStatusPacket),
+    ServoCalibrationStatus(ServoCalibrationPacket),
+    SequenceStatus(CommandStatusPacket),
+    CountdownStatus(CommandStatusPacket),
 }
 
 #[derive(Debug, Clone, Copy, defmt::Format)]
@@ -118,16 +139,20 @@ pub fn publish_temperature_sensor(packet: TempPacket) -> Result<(), PublishError
     enqueue(OutboundMessage::Temp(packet))
 }
 
-pub fn publish_armed_sensor(packet: ArmedPacket) -> Result<(), PublishError> {
-    enqueue(OutboundMessage::Armed(packet))
+pub fn publish_digital_sensor(packet: DigitalPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::Digital(packet))
+}
+
+pub fn publish_camera_event(packet: CameraPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::Camera(packet))
 }
 
 pub fn publish_state_status(status: StateStatus) -> Result<(), PublishError> {
     enqueue(OutboundMessage::StateStatus(status))
 }
 
-pub fn publish_servo_status(status: ServoStatus) -> Result<(), PublishError> {
-    enqueue(OutboundMessage::ServoStatus(status))
+pub fn publish_servo_status(servo: u8, status: ServoStatus) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::ServoStatus(servo, status))
 }
 
 pub fn publish_servo_sensor(packet: ServoSensorPacket) -> Result<(), PublishError> {
@@ -138,12 +163,57 @@ pub fn publish_command_status(status: CommandStatusPacket) -> Result<(), Publish
     enqueue(OutboundMessage::CommandStatus(status))
 }
 
+pub fn publish_time_sync(packet: TimeSyncPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::TimeSync(packet))
+}
+
+pub fn publish_crash_report(packet: CrashReportPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::CrashReport(packet))
+}
+
+pub fn publish_capture_meta(packet: CaptureMetaPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::CaptureMeta(packet))
+}
+
+pub fn publish_capture_chunk(packet: CaptureChunkPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::CaptureChunk(packet))
+}
+
+pub fn publish_calibration_status(packet: CalibrationStatusPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::CalibrationStatus(packet))
+}
+
+pub fn publish_servo_calibration(packet: ServoCalibrationPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::ServoCalibrationStatus(packet))
+}
+
+pub fn publish_sequence_status(status: CommandStatusPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::SequenceStatus(status))
+}
+
+pub fn publish_countdown_status(status: CommandStatusPacket) -> Result<(), PublishError> {
+    enqueue(OutboundMessage::CountdownStatus(status))
+}
+
+pub fn publish_log(packet: LogPacket) -> Result<(), PublishError> {
+    if OUTBOUND_QUEUE.free_capacity() < LOG_RESERVED_CAPACITY {
+        return Err(PublishError::QueueFull);
+    }
+    enqueue(OutboundMessage::Log(packet))
+}
+
 pub fn publish_command_log(msg: &str) {
     if let Ok(packet) = CommandStatusPacket::from_str(msg) {
         let _ = publish_command_status(packet);
     }
 }
 
+pub fn publish_ota_log(msg: &str) {
+    if let Ok(packet) = CommandStatusPacket::from_str(msg) {
+        let _ = enqueue(OutboundMessage::OtaStatus(packet));
+    }
+}
+
 pub(crate) async fn receive_outbound_message() -> OutboundMessage {
     OUTBOUND_QUEUE.receive().await
 }
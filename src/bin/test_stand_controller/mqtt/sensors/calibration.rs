@@ -0,0 +1,98 @@
+use core::fmt::{self, Write};
+
+use crate::calibration::ChannelCalibration;
+use crate::mqtt::codec::EncodeError;
+use crate::mqtt::commands::calibrate::CalibrationChannel;
+use crate::mqtt::sensors::EncodablePayload;
+use crate::mqtt::topics::TOPIC_STATUS_CALIBRATION;
+use crate::servo::calibration::ServoCalibration;
+
+/// Active calibration of every channel, published retained as JSON:
+///
+/// `{"tensometer":{"unit":"N","gain":1520.5,"offset":-626.4},...}`
+///
+/// The value in `unit` is `gain * input_volts + offset`.
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationStatusPacket {
+    pub channels: [ChannelCalibration; 3],
+}
+
+impl CalibrationStatusPacket {
+    pub const fn topic(&self) -> &'static str {
+        TOPIC_STATUS_CALIBRATION
+    }
+
+    fn write_json(&self, out: &mut impl Write) -> fmt::Result {
+        out.write_char('{')?;
+        for channel in CalibrationChannel::ALL {
+            let calibration = &self.channels[channel.index()];
+            if channel.index() > 0 {
+                out.write_char(',')?;
+            }
+            write!(
+                out,
+                "\"{}\":{{\"unit\":\"{}\",\"gain\":{},\"offset\":{}}}",
+                channel.name(),
+                calibration.unit(channel),
+                calibration.gain,
+                calibration.offset()
+            )?;
+        }
+        out.write_char('}')
+    }
+}
+
+impl EncodablePayload for CalibrationStatusPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        let mut writer = SliceWriter { out, len: 0 };
+        self.write_json(&mut writer)
+            .map_err(|_| EncodeError::BufferTooSmall)?;
+        Ok(writer.len)
+    }
+}
+
+/// Active calibration of a servo, published retained on
+/// `status/calibration/servo/<name>` as JSON:
+///
+/// `{"min_ticks":500,"max_ticks":2500,"open":975,"closed":1800,"full_range_ms":5000}`
+#[derive(Debug, Clone, Copy)]
+pub struct ServoCalibrationPacket {
+    /// Index in `SERVOS`
+    pub servo: u8,
+    pub calibration: ServoCalibration,
+}
+
+impl EncodablePayload for ServoCalibrationPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        let calibration = &self.calibration;
+        let mut writer = SliceWriter { out, len: 0 };
+        write!(
+            writer,
+            "{{\"min_ticks\":{},\"max_ticks\":{},\"open\":{},\"closed\":{},\"full_range_ms\":{}}}",
+            calibration.min_pulse_ticks,
+            calibration.max_pulse_ticks,
+            calibration.open_degrees,
+            calibration.closed_degrees,
+            calibration.full_range_ms
+        )
+        .map_err(|_| EncodeError::BufferTooSmall)?;
+        Ok(writer.len)
+    }
+}
+
+struct SliceWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.out
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use crate::mqtt::codec::{write_u32_le, EncodeError};
+use crate::mqtt::sensors::EncodablePayload;
+use crate::mqtt::topics::TOPIC_SENSOR_CAMERA;
+
+/// A change of the camera outputs, published on `sensor/camera` so video
+/// frames can be lined up with the sensor data.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraPacket {
+    pub timestamp_ms: u32,
+    pub focus: bool,
+    pub shutter: bool,
+    /// Full presses since boot, this one included
+    pub shot: u32,
+}
+
+impl CameraPacket {
+    pub const fn topic(&self) -> &'static str {
+        TOPIC_SENSOR_CAMERA
+    }
+}
+
+impl EncodablePayload for CameraPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        if out.len() < 9 {
+            return Err(EncodeError::BufferTooSmall);
+        }
+
+        write_u32_le(&mut out[..4], self.timestamp_ms)?;
+        out[4] = self.focus as u8 | (self.shutter as u8) << 1;
+        write_u32_le(&mut out[5..9], self.shot)?;
+        Ok(9)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::mqtt::codec::{pack_u12, validate_u12_samples, write_u16_le, write_u32_le, EncodeError};
+use crate::mqtt::sensors::EncodablePayload;
+use crate::mqtt::topics::{TOPIC_CAPTURE_CHUNK, TOPIC_CAPTURE_META};
+
+pub const CAPTURE_CHUNK_SAMPLES: usize = 128;
+
+/// No channel crossed a threshold.
+pub const NO_TRIGGER_CHANNEL: u8 = 0xFF;
+/// Samples were lost inside the window (ADC overrun or a dropped block);
+/// timestamps after the gap are off by the lost time.
+pub const CAPTURE_FLAG_GAP: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CaptureTrigger {
+    Fire,
+    Threshold,
+    Manual,
+}
+
+impl CaptureTrigger {
+    pub const fn as_u8(self) -> u8 {
+        match self {
+            Self::Fire => 0,
+            Self::Threshold => 1,
+            Self::Manual => 2,
+        }
+    }
+}
+
+/// Sequence number 0 of a capture upload, describing the window.
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureMetaPacket {
+    pub capture_id: u32,
+    /// Messages in the upload, this one included.
+    pub message_count: u16,
+    pub trigger: CaptureTrigger,
+    /// Index of the crossing channel for threshold triggers.
+    pub trigger_channel: u8,
+    pub flags: u8,
+    pub trigger_timestamp_ms: u32,
+    pub first_timestamp_ms: u32,
+    pub sample_period_ns: u32,
+    pub frames: u32,
+    pub pre_frames: u32,
+    pub channels: u8,
+}
+
+impl CaptureMetaPacket {
+    pub const fn topic(&self) -> &'static str {
+        TOPIC_CAPTURE_META
+    }
+}
+
+impl EncodablePayload for CaptureMetaPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        if out.len() < 32 {
+            return Err(EncodeError::BufferTooSmall);
+        }
+
+        write_u32_le(&mut out[..4], self.capture_id)?;
+        write_u16_le(&mut out[4..6], 0)?;
+        write_u16_le(&mut out[6..8], self.message_count)?;
+        out[8] = self.trigger.as_u8();
+        out[9] = self.trigger_channel;
+        out[10] = self.flags;
+        write_u32_le(&mut out[11..15], self.trigger_timestamp_ms)?;
+        write_u32_le(&mut out[15..19], self.first_timestamp_ms)?;
+        write_u32_le(&mut out[19..23], self.sample_period_ns)?;
+        write_u32_le(&mut out[23..27], self.frames)?;
+        write_u32_le(&mut out[27..31], self.pre_frames)?;
+        out[31] = self.channels;
+        Ok(32)
+    }
+}
+
+/// Samples of one channel, `first_frame` frames into the window.
+#[derive(Debug, Clone)]
+pub struct CaptureChunkPacket {
+    pub capture_id: u32,
+    pub sequence: u16,
+    pub channel: u8,
+    pub first_frame: u32,
+    samples: [u16; CAPTURE_CHUNK_SAMPLES],
+    sample_count: u8,
+}
+
+impl CaptureChunkPacket {
+    pub fn from_slice(
+        capture_id: u32,
+        sequence: u16,
+        channel: u8,
+        first_frame: u32,
+        samples: &[u16],
+    ) -> Result<Self, EncodeError> {
+        validate_u12_samples(samples, CAPTURE_CHUNK_SAMPLES)?;
+
+        let mut copy = [0u16; CAPTURE_CHUNK_SAMPLES];
+        copy[..samples.len()].copy_from_slice(samples);
+
+        Ok(Self {
+            capture_id,
+            sequence,
+            channel,
+            first_frame,
+            samples: copy,
+            sample_count: samples.len() as u8,
+        })
+    }
+
+    pub const fn topic(&self) -> &'static str {
+        TOPIC_CAPTURE_CHUNK
+    }
+
+    pub fn samples(&self) -> &[u16] {
+        &self.samples[..self.sample_count as usize]
+    }
+}
+
+impl EncodablePayload for CaptureChunkPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        if out.len() < 11 {
+            return Err(EncodeError::BufferTooSmall);
+        }
+
+        write_u32_le(&mut out[..4], self.capture_id)?;
+        write_u16_le(&mut out[4..6], self.sequence)?;
+        out[6] = self.channel;
+        write_u32_le(&mut out[7..11], self.first_frame)?;
+
+        let written = pack_u12(self.samples(), &mut out[11..])?;
+        Ok(11 + written)
+    }
+}
@@ -0,0 +1,51 @@
+use core::fmt;
+
+use mainboard::crash::CrashRecord;
+
+use crate::mqtt::codec::EncodeError;
+use crate::mqtt::sensors::EncodablePayload;
+use crate::mqtt::topics::TOPIC_STATUS_CRASH;
+
+/// Panic report left by the previous run, published as one line of text.
+#[derive(Debug, Clone)]
+pub struct CrashReportPacket {
+    pub record: CrashRecord,
+}
+
+impl CrashReportPacket {
+    pub const fn topic(&self) -> &'static str {
+        TOPIC_STATUS_CRASH
+    }
+}
+
+impl EncodablePayload for CrashReportPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        let mut writer = TruncatingWriter { out, len: 0 };
+        let _ = self.record.write_summary(&mut writer);
+        if writer.len == 0 {
+            return Err(EncodeError::BufferTooSmall);
+        }
+        Ok(writer.len)
+    }
+}
+
+/// Cuts the summary at the buffer end instead of failing the whole report.
+struct TruncatingWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for TruncatingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut take = s.len().min(self.out.len() - self.len);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        self.out[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        if take < s.len() {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
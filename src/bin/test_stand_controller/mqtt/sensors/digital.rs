@@ -1,27 +1,54 @@
 use crate::mqtt::codec::{write_u32_le, EncodeError};
 use crate::mqtt::sensors::EncodablePayload;
-use crate::mqtt::topics::TOPIC_SENSOR_DIGITAL_ARMED;
+use crate::mqtt::topics::{
+    TOPIC_SENSOR_DIGITAL_ARMED, TOPIC_SENSOR_DIGITAL_ARMED_SECONDARY,
+    TOPIC_SENSOR_DIGITAL_CONTINUITY,
+};
+
+/// Value of the continuity channel when the sense input cannot be read.
+pub const DIGITAL_UNKNOWN: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DigitalChannel {
+    Armed,
+    /// Second armed switch contact, when fitted
+    ArmedSecondary,
+    /// Igniter continuity: 1 closed, 0 open, [`DIGITAL_UNKNOWN`] unreadable
+    Continuity,
+}
+
+impl DigitalChannel {
+    pub const fn topic(self) -> &'static str {
+        match self {
+            Self::Armed => TOPIC_SENSOR_DIGITAL_ARMED,
+            Self::ArmedSecondary => TOPIC_SENSOR_DIGITAL_ARMED_SECONDARY,
+            Self::Continuity => TOPIC_SENSOR_DIGITAL_CONTINUITY,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
-pub struct ArmedPacket {
+pub struct DigitalPacket {
+    channel: DigitalChannel,
     pub timestamp_ms: u32,
     pub value: u8,
 }
 
-impl ArmedPacket {
-    pub const fn new(timestamp_ms: u32, value: u8) -> Self {
+impl DigitalPacket {
+    pub const fn new(channel: DigitalChannel, timestamp_ms: u32, value: u8) -> Self {
         Self {
+            channel,
             timestamp_ms,
             value,
         }
     }
 
     pub const fn topic(&self) -> &'static str {
-        TOPIC_SENSOR_DIGITAL_ARMED
+        self.channel.topic()
     }
 }
 
-impl EncodablePayload for ArmedPacket {
+impl EncodablePayload for DigitalPacket {
     fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
         if out.len() < 5 {
             return Err(EncodeError::BufferTooSmall);
@@ -29,8 +29,12 @@ pub struct FastAdcPacket {
     channel: FastAdcChannel,
     pub first_timestamp_ms: u32,
     pub last_timestamp_ms: u32,
+    /// Exact spacing of the samples.
+    pub sample_period_ns: u32,
     samples: [u16; FAST_MAX_SAMPLES],
     sample_count: u8,
+    /// `(gain, offset)` from millivolts to the calibrated value
+    scale: Option<(f32, f32)>,
 }
 
 impl FastAdcPacket {
@@ -38,6 +42,7 @@ impl FastAdcPacket {
         channel: FastAdcChannel,
         first_timestamp_ms: u32,
         last_timestamp_ms: u32,
+        sample_period_ns: u32,
         samples: [u16; FAST_MAX_SAMPLES],
         sample_count: usize,
     ) -> Result<Self, EncodeError> {
@@ -51,8 +56,10 @@ impl FastAdcPacket {
             channel,
             first_timestamp_ms,
             last_timestamp_ms,
+            sample_period_ns,
             samples,
             sample_count: sample_count as u8,
+            scale: None,
         })
     }
 
@@ -60,6 +67,7 @@ impl FastAdcPacket {
         channel: FastAdcChannel,
         first_timestamp_ms: u32,
         last_timestamp_ms: u32,
+        sample_period_ns: u32,
         samples: &[u16],
     ) -> Result<Self, EncodeError> {
         validate_u12_samples(samples, FAST_MAX_SAMPLES)?;
@@ -71,6 +79,7 @@ impl FastAdcPacket {
             channel,
             first_timestamp_ms,
             last_timestamp_ms,
+            sample_period_ns,
             copy,
             samples.len(),
         )
@@ -91,18 +100,34 @@ impl FastAdcPacket {
     pub fn samples(&self) -> &[u16] {
         &self.samples[..self.sample_count as usize]
     }
+
+    /// Append `gain` and `offset` to the payload, so that the calibrated
+    /// value of a sample is `gain * millivolts + offset`.
+    pub fn with_scale(mut self, gain: f32, offset: f32) -> Self {
+        self.scale = Some((gain, offset));
+        self
+    }
 }
 
 impl EncodablePayload for FastAdcPacket {
     fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
-        if out.len() < 8 {
+        if out.len() < 12 {
             return Err(EncodeError::BufferTooSmall);
         }
 
         write_u32_le(&mut out[..4], self.first_timestamp_ms)?;
         write_u32_le(&mut out[4..8], self.last_timestamp_ms)?;
-
-        let written = pack_u12(self.samples(), &mut out[8..])?;
-        Ok(8 + written)
+        write_u32_le(&mut out[8..12], self.sample_period_ns)?;
+
+        let mut written = 12 + pack_u12(self.samples(), &mut out[12..])?;
+        if let Some((gain, offset)) = self.scale {
+            let trailer = out
+                .get_mut(written..written + 8)
+                .ok_or(EncodeError::BufferTooSmall)?;
+            trailer[..4].copy_from_slice(&gain.to_le_bytes());
+            trailer[4..].copy_from_slice(&offset.to_le_bytes());
+            written += 8;
+        }
+        Ok(written)
     }
 }
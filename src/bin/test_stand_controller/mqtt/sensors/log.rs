@@ -0,0 +1,54 @@
+use core::fmt;
+
+use mainboard::netlog::LogRecord;
+
+use crate::mqtt::codec::EncodeError;
+use crate::mqtt::sensors::EncodablePayload;
+
+pub const LOG_LINE_MAX_LEN: usize = 160;
+
+/// One formatted log line for `log/<client-id>`.
+#[derive(Debug, Clone)]
+pub struct LogPacket {
+    line: [u8; LOG_LINE_MAX_LEN],
+    len: u8,
+}
+
+impl LogPacket {
+    pub fn from_record(record: &LogRecord) -> Self {
+        let mut packet = Self {
+            line: [0; LOG_LINE_MAX_LEN],
+            len: 0,
+        };
+        let _ = record.write_line(&mut packet);
+        packet
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.line[..self.len as usize]
+    }
+}
+
+impl fmt::Write for LogPacket {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let used = self.len as usize;
+        let mut take = s.len().min(LOG_LINE_MAX_LEN - used);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        self.line[used..used + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len = (used + take) as u8;
+        Ok(())
+    }
+}
+
+impl EncodablePayload for LogPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        let bytes = self.as_bytes();
+        if out.len() < bytes.len() {
+            return Err(EncodeError::BufferTooSmall);
+        }
+        out[..bytes.len()].copy_from_slice(bytes);
+        Ok(bytes.len())
+    }
+}
@@ -1,8 +1,14 @@
+pub mod calibration;
+pub mod camera;
+pub mod capture;
+pub mod crash;
 pub mod digital;
 pub mod fast;
+pub mod log;
 pub mod slow;
 pub mod status;
 pub mod temp;
+pub mod time;
 
 use crate::mqtt::codec::EncodeError;
 
@@ -2,7 +2,7 @@ use crate::mqtt::codec::{write_u16_le, write_u32_le, EncodeError};
 use crate::mqtt::sensors::EncodablePayload;
 use crate::mqtt::topics::{
     TOPIC_SENSOR_ADC_SLOW_BATTERY_COMPUTER, TOPIC_SENSOR_ADC_SLOW_BATTERY_STAND,
-    TOPIC_SENSOR_ADC_SLOW_BOOST_VOLTAGE, TOPIC_SENSOR_ADC_SLOW_STARTER_SENSE, TOPIC_SENSOR_SERVO,
+    TOPIC_SENSOR_ADC_SLOW_BOOST_VOLTAGE, TOPIC_SENSOR_ADC_SLOW_STARTER_SENSE,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
@@ -67,20 +67,23 @@ impl EncodablePayload for SlowAdcPacket {
 
 #[derive(Debug, Clone, Copy)]
 pub struct ServoSensorPacket {
+    servo: u8,
     pub timestamp_ms: u32,
     pub value: u16,
 }
 
 impl ServoSensorPacket {
-    pub const fn new(timestamp_ms: u32, value: u16) -> Self {
+    pub const fn new(servo: u8, timestamp_ms: u32, value: u16) -> Self {
         Self {
+            servo,
             timestamp_ms,
             value,
         }
     }
 
-    pub const fn topic(&self) -> &'static str {
-        TOPIC_SENSOR_SERVO
+    /// Index into `SERVOS`, named in the topic
+    pub const fn servo(&self) -> u8 {
+        self.servo
     }
 }
 
@@ -4,17 +4,29 @@ pub const CMD_STATUS_MAX_LEN: usize = 64;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
 pub enum StateStatus {
+    /// Safety switch open
+    Safe,
     Armed,
+    /// Seconds left until FIRE
+    Countdown(u16),
+    /// Countdown paused with the seconds left
+    Hold(u16),
     Fire,
     PostFire,
+    /// Countdown or FIRE stopped; left with FIRE_RESET.
+    Abort,
 }
 
 impl StateStatus {
     pub const fn as_str(self) -> &'static str {
         match self {
+            Self::Safe => "SAFE",
             Self::Armed => "ARMED",
+            Self::Countdown(_) => "COUNTDOWN",
+            Self::Hold(_) => "HOLD",
             Self::Fire => "FIRE",
             Self::PostFire => "POSTFIRE",
+            Self::Abort => "ABORT",
         }
     }
 
@@ -24,11 +36,20 @@ impl StateStatus {
 
     pub const fn as_log(self) -> &'static str {
         match self {
+            Self::Safe => "State: SAFE",
             Self::Armed => "State: ARMED",
+            Self::Countdown(_) => "State: COUNTDOWN",
+            Self::Hold(_) => "State: HOLD",
             Self::Fire => "State: FIRE",
             Self::PostFire => "State: POSTFIRE",
+            Self::Abort => "State: ABORT",
         }
     }
+
+    /// COUNTDOWN, HOLD or FIRE: the stand is firing or about to.
+    pub const fn is_active(self) -> bool {
+        matches!(self, Self::Countdown(_) | Self::Hold(_) | Self::Fire)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
@@ -37,6 +58,14 @@ pub enum ServoStatus {
     Opening,
     Open,
     Closing,
+    /// Moving to a position other than open or closed
+    Moving,
+    /// Stopped at a position other than open or closed
+    Positioned,
+    /// Feedback shows the servo away from its position past the timeout
+    Stalled,
+    /// Feedback missing or out of range
+    Fault,
 }
 
 impl ServoStatus {
@@ -46,6 +75,10 @@ impl ServoStatus {
             Self::Opening => "OPENING",
             Self::Open => "OPEN",
             Self::Closing => "CLOSING",
+            Self::Moving => "MOVING",
+            Self::Positioned => "POSITIONED",
+            Self::Stalled => "STALLED",
+            Self::Fault => "FAULT",
         }
     }
 
@@ -53,12 +86,17 @@ impl ServoStatus {
         self.as_str().as_bytes()
     }
 
+    /// Follows `Servo <name>` on `status/cmd`.
     pub const fn as_log(self) -> &'static str {
         match self {
-            Self::Closed => "Servo closed",
-            Self::Opening => "Servo opening",
-            Self::Open => "Servo open",
-            Self::Closing => "Servo closing",
+            Self::Closed => "closed",
+            Self::Opening => "opening",
+            Self::Open => "open",
+            Self::Closing => "closing",
+            Self::Moving => "moving",
+            Self::Positioned => "positioned",
+            Self::Stalled => "stalled",
+            Self::Fault => "feedback fault",
         }
     }
 }
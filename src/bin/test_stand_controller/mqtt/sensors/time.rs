@@ -0,0 +1,34 @@
+use crate::mqtt::codec::{write_u32_le, write_u64_le, EncodeError};
+use crate::mqtt::sensors::EncodablePayload;
+use crate::mqtt::topics::TOPIC_STATUS_TIME;
+
+/// Mapping from the boot-relative `timestamp_ms` carried by sensor packets
+/// to UTC: `utc_us = timestamp_ms * 1000 + offset_us`.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSyncPacket {
+    pub offset_us: i64,
+    pub delay_us: u32,
+    pub jitter_us: u32,
+    /// `mainboard::sntp::SyncQuality` as its discriminant (0 = unsynchronized).
+    pub quality: u8,
+}
+
+impl TimeSyncPacket {
+    pub const fn topic(&self) -> &'static str {
+        TOPIC_STATUS_TIME
+    }
+}
+
+impl EncodablePayload for TimeSyncPacket {
+    fn encode_payload(&self, out: &mut [u8]) -> Result<usize, EncodeError> {
+        if out.len() < 17 {
+            return Err(EncodeError::BufferTooSmall);
+        }
+
+        write_u64_le(&mut out[..8], self.offset_us as u64)?;
+        write_u32_le(&mut out[8..12], self.delay_us)?;
+        write_u32_le(&mut out[12..16], self.jitter_us)?;
+        out[16] = self.quality;
+        Ok(17)
+    }
+}
@@ -12,20 +12,69 @@ pub const TOPIC_SENSOR_ADC_SLOW_BOOST_VOLTAGE: &str = "sensor/adc/slow/boost_vol
 pub const TOPIC_SENSOR_ADC_SLOW_STARTER_SENSE: &str = "sensor/adc/slow/starter_sense";
 
 pub const TOPIC_SENSOR_DIGITAL_ARMED: &str = "sensor/digital/armed";
+pub const TOPIC_SENSOR_DIGITAL_ARMED_SECONDARY: &str = "sensor/digital/armed_secondary";
+pub const TOPIC_SENSOR_DIGITAL_CONTINUITY: &str = "sensor/digital/continuity";
 pub const TOPIC_SENSOR_TEMP_PREFIX: &str = "sensor/temp/";
-pub const TOPIC_SENSOR_SERVO: &str = "sensor/servo";
+pub const TOPIC_SENSOR_SERVO_PREFIX: &str = "sensor/servo/";
+pub const TOPIC_SENSOR_CAMERA: &str = "sensor/camera";
+
+pub const TOPIC_CAPTURE_META: &str = "capture/meta";
+pub const TOPIC_CAPTURE_CHUNK: &str = "capture/chunk";
+
+pub const TOPIC_LOG_PREFIX: &str = "log/";
 
 pub const TOPIC_CMD_STATE: &str = "cmd/state";
-pub const TOPIC_CMD_SERVO: &str = "cmd/servo";
+pub const TOPIC_CMD_SERVO_FILTER: &str = "cmd/servo/+";
+pub const TOPIC_CMD_SERVO_PREFIX: &str = "cmd/servo/";
 pub const TOPIC_CMD_SHUTDOWN: &str = "cmd/shutdown";
+pub const TOPIC_CMD_OTA_BEGIN: &str = "cmd/ota/begin";
+pub const TOPIC_CMD_OTA_CHUNK: &str = "cmd/ota/chunk";
+pub const TOPIC_CMD_OTA_FINISH: &str = "cmd/ota/finish";
+pub const TOPIC_CMD_OTA_ABORT: &str = "cmd/ota/abort";
+pub const TOPIC_CMD_OTA_FILTER: &str = "cmd/ota/+";
+pub const TOPIC_CMD_OTA_PREFIX: &str = "cmd/ota/";
+pub const TOPIC_CMD_CAPTURE_ACK: &str = "cmd/capture/ack";
+pub const TOPIC_CMD_CAPTURE_ABORT: &str = "cmd/capture/abort";
+pub const TOPIC_CMD_CAPTURE_TRIGGER: &str = "cmd/capture/trigger";
+pub const TOPIC_CMD_CAPTURE_FILTER: &str = "cmd/capture/+";
+pub const TOPIC_CMD_CAPTURE_PREFIX: &str = "cmd/capture/";
+pub const TOPIC_CMD_CALIBRATE: &str = "cmd/calibrate";
+pub const TOPIC_CMD_CALIBRATE_SERVO_FILTER: &str = "cmd/calibrate/servo/+";
+pub const TOPIC_CMD_CALIBRATE_SERVO_PREFIX: &str = "cmd/calibrate/servo/";
+pub const TOPIC_CMD_SEQUENCE_LOAD: &str = "cmd/sequence/load";
+pub const TOPIC_CMD_SEQUENCE_CLEAR: &str = "cmd/sequence/clear";
+pub const TOPIC_CMD_SEQUENCE_FILTER: &str = "cmd/sequence/+";
+pub const TOPIC_CMD_SEQUENCE_PREFIX: &str = "cmd/sequence/";
+pub const TOPIC_CMD_HEARTBEAT: &str = "cmd/heartbeat";
+pub const TOPIC_CMD_TRIGGER: &str = "cmd/trigger";
+pub const TOPIC_CMD_CAMERA: &str = "cmd/camera";
 
 pub const TOPIC_STATUS_STATE: &str = "status/state";
-pub const TOPIC_STATUS_SERVO: &str = "status/servo";
+pub const TOPIC_STATUS_SERVO_PREFIX: &str = "status/servo/";
 pub const TOPIC_STATUS_CMD: &str = "status/cmd";
-
-pub const COMMAND_TOPICS: [&str; 3] = [TOPIC_CMD_STATE, TOPIC_CMD_SERVO, TOPIC_CMD_SHUTDOWN];
-
-pub const TEMP_TOPIC_BUFFER_LEN: usize = 32;
+pub const TOPIC_STATUS_TIME: &str = "status/time";
+pub const TOPIC_STATUS_OTA: &str = "status/ota";
+pub const TOPIC_STATUS_CRASH: &str = "status/crash";
+pub const TOPIC_STATUS_CALIBRATION: &str = "status/calibration";
+pub const TOPIC_STATUS_CALIBRATION_SERVO_PREFIX: &str = "status/calibration/servo/";
+pub const TOPIC_STATUS_SEQUENCE: &str = "status/sequence";
+pub const TOPIC_STATUS_COUNTDOWN: &str = "status/countdown";
+
+pub const COMMAND_TOPICS: [&str; 11] = [
+    TOPIC_CMD_STATE,
+    TOPIC_CMD_SERVO_FILTER,
+    TOPIC_CMD_SHUTDOWN,
+    TOPIC_CMD_OTA_FILTER,
+    TOPIC_CMD_CAPTURE_FILTER,
+    TOPIC_CMD_CALIBRATE,
+    TOPIC_CMD_CALIBRATE_SERVO_FILTER,
+    TOPIC_CMD_SEQUENCE_FILTER,
+    TOPIC_CMD_HEARTBEAT,
+    TOPIC_CMD_TRIGGER,
+    TOPIC_CMD_CAMERA,
+];
+
+pub const TEMP_TOPIC_BUFFER_LEN: usize = 64;
 
 #[derive(Debug, Clone, Copy, defmt::Format)]
 pub enum TopicBuildError {
@@ -67,6 +116,32 @@ pub fn format_temp_topic(
     str::from_utf8(&out[..len]).map_err(|_| TopicBuildError::InvalidUtf8)
 }
 
+/// `log/<client-id>`
+pub fn format_log_topic<'a>(
+    client_id: &str,
+    out: &'a mut [u8; TEMP_TOPIC_BUFFER_LEN],
+) -> Result<&'a str, TopicBuildError> {
+    format_named_topic(TOPIC_LOG_PREFIX, client_id, out)
+}
+
+/// `<prefix><name>`, such as `status/servo/fuel`
+pub fn format_named_topic<'a>(
+    prefix: &str,
+    name: &str,
+    out: &'a mut [u8; TEMP_TOPIC_BUFFER_LEN],
+) -> Result<&'a str, TopicBuildError> {
+    let prefix = prefix.as_bytes();
+    let len = prefix.len() + name.len();
+    if len > out.len() {
+        return Err(TopicBuildError::BufferTooSmall);
+    }
+
+    out[..prefix.len()].copy_from_slice(prefix);
+    out[prefix.len()..len].copy_from_slice(name.as_bytes());
+
+    str::from_utf8(&out[..len]).map_err(|_| TopicBuildError::InvalidUtf8)
+}
+
 fn write_u8_decimal(value: u8, out: &mut [u8]) -> Result<usize, TopicBuildError> {
     if value >= 100 {
         if out.len() < 3 {
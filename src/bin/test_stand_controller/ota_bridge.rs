@@ -0,0 +1,93 @@
+use alloc::format;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{info, warn};
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use mainboard::ota::{BootState, OtaHandle, OtaRequest, OtaResponse};
+
+use crate::mqtt::commands::ota::OtaCommand;
+use crate::mqtt::queue;
+
+// Flash writes are slow; keep the MQTT task from blocking on them
+static OTA_COMMANDS: Channel<CriticalSectionRawMutex, OtaCommand, 2> = Channel::new();
+static HEALTHY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+static ABORT: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Set from before `Finish` is handled until it fails; a finish that
+/// succeeds reboots the board
+static FINISHING: AtomicBool = AtomicBool::new(false);
+
+pub fn submit_ota_command(command: OtaCommand) {
+    if OTA_COMMANDS.try_send(command).is_err() {
+        warn!("OTA command dropped: bridge busy");
+        queue::publish_ota_log("ERR BUSY");
+    }
+}
+
+/// Reaching the broker is what proves a freshly updated image works.
+pub fn report_healthy() {
+    HEALTHY.signal(());
+}
+
+/// Drop an update in progress; the stand is about to fire.
+pub fn abort_session() {
+    ABORT.signal(());
+}
+
+/// An update is being finished, which ends in a reboot.
+pub fn finishing() -> bool {
+    FINISHING.load(Ordering::SeqCst)
+}
+
+#[embassy_executor::task]
+pub async fn ota_bridge_task(ota: OtaHandle) {
+    loop {
+        match select3(OTA_COMMANDS.receive(), HEALTHY.wait(), ABORT.wait()).await {
+            Either3::First(command) => {
+                let finish = matches!(command, OtaCommand::Finish);
+                FINISHING.store(finish, Ordering::SeqCst);
+                // Queued before the countdown started
+                if crate::sequencer::load_state().is_active() {
+                    FINISHING.store(false, Ordering::SeqCst);
+                    warn!("OTA command dropped: stand is firing");
+                    queue::publish_ota_log("ERR FIRE_STATE");
+                    continue;
+                }
+
+                let request = match command {
+                    OtaCommand::Begin { size, sha256 } => OtaRequest::Begin { size, sha256 },
+                    OtaCommand::Chunk { offset, data } => OtaRequest::Write { offset, data },
+                    OtaCommand::Finish => OtaRequest::Finish,
+                    OtaCommand::Abort => OtaRequest::Abort,
+                };
+
+                match ota.transact(request).await {
+                    OtaResponse::Ok(progress) => queue::publish_ota_log(&format!(
+                        "OK {}/{}",
+                        progress.written, progress.size
+                    )),
+                    OtaResponse::Err(e) => {
+                        FINISHING.store(false, Ordering::SeqCst);
+                        warn!("OTA command failed: {:?}", e);
+                        queue::publish_ota_log(&format!("ERR {}", e.as_str()));
+                    }
+                }
+            }
+            Either3::Second(()) => {
+                if ota.boot_state() == BootState::PendingVerify {
+                    if let OtaResponse::Ok(_) = ota.confirm_healthy().await {
+                        info!("OTA image confirmed after MQTT connect");
+                        queue::publish_ota_log("OK CONFIRMED");
+                    }
+                }
+            }
+            Either3::Third(()) => {
+                if let OtaResponse::Err(e) = ota.abort().await {
+                    warn!("OTA abort failed: {:?}", e);
+                }
+            }
+        }
+    }
+}
@@ -1,150 +1,110 @@
 use defmt::warn;
-use embassy_time::{Duration, Instant, Ticker};
-use esp_hal::analog::adc::{
-    Adc, AdcCalBasic, AdcCalScheme, AdcChannel, AdcConfig, AdcPin, Attenuation,
+use embassy_time::Duration;
+use mainboard::analog::{
+    AnalogBlock, AnalogBlockSubscriber, AnalogChannel, AnalogConfig, FilterConfig, SamplingMode,
 };
-use esp_hal::peripherals::ADC1;
-use esp_hal::Blocking;
+use mainboard::net_warn;
 
+use crate::config::{CALIBRATED_SENSOR_PAYLOADS, TASK_DEADLINE_MS};
+use crate::interlock;
 use crate::mqtt::sensors::fast::{FastAdcChannel, FastAdcPacket};
 use crate::mqtt::sensors::slow::{SlowAdcChannel, SlowAdcPacket};
 use crate::mqtt::{publish_fast_sensors, publish_slow_sensors, FastSensorsBatch, SlowSensorsBatch};
-use mainboard::board::{A0Pin, A1Pin, A2Pin, A3Pin, A4Pin, BatVolPin, BoostVolPin};
+use crate::servo;
+use mainboard::watchdog;
 
 const FAST_BATCH_SAMPLES: usize = 100;
-const FAST_SAMPLE_INTERVAL_MS: u64 = 1;
-
-pub struct SensorCollectionIo {
-    pub adc: ADC1<'static>,
-    pub tensometer: A0Pin,
-    pub pressure_tank: A1Pin,
-    pub pressure_combustion: A2Pin,
-    pub starter_sense: A3Pin,
-    pub battery_stand: A4Pin,
-    pub battery_computer: BatVolPin,
-    pub boost_voltage: BoostVolPin,
-}
-
-struct SensorCollectionState {
-    adc: Adc<'static, ADC1<'static>, Blocking>,
-    tensometer: AdcPin<A0Pin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-    pressure_tank: AdcPin<A1Pin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-    pressure_combustion: AdcPin<A2Pin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-    starter_sense: AdcPin<A3Pin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-    battery_stand: AdcPin<A4Pin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-    battery_computer: AdcPin<BatVolPin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-    boost_voltage: AdcPin<BoostVolPin, ADC1<'static>, AdcCalBasic<ADC1<'static>>>,
-}
-
-impl SensorCollectionState {
-    fn new(io: SensorCollectionIo) -> Self {
-        let mut config = AdcConfig::new();
-
-        let tensometer = config.enable_pin_with_cal::<A0Pin, AdcCalBasic<ADC1<'static>>>(
-            io.tensometer,
-            Attenuation::_0dB,
-        );
-        let pressure_tank = config.enable_pin_with_cal::<A1Pin, AdcCalBasic<ADC1<'static>>>(
-            io.pressure_tank,
-            Attenuation::_0dB,
-        );
-        let pressure_combustion = config.enable_pin_with_cal::<A2Pin, AdcCalBasic<ADC1<'static>>>(
-            io.pressure_combustion,
-            Attenuation::_0dB,
-        );
-        let starter_sense = config.enable_pin_with_cal::<A3Pin, AdcCalBasic<ADC1<'static>>>(
-            io.starter_sense,
-            Attenuation::_0dB,
-        );
-        let battery_stand = config.enable_pin_with_cal::<A4Pin, AdcCalBasic<ADC1<'static>>>(
-            io.battery_stand,
-            Attenuation::_0dB,
-        );
-        let battery_computer = config.enable_pin_with_cal::<BatVolPin, AdcCalBasic<ADC1<'static>>>(
-            io.battery_computer,
-            Attenuation::_0dB,
-        );
-        let boost_voltage = config.enable_pin_with_cal::<BoostVolPin, AdcCalBasic<ADC1<'static>>>(
-            io.boost_voltage,
-            Attenuation::_0dB,
-        );
-
-        let adc = Adc::new(io.adc, config);
+/// Conversions per second over all seven channels, about 2 kHz per channel.
+const CONVERSION_RATE_HZ: u32 = 14_000;
+/// Slow channels keep one sample per block.
+const SLOW_DECIMATION: u16 = FAST_BATCH_SAMPLES as u16;
+/// Slow channels: 5 ms averages, spike rejection over 25 ms, 2 Hz low pass.
+const SLOW_FILTER: FilterConfig = FilterConfig {
+    oversample: 10,
+    median: 5,
+    low_pass_mhz: 2000,
+};
 
-        Self {
-            adc,
-            tensometer,
-            pressure_tank,
-            pressure_combustion,
-            starter_sense,
-            battery_stand,
-            battery_computer,
-            boost_voltage,
-        }
+/// Channel wiring of the stand
+pub const TENSOMETER: AnalogChannel = AnalogChannel::A0;
+pub const PRESSURE_TANK: AnalogChannel = AnalogChannel::A1;
+pub const PRESSURE_COMBUSTION: AnalogChannel = AnalogChannel::A2;
+const STARTER_SENSE: AnalogChannel = AnalogChannel::A3;
+const BATTERY_STAND: AnalogChannel = AnalogChannel::A4;
+const BATTERY_COMPUTER: AnalogChannel = AnalogChannel::BatVol;
+const BOOST_VOLTAGE: AnalogChannel = AnalogChannel::BoostVol;
+
+/// All channels converted continuously over DMA; one block is one fast batch.
+pub fn analog_config() -> AnalogConfig {
+    let mut config = AnalogConfig {
+        mode: SamplingMode::Continuous {
+            conversion_rate_hz: CONVERSION_RATE_HZ,
+        },
+        samples_per_block: FAST_BATCH_SAMPLES,
+        ..Default::default()
+    };
+    for channel in [
+        STARTER_SENSE,
+        BATTERY_STAND,
+        BATTERY_COMPUTER,
+        BOOST_VOLTAGE,
+    ] {
+        let slow = config.channel_mut(channel);
+        *slow = slow
+            .with_decimation(SLOW_DECIMATION)
+            .with_filter(SLOW_FILTER);
     }
+    config
 }
 
 #[embassy_executor::task]
-pub async fn sensor_collection_task(io: SensorCollectionIo) {
-    let mut state = SensorCollectionState::new(io);
+pub async fn sensor_collection_task(mut blocks: AnalogBlockSubscriber) {
+    let watch = watchdog::register("sensors", Duration::from_millis(TASK_DEADLINE_MS));
+    let mut overruns = 0;
 
     loop {
-        collect_and_publish_fast(&mut state).await;
-        collect_and_publish_slow(&mut state);
-    }
-}
-
-async fn collect_and_publish_fast(state: &mut SensorCollectionState) {
-    let mut tensometer = [0u16; FAST_BATCH_SAMPLES];
-    let mut pressure_tank = [0u16; FAST_BATCH_SAMPLES];
-    let mut pressure_combustion = [0u16; FAST_BATCH_SAMPLES];
-
-    let mut first_timestamp_ms = 0u32;
-    let mut last_timestamp_ms = 0u32;
-
-    let mut ticker = Ticker::every(Duration::from_millis(FAST_SAMPLE_INTERVAL_MS));
-    for index in 0..FAST_BATCH_SAMPLES {
-        let timestamp_ms = timestamp_ms();
-        if index == 0 {
-            first_timestamp_ms = timestamp_ms;
+        let block = blocks.next_message_pure().await;
+        watch.check_in();
+        if block.overruns != overruns {
+            net_warn!("ADC overrun, samples lost ({} so far)", block.overruns);
+            overruns = block.overruns;
         }
-        last_timestamp_ms = timestamp_ms;
-
-        tensometer[index] = read_adc_raw(&mut state.adc, &mut state.tensometer);
-        pressure_tank[index] = read_adc_raw(&mut state.adc, &mut state.pressure_tank);
-        pressure_combustion[index] = read_adc_raw(&mut state.adc, &mut state.pressure_combustion);
-
-        ticker.next().await;
+        interlock::feed_analog(&block);
+        servo::feed_analog(&block);
+        publish_fast(&block);
+        publish_slow(&block);
     }
+}
 
-    let tensometer_packet = FastAdcPacket::from_slice(
-        FastAdcChannel::Tensometer,
-        first_timestamp_ms,
-        last_timestamp_ms,
-        &tensometer,
-    )
-    .expect("fast packet validation failed for tensometer");
-
-    let tank_packet = FastAdcPacket::from_slice(
-        FastAdcChannel::PressureTank,
-        first_timestamp_ms,
-        last_timestamp_ms,
-        &pressure_tank,
-    )
-    .expect("fast packet validation failed for tank pressure");
-
-    let combustion_packet = FastAdcPacket::from_slice(
-        FastAdcChannel::PressureCombustion,
-        first_timestamp_ms,
-        last_timestamp_ms,
-        &pressure_combustion,
-    )
-    .expect("fast packet validation failed for combustion pressure");
+fn publish_fast(block: &AnalogBlock) {
+    let packet = |fast_channel, channel| {
+        let scale = block.channel_config(channel).scaling.linear_per_mv();
+        FastAdcPacket::from_slice(
+            fast_channel,
+            block.first_timestamp_ms,
+            block.last_timestamp_ms(channel),
+            block.sample_period_ns(channel),
+            block.millivolts(channel).unwrap_or_default(),
+        )
+        .map(|packet| match scale {
+            Some((gain, offset)) if CALIBRATED_SENSOR_PAYLOADS => packet.with_scale(gain, offset),
+            _ => packet,
+        })
+    };
 
+    let packets = (
+        packet(FastAdcChannel::Tensometer, TENSOMETER),
+        packet(FastAdcChannel::PressureTank, PRESSURE_TANK),
+        packet(FastAdcChannel::PressureCombustion, PRESSURE_COMBUSTION),
+    );
+    let (Ok(tensometer), Ok(tank_pressure), Ok(combustion_pressure)) = packets else {
+        warn!("Dropping fast sensors batch: packet validation failed");
+        return;
+    };
     let batch = FastSensorsBatch {
-        tensometer: Some(tensometer_packet),
-        tank_pressure: Some(tank_packet),
-        combustion_pressure: Some(combustion_packet),
+        tensometer: Some(tensometer),
+        tank_pressure: Some(tank_pressure),
+        combustion_pressure: Some(combustion_pressure),
     };
 
     if publish_fast_sensors(batch).is_err() {
@@ -152,36 +112,24 @@ async fn collect_and_publish_fast(state: &mut SensorCollectionState) {
     }
 }
 
-fn collect_and_publish_slow(state: &mut SensorCollectionState) {
-    let battery_stand = SlowAdcPacket::new(
-        SlowAdcChannel::BatteryStand,
-        timestamp_ms(),
-        read_adc_raw(&mut state.adc, &mut state.battery_stand),
-    );
-
-    let battery_computer = SlowAdcPacket::new(
-        SlowAdcChannel::BatteryComputer,
-        timestamp_ms(),
-        read_adc_raw(&mut state.adc, &mut state.battery_computer),
-    );
-
-    let boost_voltage = SlowAdcPacket::new(
-        SlowAdcChannel::BoostVoltage,
-        timestamp_ms(),
-        read_adc_raw(&mut state.adc, &mut state.boost_voltage),
-    );
-
-    let starter_sense = SlowAdcPacket::new(
-        SlowAdcChannel::StarterSense,
-        timestamp_ms(),
-        read_adc_raw(&mut state.adc, &mut state.starter_sense),
-    );
+fn publish_slow(block: &AnalogBlock) {
+    let packet = |slow_channel, channel| {
+        let value = block
+            .millivolts(channel)
+            .and_then(|samples| samples.last().copied())
+            .unwrap_or(0);
+        Some(SlowAdcPacket::new(
+            slow_channel,
+            block.last_timestamp_ms(channel),
+            value,
+        ))
+    };
 
     let batch = SlowSensorsBatch {
-        battery_stand: Some(battery_stand),
-        battery_computer: Some(battery_computer),
-        boost_voltage: Some(boost_voltage),
-        starter_sense: Some(starter_sense),
+        battery_stand: packet(SlowAdcChannel::BatteryStand, BATTERY_STAND),
+        battery_computer: packet(SlowAdcChannel::BatteryComputer, BATTERY_COMPUTER),
+        boost_voltage: packet(SlowAdcChannel::BoostVoltage, BOOST_VOLTAGE),
+        starter_sense: packet(SlowAdcChannel::StarterSense, STARTER_SENSE),
         servo: None,
     };
 
@@ -189,18 +137,3 @@ fn collect_and_publish_slow(state: &mut SensorCollectionState) {
         warn!("Dropping slow sensors batch: outbound queue full");
     }
 }
-
-fn read_adc_raw<PIN, CS>(
-    adc: &mut Adc<'static, ADC1<'static>, Blocking>,
-    pin: &mut AdcPin<PIN, ADC1<'static>, CS>,
-) -> u16
-where
-    PIN: AdcChannel,
-    CS: AdcCalScheme<ADC1<'static>>,
-{
-    nb::block!(adc.read_oneshot(pin)).expect("ADC oneshot read failed")
-}
-
-fn timestamp_ms() -> u32 {
-    Instant::now().as_millis() as u32
-}
@@ -0,0 +1,31 @@
+//! The hardware limits of `config.rs` that sequences are validated against.
+
+use crate::camera::shot::SHOT_CYCLE_MS;
+use crate::config::{
+    FIRE_CHANNEL_MASK, FIRE_CHANNEL_MAX_ON_MS, SEQUENCE_MAX_COUNTDOWN_MS, SEQUENCE_MAX_DURATION_MS,
+    SERVOS,
+};
+use crate::servo::calibration::ServoCalibration;
+
+use super::script::{Limits, ServoLimits};
+
+/// The limits with servo `n` of `SERVOS` calibrated as `calibration(n)`.
+pub fn configured(calibration: impl Fn(usize) -> ServoCalibration) -> Limits {
+    Limits {
+        max_countdown_ms: SEQUENCE_MAX_COUNTDOWN_MS,
+        max_duration_ms: SEQUENCE_MAX_DURATION_MS,
+        servos: core::array::from_fn(|index| {
+            SERVOS.get(index).map(|config| {
+                let calibration = calibration(index);
+                ServoLimits {
+                    name: config.name,
+                    travel_ms: config.full_travel_ms(&calibration),
+                    max_rate: calibration.max_rate(),
+                }
+            })
+        }),
+        camera_cycle_ms: SHOT_CYCLE_MS,
+        max_trigger_ms: FIRE_CHANNEL_MAX_ON_MS,
+        trigger_channels: FIRE_CHANNEL_MASK,
+    }
+}
@@ -0,0 +1,173 @@
+//! Timed fire sequences.
+//!
+//! A sequence uploaded on `cmd/sequence/load` is validated against the
+//! hardware [`limits()`], stored in flash and reported on the retained
+//! `status/sequence` topic. While one is loaded, FIRE runs its steps instead
+//! of the manual trigger, starting during the countdown for steps before T-0
+//! (see `sequencer`); HOLD pauses it, FIRE_END, ABORT and the interlocks stop
+//! it at any step.
+
+pub mod limits;
+pub mod script;
+
+use alloc::format;
+use alloc::string::String;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use mainboard::storage::{self, StorageError, StorageKey};
+use mainboard::{net_info, net_warn};
+
+use crate::mqtt::commands::sequence::SequenceCommand;
+use crate::mqtt::queue;
+use crate::mqtt::sensors::status::CommandStatusPacket;
+use crate::servo;
+use script::{Limits, Sequence, ENCODED_MAX_LEN};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+const SEQUENCE_KEY: StorageKey = StorageKey(1);
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static SEQUENCE_COMMANDS: Channel<CriticalSectionRawMutex, SequenceCommand, 2> = Channel::new();
+static LOADED: Mutex<CriticalSectionRawMutex, Cell<Option<Sequence>>> = Mutex::new(Cell::new(None));
+/// `LOADED` reflects the flash contents
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// With the servo calibrations in use.
+pub fn limits() -> Limits {
+    limits::configured(servo::calibration)
+}
+
+/// The sequence FIRE runs, `None` for the manual sequence.
+pub fn loaded() -> Option<Sequence> {
+    LOADED.lock(|loaded| loaded.get())
+}
+
+pub fn submit_sequence_command(command: SequenceCommand) {
+    if SEQUENCE_COMMANDS.try_send(command).is_err() {
+        warn!("Sequence command dropped: busy");
+        queue::publish_command_log("Sequence rejected: busy");
+    }
+}
+
+pub fn republish_sequence() {
+    if READY.load(Ordering::Relaxed) {
+        publish_status(loaded().as_ref());
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn publish_status(sequence: Option<&Sequence>) {
+    let status = match sequence {
+        Some(sequence) => format!(
+            "{} steps T{:+} to T{:+} ms",
+            sequence.steps().len(),
+            sequence.first_ms(),
+            sequence.last_ms()
+        ),
+        None => String::from("NONE"),
+    };
+    let Ok(packet) = CommandStatusPacket::from_str(&status) else {
+        return;
+    };
+    if queue::publish_sequence_status(packet).is_err() {
+        warn!("Sequence status not queued: outbound queue full");
+    }
+}
+
+fn set_loaded(sequence: Option<Sequence>) {
+    LOADED.lock(|loaded| loaded.set(sequence));
+    READY.store(true, Ordering::Relaxed);
+    publish_status(sequence.as_ref());
+}
+
+async fn load() -> Option<Sequence> {
+    let mut bytes = [0u8; ENCODED_MAX_LEN];
+    match storage::load(SEQUENCE_KEY, &mut bytes).await {
+        Ok(len) => {
+            let sequence = Sequence::decode(&bytes[..len], &limits());
+            if sequence.is_none() {
+                net_warn!("Stored sequence invalid for this firmware, ignored");
+            }
+            sequence
+        }
+        Err(StorageError::NotFound) => None,
+        Err(e) => {
+            net_warn!("Failed to load sequence: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn handle_command(command: SequenceCommand) {
+    match command {
+        SequenceCommand::Load(Ok(sequence)) => {
+            let mut bytes = [0u8; ENCODED_MAX_LEN];
+            let len = sequence.encode(&mut bytes);
+            set_loaded(Some(sequence));
+            net_info!(
+                "Sequence loaded: {} steps, T{:+} to T{:+} ms",
+                sequence.steps().len(),
+                sequence.first_ms(),
+                sequence.last_ms()
+            );
+
+            match storage::store(SEQUENCE_KEY, &bytes[..len]).await {
+                Ok(()) => queue::publish_command_log("Sequence loaded"),
+                Err(e) => {
+                    net_warn!("Failed to store sequence: {:?}", e);
+                    queue::publish_command_log("Sequence loaded, not stored");
+                }
+            }
+        }
+        SequenceCommand::Load(Err(error)) => {
+            let message = format!(
+                "Sequence rejected: line {}: {}",
+                error.line,
+                error.kind.as_str()
+            );
+            net_warn!("{}", message);
+            queue::publish_command_log(&message);
+        }
+        SequenceCommand::Clear => {
+            set_loaded(None);
+            net_info!("Sequence cleared");
+
+            match storage::remove(SEQUENCE_KEY).await {
+                Ok(()) => queue::publish_command_log("Sequence cleared"),
+                Err(e) => {
+                    net_warn!("Failed to clear stored sequence: {:?}", e);
+                    queue::publish_command_log("Sequence cleared, not stored");
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn sequence_task() {
+    set_loaded(load().await);
+    info!("Sequence loaded from flash");
+
+    loop {
+        let command = SEQUENCE_COMMANDS.receive().await;
+        handle_command(command).await;
+    }
+}
@@ -0,0 +1,797 @@
+//! Fire sequence definitions: parsing, validation and the stored form.
+//!
+//! A definition is text, one step per line, `#` starts a comment:
+//!
+//! ```text
+//! T-10000 SERVO fuel OPEN
+//! T-9000  SERVO oxidiser OPEN
+//! T-3000  CAMERA
+//! T-3000  LIGHT RED BUZZER
+//! T+0     TRIGGER ON 0
+//! T+1500  TRIGGER ON 1 2
+//! T+2000  TRIGGER OFF
+//! T+5000  SERVO fuel CLOSE
+//! T+5000  SERVO oxidiser CLOSE
+//! T+30000 LIGHT GREEN
+//! ```
+//!
+//! Times are milliseconds relative to T-0; steps run in order of time, and
+//! steps at the same time in the order written. Actions:
+//!
+//! - `SERVO <name> OPEN|CLOSE`
+//! - `SERVO <name> MOVE <position> [<rate>]`: `position` in tenths of a
+//!   degree, 0 to 1800, at most `rate` tenths of a degree per second
+//! - `SERVO MANUAL`: `cmd/servo/<name>` may move the servos until the
+//!   sequence stops
+//! - `TRIGGER ON|OFF [<channel>...]`: fire trigger channels 0 to 7, every
+//!   wired channel when none is named; `ON` not before T-0
+//! - `CAMERA`: one shutter press
+//! - `LIGHT [GREEN] [RED] [YELLOW] [BLUE] [WHITE] [BUZZER]`: the lamps that
+//!   are on, none turns the light off
+//! - `END`: finish here, must be the last step
+
+use mainboard::fire_trigger::CHANNELS;
+use mainboard::signal_light::SignalLightConfig;
+
+use crate::mqtt::commands::servo::{ServoCommand, MAX_SERVOS, SERVO_MAX_POSITION};
+
+pub const MAX_STEPS: usize = 32;
+/// Stored form: version, step count, then 10 bytes per step.
+pub const ENCODED_MAX_LEN: usize = 2 + MAX_STEPS * STEP_LEN;
+
+const ENCODED_VERSION: u8 = 4;
+const STEP_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Action {
+    /// Servo by its index in [`Limits::servos`]
+    Servo {
+        servo: u8,
+        command: ServoCommand,
+    },
+    /// Hand the servos to `cmd/servo/<name>`
+    ServoManual,
+    /// Fire trigger channels in `mask` on or off
+    Trigger {
+        mask: u8,
+        on: bool,
+    },
+    Camera,
+    Light(SignalLightConfig),
+    End,
+}
+
+impl Action {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Servo {
+                command: ServoCommand::Open,
+                ..
+            } => "SERVO OPEN",
+            Self::Servo {
+                command: ServoCommand::Close,
+                ..
+            } => "SERVO CLOSE",
+            Self::Servo {
+                command: ServoCommand::Move { .. },
+                ..
+            } => "SERVO MOVE",
+            Self::ServoManual => "SERVO MANUAL",
+            Self::Trigger { on: true, .. } => "TRIGGER ON",
+            Self::Trigger { on: false, .. } => "TRIGGER OFF",
+            Self::Camera => "CAMERA",
+            Self::Light(_) => "LIGHT",
+            Self::End => "END",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Step {
+    /// Milliseconds relative to T-0
+    pub at_ms: i32,
+    pub action: Action,
+}
+
+/// What the hardware can do, checked by [`Sequence::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Limits {
+    /// Earliest step before T-0
+    pub max_countdown_ms: u32,
+    /// Latest step after T-0
+    pub max_duration_ms: u32,
+    /// Configured servos, by index
+    pub servos: [Option<ServoLimits>; MAX_SERVOS],
+    /// One shutter press and release
+    pub camera_cycle_ms: u32,
+    /// Longest a fire trigger channel may stay on
+    pub max_trigger_ms: u32,
+    /// Wired fire trigger channels
+    pub trigger_channels: u8,
+}
+
+impl Limits {
+    fn servo(&self, name: &[u8]) -> Option<u8> {
+        self.servos
+            .iter()
+            .position(|servo| servo.is_some_and(|servo| servo.name.as_bytes() == name))
+            .map(|index| index as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ServoLimits {
+    pub name: &'static str,
+    /// Full travel at full rate; a move must finish before the next on the
+    /// same servo starts
+    pub travel_ms: u32,
+    /// Full rate, tenths of a degree per second
+    pub max_rate: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ScriptErrorKind {
+    Syntax,
+    Empty,
+    TooManySteps,
+    OutOfOrder,
+    OutOfRange,
+    AfterEnd,
+    ServoTooSoon,
+    UnknownServo,
+    CameraTooSoon,
+    TriggerTooLong,
+    UnknownChannel,
+    TriggerBeforeZero,
+}
+
+impl ScriptErrorKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Syntax => "syntax error",
+            Self::Empty => "no steps",
+            Self::TooManySteps => "too many steps",
+            Self::OutOfOrder => "steps out of order",
+            Self::OutOfRange => "time out of range",
+            Self::AfterEnd => "step after END",
+            Self::ServoTooSoon => "servo still moving",
+            Self::UnknownServo => "servo not configured",
+            Self::CameraTooSoon => "camera still busy",
+            Self::TriggerTooLong => "trigger on too long",
+            Self::UnknownChannel => "trigger channel not wired",
+            Self::TriggerBeforeZero => "trigger on before T-0",
+        }
+    }
+}
+
+/// Why a definition was rejected; `line` is 1-based, 0 for the whole
+/// definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ScriptError {
+    pub line: u16,
+    pub kind: ScriptErrorKind,
+}
+
+/// A validated sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Sequence {
+    steps: [Step; MAX_STEPS],
+    len: u8,
+}
+
+impl Sequence {
+    pub fn parse(text: &[u8], limits: &Limits) -> Result<Self, ScriptError> {
+        let mut sequence = Self::empty();
+        let mut lines = [0u16; MAX_STEPS];
+
+        for (index, line) in text.split(|&byte| byte == b'\n').enumerate() {
+            let number = (index + 1).min(u16::MAX as usize) as u16;
+            let error = |kind| ScriptError { line: number, kind };
+
+            let line = match line.iter().position(|&byte| byte == b'#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            let mut words = line
+                .split(|byte| byte.is_ascii_whitespace())
+                .filter(|word| !word.is_empty());
+            let Some(time) = words.next() else {
+                continue;
+            };
+
+            let at_ms = parse_time(time).ok_or(error(ScriptErrorKind::Syntax))?;
+            let action = parse_action(&mut words, limits).ok_or(error(ScriptErrorKind::Syntax))?;
+            if sequence.len as usize == MAX_STEPS {
+                return Err(error(ScriptErrorKind::TooManySteps));
+            }
+            lines[sequence.len as usize] = number;
+            sequence.steps[sequence.len as usize] = Step { at_ms, action };
+            sequence.len += 1;
+        }
+
+        sequence
+            .validate(limits)
+            .map_err(|(index, kind)| ScriptError {
+                line: index.map_or(0, |index| lines[index]),
+                kind,
+            })?;
+        Ok(sequence)
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps[..self.len as usize]
+    }
+
+    /// Time of the first step, where the countdown starts.
+    pub fn first_ms(&self) -> i32 {
+        self.steps().first().map_or(0, |step| step.at_ms)
+    }
+
+    pub fn last_ms(&self) -> i32 {
+        self.steps().last().map_or(0, |step| step.at_ms)
+    }
+
+    /// Seconds from FIRE to T-0: at least `min_s`, and enough to run the
+    /// first step.
+    pub fn countdown_s(&self, min_s: u16) -> u16 {
+        let lead_ms = self.first_ms().min(0).unsigned_abs();
+        min_s.max(lead_ms.div_ceil(1000).min(u16::MAX as u32) as u16)
+    }
+
+    pub fn encode(&self, out: &mut [u8; ENCODED_MAX_LEN]) -> usize {
+        out[0] = ENCODED_VERSION;
+        out[1] = self.len;
+        for (chunk, step) in out[2..].chunks_exact_mut(STEP_LEN).zip(self.steps()) {
+            let (kind, target, argument) = encode_action(step.action);
+            chunk[0..4].copy_from_slice(&step.at_ms.to_le_bytes());
+            chunk[4] = kind;
+            chunk[5] = target;
+            chunk[6..10].copy_from_slice(&argument.to_le_bytes());
+        }
+        2 + self.len as usize * STEP_LEN
+    }
+
+    /// The stored form, validated again since the limits may have changed.
+    pub fn decode(bytes: &[u8], limits: &Limits) -> Option<Self> {
+        let (&version, rest) = bytes.split_first()?;
+        let (&len, rest) = rest.split_first()?;
+        if version != ENCODED_VERSION
+            || len as usize > MAX_STEPS
+            || rest.len() != len as usize * STEP_LEN
+        {
+            return None;
+        }
+
+        let mut sequence = Self::empty();
+        for chunk in rest.chunks_exact(STEP_LEN) {
+            sequence.steps[sequence.len as usize] = Step {
+                at_ms: i32::from_le_bytes(chunk[0..4].try_into().ok()?),
+                action: decode_action(
+                    chunk[4],
+                    chunk[5],
+                    u32::from_le_bytes(chunk[6..10].try_into().ok()?),
+                )?,
+            };
+            sequence.len += 1;
+        }
+        sequence.validate(limits).ok()?;
+        Some(sequence)
+    }
+
+    const fn empty() -> Self {
+        Self {
+            steps: [Step {
+                at_ms: 0,
+                action: Action::End,
+            }; MAX_STEPS],
+            len: 0,
+        }
+    }
+
+    /// The offending step index and why.
+    fn validate(&self, limits: &Limits) -> Result<(), (Option<usize>, ScriptErrorKind)> {
+        if self.len == 0 {
+            return Err((None, ScriptErrorKind::Empty));
+        }
+
+        let min_ms = -(limits.max_countdown_ms.min(i32::MAX as u32) as i64);
+        let max_ms = limits.max_duration_ms as i64;
+        let mut previous_ms = i64::MIN;
+        // Start of the last move of each servo and how long it may take
+        let mut last_servo: [Option<(i64, i64)>; MAX_SERVOS] = [None; MAX_SERVOS];
+        let mut last_camera_ms: Option<i64> = None;
+        let mut trigger_on_ms: [Option<i64>; CHANNELS] = [None; CHANNELS];
+
+        for (index, step) in self.steps().iter().enumerate() {
+            let fail = |kind| Err((Some(index), kind));
+            let at_ms = step.at_ms as i64;
+
+            if at_ms < min_ms || at_ms > max_ms {
+                return fail(ScriptErrorKind::OutOfRange);
+            }
+            if at_ms < previous_ms {
+                return fail(ScriptErrorKind::OutOfOrder);
+            }
+            if trigger_on_ms
+                .iter()
+                .flatten()
+                .any(|on_ms| at_ms - on_ms > limits.max_trigger_ms as i64)
+            {
+                return fail(ScriptErrorKind::TriggerTooLong);
+            }
+            previous_ms = at_ms;
+
+            match step.action {
+                Action::Servo { servo, command } => {
+                    let Some(Some(servo_limits)) = limits.servos.get(servo as usize) else {
+                        return fail(ScriptErrorKind::UnknownServo);
+                    };
+                    let last = &mut last_servo[servo as usize];
+                    if last.is_some_and(|(last_ms, travel_ms)| at_ms - last_ms < travel_ms) {
+                        return fail(ScriptErrorKind::ServoTooSoon);
+                    }
+                    *last = Some((at_ms, servo_travel_ms(command, servo_limits)));
+                }
+                Action::ServoManual => {}
+                Action::Camera => {
+                    if last_camera_ms
+                        .is_some_and(|last| at_ms - last < limits.camera_cycle_ms as i64)
+                    {
+                        return fail(ScriptErrorKind::CameraTooSoon);
+                    }
+                    last_camera_ms = Some(at_ms);
+                }
+                Action::Trigger { mask, on } => {
+                    if mask & !limits.trigger_channels != 0 {
+                        return fail(ScriptErrorKind::UnknownChannel);
+                    }
+                    // The countdown may still be held or aborted before T-0
+                    if on && at_ms < 0 {
+                        return fail(ScriptErrorKind::TriggerBeforeZero);
+                    }
+                    for (channel, on_ms) in trigger_on_ms.iter_mut().enumerate() {
+                        if mask & (1 << channel) == 0 {
+                            continue;
+                        }
+                        if !on {
+                            *on_ms = None;
+                        } else if on_ms.is_none() {
+                            *on_ms = Some(at_ms);
+                        }
+                    }
+                }
+                Action::Light(_) => {}
+                Action::End => {
+                    if index + 1 != self.len as usize {
+                        return Err((Some(index + 1), ScriptErrorKind::AfterEnd));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Longest `command` can take: full travel, slowed down to its rate.
+fn servo_travel_ms(command: ServoCommand, limits: &ServoLimits) -> i64 {
+    let travel_ms = limits.travel_ms as i64;
+    match command {
+        ServoCommand::Move {
+            rate: Some(rate), ..
+        } if rate < limits.max_rate => travel_ms * limits.max_rate as i64 / rate as i64,
+        _ => travel_ms,
+    }
+}
+
+/// `T+<ms>`, `T-<ms>` or `T0`
+fn parse_time(word: &[u8]) -> Option<i32> {
+    let rest = word.strip_prefix(b"T")?;
+    let (negative, digits) = match rest {
+        [b'+', digits @ ..] => (false, digits),
+        [b'-', digits @ ..] => (true, digits),
+        digits => (false, digits),
+    };
+    if digits.is_empty() || digits.len() > 9 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let value = digits
+        .iter()
+        .fold(0i32, |value, &digit| value * 10 + (digit - b'0') as i32);
+    Some(if negative { -value } else { value })
+}
+
+/// An unknown servo name parses to an index past the configured servos,
+/// which [`Sequence::validate`] reports; every wired channel stands in for a
+/// TRIGGER without channels.
+fn parse_action<'a>(words: &mut impl Iterator<Item = &'a [u8]>, limits: &Limits) -> Option<Action> {
+    let action = match words.next()? {
+        b"SERVO" => match words.next()? {
+            b"MANUAL" => Action::ServoManual,
+            name => Action::Servo {
+                servo: limits.servo(name).unwrap_or(MAX_SERVOS as u8),
+                command: ServoCommand::parse(words.next()?, words)?,
+            },
+        },
+        b"TRIGGER" => {
+            let on = match words.next()? {
+                b"ON" => true,
+                b"OFF" => false,
+                _ => return None,
+            };
+            let mut mask = 0u8;
+            for word in words.by_ref() {
+                let [digit @ b'0'..=b'7'] = word else {
+                    return None;
+                };
+                mask |= 1 << (digit - b'0');
+            }
+            Action::Trigger {
+                mask: if mask == 0 {
+                    limits.trigger_channels
+                } else {
+                    mask
+                },
+                on,
+            }
+        }
+        b"CAMERA" => Action::Camera,
+        b"LIGHT" => {
+            let mut light = SignalLightConfig::default();
+            for word in words.by_ref() {
+                let lamp = match word {
+                    b"GREEN" => &mut light.green,
+                    b"RED" => &mut light.red,
+                    b"YELLOW" => &mut light.yellow,
+                    b"BLUE" => &mut light.blue,
+                    b"WHITE" => &mut light.white,
+                    b"BUZZER" => &mut light.buzzer,
+                    _ => return None,
+                };
+                *lamp = true;
+            }
+            Action::Light(light)
+        }
+        b"END" => Action::End,
+        _ => return None,
+    };
+
+    words.next().is_none().then_some(action)
+}
+
+/// Kind, target and argument; the target is the servo index, 0 for other
+/// actions. A move carries its position in the low half and its rate, 0 for
+/// full rate, in the high half.
+fn encode_action(action: Action) -> (u8, u8, u32) {
+    match action {
+        Action::Servo {
+            servo,
+            command: ServoCommand::Open,
+        } => (0, servo, 1),
+        Action::Servo {
+            servo,
+            command: ServoCommand::Close,
+        } => (0, servo, 0),
+        Action::Servo {
+            servo,
+            command: ServoCommand::Move { position, rate },
+        } => (6, servo, position as u32 | (rate.unwrap_or(0) as u32) << 16),
+        Action::ServoManual => (7, 0, 0),
+        Action::Trigger { mask, on: true } => (1, 0, mask as u32),
+        Action::Trigger { mask, on: false } => (5, 0, mask as u32),
+        Action::Camera => (2, 0, 0),
+        Action::Light(light) => (
+            3,
+            0,
+            light.green as u32
+                | (light.red as u32) << 1
+                | (light.yellow as u32) << 2
+                | (light.blue as u32) << 3
+                | (light.white as u32) << 4
+                | (light.buzzer as u32) << 5,
+        ),
+        Action::End => (4, 0, 0),
+    }
+}
+
+fn decode_action(kind: u8, target: u8, argument: u32) -> Option<Action> {
+    if kind != 0 && kind != 6 && target != 0 {
+        return None;
+    }
+    Some(match (kind, argument) {
+        (0, 1) => Action::Servo {
+            servo: target,
+            command: ServoCommand::Open,
+        },
+        (0, 0) => Action::Servo {
+            servo: target,
+            command: ServoCommand::Close,
+        },
+        (6, argument) => {
+            let position = argument as u16;
+            if position > SERVO_MAX_POSITION {
+                return None;
+            }
+            Action::Servo {
+                servo: target,
+                command: ServoCommand::Move {
+                    position,
+                    rate: Some((argument >> 16) as u16).filter(|&rate| rate != 0),
+                },
+            }
+        }
+        (7, 0) => Action::ServoManual,
+        (1, mask @ 0..=0xFF) => Action::Trigger {
+            mask: mask as u8,
+            on: true,
+        },
+        (5, mask @ 0..=0xFF) => Action::Trigger {
+            mask: mask as u8,
+            on: false,
+        },
+        (2, _) => Action::Camera,
+        (3, bits) => Action::Light(SignalLightConfig {
+            green: bits & 1 != 0,
+            red: bits & 1 << 1 != 0,
+            yellow: bits & 1 << 2 != 0,
+            blue: bits & 1 << 3 != 0,
+            white: bits & 1 << 4 != 0,
+            buzzer: bits & 1 << 5 != 0,
+        }),
+        (4, _) => Action::End,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+T-10000 SERVO fuel OPEN
+T-9000  SERVO oxidiser OPEN
+T-3000  CAMERA
+T-3000  LIGHT RED BUZZER
+T+0     TRIGGER ON 0   # first motor
+T+1500  TRIGGER ON 1 2
+T+2000  TRIGGER OFF
+T+5000  SERVO fuel CLOSE
+T+5000  SERVO oxidiser CLOSE
+T+30000 LIGHT GREEN
+";
+
+    fn limits() -> Limits {
+        let servo = |name| {
+            Some(ServoLimits {
+                name,
+                travel_ms: 5000,
+                max_rate: 360,
+            })
+        };
+        Limits {
+            max_countdown_ms: 60_000,
+            max_duration_ms: 120_000,
+            servos: [servo("fuel"), servo("oxidiser"), None],
+            camera_cycle_ms: 700,
+            max_trigger_ms: 10_000,
+            trigger_channels: 0x7F,
+        }
+    }
+
+    fn parse(text: &str) -> Result<Sequence, ScriptError> {
+        Sequence::parse(text.as_bytes(), &limits())
+    }
+
+    fn error(line: u16, kind: ScriptErrorKind) -> Result<Sequence, ScriptError> {
+        Err(ScriptError { line, kind })
+    }
+
+    #[test]
+    fn parses_example() {
+        let sequence = parse(EXAMPLE).unwrap();
+        assert_eq!(sequence.steps().len(), 10);
+        assert_eq!((sequence.first_ms(), sequence.last_ms()), (-10_000, 30_000));
+        assert_eq!(
+            sequence.steps()[1].action,
+            Action::Servo {
+                servo: 1,
+                command: ServoCommand::Open
+            }
+        );
+        assert_eq!(
+            sequence.steps()[5].action,
+            Action::Trigger {
+                mask: 0b110,
+                on: true
+            }
+        );
+        // Every wired channel when none is named
+        assert_eq!(
+            sequence.steps()[6].action,
+            Action::Trigger {
+                mask: 0x7F,
+                on: false
+            }
+        );
+    }
+
+    #[test]
+    fn countdown_reaches_the_first_step() {
+        assert_eq!(parse(EXAMPLE).unwrap().countdown_s(5), 10);
+        assert_eq!(parse(EXAMPLE).unwrap().countdown_s(15), 15);
+        assert_eq!(parse("T-10001 LIGHT").unwrap().countdown_s(0), 11);
+        assert_eq!(parse("T+500 LIGHT").unwrap().countdown_s(0), 0);
+    }
+
+    #[test]
+    fn parses_times() {
+        for (word, at_ms) in [("T0", 0), ("T+0", 0), ("T-0", 0), ("T1500", 1500)] {
+            let text = format!("{word} CAMERA");
+            assert_eq!(parse(&text).unwrap().first_ms(), at_ms, "{word}");
+        }
+        for word in ["0", "T", "T+", "T1.5", "T+-1", "T1000000000"] {
+            let text = format!("{word} CAMERA");
+            assert_eq!(parse(&text), error(1, ScriptErrorKind::Syntax), "{word}");
+        }
+    }
+
+    #[test]
+    fn rejects_syntax_errors() {
+        for text in [
+            "T0 FIRE",
+            "T0 CAMERA NOW",
+            "T0 TRIGGER",
+            "T0 TRIGGER ON 8",
+            "T0 LIGHT PINK",
+            "T0 SERVO fuel",
+        ] {
+            assert_eq!(parse(text), error(1, ScriptErrorKind::Syntax), "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_empty() {
+        assert_eq!(parse("# nothing\n\n"), error(0, ScriptErrorKind::Empty));
+    }
+
+    #[test]
+    fn rejects_too_many_steps() {
+        let text: String = (0..=MAX_STEPS).map(|_| "T0 LIGHT\n").collect();
+        assert_eq!(
+            parse(&text),
+            error(MAX_STEPS as u16 + 1, ScriptErrorKind::TooManySteps)
+        );
+    }
+
+    #[test]
+    fn checks_time_range_and_order() {
+        assert!(parse("T-60000 LIGHT\nT+120000 LIGHT").is_ok());
+        assert_eq!(
+            parse("T-60001 LIGHT"),
+            error(1, ScriptErrorKind::OutOfRange)
+        );
+        assert_eq!(
+            parse("T120001 LIGHT"),
+            error(1, ScriptErrorKind::OutOfRange)
+        );
+        assert_eq!(
+            parse("T0 LIGHT\n\nT-1 LIGHT"),
+            error(3, ScriptErrorKind::OutOfOrder)
+        );
+    }
+
+    #[test]
+    fn end_comes_last() {
+        assert!(parse("T0 LIGHT\nT10 END").is_ok());
+        assert_eq!(
+            parse("T0 END\nT10 LIGHT"),
+            error(2, ScriptErrorKind::AfterEnd)
+        );
+    }
+
+    #[test]
+    fn servo_moves_need_their_travel_time() {
+        assert_eq!(
+            parse("T0 SERVO nitrous OPEN"),
+            error(1, ScriptErrorKind::UnknownServo)
+        );
+        assert!(parse("T0 SERVO fuel OPEN\nT5000 SERVO fuel CLOSE").is_ok());
+        assert!(parse("T0 SERVO fuel OPEN\nT0 SERVO oxidiser OPEN").is_ok());
+        assert_eq!(
+            parse("T0 SERVO fuel OPEN\nT4999 SERVO fuel CLOSE"),
+            error(2, ScriptErrorKind::ServoTooSoon)
+        );
+        // Half the rate takes twice as long
+        assert_eq!(
+            parse("T0 SERVO fuel MOVE 900 180\nT9999 SERVO fuel CLOSE"),
+            error(2, ScriptErrorKind::ServoTooSoon)
+        );
+        assert!(parse("T0 SERVO fuel MOVE 900 180\nT10000 SERVO fuel CLOSE").is_ok());
+    }
+
+    #[test]
+    fn camera_shots_need_their_cycle() {
+        assert!(parse("T0 CAMERA\nT700 CAMERA").is_ok());
+        assert_eq!(
+            parse("T0 CAMERA\nT699 CAMERA"),
+            error(2, ScriptErrorKind::CameraTooSoon)
+        );
+    }
+
+    #[test]
+    fn trigger_channels_must_be_wired() {
+        assert_eq!(
+            parse("T0 TRIGGER ON 7"),
+            error(1, ScriptErrorKind::UnknownChannel)
+        );
+    }
+
+    #[test]
+    fn trigger_on_not_before_zero() {
+        assert_eq!(
+            parse("T-1 TRIGGER ON 0"),
+            error(1, ScriptErrorKind::TriggerBeforeZero)
+        );
+        assert_eq!(
+            parse("T-3000 LIGHT RED\nT-1 TRIGGER ON"),
+            error(2, ScriptErrorKind::TriggerBeforeZero)
+        );
+        assert!(parse("T-1 TRIGGER OFF\nT0 TRIGGER ON 0").is_ok());
+    }
+
+    #[test]
+    fn trigger_on_time_is_limited() {
+        assert!(parse("T0 TRIGGER ON 0\nT10000 TRIGGER OFF 0").is_ok());
+        assert_eq!(
+            parse("T0 TRIGGER ON 0\nT10001 TRIGGER OFF 0"),
+            error(2, ScriptErrorKind::TriggerTooLong)
+        );
+        // Turning it on again does not restart its time
+        assert_eq!(
+            parse("T0 TRIGGER ON 0\nT5000 TRIGGER ON 0\nT10001 LIGHT"),
+            error(3, ScriptErrorKind::TriggerTooLong)
+        );
+        // A channel left on counts up to the last step
+        assert_eq!(
+            parse("T0 TRIGGER ON 0\nT20000 LIGHT"),
+            error(2, ScriptErrorKind::TriggerTooLong)
+        );
+        assert!(parse("T0 TRIGGER ON 0\nT5000 TRIGGER OFF 0\nT20000 LIGHT").is_ok());
+    }
+
+    #[test]
+    fn stored_form_round_trips() {
+        let sequence = parse(
+            "T-1000 SERVO MANUAL\nT0 SERVO fuel MOVE 450 90\nT0 TRIGGER ON 1 3\n\
+             T10 LIGHT YELLOW BUZZER\nT5000 TRIGGER OFF\nT30000 END",
+        )
+        .unwrap();
+        let mut encoded = [0; ENCODED_MAX_LEN];
+        let len = sequence.encode(&mut encoded);
+        assert_eq!(len, 2 + 6 * STEP_LEN);
+        assert_eq!(Sequence::decode(&encoded[..len], &limits()), Some(sequence));
+        assert_eq!(Sequence::decode(&encoded[..len - 1], &limits()), None);
+
+        encoded[0] = ENCODED_VERSION - 1;
+        assert_eq!(Sequence::decode(&encoded[..len], &limits()), None);
+    }
+
+    #[test]
+    fn stored_form_is_validated_again() {
+        let sequence = parse("T0 SERVO oxidiser OPEN\nT0 TRIGGER ON 6").unwrap();
+        let mut encoded = [0; ENCODED_MAX_LEN];
+        let len = sequence.encode(&mut encoded);
+
+        let mut fewer = limits();
+        fewer.servos[1] = None;
+        assert_eq!(Sequence::decode(&encoded[..len], &fewer), None);
+        let mut fewer = limits();
+        fewer.trigger_channels = 0x3F;
+        assert_eq!(Sequence::decode(&encoded[..len], &fewer), None);
+
+        // A stored TRIGGER ON moved before T-0
+        let step = 2 + STEP_LEN;
+        encoded[step..step + 4].copy_from_slice(&(-1i32).to_le_bytes());
+        assert_eq!(Sequence::decode(&encoded[..len], &limits()), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Armed key switch: a contact on `D2` and, with `ARMED_SECOND_CONTACT`, a
+//! second one on `Motor1`, each debounced. The stand counts as armed only
+//! while every fitted contact is closed, so one stuck contact cannot arm it;
+//! contacts that disagree for longer than `ARMED_DISAGREE_MS` are reported.
+
+use core::future::pending;
+
+use embassy_futures::select::{select3, Either3};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::Input;
+use mainboard::debounce::{DebouncedInput, Edge};
+
+use crate::config::{ARMED_DEBOUNCE_MS, ARMED_DISAGREE_MS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Contact {
+    Primary,
+    Secondary,
+}
+
+impl Contact {
+    /// The pin it is wired to.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Primary => "D2",
+            Self::Secondary => "Motor1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ArmedEvent {
+    Edge(Contact, Edge),
+    /// The contacts have disagreed for `ARMED_DISAGREE_MS`
+    Disagreement,
+}
+
+pub struct ArmedSwitch {
+    primary: DebouncedInput<'static>,
+    secondary: Option<DebouncedInput<'static>>,
+    /// Since when the contacts disagree, until reported
+    disagree_since: Option<Instant>,
+}
+
+impl ArmedSwitch {
+    pub fn new(primary: Input<'static>, secondary: Option<Input<'static>>) -> Self {
+        let stable = Duration::from_millis(ARMED_DEBOUNCE_MS);
+        let mut switch = Self {
+            primary: DebouncedInput::new(primary, stable),
+            secondary: secondary.map(|pin| DebouncedInput::new(pin, stable)),
+            disagree_since: None,
+        };
+        switch.track_agreement();
+        switch
+    }
+
+    /// Whether every fitted contact is closed.
+    pub fn armed(&self) -> bool {
+        self.primary.level() && self.secondary.as_ref().is_none_or(DebouncedInput::level)
+    }
+
+    pub fn agree(&self) -> bool {
+        self.secondary
+            .as_ref()
+            .is_none_or(|secondary| secondary.level() == self.primary.level())
+    }
+
+    /// The debounced input of `contact`, `None` if it is not fitted.
+    pub fn contact(&self, contact: Contact) -> Option<&DebouncedInput<'static>> {
+        match contact {
+            Contact::Primary => Some(&self.primary),
+            Contact::Secondary => self.secondary.as_ref(),
+        }
+    }
+
+    /// Wait for a debounced change of either contact or for a disagreement
+    /// to last too long. Cancel safe.
+    pub async fn wait(&mut self) -> ArmedEvent {
+        let deadline = self.disagree_since.map_or(Instant::MAX, |since| {
+            since + Duration::from_millis(ARMED_DISAGREE_MS)
+        });
+        let secondary = &mut self.secondary;
+        let secondary_edge = async move {
+            match secondary {
+                Some(input) => input.wait_for_edge().await,
+                None => pending().await,
+            }
+        };
+        let event = select3(
+            self.primary.wait_for_edge(),
+            secondary_edge,
+            Timer::at(deadline),
+        );
+        let event = match event.await {
+            Either3::First(edge) => ArmedEvent::Edge(Contact::Primary, edge),
+            Either3::Second(edge) => ArmedEvent::Edge(Contact::Secondary, edge),
+            Either3::Third(()) => {
+                self.disagree_since = None;
+                return ArmedEvent::Disagreement;
+            }
+        };
+        self.track_agreement();
+        event
+    }
+
+    /// Start timing a disagreement; one already reported is not timed again
+    /// until the contacts agree, which the next edge of either makes them do.
+    fn track_agreement(&mut self) {
+        if self.agree() {
+            self.disagree_since = None;
+        } else if self.disagree_since.is_none() {
+            self.disagree_since = Some(Instant::now());
+        }
+    }
+}
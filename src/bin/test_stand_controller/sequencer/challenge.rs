@@ -0,0 +1,286 @@
+//! Two-step arming: FIRE must quote the nonce of a recent ARM_REQUEST and,
+//! with a pre-shared key, its HMAC.
+
+use sha2::{Digest, Sha256};
+
+use crate::mqtt::commands::state::{FireToken, MAC_LEN};
+
+const HMAC_BLOCK_LEN: usize = 64;
+
+/// Why FIRE does not answer the challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ChallengeError {
+    NoChallenge,
+    MissingNonce,
+    Expired,
+    WrongNonce,
+    MissingMac,
+    BadMac,
+}
+
+impl ChallengeError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NoChallenge => "no ARM_REQUEST",
+            Self::MissingNonce => "nonce missing",
+            Self::Expired => "nonce expired",
+            Self::WrongNonce => "wrong nonce",
+            Self::MissingMac => "HMAC missing",
+            Self::BadMac => "HMAC mismatch",
+        }
+    }
+}
+
+/// The nonce handed out by the last ARM_REQUEST.
+///
+/// A nonce answers one FIRE at most: it is used up by the first attempt,
+/// right or wrong, so a guess or a replay needs a new ARM_REQUEST.
+/// Timestamps are milliseconds and may wrap.
+#[derive(Debug, Clone, Copy)]
+pub struct Challenge<'a> {
+    /// Pre-shared key; FIRE needs a HMAC when set
+    key: Option<&'a [u8]>,
+    validity_ms: u32,
+    /// Nonce and when it was issued
+    pending: Option<(u64, u32)>,
+}
+
+impl<'a> Challenge<'a> {
+    pub const fn new(key: Option<&'a [u8]>, validity_ms: u32) -> Self {
+        Self {
+            key,
+            validity_ms,
+            pending: None,
+        }
+    }
+
+    pub fn issue(&mut self, nonce: u64, now_ms: u32) {
+        self.pending = Some((nonce, now_ms));
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    pub fn verify(&mut self, token: Option<FireToken>, now_ms: u32) -> Result<(), ChallengeError> {
+        let (nonce, issued_ms) = self.pending.take().ok_or(ChallengeError::NoChallenge)?;
+        let token = token.ok_or(ChallengeError::MissingNonce)?;
+        if now_ms.wrapping_sub(issued_ms) > self.validity_ms {
+            return Err(ChallengeError::Expired);
+        }
+        if token.nonce != nonce {
+            return Err(ChallengeError::WrongNonce);
+        }
+        let Some(key) = self.key else {
+            return Ok(());
+        };
+        let mac = token.mac.ok_or(ChallengeError::MissingMac)?;
+        if !constant_time_eq(&mac, &fire_mac(key, nonce)) {
+            return Err(ChallengeError::BadMac);
+        }
+        Ok(())
+    }
+}
+
+/// HMAC-SHA256 under `key` of the FIRE payload without its HMAC,
+/// `FIRE <nonce as 16 lower-case hex digits>`.
+pub fn fire_mac(key: &[u8], nonce: u64) -> [u8; MAC_LEN] {
+    let mut message = *b"FIRE 0000000000000000";
+    for (index, digit) in message[5..].iter_mut().enumerate() {
+        let nibble = (nonce >> (60 - 4 * index)) & 0xF;
+        *digit = b"0123456789abcdef"[nibble as usize];
+    }
+    hmac_sha256(key, &message)
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; MAC_LEN] {
+    let mut block = [0u8; HMAC_BLOCK_LEN];
+    if key.len() > HMAC_BLOCK_LEN {
+        block[..MAC_LEN].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5C));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn constant_time_eq(a: &[u8; MAC_LEN], b: &[u8; MAC_LEN]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mainboard::ota::parse_sha256_hex;
+
+    const KEY: &[u8] = b"stand-secret";
+    const NONCE: u64 = 0x0123_4567_89ab_cdef;
+    const VALIDITY_MS: u32 = 10_000;
+
+    fn hex(digest: &str) -> [u8; MAC_LEN] {
+        parse_sha256_hex(digest.as_bytes()).unwrap()
+    }
+
+    fn token(nonce: u64, mac: Option<[u8; MAC_LEN]>) -> Option<FireToken> {
+        Some(FireToken { nonce, mac })
+    }
+
+    fn issued(key: Option<&[u8]>, now_ms: u32) -> Challenge<'_> {
+        let mut challenge = Challenge::new(key, VALIDITY_MS);
+        challenge.issue(NONCE, now_ms);
+        challenge
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        let long_key = [0xAA; 131];
+        let vectors: [(&[u8], &[u8], &str); 6] = [
+            (
+                &[0x0B; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xAA; 20],
+                &[0xDD; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                &[
+                    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22,
+                    23, 24, 25,
+                ],
+                &[0xCD; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            // Keys longer than a block are hashed first
+            (
+                &long_key,
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &long_key,
+                b"This is a test using a larger than block-size key and a larger than block-size \
+                  data. The key needs to be hashed before being used by the HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for (key, message, digest) in vectors {
+            assert_eq!(hmac_sha256(key, message), hex(digest));
+        }
+    }
+
+    #[test]
+    fn fire_mac_matches_the_fire_script() {
+        // printf '%s' 'FIRE 0123456789abcdef' | openssl dgst -sha256 -hmac stand-secret
+        assert_eq!(
+            fire_mac(KEY, NONCE),
+            hex("13c8f9c403e2dbeedbe3aeeb9047f1a6cbd99d4af08794d62204feaf3d9eeb33")
+        );
+    }
+
+    #[test]
+    fn accepts_the_nonce_without_a_key() {
+        let mut challenge = issued(None, 1000);
+        let result = challenge.verify(token(NONCE, None), 1000 + VALIDITY_MS);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn accepts_the_mac_with_a_key() {
+        let mut challenge = issued(Some(KEY), 1000);
+        let mac = fire_mac(KEY, NONCE);
+        assert_eq!(challenge.verify(token(NONCE, Some(mac)), 2000), Ok(()));
+    }
+
+    #[test]
+    fn needs_an_arm_request() {
+        let mut challenge = Challenge::new(None, VALIDITY_MS);
+        let result = challenge.verify(token(NONCE, None), 0);
+        assert_eq!(result, Err(ChallengeError::NoChallenge));
+
+        let mut challenge = issued(None, 0);
+        challenge.cancel();
+        let result = challenge.verify(token(NONCE, None), 0);
+        assert_eq!(result, Err(ChallengeError::NoChallenge));
+    }
+
+    #[test]
+    fn rejects_missing_and_wrong_nonces() {
+        let mut challenge = issued(None, 0);
+        assert_eq!(challenge.verify(None, 0), Err(ChallengeError::MissingNonce));
+
+        let mut challenge = issued(None, 0);
+        let result = challenge.verify(token(NONCE + 1, None), 0);
+        assert_eq!(result, Err(ChallengeError::WrongNonce));
+    }
+
+    #[test]
+    fn nonce_expires() {
+        let mut challenge = issued(None, 1000);
+        let result = challenge.verify(token(NONCE, None), 1001 + VALIDITY_MS);
+        assert_eq!(result, Err(ChallengeError::Expired));
+    }
+
+    #[test]
+    fn validity_across_timestamp_wrap() {
+        let mut challenge = issued(None, u32::MAX - 100);
+        assert_eq!(challenge.verify(token(NONCE, None), 5000), Ok(()));
+
+        let mut challenge = issued(None, u32::MAX - 100);
+        let result = challenge.verify(token(NONCE, None), VALIDITY_MS);
+        assert_eq!(result, Err(ChallengeError::Expired));
+    }
+
+    #[test]
+    fn nonce_answers_one_attempt() {
+        // Used up by a success: a replay needs a new ARM_REQUEST
+        let mut challenge = issued(None, 0);
+        assert_eq!(challenge.verify(token(NONCE, None), 0), Ok(()));
+        let result = challenge.verify(token(NONCE, None), 0);
+        assert_eq!(result, Err(ChallengeError::NoChallenge));
+
+        // And by a failure, so a guess cannot be retried
+        let mut challenge = issued(None, 0);
+        let result = challenge.verify(token(NONCE + 1, None), 0);
+        assert_eq!(result, Err(ChallengeError::WrongNonce));
+        let result = challenge.verify(token(NONCE, None), 0);
+        assert_eq!(result, Err(ChallengeError::NoChallenge));
+    }
+
+    #[test]
+    fn rejects_missing_and_bad_macs() {
+        let mut challenge = issued(Some(KEY), 0);
+        let result = challenge.verify(token(NONCE, None), 0);
+        assert_eq!(result, Err(ChallengeError::MissingMac));
+
+        let mut challenge = issued(Some(KEY), 0);
+        let mut mac = fire_mac(KEY, NONCE);
+        mac[MAC_LEN - 1] ^= 1;
+        let result = challenge.verify(token(NONCE, Some(mac)), 0);
+        assert_eq!(result, Err(ChallengeError::BadMac));
+
+        // The MAC of another key or nonce
+        let mut challenge = issued(Some(KEY), 0);
+        let mac = fire_mac(b"other", NONCE);
+        let result = challenge.verify(token(NONCE, Some(mac)), 0);
+        assert_eq!(result, Err(ChallengeError::BadMac));
+
+        let mut challenge = issued(Some(KEY), 0);
+        let mac = fire_mac(KEY, NONCE + 1);
+        let result = challenge.verify(token(NONCE, Some(mac)), 0);
+        assert_eq!(result, Err(ChallengeError::BadMac));
+    }
+}
@@ -0,0 +1,338 @@
+//! Transition table of the stand state. The sequencer drives the light,
+//! trigger and servo from the transitions it returns.
+
+use crate::mqtt::sensors::status::StateStatus;
+
+/// Something that may move the stand to another state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Input {
+    /// Start a countdown of that many seconds; 0 fires at once
+    Fire(u16),
+    Hold,
+    Resume,
+    Abort,
+    FireEnd,
+    FireReset,
+    /// One second of the countdown elapsed
+    Tick,
+    /// The safety switch changed, `true` when armed
+    Switch(bool),
+    SequenceComplete,
+    /// Interlock trip or lost operator link
+    Fault,
+}
+
+/// Why a command does not apply in the current state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Rejection {
+    NotArmed,
+    NotSafetyArmed,
+    NotCountdown,
+    /// The last countdown tick may already be on its way to T-0
+    FinalSecond,
+    NotHold,
+    NotActive,
+    NotFire,
+    NotFinished,
+}
+
+impl Rejection {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NotArmed => "not in ARMED state",
+            Self::NotSafetyArmed => "safety switch not armed",
+            Self::NotCountdown => "not in COUNTDOWN state",
+            Self::FinalSecond => "less than a second to T-0",
+            Self::NotHold => "not in HOLD state",
+            Self::NotActive => "not in COUNTDOWN, HOLD or FIRE state",
+            Self::NotFire => "not in FIRE state",
+            Self::NotFinished => "not in POSTFIRE or ABORT state",
+        }
+    }
+}
+
+/// The stand state and the safety switch it depends on.
+///
+/// SAFE and ARMED follow the switch. FIRE starts a countdown that HOLD
+/// pauses before T-1 and RESUME continues; at its end the stand fires.
+/// ABORT, a fault or disarming the switch during COUNTDOWN, HOLD or FIRE
+/// aborts. FIRE_END and a finished sequence end FIRE; FIRE_RESET leaves
+/// POSTFIRE and ABORT.
+#[derive(Debug, Clone, Copy)]
+pub struct Machine {
+    state: StateStatus,
+    switch_armed: bool,
+}
+
+impl Machine {
+    pub const fn new(switch_armed: bool) -> Self {
+        Self {
+            state: if switch_armed {
+                StateStatus::Armed
+            } else {
+                StateStatus::Safe
+            },
+            switch_armed,
+        }
+    }
+
+    pub const fn state(&self) -> StateStatus {
+        self.state
+    }
+
+    /// Apply `input`; returns the new state, or `None` if it stays.
+    /// Commands that do not apply are rejected, other inputs are ignored.
+    pub fn handle(&mut self, input: Input) -> Result<Option<StateStatus>, Rejection> {
+        let next = self.next(input)?;
+        if next == self.state {
+            return Ok(None);
+        }
+        self.state = next;
+        Ok(Some(next))
+    }
+
+    fn next(&mut self, input: Input) -> Result<StateStatus, Rejection> {
+        use StateStatus::*;
+
+        let state = self.state;
+        let next = match (state, input) {
+            (Armed, Input::Fire(0)) => Fire,
+            (Armed, Input::Fire(countdown_s)) => Countdown(countdown_s),
+            (Safe, Input::Fire(_)) => return Err(Rejection::NotSafetyArmed),
+            (_, Input::Fire(_)) => return Err(Rejection::NotArmed),
+
+            (Countdown(remaining_s), Input::Hold) if remaining_s > 1 => Hold(remaining_s),
+            (Countdown(_), Input::Hold) => return Err(Rejection::FinalSecond),
+            (_, Input::Hold) => return Err(Rejection::NotCountdown),
+
+            (Hold(remaining_s), Input::Resume) => Countdown(remaining_s),
+            (_, Input::Resume) => return Err(Rejection::NotHold),
+
+            (Countdown(_) | Hold(_) | Fire, Input::Abort) => Abort,
+            (_, Input::Abort) => return Err(Rejection::NotActive),
+
+            (Fire, Input::FireEnd) => PostFire,
+            (_, Input::FireEnd) => return Err(Rejection::NotFire),
+
+            (PostFire | Abort, Input::FireReset) if self.switch_armed => Armed,
+            (PostFire | Abort, Input::FireReset) => Safe,
+            (_, Input::FireReset) => return Err(Rejection::NotFinished),
+
+            (Countdown(remaining_s), Input::Tick) if remaining_s > 1 => Countdown(remaining_s - 1),
+            (Countdown(_), Input::Tick) => Fire,
+            // A tick already on its way when HOLD arrived still counts,
+            // but only RESUME leaves HOLD
+            (Hold(remaining_s), Input::Tick) => Hold(remaining_s.saturating_sub(1).max(1)),
+
+            (_, Input::Switch(armed)) => {
+                self.switch_armed = armed;
+                match (state, armed) {
+                    (Safe, true) => Armed,
+                    (Armed, false) => Safe,
+                    (Countdown(_) | Hold(_) | Fire, false) => Abort,
+                    _ => state,
+                }
+            }
+
+            (Fire, Input::SequenceComplete) => PostFire,
+
+            (Countdown(_) | Hold(_) | Fire, Input::Fault) => Abort,
+
+            _ => state,
+        };
+        Ok(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StateStatus::*;
+
+    fn armed() -> Machine {
+        Machine::new(true)
+    }
+
+    /// Apply `inputs` in turn, each of which must be accepted.
+    fn run(machine: &mut Machine, inputs: &[Input]) -> StateStatus {
+        for &input in inputs {
+            machine.handle(input).unwrap();
+        }
+        machine.state()
+    }
+
+    #[test]
+    fn switch_arms_and_disarms() {
+        let mut machine = Machine::new(false);
+        assert_eq!(machine.state(), Safe);
+        assert_eq!(machine.handle(Input::Switch(true)), Ok(Some(Armed)));
+        assert_eq!(machine.handle(Input::Switch(true)), Ok(None));
+        assert_eq!(machine.handle(Input::Switch(false)), Ok(Some(Safe)));
+    }
+
+    #[test]
+    fn fire_needs_armed() {
+        assert_eq!(
+            Machine::new(false).handle(Input::Fire(10)),
+            Err(Rejection::NotSafetyArmed)
+        );
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(10)]);
+        assert_eq!(machine.handle(Input::Fire(10)), Err(Rejection::NotArmed));
+    }
+
+    #[test]
+    fn countdown_ticks_into_fire() {
+        let mut machine = armed();
+        assert_eq!(machine.handle(Input::Fire(3)), Ok(Some(Countdown(3))));
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Countdown(2))));
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Countdown(1))));
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Fire)));
+        assert_eq!(machine.handle(Input::Tick), Ok(None));
+    }
+
+    #[test]
+    fn fire_without_countdown() {
+        assert_eq!(armed().handle(Input::Fire(0)), Ok(Some(Fire)));
+    }
+
+    #[test]
+    fn hold_pauses_countdown() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(5), Input::Tick]);
+        assert_eq!(machine.handle(Input::Hold), Ok(Some(Hold(4))));
+        assert_eq!(machine.handle(Input::Hold), Err(Rejection::NotCountdown));
+        assert_eq!(machine.handle(Input::Resume), Ok(Some(Countdown(4))));
+        assert_eq!(machine.handle(Input::Resume), Err(Rejection::NotHold));
+    }
+
+    #[test]
+    fn tick_in_flight_during_hold_never_fires() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(3), Input::Hold]);
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Hold(2))));
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Hold(1))));
+        assert_eq!(machine.handle(Input::Tick), Ok(None));
+        assert_eq!(machine.state(), Hold(1));
+        assert_eq!(machine.handle(Input::Resume), Ok(Some(Countdown(1))));
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Fire)));
+    }
+
+    #[test]
+    fn no_hold_in_the_final_second() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(2), Input::Tick]);
+        assert_eq!(machine.handle(Input::Hold), Err(Rejection::FinalSecond));
+        assert_eq!(machine.handle(Input::Tick), Ok(Some(Fire)));
+    }
+
+    #[test]
+    fn hold_only_during_countdown() {
+        assert_eq!(armed().handle(Input::Hold), Err(Rejection::NotCountdown));
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(0)]);
+        assert_eq!(machine.handle(Input::Hold), Err(Rejection::NotCountdown));
+    }
+
+    #[test]
+    fn abort_from_every_active_state() {
+        for inputs in [
+            &[Input::Fire(3)][..],
+            &[Input::Fire(3), Input::Hold],
+            &[Input::Fire(0)],
+        ] {
+            let mut machine = armed();
+            run(&mut machine, inputs);
+            assert_eq!(machine.handle(Input::Abort), Ok(Some(Abort)));
+        }
+        assert_eq!(armed().handle(Input::Abort), Err(Rejection::NotActive));
+        assert_eq!(
+            Machine::new(false).handle(Input::Abort),
+            Err(Rejection::NotActive)
+        );
+    }
+
+    #[test]
+    fn fire_ends_in_postfire() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(0)]);
+        assert_eq!(machine.handle(Input::FireEnd), Ok(Some(PostFire)));
+        assert_eq!(machine.handle(Input::FireEnd), Err(Rejection::NotFire));
+
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(0)]);
+        assert_eq!(machine.handle(Input::SequenceComplete), Ok(Some(PostFire)));
+    }
+
+    #[test]
+    fn fire_end_only_during_fire() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(3)]);
+        assert_eq!(machine.handle(Input::FireEnd), Err(Rejection::NotFire));
+        assert_eq!(machine.handle(Input::SequenceComplete), Ok(None));
+    }
+
+    #[test]
+    fn reset_follows_switch() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(0), Input::FireEnd]);
+        assert_eq!(machine.handle(Input::FireReset), Ok(Some(Armed)));
+        assert_eq!(
+            machine.handle(Input::FireReset),
+            Err(Rejection::NotFinished)
+        );
+
+        let mut machine = armed();
+        run(
+            &mut machine,
+            &[Input::Fire(0), Input::Abort, Input::Switch(false)],
+        );
+        assert_eq!(machine.handle(Input::FireReset), Ok(Some(Safe)));
+    }
+
+    #[test]
+    fn disarm_aborts_active_states() {
+        for inputs in [
+            &[Input::Fire(3)][..],
+            &[Input::Fire(3), Input::Hold],
+            &[Input::Fire(0)],
+        ] {
+            let mut machine = armed();
+            run(&mut machine, inputs);
+            assert_eq!(machine.handle(Input::Switch(false)), Ok(Some(Abort)));
+        }
+    }
+
+    #[test]
+    fn switch_does_not_leave_finished_states() {
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(0), Input::FireEnd]);
+        assert_eq!(machine.handle(Input::Switch(false)), Ok(None));
+        assert_eq!(machine.handle(Input::Switch(true)), Ok(None));
+        assert_eq!(machine.state(), PostFire);
+    }
+
+    #[test]
+    fn fault_aborts_active_states() {
+        for inputs in [
+            &[Input::Fire(3)][..],
+            &[Input::Fire(3), Input::Hold],
+            &[Input::Fire(0)],
+        ] {
+            let mut machine = armed();
+            run(&mut machine, inputs);
+            assert_eq!(machine.handle(Input::Fault), Ok(Some(Abort)));
+        }
+    }
+
+    #[test]
+    fn fault_ignored_while_idle() {
+        let mut machine = armed();
+        assert_eq!(machine.handle(Input::Fault), Ok(None));
+        assert_eq!(machine.state(), Armed);
+
+        let mut machine = armed();
+        run(&mut machine, &[Input::Fire(0), Input::Abort]);
+        assert_eq!(machine.handle(Input::Fault), Ok(None));
+    }
+}
@@ -0,0 +1,943 @@
+pub mod armed;
+pub mod challenge;
+pub mod machine;
+
+use alloc::format;
+use alloc::string::String;
+use core::future::Future;
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+use defmt::{info, warn};
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::rng::Rng;
+use mainboard::board::I2cType;
+use mainboard::debounce::Edge;
+use mainboard::fire_trigger::{FireTrigger, FireTriggerError, CHANNELS};
+use mainboard::signal_light::SignalLightConfig;
+use mainboard::watchdog::{self, TaskWatch};
+use mainboard::{net_info, net_warn};
+use rand_core::RngCore as _;
+
+use crate::camera;
+use crate::capture;
+use crate::config::{
+    ARMED_SECOND_CONTACT, ARM_NONCE_VALIDITY_MS, CONTINUITY_CHECK_INTERVAL_MS, FIRE_CHANNEL_MASK,
+    FIRE_CHANNEL_MAX_ON_MS, FIRE_CHANNEL_OFFSETS_MS, FIRE_CONTINUITY_SENSE_MASK, FIRE_COUNTDOWN_S,
+    FIRE_HMAC_KEY, FIRE_RELEASE_RETRY_MS, TASK_DEADLINE_MS, TASK_IDLE_CHECK_IN_MS,
+};
+use crate::heartbeat::{self, monitor::LossReason};
+use crate::interlock::{self, rules::Rule};
+use crate::light::{
+    self,
+    pattern::{Pattern, Priority, Wave},
+};
+use crate::mqtt::commands::state::StateCommand;
+use crate::mqtt::commands::trigger::TriggerCommand;
+use crate::mqtt::queue;
+use crate::mqtt::sensors::capture::CaptureTrigger;
+use crate::mqtt::sensors::digital::{DigitalChannel, DigitalPacket, DIGITAL_UNKNOWN};
+use crate::mqtt::sensors::status::{CommandStatusPacket, StateStatus};
+use crate::ota_bridge;
+use crate::sequence::{
+    self,
+    script::{Action, Sequence, Step},
+};
+use crate::servo;
+use armed::{ArmedEvent, ArmedSwitch, Contact};
+use challenge::Challenge;
+use machine::{Input as MachineInput, Machine, Rejection};
+
+const _: () = assert!(
+    FIRE_CHANNEL_MASK & FIRE_CONTINUITY_SENSE_MASK == 0,
+    "FIRE_CHANNEL_MASK must leave out the continuity sense pin"
+);
+
+enum SequencerMessage {
+    Command(StateCommand),
+    /// One second of the countdown elapsed
+    Tick,
+    Interlock(&'static Rule),
+    OperatorLost(LossReason),
+    /// `cmd/trigger`, carried out in FIRE only
+    Trigger(TriggerCommand),
+    /// The fire trigger outputs are not what was written
+    TriggerFault(String),
+    SequenceComplete,
+}
+
+/// Countdown handed to the fire sequencer
+struct FireStart {
+    /// The sequence to run, `None` for the manual one
+    sequence: Option<Sequence>,
+    countdown_s: u16,
+}
+
+static SEQUENCER_CHANNEL: Channel<CriticalSectionRawMutex, SequencerMessage, 8> = Channel::new();
+
+static FIRE_ACTIVATE: Signal<CriticalSectionRawMutex, FireStart> = Signal::new();
+static FIRE_CANCEL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// `true` on HOLD, `false` on RESUME
+static FIRE_HOLD: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+static TRIGGER_COMMANDS: Channel<CriticalSectionRawMutex, TriggerCommand, 4> = Channel::new();
+/// Set by a `SERVO MANUAL` step until the sequence stops
+static MANUAL_SERVO: AtomicBool = AtomicBool::new(false);
+
+static LAST_ARMED_VALUE: AtomicU8 = AtomicU8::new(0);
+static LAST_ARMED_SECONDARY_VALUE: AtomicU8 = AtomicU8::new(0);
+/// Last igniter continuity reading, as published on the continuity sensor
+static CONTINUITY_VALUE: AtomicU8 = AtomicU8::new(DIGITAL_UNKNOWN);
+/// State tag in the low byte, countdown seconds above it
+static CURRENT_STATE: AtomicU32 = AtomicU32::new(0);
+
+pub fn send_state_command(command: StateCommand) {
+    let msg = SequencerMessage::Command(command);
+    if SEQUENCER_CHANNEL.try_send(msg).is_err() {
+        warn!("Sequencer command channel full, dropping command");
+    }
+}
+
+pub fn send_trigger_command(command: TriggerCommand) {
+    let msg = SequencerMessage::Trigger(command);
+    if SEQUENCER_CHANNEL.try_send(msg).is_err() {
+        warn!("Sequencer command channel full, dropping trigger command");
+    }
+}
+
+/// Abort the countdown or FIRE because `rule` is violated; ignored in other
+/// states.
+pub fn report_interlock(rule: &'static Rule) {
+    let msg = SequencerMessage::Interlock(rule);
+    if SEQUENCER_CHANNEL.try_send(msg).is_err() {
+        warn!("Sequencer channel full, dropping interlock report");
+    }
+}
+
+/// Abort the countdown or FIRE, or close the servo in ARMED, because the
+/// operator link is lost.
+pub fn report_operator_lost(reason: LossReason) {
+    let msg = SequencerMessage::OperatorLost(reason);
+    if SEQUENCER_CHANNEL.try_send(msg).is_err() {
+        warn!("Sequencer channel full, dropping operator loss report");
+    }
+}
+
+pub fn init_armed_state(switch: &ArmedSwitch) {
+    for contact in [Contact::Primary, Contact::Secondary] {
+        if let Some(input) = switch.contact(contact) {
+            let value = input.level() as u8;
+            last_armed_value(contact).store(value, Ordering::Relaxed);
+            info!("Armed switch {} initial state: {}", contact.as_str(), value);
+        }
+    }
+}
+
+/// Whether the running sequence lets `cmd/servo/<name>` move the servos.
+pub fn manual_servo_allowed() -> bool {
+    MANUAL_SERVO.load(Ordering::Relaxed)
+}
+
+pub fn load_state() -> StateStatus {
+    let value = CURRENT_STATE.load(Ordering::Relaxed);
+    let seconds = (value >> 8) as u16;
+    match value & 0xFF {
+        1 => StateStatus::Armed,
+        2 => StateStatus::Countdown(seconds),
+        3 => StateStatus::Hold(seconds),
+        4 => StateStatus::Fire,
+        5 => StateStatus::PostFire,
+        6 => StateStatus::Abort,
+        _ => StateStatus::Safe,
+    }
+}
+
+pub fn republish_sequencer_state() {
+    let status = load_state();
+    let _ = queue::publish_state_status(status);
+}
+
+pub fn republish_armed_state() {
+    let value = LAST_ARMED_VALUE.load(Ordering::Relaxed);
+    let packet = DigitalPacket::new(DigitalChannel::Armed, timestamp_ms(), value);
+    let _ = crate::mqtt::publish_digital_sensor(packet);
+    if ARMED_SECOND_CONTACT {
+        let value = LAST_ARMED_SECONDARY_VALUE.load(Ordering::Relaxed);
+        let packet = DigitalPacket::new(DigitalChannel::ArmedSecondary, timestamp_ms(), value);
+        let _ = crate::mqtt::publish_digital_sensor(packet);
+    }
+    if FIRE_CONTINUITY_SENSE_MASK != 0 {
+        let value = CONTINUITY_VALUE.load(Ordering::Relaxed);
+        let packet = DigitalPacket::new(DigitalChannel::Continuity, timestamp_ms(), value);
+        let _ = crate::mqtt::publish_digital_sensor(packet);
+    }
+}
+
+fn store_state(status: StateStatus) {
+    let v = match status {
+        StateStatus::Safe => 0,
+        StateStatus::Armed => 1,
+        StateStatus::Countdown(seconds) => 2 | ((seconds as u32) << 8),
+        StateStatus::Hold(seconds) => 3 | ((seconds as u32) << 8),
+        StateStatus::Fire => 4,
+        StateStatus::PostFire => 5,
+        StateStatus::Abort => 6,
+    };
+    CURRENT_STATE.store(v, Ordering::Relaxed);
+}
+
+fn timestamp_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+fn last_armed_value(contact: Contact) -> &'static AtomicU8 {
+    match contact {
+        Contact::Primary => &LAST_ARMED_VALUE,
+        Contact::Secondary => &LAST_ARMED_SECONDARY_VALUE,
+    }
+}
+
+/// Publish the debounced `edge` of `contact`, stamped when the contact
+/// settled.
+fn publish_armed_change(switch: &ArmedSwitch, contact: Contact, edge: Edge) {
+    let value = edge.level as u8;
+    last_armed_value(contact).store(value, Ordering::Relaxed);
+    let channel = match contact {
+        Contact::Primary => DigitalChannel::Armed,
+        Contact::Secondary => DigitalChannel::ArmedSecondary,
+    };
+    let packet = DigitalPacket::new(channel, edge.timestamp_ms as u32, value);
+
+    let chatter = switch.contact(contact).map_or(0, |input| input.chatter());
+    net_info!(
+        "Armed switch {}: {} ({} bounces since boot)",
+        contact.as_str(),
+        value,
+        chatter
+    );
+    if crate::mqtt::publish_digital_sensor(packet).is_err() {
+        warn!("Dropping armed packet: outbound queue full");
+    }
+}
+
+fn report_armed_disagreement(switch: &ArmedSwitch) {
+    let level = |contact| {
+        switch
+            .contact(contact)
+            .map_or(0, |input| input.level() as u8)
+    };
+    let message = format!(
+        "Armed contacts disagree: {} {}, {} {}",
+        Contact::Primary.as_str(),
+        level(Contact::Primary),
+        Contact::Secondary.as_str(),
+        level(Contact::Secondary)
+    );
+    net_warn!("{}", message);
+    queue::publish_command_log(&message);
+}
+
+/// Whether the igniter continuity allows FIRE, or why not.
+fn continuity_status() -> Result<(), &'static str> {
+    if FIRE_CONTINUITY_SENSE_MASK == 0 {
+        return Ok(());
+    }
+    match CONTINUITY_VALUE.load(Ordering::Relaxed) {
+        1 => Ok(()),
+        0 => Err("igniter continuity open"),
+        _ => Err("igniter continuity unknown"),
+    }
+}
+
+fn publish_countdown(status: &str) {
+    let Ok(packet) = CommandStatusPacket::from_str(status) else {
+        return;
+    };
+    if queue::publish_countdown_status(packet).is_err() {
+        warn!("Countdown status not queued: outbound queue full");
+    }
+}
+
+/// Played at [`Priority::Alert`] for ABORT, at [`Priority::State`] otherwise.
+fn state_pattern(state: StateStatus) -> Pattern {
+    let off = Pattern::steady(SignalLightConfig::default());
+    match state {
+        StateStatus::Safe => Pattern {
+            green: Wave::On,
+            ..off
+        },
+        StateStatus::Armed => Pattern {
+            yellow: Wave::On,
+            ..off
+        },
+        // Amber blink and a buzzer chirp every second
+        StateStatus::Countdown(_) => Pattern {
+            red: Wave::On,
+            yellow: Wave::Blink {
+                period_ms: 1000,
+                on_ms: 500,
+            },
+            buzzer: Wave::Blink {
+                period_ms: 1000,
+                on_ms: 50,
+            },
+            ..off
+        },
+        StateStatus::Hold(_) => Pattern {
+            red: Wave::On,
+            yellow: Wave::Blink {
+                period_ms: 2000,
+                on_ms: 1000,
+            },
+            ..off
+        },
+        StateStatus::Fire => Pattern {
+            red: Wave::On,
+            ..off
+        },
+        StateStatus::PostFire => Pattern {
+            green: Wave::On,
+            red: Wave::On,
+            ..off
+        },
+        StateStatus::Abort => Pattern {
+            red: Wave::Blink {
+                period_ms: 250,
+                on_ms: 125,
+            },
+            buzzer: Wave::Beeps {
+                count: 3,
+                on_ms: 100,
+                gap_ms: 100,
+                period_ms: 2000,
+            },
+            ..off
+        },
+    }
+}
+
+/// Show `state` on the signal light.
+fn show_state(state: StateStatus) {
+    if state == StateStatus::Abort {
+        light::play(Priority::Alert, state_pattern(state));
+        return;
+    }
+    light::stop(Priority::Alert);
+    light::play(Priority::State, state_pattern(state));
+    // Sequence light steps last from the countdown to the end of FIRE
+    if !matches!(state, StateStatus::Countdown(_) | StateStatus::Fire) {
+        light::stop(Priority::Sequence);
+    }
+}
+
+/// Seconds FIRE counts down: the configured countdown, or longer when the
+/// loaded sequence starts earlier.
+fn countdown_for(sequence: Option<&Sequence>) -> u16 {
+    sequence.map_or(FIRE_COUNTDOWN_S, |sequence| {
+        sequence.countdown_s(FIRE_COUNTDOWN_S)
+    })
+}
+
+/// `at_ms` relative to `ignition`
+fn instant_at(ignition: Instant, at_ms: i32) -> Instant {
+    let offset = Duration::from_millis(at_ms.unsigned_abs() as u64);
+    if at_ms < 0 {
+        ignition.checked_sub(offset).unwrap_or(Instant::MIN)
+    } else {
+        ignition + offset
+    }
+}
+
+// ============================================================================
+// FIRE SEQUENCER
+// ============================================================================
+
+/// T-0 of a running countdown. Sends the countdown ticks to the state
+/// sequencer and moves T-0 later by the time spent in HOLD.
+struct FireClock {
+    ignition: Instant,
+    /// Seconds to T-0, 0 once it passed
+    remaining_s: u16,
+}
+
+impl FireClock {
+    fn start(countdown_s: u16) -> Self {
+        Self {
+            ignition: Instant::now() + Duration::from_secs(countdown_s as u64),
+            remaining_s: countdown_s,
+        }
+    }
+
+    /// Wait until `at_ms` relative to T-0; returns false if cancelled.
+    async fn wait_until(&mut self, at_ms: i32, watch: &TaskWatch) -> bool {
+        let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+        loop {
+            let due = instant_at(self.ignition, at_ms);
+            let tick = (self.remaining_s > 0)
+                .then(|| instant_at(self.ignition, -1000 * (self.remaining_s as i32 - 1)))
+                .filter(|tick| *tick <= due);
+
+            let event = select3(
+                Timer::at(tick.unwrap_or(due)),
+                FIRE_CANCEL.wait(),
+                FIRE_HOLD.wait(),
+            );
+            match watch.idle(idle_period, event).await {
+                Either3::First(()) if tick.is_some() => {
+                    self.remaining_s -= 1;
+                    // A lost tick would stall the countdown, so wait for room
+                    SEQUENCER_CHANNEL.send(SequencerMessage::Tick).await;
+                }
+                Either3::First(()) => return true,
+                Either3::Second(()) => return false,
+                // Past T-0 a late HOLD no longer applies
+                Either3::Third(true) if self.remaining_s > 0 => {
+                    if !self.hold(watch).await {
+                        return false;
+                    }
+                }
+                Either3::Third(_) => {}
+            }
+        }
+    }
+
+    /// Wait for RESUME; returns false if cancelled instead.
+    async fn hold(&mut self, watch: &TaskWatch) -> bool {
+        let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+        let left = self.ignition.saturating_duration_since(Instant::now());
+        loop {
+            let event = select(FIRE_CANCEL.wait(), FIRE_HOLD.wait());
+            match watch.idle(idle_period, event).await {
+                Either::First(()) => return false,
+                Either::Second(false) => {
+                    self.ignition = Instant::now() + left;
+                    return true;
+                }
+                Either::Second(true) => {}
+            }
+        }
+    }
+}
+
+fn send_sequencer_message(msg: SequencerMessage) {
+    if SEQUENCER_CHANNEL.try_send(msg).is_err() {
+        warn!("Sequencer channel full, dropping sequence event");
+    }
+}
+
+/// Read the igniter continuity and publish it when it changed.
+fn update_continuity(trigger: &mut FireTrigger<I2cType>) {
+    let value = match trigger.continuity() {
+        Ok(Some(closed)) => closed as u8,
+        Ok(None) => return,
+        Err(_e) => DIGITAL_UNKNOWN,
+    };
+    if CONTINUITY_VALUE.swap(value, Ordering::Relaxed) == value {
+        return;
+    }
+
+    match value {
+        1 => net_info!("Igniter continuity: closed"),
+        0 => net_warn!("Igniter continuity: open"),
+        _ => net_warn!("Igniter continuity: sense unreadable"),
+    }
+    let packet = DigitalPacket::new(DigitalChannel::Continuity, timestamp_ms(), value);
+    if crate::mqtt::publish_digital_sensor(packet).is_err() {
+        warn!("Dropping continuity packet: outbound queue full");
+    }
+}
+
+/// Abort the countdown or FIRE because the trigger failed to `action`.
+fn report_trigger_fault<E>(action: &str, error: FireTriggerError<E>) {
+    let detail = match error {
+        FireTriggerError::Bus(_) => String::from("I2C error"),
+        FireTriggerError::Readback { expected, actual } => {
+            format!("read back {:#04x}, expected {:#04x}", actual, expected)
+        }
+        FireTriggerError::UnknownChannel(mask) => format!("channels {:#04x} not wired", mask),
+    };
+    net_warn!("Failed to {} fire trigger: {}", action, detail);
+    send_sequencer_message(SequencerMessage::TriggerFault(detail));
+}
+
+fn publish_trigger_channels(trigger: &FireTrigger<I2cType>) {
+    let message = format!("Trigger channels on: {:#04x}", trigger.on_mask());
+    net_info!("{}", message);
+    queue::publish_command_log(&message);
+}
+
+fn fire_channels(trigger: &mut FireTrigger<I2cType>, mask: u8) {
+    if let Err(error) = trigger.fire(mask) {
+        report_trigger_fault("activate", error);
+    }
+}
+
+/// Release every channel, retrying until the outputs read back off. Does not
+/// check in meanwhile: if the release never goes through, the watchdog
+/// resets the board, whose start writes every channel off again.
+async fn release_all(trigger: &mut FireTrigger<I2cType>) {
+    let retry_period = Duration::from_millis(FIRE_RELEASE_RETRY_MS);
+    let mut reported = false;
+    while let Err(error) = trigger.abort() {
+        if !core::mem::replace(&mut reported, true) {
+            report_trigger_fault("release", error);
+        }
+        Timer::after(retry_period).await;
+    }
+    if reported {
+        net_info!("Fire trigger released after retrying");
+    }
+}
+
+/// Release the channels that reached `FIRE_CHANNEL_MAX_ON_MS` or the end of
+/// their pulse; returns whether the release went through.
+fn expire_channels(trigger: &mut FireTrigger<I2cType>) -> bool {
+    match trigger.expire(Instant::now()) {
+        Ok(0) => {}
+        Ok(mask) => {
+            let message = format!("Trigger channels {:#04x} released: on time over", mask);
+            net_info!("{}", message);
+            queue::publish_command_log(&message);
+        }
+        Err(error) => {
+            report_trigger_fault("release", error);
+            return false;
+        }
+    }
+    true
+}
+
+fn execute_trigger_command(command: TriggerCommand, trigger: &mut FireTrigger<I2cType>) {
+    let result = match command {
+        TriggerCommand::Fire(mask) => trigger.fire(mask),
+        TriggerCommand::Pulse { mask, duration_ms } => {
+            trigger.pulse(mask, Duration::from_millis(duration_ms as u64))
+        }
+        TriggerCommand::Release(mask) => trigger.release(mask),
+        TriggerCommand::ReleaseAll => trigger.abort(),
+    };
+    match result {
+        Ok(()) => publish_trigger_channels(trigger),
+        Err(error) => report_trigger_fault("switch", error),
+    }
+}
+
+/// Run `fut` while releasing channels on time and carrying out `cmd/trigger`
+/// commands. A release that fails is retried every `FIRE_RELEASE_RETRY_MS`.
+async fn drive<F: Future>(trigger: &mut FireTrigger<I2cType>, fut: F) -> F::Output {
+    let retry_period = Duration::from_millis(FIRE_RELEASE_RETRY_MS);
+    let mut fut = pin!(fut);
+    let mut retry_at = Instant::MIN;
+    loop {
+        let release_at = trigger
+            .next_release()
+            .map_or(Instant::MAX, |at| at.max(retry_at));
+        let event = select3(
+            fut.as_mut(),
+            Timer::at(release_at),
+            TRIGGER_COMMANDS.receive(),
+        );
+        match event.await {
+            Either3::First(output) => return output,
+            Either3::Second(()) => {
+                if !expire_channels(trigger) {
+                    retry_at = Instant::now() + retry_period;
+                }
+            }
+            Either3::Third(command) => execute_trigger_command(command, trigger),
+        }
+    }
+}
+
+fn execute_step(step: &Step, trigger: &mut FireTrigger<I2cType>, captured: &mut bool) {
+    match step.action {
+        Action::Servo { servo, command } => servo::send_servo_command(servo as usize, command),
+        Action::ServoManual => MANUAL_SERVO.store(true, Ordering::Relaxed),
+        Action::Trigger { mask, on: true } => {
+            fire_channels(trigger, mask);
+            if !core::mem::replace(captured, true) {
+                capture::trigger_capture(CaptureTrigger::Fire);
+            }
+        }
+        Action::Trigger { mask, on: false } => {
+            if let Err(error) = trigger.release(mask) {
+                report_trigger_fault("release", error);
+            }
+        }
+        Action::Camera => camera::shoot(),
+        Action::Light(config) => light::play(Priority::Sequence, Pattern::steady(config)),
+        Action::End => {}
+    }
+}
+
+/// The action as written in the definition, with the servo named.
+fn describe(action: Action) -> String {
+    match action {
+        Action::Servo { servo, .. } => {
+            let (keyword, rest) = action.as_str().split_once(' ').unwrap_or(("SERVO", ""));
+            format!("{} {} {}", keyword, servo::name(servo as usize), rest)
+        }
+        _ => action.as_str().into(),
+    }
+}
+
+/// Run the steps of `sequence` on `clock`, and the countdown to T-0 if they
+/// end before it; returns whether it ran to the end rather than being
+/// cancelled.
+async fn run_sequence(
+    sequence: &Sequence,
+    clock: &mut FireClock,
+    trigger: &mut FireTrigger<I2cType>,
+    watch: &TaskWatch,
+) -> bool {
+    let steps = sequence.steps();
+    let mut captured = false;
+    net_info!(
+        "Sequence started: {} steps, T{:+} to T{:+} ms",
+        steps.len(),
+        sequence.first_ms(),
+        sequence.last_ms()
+    );
+
+    for (index, step) in steps.iter().enumerate() {
+        if !drive(trigger, clock.wait_until(step.at_ms, watch)).await {
+            net_warn!("Sequence stopped before step {}", index + 1);
+            return false;
+        }
+
+        execute_step(step, trigger, &mut captured);
+        let message = format!(
+            "Step {} T{:+} {} at {} ms",
+            index + 1,
+            step.at_ms,
+            describe(step.action),
+            timestamp_ms()
+        );
+        net_info!("Sequence: {}", message);
+        queue::publish_command_log(&message);
+    }
+    drive(trigger, clock.wait_until(0, watch)).await
+}
+
+/// The earliest of `FIRE_CHANNEL_OFFSETS_MS` among the channels in
+/// `pending`, and the channels due then.
+fn next_manual_channels(pending: u8) -> Option<(u32, u8)> {
+    let channels = (0..CHANNELS).filter(|&channel| pending & (1 << channel) != 0);
+    let at_ms = channels
+        .clone()
+        .filter_map(|channel| FIRE_CHANNEL_OFFSETS_MS[channel])
+        .min()?;
+    let mask = channels
+        .filter(|&channel| FIRE_CHANNEL_OFFSETS_MS[channel] == Some(at_ms))
+        .fold(0, |mask, channel| mask | (1 << channel));
+    Some((at_ms, mask))
+}
+
+/// FIRE without a sequence: turn the channels on at their offsets from T-0
+/// and keep them on until FIRE_END, an abort or their time is over.
+async fn run_manual(clock: &mut FireClock, trigger: &mut FireTrigger<I2cType>, watch: &TaskWatch) {
+    let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+    let mut pending = FIRE_CHANNEL_MASK;
+
+    while let Some((at_ms, mask)) = next_manual_channels(pending) {
+        let at_ms = at_ms.min(i32::MAX as u32) as i32;
+        if !drive(trigger, clock.wait_until(at_ms, watch)).await {
+            return;
+        }
+        fire_channels(trigger, mask);
+        publish_trigger_channels(trigger);
+        pending &= !mask;
+    }
+
+    drive(trigger, watch.idle(idle_period, FIRE_CANCEL.wait())).await;
+}
+
+#[embassy_executor::task]
+pub async fn fire_sequencer_task(fire_trigger_i2c: I2cType) {
+    let address = pcf857x::SlaveAddr::Alternative(false, false, false);
+    let mut trigger = match FireTrigger::new(
+        fire_trigger_i2c,
+        address,
+        FIRE_CHANNEL_MASK,
+        FIRE_CONTINUITY_SENSE_MASK,
+        Duration::from_millis(FIRE_CHANNEL_MAX_ON_MS as u64),
+    ) {
+        Ok(t) => t,
+        Err(_e) => {
+            net_warn!("Failed to initialize fire trigger");
+            return;
+        }
+    };
+    let watch = watchdog::register("fire_sequencer", Duration::from_millis(TASK_DEADLINE_MS));
+    let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+
+    let continuity_period = Duration::from_millis(CONTINUITY_CHECK_INTERVAL_MS);
+
+    loop {
+        update_continuity(&mut trigger);
+        let event = select3(
+            FIRE_ACTIVATE.wait(),
+            Timer::after(continuity_period),
+            TRIGGER_COMMANDS.receive(),
+        );
+        let start = match watch.idle(idle_period, event).await {
+            Either3::First(start) => start,
+            Either3::Second(()) => continue,
+            Either3::Third(_) => {
+                warn!("Trigger command dropped: not firing");
+                continue;
+            }
+        };
+        let mut clock = FireClock::start(start.countdown_s);
+
+        match start.sequence {
+            Some(sequence) => {
+                let completed = run_sequence(&sequence, &mut clock, &mut trigger, &watch).await;
+                MANUAL_SERVO.store(false, Ordering::Relaxed);
+                release_all(&mut trigger).await;
+                if completed {
+                    SEQUENCER_CHANNEL
+                        .send(SequencerMessage::SequenceComplete)
+                        .await;
+                }
+            }
+            None => {
+                run_manual(&mut clock, &mut trigger, &watch).await;
+                release_all(&mut trigger).await;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// STATE SEQUENCER
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn state_sequencer_task(mut armed_switch: ArmedSwitch, mut rng: Rng) {
+    let mut machine = Machine::new(armed_switch.armed());
+    let mut challenge = Challenge::new(FIRE_HMAC_KEY.map(str::as_bytes), ARM_NONCE_VALIDITY_MS);
+    let state = machine.state();
+    store_state(state);
+    show_state(state);
+    info!("State sequencer initialized: {}", state.as_str());
+
+    let watch = watchdog::register("state_sequencer", Duration::from_millis(TASK_DEADLINE_MS));
+    let idle_period = Duration::from_millis(TASK_IDLE_CHECK_IN_MS);
+
+    loop {
+        let event = select(SEQUENCER_CHANNEL.receive(), armed_switch.wait());
+        match watch.idle(idle_period, event).await {
+            Either::First(msg) => match msg {
+                SequencerMessage::Command(cmd) => {
+                    handle_command(cmd, &mut machine, &mut challenge, &mut rng);
+                }
+                SequencerMessage::Tick => {
+                    on_event(&mut machine, MachineInput::Tick, "");
+                }
+                SequencerMessage::Interlock(rule) => {
+                    let reason = format!("interlock {}", rule.name);
+                    on_event(&mut machine, MachineInput::Fault, &reason);
+                }
+                SequencerMessage::OperatorLost(reason) => {
+                    if machine.state() == StateStatus::Armed {
+                        net_warn!("Operator link lost in ARMED, closing servos");
+                        servo::close_all();
+                    }
+                    let reason = format!("operator {}", reason.as_str());
+                    on_event(&mut machine, MachineInput::Fault, &reason);
+                }
+                SequencerMessage::Trigger(command) => {
+                    forward_trigger_command(command, machine.state());
+                }
+                SequencerMessage::TriggerFault(detail) => {
+                    let reason = format!("fire trigger {}", detail);
+                    on_event(&mut machine, MachineInput::Fault, &reason);
+                }
+                SequencerMessage::SequenceComplete => {
+                    on_event(&mut machine, MachineInput::SequenceComplete, "");
+                }
+            },
+            Either::Second(ArmedEvent::Edge(contact, edge)) => {
+                publish_armed_change(&armed_switch, contact, edge);
+                let input = MachineInput::Switch(armed_switch.armed());
+                on_event(&mut machine, input, "safety switch disarmed");
+            }
+            Either::Second(ArmedEvent::Disagreement) => report_armed_disagreement(&armed_switch),
+        }
+
+        // A nonce only answers FIRE from the ARMED state it was issued in
+        if machine.state() != StateStatus::Armed {
+            challenge.cancel();
+        }
+    }
+}
+
+/// Feed `input` to the state machine and carry out the transition; `reason`
+/// is reported if it aborts. Rejections are left to the caller.
+fn apply(
+    machine: &mut Machine,
+    input: MachineInput,
+    reason: &str,
+) -> Result<Option<StateStatus>, Rejection> {
+    let previous = machine.state();
+    let next = machine.handle(input)?;
+    if let Some(state) = next {
+        enter_state(previous, state, reason);
+    }
+    Ok(next)
+}
+
+/// [`apply`] for inputs other than commands, which are never rejected.
+fn on_event(machine: &mut Machine, input: MachineInput, reason: &str) {
+    let _ = apply(machine, input, reason);
+}
+
+fn enter_state(previous: StateStatus, state: StateStatus, reason: &str) {
+    store_state(state);
+    if core::mem::discriminant(&previous) != core::mem::discriminant(&state) {
+        let _ = queue::publish_state_status(state);
+        queue::publish_command_log(state.as_log());
+        net_info!("State: {}", state.as_str());
+        show_state(state);
+    }
+
+    if previous == StateStatus::Armed {
+        start_fire(state);
+    }
+
+    match state {
+        StateStatus::Countdown(seconds) => {
+            if matches!(previous, StateStatus::Hold(_)) {
+                FIRE_HOLD.signal(false);
+            }
+            publish_countdown(&format!("T-{}", seconds));
+        }
+        StateStatus::Hold(seconds) => {
+            FIRE_HOLD.signal(true);
+            publish_countdown(&format!("HOLD T-{}", seconds));
+        }
+        StateStatus::Fire => {
+            // A sequence captures at its first TRIGGER ON
+            if sequence::loaded().is_none() {
+                capture::trigger_capture(CaptureTrigger::Fire);
+            }
+            camera::start_fire_interval();
+            publish_countdown("T-0");
+        }
+        StateStatus::Abort => {
+            FIRE_CANCEL.signal(());
+            camera::end_interval();
+            servo::close_all();
+            net_warn!("ABORT: {}", reason);
+            queue::publish_command_log(&format!("ABORT: {}", reason));
+        }
+        StateStatus::PostFire => {
+            FIRE_CANCEL.signal(());
+            camera::end_interval();
+        }
+        StateStatus::Safe | StateStatus::Armed => {}
+    }
+}
+
+/// Hand the countdown that `state` starts to the fire sequencer.
+fn start_fire(state: StateStatus) {
+    let countdown_s = match state {
+        StateStatus::Countdown(seconds) => seconds,
+        StateStatus::Fire => 0,
+        _ => return,
+    };
+    // An update must not reboot the board mid-burn
+    ota_bridge::abort_session();
+    // A sequence has its own camera steps
+    let sequence = sequence::loaded();
+    if sequence.is_none() {
+        camera::shoot();
+    }
+    FIRE_CANCEL.reset();
+    FIRE_HOLD.reset();
+    FIRE_ACTIVATE.signal(FireStart {
+        sequence,
+        countdown_s,
+    });
+}
+
+fn reject(command: &str, reason: &str) {
+    let message = format!("{} rejected: {}", command, reason);
+    net_warn!("{}", message);
+    queue::publish_command_log(&message);
+}
+
+/// Hand `command` to the fire sequencer, which is running in FIRE.
+fn forward_trigger_command(command: TriggerCommand, state: StateStatus) {
+    if state != StateStatus::Fire {
+        reject("TRIGGER", Rejection::NotFire.as_str());
+    } else if command.mask() & !FIRE_CHANNEL_MASK != 0 {
+        reject("TRIGGER", "channel not wired");
+    } else if TRIGGER_COMMANDS.try_send(command).is_err() {
+        reject("TRIGGER", "busy");
+    }
+}
+
+fn handle_command(
+    command: StateCommand,
+    machine: &mut Machine,
+    challenge: &mut Challenge,
+    rng: &mut Rng,
+) {
+    let input = match command {
+        StateCommand::ArmRequest => {
+            if machine.state() != StateStatus::Armed {
+                reject(command.as_str(), Rejection::NotArmed.as_str());
+                return;
+            }
+            let nonce = rng.next_u64();
+            challenge.issue(nonce, timestamp_ms());
+            net_info!("ARM_REQUEST: nonce issued");
+            queue::publish_command_log(&format!(
+                "ARM_NONCE {:016x} valid {} ms",
+                nonce, ARM_NONCE_VALIDITY_MS
+            ));
+            return;
+        }
+        StateCommand::Fire(token) => {
+            if machine.state() == StateStatus::Armed {
+                if let Err(error) = challenge.verify(token, timestamp_ms()) {
+                    reject(command.as_str(), error.as_str());
+                    return;
+                }
+                if let Err(reason) = heartbeat::status() {
+                    reject(command.as_str(), &format!("operator {}", reason.as_str()));
+                    return;
+                }
+                if let Some(rule) = interlock::active_violation() {
+                    reject(command.as_str(), &format!("interlock {}", rule.name));
+                    return;
+                }
+                if let Err(reason) = continuity_status() {
+                    reject(command.as_str(), reason);
+                    return;
+                }
+                if ota_bridge::finishing() {
+                    reject(command.as_str(), "OTA update finishing");
+                    return;
+                }
+            }
+            MachineInput::Fire(countdown_for(sequence::loaded().as_ref()))
+        }
+        StateCommand::Hold => MachineInput::Hold,
+        StateCommand::Resume => MachineInput::Resume,
+        StateCommand::Abort => MachineInput::Abort,
+        StateCommand::FireEnd => MachineInput::FireEnd,
+        StateCommand::FireReset => MachineInput::FireReset,
+    };
+
+    match apply(machine, input, "command") {
+        Ok(Some(StateStatus::PostFire)) => camera::shoot(),
+        Ok(_) => {}
+        Err(rejection) => reject(command.as_str(), rejection.as_str()),
+    }
+}
@@ -0,0 +1,203 @@
+//! Servo calibration: the pulse widths of the range ends, the open and closed
+//! positions and the full-range time, their stored form and the full-range
+//! measurement.
+//!
+//! Positions are tenths of a degree within 0-1800, pulse widths MCPWM ticks.
+
+use crate::mqtt::commands::calibrate::ServoPoint;
+use crate::mqtt::commands::servo::SERVO_MAX_POSITION;
+
+/// Longest stored form: version, name length, a name of up to 32 bytes,
+/// then 12 bytes of calibration.
+pub const STORED_MAX_LEN: usize = 2 + MAX_NAME_LEN + 12;
+
+const STORED_VERSION: u8 = 1;
+const MAX_NAME_LEN: usize = 32;
+/// Narrowest pulse range between the ends.
+const MIN_SPAN_TICKS: u16 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ServoCalibration {
+    /// Pulse width of position 0
+    pub min_pulse_ticks: u16,
+    /// Pulse width of position 1800
+    pub max_pulse_ticks: u16,
+    pub open_degrees: u16,
+    pub closed_degrees: u16,
+    /// Time for the full 0-1800 travel
+    pub full_range_ms: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CalibrationError {
+    /// OPEN or CLOSED outside the pulse range
+    OutOfRange,
+    /// MIN not at least `MIN_SPAN_TICKS` below MAX
+    TooNarrow,
+}
+
+impl CalibrationError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::OutOfRange => "outside MIN to MAX",
+            Self::TooNarrow => "MIN too close to MAX",
+        }
+    }
+}
+
+impl ServoCalibration {
+    /// Full rate, the one that covers the range in `full_range_ms`.
+    pub const fn max_rate(&self) -> u16 {
+        let full_range_ms = if self.full_range_ms == 0 {
+            1
+        } else {
+            self.full_range_ms
+        };
+        (SERVO_MAX_POSITION as u32 * 1000 / full_range_ms) as u16
+    }
+
+    pub fn degrees_to_ticks(&self, degrees: u16) -> u16 {
+        let range = self.max_pulse_ticks - self.min_pulse_ticks;
+        self.min_pulse_ticks + ((degrees as u32 * range as u32) / SERVO_MAX_POSITION as u32) as u16
+    }
+
+    /// Nearest position to the pulse width `ticks`, clamped to 0-1800.
+    pub fn ticks_to_degrees(&self, ticks: u16) -> u16 {
+        let range = (self.max_pulse_ticks - self.min_pulse_ticks) as u32;
+        let offset = ticks.clamp(self.min_pulse_ticks, self.max_pulse_ticks) - self.min_pulse_ticks;
+        ((offset as u32 * SERVO_MAX_POSITION as u32 + range / 2) / range) as u16
+    }
+
+    /// Record the pulse width `ticks` as `point`. Moving an end keeps the open
+    /// and closed pulse widths, clamped into the new range.
+    pub fn set(&mut self, point: ServoPoint, ticks: u16) -> Result<(), CalibrationError> {
+        match point {
+            ServoPoint::Open | ServoPoint::Closed => {
+                if ticks < self.min_pulse_ticks || ticks > self.max_pulse_ticks {
+                    return Err(CalibrationError::OutOfRange);
+                }
+                let degrees = self.ticks_to_degrees(ticks);
+                match point {
+                    ServoPoint::Open => self.open_degrees = degrees,
+                    _ => self.closed_degrees = degrees,
+                }
+            }
+            ServoPoint::Min | ServoPoint::Max => {
+                let (min, max) = match point {
+                    ServoPoint::Min => (ticks, self.max_pulse_ticks),
+                    _ => (self.min_pulse_ticks, ticks),
+                };
+                if min.saturating_add(MIN_SPAN_TICKS) > max {
+                    return Err(CalibrationError::TooNarrow);
+                }
+                let open_ticks = self.degrees_to_ticks(self.open_degrees);
+                let closed_ticks = self.degrees_to_ticks(self.closed_degrees);
+                self.min_pulse_ticks = min;
+                self.max_pulse_ticks = max;
+                self.open_degrees = self.ticks_to_degrees(open_ticks);
+                self.closed_degrees = self.ticks_to_degrees(closed_ticks);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stored form, tagged with the servo `name` so a reordered table does
+    /// not pick up another servo's calibration.
+    pub fn encode(&self, name: &str, out: &mut [u8; STORED_MAX_LEN]) -> usize {
+        let name = &name.as_bytes()[..name.len().min(MAX_NAME_LEN)];
+        out[0] = STORED_VERSION;
+        out[1] = name.len() as u8;
+        out[2..2 + name.len()].copy_from_slice(name);
+        let fields = &mut out[2 + name.len()..];
+        fields[0..2].copy_from_slice(&self.min_pulse_ticks.to_le_bytes());
+        fields[2..4].copy_from_slice(&self.max_pulse_ticks.to_le_bytes());
+        fields[4..6].copy_from_slice(&self.open_degrees.to_le_bytes());
+        fields[6..8].copy_from_slice(&self.closed_degrees.to_le_bytes());
+        fields[8..12].copy_from_slice(&self.full_range_ms.to_le_bytes());
+        2 + name.len() + 12
+    }
+
+    /// The stored form of servo `name`, `None` if it is another servo's or
+    /// invalid.
+    pub fn decode(name: &str, bytes: &[u8]) -> Option<Self> {
+        let name = &name.as_bytes()[..name.len().min(MAX_NAME_LEN)];
+        let (&version, rest) = bytes.split_first()?;
+        let (&name_len, rest) = rest.split_first()?;
+        if version != STORED_VERSION || rest.len() != name_len as usize + 12 {
+            return None;
+        }
+        let (stored_name, fields) = rest.split_at(name_len as usize);
+        if stored_name != name {
+            return None;
+        }
+
+        let u16_at = |at: usize| u16::from_le_bytes([fields[at], fields[at + 1]]);
+        let calibration = Self {
+            min_pulse_ticks: u16_at(0),
+            max_pulse_ticks: u16_at(2),
+            open_degrees: u16_at(4),
+            closed_degrees: u16_at(6),
+            full_range_ms: u32::from_le_bytes(fields[8..12].try_into().ok()?),
+        };
+        let valid = calibration.min_pulse_ticks.saturating_add(MIN_SPAN_TICKS)
+            <= calibration.max_pulse_ticks
+            && calibration.open_degrees <= SERVO_MAX_POSITION
+            && calibration.closed_degrees <= SERVO_MAX_POSITION
+            && calibration.full_range_ms > 0;
+        valid.then_some(calibration)
+    }
+}
+
+/// What a [`Measurement`] needs next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Progress {
+    /// Hold the pulse at this position
+    Drive(u16),
+    /// The full-range time
+    Done(u32),
+    /// A leg did not arrive within the timeout
+    Failed,
+}
+
+/// Full-range timing: drive to 0, then time the jump to 1800 until the
+/// feedback is within the tolerance. Timestamps are milliseconds and may
+/// wrap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Measurement {
+    to_full: bool,
+    /// Start of the current leg
+    since_ms: u32,
+}
+
+impl Measurement {
+    pub const fn new(now_ms: u32) -> Self {
+        Self {
+            to_full: false,
+            since_ms: now_ms,
+        }
+    }
+
+    /// Advance with `position`, the feedback position if it has one.
+    pub fn update(
+        &mut self,
+        now_ms: u32,
+        position: Option<u16>,
+        tolerance: u16,
+        timeout_ms: u32,
+    ) -> Progress {
+        let target = if self.to_full { SERVO_MAX_POSITION } else { 0 };
+        let elapsed_ms = now_ms.wrapping_sub(self.since_ms);
+        if position.is_some_and(|position| position.abs_diff(target) <= tolerance) {
+            if self.to_full {
+                return Progress::Done(elapsed_ms.max(1));
+            }
+            self.to_full = true;
+            self.since_ms = now_ms;
+            return Progress::Drive(SERVO_MAX_POSITION);
+        }
+        if elapsed_ms > timeout_ms {
+            return Progress::Failed;
+        }
+        Progress::Drive(target)
+    }
+}
@@ -0,0 +1,66 @@
+//! The servo table entries of `config.rs`.
+
+use crate::mqtt::commands::servo::{ServoCommand, SERVO_MAX_POSITION};
+
+use super::calibration::ServoCalibration;
+use super::feedback::ServoFeedback;
+use super::profile::Profile;
+
+/// Pins a servo can be wired to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ServoPin {
+    D1,
+    Motor0,
+    Motor1,
+}
+
+/// A servo of the `SERVOS` table. Positions are tenths of a degree within
+/// 0-1800.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ServoConfig {
+    /// Names its topics: `cmd/servo/<name>`, `status/servo/<name>` and
+    /// `sensor/servo/<name>`
+    pub name: &'static str,
+    pub pin: ServoPin,
+    /// Until `cmd/calibrate/servo/<name>` stores another
+    pub calibration: ServoCalibration,
+    /// Travel limits; commanded positions are clamped into them
+    pub min_degrees: u16,
+    pub max_degrees: u16,
+    /// Acceleration at the start and end of a move, tenths of a degree per
+    /// second squared
+    pub accel_degrees_per_s2: u32,
+    /// Position potentiometer, `None` to trust the timing of the move
+    pub feedback: Option<ServoFeedback>,
+}
+
+impl ServoConfig {
+    /// Time a full-range move takes at full rate.
+    pub fn full_travel_ms(&self, calibration: &ServoCalibration) -> u32 {
+        Profile::new(
+            0,
+            SERVO_MAX_POSITION,
+            calibration.max_rate(),
+            self.accel_degrees_per_s2,
+        )
+        .duration_ms()
+    }
+
+    /// The move `command` asks for from `from`, within the travel limits.
+    pub fn profile(
+        &self,
+        calibration: &ServoCalibration,
+        command: ServoCommand,
+        from: u16,
+    ) -> Profile {
+        let (position, rate) = match command {
+            ServoCommand::Open => (calibration.open_degrees, None),
+            ServoCommand::Close => (calibration.closed_degrees, None),
+            ServoCommand::Move { position, rate } => (position, rate),
+        };
+        let target = position.clamp(self.min_degrees, self.max_degrees);
+        let max_rate = calibration.max_rate();
+        let rate = rate.map_or(max_rate, |rate| rate.min(max_rate));
+        Profile::new(from, target, rate, self.accel_degrees_per_s2)
+    }
+}
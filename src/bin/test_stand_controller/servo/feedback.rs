@@ -0,0 +1,110 @@
+//! Closed-loop check of a servo against a position potentiometer.
+//!
+//! Positions are tenths of a degree, readings millivolts at the ADC input.
+
+use mainboard::analog::AnalogChannel;
+
+use crate::mqtt::commands::servo::SERVO_MAX_POSITION;
+
+/// Potentiometer on the servo shaft, wired to an analog input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ServoFeedback {
+    pub channel: AnalogChannel,
+    /// Input at position 0 and at 1800; either may be the higher
+    pub zero_mv: u16,
+    pub full_mv: u16,
+    /// Largest distance from the commanded position still counted as there;
+    /// readings this far outside 0-1800 mean a broken wire
+    pub tolerance: u16,
+    /// How long the servo may take to arrive after the end of a move, stay
+    /// off its position or go without a reading
+    pub timeout_ms: u32,
+}
+
+impl ServoFeedback {
+    /// Position the reading `mv` stands for, `None` outside the range.
+    pub fn position(&self, mv: u16) -> Option<u16> {
+        let span = self.full_mv as i32 - self.zero_mv as i32;
+        if span == 0 {
+            return None;
+        }
+        let position = (mv as i32 - self.zero_mv as i32) * SERVO_MAX_POSITION as i32 / span;
+        let margin = self.tolerance as i32;
+        if position < -margin || position > SERVO_MAX_POSITION as i32 + margin {
+            return None;
+        }
+        Some(position.clamp(0, SERVO_MAX_POSITION as i32) as u16)
+    }
+}
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Check {
+    /// Moving, or on its way to the target since less than the timeout
+    Underway,
+    /// Within the tolerance of the target
+    Arrived,
+    /// Off the target for longer than the timeout
+    Stalled,
+    /// No reading for longer than the timeout, or a reading out of range
+    Fault,
+}
+
+/// Tracks one servo against its feedback. Timestamps are milliseconds and may
+/// wrap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Monitor {
+    /// Since when the servo has been away from a target it should be at
+    off_since_ms: Option<u32>,
+    /// First check, the timeout of a servo that never reported
+    first_ms: Option<u32>,
+}
+
+impl Monitor {
+    pub const fn new() -> Self {
+        Self {
+            off_since_ms: None,
+            first_ms: None,
+        }
+    }
+
+    /// Check at `now_ms` against `target`, with `reading` the timestamp and
+    /// value of the latest reading. While `moving` only the reading itself is
+    /// checked.
+    pub fn check(
+        &mut self,
+        feedback: &ServoFeedback,
+        now_ms: u32,
+        target: u16,
+        moving: bool,
+        reading: Option<(u32, u16)>,
+    ) -> Check {
+        let first_ms = *self.first_ms.get_or_insert(now_ms);
+        let last_ms = reading.map_or(first_ms, |(timestamp_ms, _)| timestamp_ms);
+        // A reading may be stamped just after `now_ms`
+        if now_ms.wrapping_sub(last_ms) as i32 > feedback.timeout_ms as i32 {
+            return Check::Fault;
+        }
+        let Some((_, mv)) = reading else {
+            return Check::Underway;
+        };
+        let Some(position) = feedback.position(mv) else {
+            return Check::Fault;
+        };
+
+        if moving {
+            self.off_since_ms = None;
+            return Check::Underway;
+        }
+        if position.abs_diff(target) <= feedback.tolerance {
+            self.off_since_ms = None;
+            return Check::Arrived;
+        }
+        let off_since_ms = *self.off_since_ms.get_or_insert(now_ms);
+        if now_ms.wrapping_sub(off_since_ms) > feedback.timeout_ms {
+            Check::Stalled
+        } else {
+            Check::Underway
+        }
+    }
+}
@@ -0,0 +1,694 @@
+//! Servos on the MCPWM operators, one each, as listed in [`SERVOS`].
+//!
+//! A servo with position feedback only reports arriving once the
+//! potentiometer agrees, and reports `STALLED` or `FAULT` to the interlocks.
+//!
+//! `cmd/calibrate/servo/<name>` jogs a servo by pulse ticks, records the
+//! pulse width as a calibration point and times a full-range move. The
+//! calibration is stored in flash, one key per servo, and reported on the
+//! retained `status/calibration/servo/<name>`.
+
+pub mod calibration;
+pub mod config;
+pub mod feedback;
+pub mod profile;
+
+use alloc::format;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use defmt::{info, warn};
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::{AnyPin, Pin};
+use esp_hal::mcpwm::operator::{PwmPin, PwmPinConfig};
+use esp_hal::mcpwm::timer::PwmWorkingMode;
+use esp_hal::mcpwm::{McPwm, PeripheralClockConfig};
+use esp_hal::peripherals::MCPWM0;
+use esp_hal::time::Rate;
+use mainboard::analog::AnalogBlock;
+use mainboard::board::{D1Pin, Motor0Pin, Motor1Pin};
+use mainboard::storage::{self, StorageError, StorageKey};
+use mainboard::{net_info, net_warn};
+
+use crate::config::{
+    SERVOS, SERVO_JOG_MAX_PULSE_TICKS, SERVO_JOG_MIN_PULSE_TICKS, SERVO_MEASURE_TIMEOUT_MS,
+};
+use crate::interlock;
+use crate::mqtt::commands::calibrate::ServoCalibrateCommand;
+use crate::mqtt::commands::servo::{ServoCommand, MAX_SERVOS, SERVO_MAX_POSITION};
+use crate::mqtt::queue;
+use crate::mqtt::sensors::calibration::ServoCalibrationPacket;
+use crate::mqtt::sensors::slow::ServoSensorPacket;
+use crate::mqtt::sensors::status::ServoStatus;
+use calibration::{Measurement, Progress, ServoCalibration, STORED_MAX_LEN};
+use feedback::{Check, Monitor};
+use profile::Profile;
+
+pub use config::{ServoConfig, ServoPin};
+
+const TICK_INTERVAL_MS: u64 = 20;
+/// Servo `n` stores its calibration under key `2 + n`; 0 and 1 are taken.
+const CALIBRATION_KEY_BASE: u8 = 2;
+
+const _: () = assert!(
+    !SERVOS.is_empty() && SERVOS.len() <= MAX_SERVOS,
+    "SERVOS lists 1 to 3 servos, one per MCPWM operator"
+);
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// The pins [`ServoPin`] names; `motor1` is `None` while it carries the
+/// second armed contact.
+pub struct ServoPins {
+    pub d1: D1Pin,
+    pub motor0: Motor0Pin,
+    pub motor1: Option<Motor1Pin>,
+}
+
+/// The pulse output of one MCPWM operator.
+trait PulseOutput {
+    fn set_pulse(&mut self, ticks: u16);
+}
+
+impl<const OP: u8> PulseOutput for PwmPin<'static, MCPWM0<'static>, OP, true> {
+    fn set_pulse(&mut self, ticks: u16) {
+        self.set_timestamp(ticks);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    command: ServoCommand,
+    profile: Profile,
+    start: Instant,
+}
+
+/// What the task asks of a servo.
+#[derive(Debug, Clone, Copy)]
+enum Request {
+    Command(ServoCommand),
+    Calibrate(ServoCalibrateCommand),
+}
+
+/// A driven servo: where it is and what it is doing.
+struct Drive<'a> {
+    output: &'a mut dyn PulseOutput,
+    /// Commanded position
+    current: u16,
+    /// Pulse width output; off the calibrated range after a jog
+    pulse: u16,
+    motion: Option<Motion>,
+    measurement: Option<Measurement>,
+    monitor: Monitor,
+}
+
+impl<'a> Drive<'a> {
+    fn new(output: &'a mut dyn PulseOutput) -> Self {
+        Self {
+            output,
+            current: 0,
+            pulse: 0,
+            motion: None,
+            measurement: None,
+            monitor: Monitor::new(),
+        }
+    }
+
+    fn idle(&self) -> bool {
+        self.motion.is_none() && self.measurement.is_none()
+    }
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static SERVO_COMMAND_CHANNEL: Channel<CriticalSectionRawMutex, (usize, Request), 8> =
+    Channel::new();
+static CALIBRATIONS: Mutex<CriticalSectionRawMutex, Cell<[ServoCalibration; MAX_SERVOS]>> =
+    Mutex::new(Cell::new(default_calibrations()));
+static CURRENT_SERVO_STATUS: [AtomicU32; MAX_SERVOS] = [const { AtomicU32::new(0) }; MAX_SERVOS];
+static CURRENT_SERVO_TICKS: [AtomicU32; MAX_SERVOS] = [const { AtomicU32::new(0) }; MAX_SERVOS];
+/// Timestamp and millivolts of the latest feedback block mean
+static FEEDBACK_READINGS: Mutex<CriticalSectionRawMutex, Cell<[Option<(u32, u16)>; MAX_SERVOS]>> =
+    Mutex::new(Cell::new([None; MAX_SERVOS]));
+
+/// `servo` is an index into [`SERVOS`].
+pub fn send_servo_command(servo: usize, command: ServoCommand) {
+    if SERVO_COMMAND_CHANNEL
+        .try_send((servo, Request::Command(command)))
+        .is_err()
+    {
+        warn!("Servo command channel full, dropping command");
+    }
+}
+
+pub fn send_calibrate_command(servo: usize, command: ServoCalibrateCommand) {
+    if SERVO_COMMAND_CHANNEL
+        .try_send((servo, Request::Calibrate(command)))
+        .is_err()
+    {
+        warn!("Servo command channel full, dropping calibration command");
+        queue::publish_command_log("Servo calibration rejected: busy");
+    }
+}
+
+/// Close every valve.
+pub fn close_all() {
+    for servo in 0..SERVOS.len() {
+        send_servo_command(servo, ServoCommand::Close);
+    }
+}
+
+/// Index of the servo called `name`.
+pub fn find(name: &str) -> Option<usize> {
+    SERVOS.iter().position(|config| config.name == name)
+}
+
+pub fn name(servo: usize) -> &'static str {
+    SERVOS.get(servo).map_or("", |config| config.name)
+}
+
+/// Active calibration of `servo`, the `SERVOS` default until loaded.
+pub fn calibration(servo: usize) -> ServoCalibration {
+    CALIBRATIONS.lock(|calibrations| calibrations.get()[servo])
+}
+
+/// Take the feedback readings of the servos that have one from `block`.
+pub fn feed_analog(block: &AnalogBlock) {
+    for (servo, config) in SERVOS.iter().enumerate() {
+        let Some(feedback) = config.feedback else {
+            continue;
+        };
+        let Some(samples) = block
+            .millivolts(feedback.channel)
+            .filter(|samples| !samples.is_empty())
+        else {
+            continue;
+        };
+        let mean_mv = samples.iter().map(|&mv| mv as u32).sum::<u32>() / samples.len() as u32;
+        let reading = (block.last_timestamp_ms(feedback.channel), mean_mv as u16);
+        FEEDBACK_READINGS.lock(|readings| {
+            let mut all = readings.get();
+            all[servo] = Some(reading);
+            readings.set(all);
+        });
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+const fn default_calibrations() -> [ServoCalibration; MAX_SERVOS] {
+    let mut calibrations = [SERVOS[0].calibration; MAX_SERVOS];
+    let mut servo = 0;
+    while servo < SERVOS.len() {
+        calibrations[servo] = SERVOS[servo].calibration;
+        servo += 1;
+    }
+    calibrations
+}
+
+fn statuses(calibration: &ServoCalibration, target: u16) -> (ServoStatus, ServoStatus) {
+    if target == calibration.open_degrees {
+        (ServoStatus::Opening, ServoStatus::Open)
+    } else if target == calibration.closed_degrees {
+        (ServoStatus::Closing, ServoStatus::Closed)
+    } else {
+        (ServoStatus::Moving, ServoStatus::Positioned)
+    }
+}
+
+fn publish_servo_status(servo: usize, status: ServoStatus) {
+    let encoded = match status {
+        ServoStatus::Closed => 0,
+        ServoStatus::Opening => 1,
+        ServoStatus::Open => 2,
+        ServoStatus::Closing => 3,
+        ServoStatus::Moving => 4,
+        ServoStatus::Positioned => 5,
+        ServoStatus::Stalled => 6,
+        ServoStatus::Fault => 7,
+    };
+    CURRENT_SERVO_STATUS[servo].store(encoded, Ordering::Relaxed);
+    if queue::publish_servo_status(servo as u8, status).is_err() {
+        warn!("Failed to publish servo status: queue full");
+    }
+}
+
+fn log_servo_status(servo: usize, status: ServoStatus) {
+    queue::publish_command_log(&format!("Servo {} {}", name(servo), status.as_log()));
+}
+
+fn publish_servo_position(servo: usize, ticks: u16) {
+    CURRENT_SERVO_TICKS[servo].store(ticks as u32, Ordering::Relaxed);
+    let timestamp_ms = Instant::now().as_millis() as u32;
+    let packet = ServoSensorPacket::new(servo as u8, timestamp_ms, ticks);
+    if queue::publish_servo_sensor(packet).is_err() {
+        warn!("Failed to publish servo position: queue full");
+    }
+}
+
+pub fn current_servo_status(servo: usize) -> ServoStatus {
+    match CURRENT_SERVO_STATUS[servo].load(Ordering::Relaxed) {
+        1 => ServoStatus::Opening,
+        2 => ServoStatus::Open,
+        3 => ServoStatus::Closing,
+        4 => ServoStatus::Moving,
+        5 => ServoStatus::Positioned,
+        6 => ServoStatus::Stalled,
+        7 => ServoStatus::Fault,
+        _ => ServoStatus::Closed,
+    }
+}
+
+pub fn current_servo_ticks(servo: usize) -> u16 {
+    CURRENT_SERVO_TICKS[servo].load(Ordering::Relaxed) as u16
+}
+
+pub fn republish_servo_state() {
+    for servo in 0..SERVOS.len() {
+        let status = current_servo_status(servo);
+        let _ = queue::publish_servo_status(servo as u8, status);
+        let ticks = current_servo_ticks(servo);
+        let timestamp_ms = Instant::now().as_millis() as u32;
+        let packet = ServoSensorPacket::new(servo as u8, timestamp_ms, ticks);
+        let _ = queue::publish_servo_sensor(packet);
+        publish_calibration(servo);
+    }
+}
+
+fn publish_calibration(servo: usize) {
+    let packet = ServoCalibrationPacket {
+        servo: servo as u8,
+        calibration: calibration(servo),
+    };
+    if queue::publish_servo_calibration(packet).is_err() {
+        warn!("Servo calibration status not queued: outbound queue full");
+    }
+}
+
+const fn calibration_key(servo: usize) -> StorageKey {
+    StorageKey(CALIBRATION_KEY_BASE + servo as u8)
+}
+
+async fn load_calibration(servo: usize) -> ServoCalibration {
+    let default = SERVOS[servo].calibration;
+    let mut bytes = [0u8; STORED_MAX_LEN];
+    match storage::load(calibration_key(servo), &mut bytes).await {
+        Ok(len) => ServoCalibration::decode(name(servo), &bytes[..len]).unwrap_or_else(|| {
+            net_warn!(
+                "Stored calibration of servo {} invalid, using defaults",
+                name(servo)
+            );
+            default
+        }),
+        Err(StorageError::NotFound) => default,
+        Err(e) => {
+            net_warn!(
+                "Failed to load calibration of servo {}: {:?}",
+                name(servo),
+                e
+            );
+            default
+        }
+    }
+}
+
+/// Apply `calibration` to `servo`, report it and store it in flash; `None`
+/// goes back to the `SERVOS` default and drops the stored one.
+async fn update_calibration(servo: usize, calibration: Option<ServoCalibration>) {
+    let applied = calibration.unwrap_or(SERVOS[servo].calibration);
+    CALIBRATIONS.lock(|calibrations| {
+        let mut all = calibrations.get();
+        all[servo] = applied;
+        calibrations.set(all);
+    });
+    publish_calibration(servo);
+
+    let result = match calibration {
+        Some(calibration) => {
+            let mut bytes = [0u8; STORED_MAX_LEN];
+            let len = calibration.encode(name(servo), &mut bytes);
+            storage::store(calibration_key(servo), &bytes[..len]).await
+        }
+        None => match storage::remove(calibration_key(servo)).await {
+            Err(StorageError::NotFound) => Ok(()),
+            result => result,
+        },
+    };
+    match result {
+        Ok(()) => queue::publish_command_log(&format!("Servo {} calibration updated", name(servo))),
+        Err(e) => {
+            net_warn!(
+                "Failed to store calibration of servo {}: {:?}",
+                name(servo),
+                e
+            );
+            queue::publish_command_log("Servo calibration applied, not stored");
+        }
+    }
+}
+
+fn reject_calibration(servo: usize, reason: &str) {
+    net_warn!("Servo {} calibration rejected: {}", name(servo), reason);
+    queue::publish_command_log(&format!(
+        "Servo {} calibration rejected: {}",
+        name(servo),
+        reason
+    ));
+}
+
+/// Output `ticks` to `servo` and report it.
+fn set_pulse(servo: usize, drive: &mut Drive, ticks: u16) {
+    drive.output.set_pulse(ticks);
+    drive.pulse = ticks;
+    publish_servo_position(servo, ticks);
+}
+
+/// Start the move `command` asks of `servo`, from where it is.
+fn start_motion(servo: usize, drive: &mut Drive, command: ServoCommand) {
+    // Repeating the command does not restart the move
+    if drive.motion.is_some_and(|motion| motion.command == command) {
+        return;
+    }
+    if drive.measurement.take().is_some() {
+        queue::publish_command_log(&format!("Servo {} measurement aborted", name(servo)));
+    }
+
+    let config = &SERVOS[servo];
+    let calibration = calibration(servo);
+    let profile = config.profile(&calibration, command, drive.current);
+    let (moving_status, arrived_status) = statuses(&calibration, profile.target());
+    // Off the calibrated range after a jog, the zero-length move brings the
+    // pulse back
+    if profile.target() == drive.current
+        && drive.pulse == calibration.degrees_to_ticks(drive.current)
+    {
+        drive.motion = None;
+        // Stopped here by a command that interrupted a move; feedback
+        // reports the arrival itself
+        if config.feedback.is_none() && current_servo_status(servo) != arrived_status {
+            publish_servo_status(servo, arrived_status);
+            log_servo_status(servo, arrived_status);
+        }
+        return;
+    }
+
+    publish_servo_status(servo, moving_status);
+    log_servo_status(servo, moving_status);
+    drive.motion = Some(Motion {
+        command,
+        profile,
+        start: Instant::now(),
+    });
+}
+
+/// Move `servo` on to where its motion puts it now.
+fn advance(servo: usize, drive: &mut Drive) {
+    let Some(Motion { profile, start, .. }) = drive.motion else {
+        return;
+    };
+    let config = &SERVOS[servo];
+    let calibration = calibration(servo);
+    let elapsed_ms = start.elapsed().as_millis().min(u32::MAX as u64) as u32;
+    drive.current = profile.position(elapsed_ms);
+    set_pulse(servo, drive, calibration.degrees_to_ticks(drive.current));
+
+    if elapsed_ms >= profile.duration_ms() {
+        drive.motion = None;
+        if config.feedback.is_none() {
+            let (_, arrived_status) = statuses(&calibration, profile.target());
+            publish_servo_status(servo, arrived_status);
+            log_servo_status(servo, arrived_status);
+        }
+    }
+}
+
+/// Drive `servo` through its full-range measurement; store the result and
+/// close the servo once it is done.
+async fn measure(servo: usize, drive: &mut Drive) {
+    let (Some(measurement), Some(feedback)) = (drive.measurement.as_mut(), SERVOS[servo].feedback)
+    else {
+        return;
+    };
+    let mut calibration = calibration(servo);
+    let now_ms = Instant::now().as_millis() as u32;
+    let position = FEEDBACK_READINGS
+        .lock(|readings| readings.get()[servo])
+        .and_then(|(_, mv)| feedback.position(mv));
+
+    match measurement.update(
+        now_ms,
+        position,
+        feedback.tolerance,
+        SERVO_MEASURE_TIMEOUT_MS,
+    ) {
+        Progress::Drive(position) => {
+            drive.current = position;
+            let ticks = calibration.degrees_to_ticks(position);
+            if ticks != drive.pulse {
+                set_pulse(servo, drive, ticks);
+            }
+        }
+        Progress::Done(full_range_ms) => {
+            drive.measurement = None;
+            net_info!("Servo {} full range in {} ms", name(servo), full_range_ms);
+            calibration.full_range_ms = full_range_ms;
+            update_calibration(servo, Some(calibration)).await;
+            start_motion(servo, drive, ServoCommand::Close);
+        }
+        Progress::Failed => {
+            drive.measurement = None;
+            reject_calibration(servo, "no arrival within the measurement timeout");
+            start_motion(servo, drive, ServoCommand::Close);
+        }
+    }
+}
+
+async fn calibrate(servo: usize, drive: &mut Drive, command: ServoCalibrateCommand) {
+    let mut calibration = calibration(servo);
+    match command {
+        ServoCalibrateCommand::Jog(delta) => {
+            drive.motion = None;
+            drive.measurement = None;
+            let ticks = (drive.pulse as i32 + delta as i32).clamp(
+                SERVO_JOG_MIN_PULSE_TICKS as i32,
+                SERVO_JOG_MAX_PULSE_TICKS as i32,
+            ) as u16;
+            drive.current = calibration.ticks_to_degrees(ticks);
+            set_pulse(servo, drive, ticks);
+            if SERVOS[servo].feedback.is_none() {
+                let (_, status) = statuses(&calibration, drive.current);
+                if status != current_servo_status(servo) {
+                    publish_servo_status(servo, status);
+                }
+            }
+            queue::publish_command_log(&format!("Servo {} at {} ticks", name(servo), ticks));
+        }
+        ServoCalibrateCommand::Set(point) => {
+            if !drive.idle() {
+                reject_calibration(servo, "servo moving");
+                return;
+            }
+            if let Err(error) = calibration.set(point, drive.pulse) {
+                reject_calibration(servo, error.as_str());
+                return;
+            }
+            net_info!(
+                "Servo {} {} at {} ticks",
+                name(servo),
+                point.as_str(),
+                drive.pulse
+            );
+            drive.current = calibration.ticks_to_degrees(drive.pulse);
+            update_calibration(servo, Some(calibration)).await;
+        }
+        ServoCalibrateCommand::Measure => {
+            if SERVOS[servo].feedback.is_none() {
+                reject_calibration(servo, "no position feedback");
+                return;
+            }
+            drive.motion = None;
+            drive.measurement = Some(Measurement::new(Instant::now().as_millis() as u32));
+            publish_servo_status(servo, ServoStatus::Moving);
+            queue::publish_command_log(&format!("Servo {} measuring full range", name(servo)));
+        }
+        ServoCalibrateCommand::Reset => {
+            if !drive.idle() {
+                reject_calibration(servo, "servo moving");
+                return;
+            }
+            net_info!("Servo {} calibration reset", name(servo));
+            drive.current = SERVOS[servo].calibration.ticks_to_degrees(drive.pulse);
+            update_calibration(servo, None).await;
+        }
+    }
+}
+
+/// Hold `servo` to its feedback, if it has one: report the arrival, a stall
+/// or a fault, and whether it is faulted to the interlocks.
+fn check_feedback(servo: usize, drive: &mut Drive) {
+    let config = &SERVOS[servo];
+    let Some(feedback) = config.feedback else {
+        return;
+    };
+    let target = drive
+        .motion
+        .map_or(drive.current, |motion| motion.profile.target());
+    let now_ms = Instant::now().as_millis() as u32;
+    let reading = FEEDBACK_READINGS.lock(|readings| readings.get()[servo]);
+
+    let check = drive
+        .monitor
+        .check(&feedback, now_ms, target, !drive.idle(), reading);
+    let status = match check {
+        Check::Underway => None,
+        Check::Arrived => Some(statuses(&calibration(servo), target).1),
+        Check::Stalled => Some(ServoStatus::Stalled),
+        Check::Fault => Some(ServoStatus::Fault),
+    };
+    if let Some(status) = status.filter(|&status| status != current_servo_status(servo)) {
+        publish_servo_status(servo, status);
+        log_servo_status(servo, status);
+    }
+
+    let faulted = matches!(
+        current_servo_status(servo),
+        ServoStatus::Stalled | ServoStatus::Fault
+    );
+    interlock::feed_servo(servo, now_ms, faulted);
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+pub async fn servo_controller_task(mcpwm: MCPWM0<'static>, pins: ServoPins) {
+    let clock_cfg = PeripheralClockConfig::with_frequency(Rate::from_mhz(160))
+        .expect("Failed to configure MCPWM clock");
+
+    let mut mcpwm = McPwm::new(mcpwm, clock_cfg);
+    mcpwm.operator0.set_timer(&mcpwm.timer0);
+    mcpwm.operator1.set_timer(&mcpwm.timer0);
+    mcpwm.operator2.set_timer(&mcpwm.timer0);
+
+    let mut free_pins: [Option<AnyPin<'static>>; 3] = [
+        Some(pins.d1.degrade()),
+        Some(pins.motor0.degrade()),
+        pins.motor1.map(|pin| pin.degrade()),
+    ];
+    let mut pin_for = |servo: usize| -> Option<AnyPin<'static>> {
+        let config = SERVOS.get(servo)?;
+        let pin = free_pins[config.pin as usize].take();
+        if pin.is_none() {
+            warn!("Servo {} pin taken, not driven", config.name);
+        }
+        pin
+    };
+    let mut pwm_pin0 = pin_for(0).map(|pin| {
+        mcpwm
+            .operator0
+            .with_pin_a(pin, PwmPinConfig::UP_ACTIVE_HIGH)
+    });
+    let mut pwm_pin1 = pin_for(1).map(|pin| {
+        mcpwm
+            .operator1
+            .with_pin_a(pin, PwmPinConfig::UP_ACTIVE_HIGH)
+    });
+    let mut pwm_pin2 = pin_for(2).map(|pin| {
+        mcpwm
+            .operator2
+            .with_pin_a(pin, PwmPinConfig::UP_ACTIVE_HIGH)
+    });
+    let outputs: [Option<&mut dyn PulseOutput>; MAX_SERVOS] = [
+        pwm_pin0.as_mut().map(|pin| pin as &mut dyn PulseOutput),
+        pwm_pin1.as_mut().map(|pin| pin as &mut dyn PulseOutput),
+        pwm_pin2.as_mut().map(|pin| pin as &mut dyn PulseOutput),
+    ];
+
+    let timer_clock_cfg = clock_cfg
+        .timer_clock_with_frequency(19_999, PwmWorkingMode::Increase, Rate::from_hz(50))
+        .expect("Failed to configure MCPWM timer");
+    mcpwm.timer0.start(timer_clock_cfg);
+
+    for servo in 0..SERVOS.len() {
+        let calibration = load_calibration(servo).await;
+        CALIBRATIONS.lock(|calibrations| {
+            let mut all = calibrations.get();
+            all[servo] = calibration;
+            calibrations.set(all);
+        });
+        publish_calibration(servo);
+    }
+
+    // Boot: drive every servo to its closed position
+    let mut drives: [Option<Drive>; MAX_SERVOS] = outputs.map(|output| output.map(Drive::new));
+    for (servo, drive) in drives.iter_mut().enumerate() {
+        let Some(drive) = drive else {
+            continue;
+        };
+        let calibration = calibration(servo);
+        drive.current = calibration.closed_degrees;
+        set_pulse(servo, drive, calibration.degrees_to_ticks(drive.current));
+        publish_servo_status(servo, ServoStatus::Closed);
+        info!(
+            "Servo {} initialized at closed position ({} ticks)",
+            name(servo),
+            drive.pulse
+        );
+    }
+
+    let tick_interval = Duration::from_millis(TICK_INTERVAL_MS);
+    // Feedback is checked on every tick, moving or not
+    let closed_loop = SERVOS.iter().any(|config| config.feedback.is_some());
+    let mut next_tick = if closed_loop {
+        Instant::now()
+    } else {
+        Instant::MAX
+    };
+
+    loop {
+        match select(SERVO_COMMAND_CHANNEL.receive(), Timer::at(next_tick)).await {
+            Either::First((servo, request)) => {
+                let Some(drive) = drives.get_mut(servo).and_then(Option::as_mut) else {
+                    warn!("Servo command for servo {} dropped: not driven", servo);
+                    continue;
+                };
+                match request {
+                    Request::Command(command) => start_motion(servo, drive, command),
+                    Request::Calibrate(command) => calibrate(servo, drive, command).await,
+                }
+            }
+            Either::Second(()) => {
+                for (servo, drive) in drives.iter_mut().enumerate() {
+                    let Some(drive) = drive else {
+                        continue;
+                    };
+                    if drive.measurement.is_some() {
+                        measure(servo, drive).await;
+                    } else {
+                        advance(servo, drive);
+                    }
+                    check_feedback(servo, drive);
+                }
+                next_tick = (next_tick + tick_interval).max(Instant::now());
+            }
+        }
+
+        // Commands do not hold back the ticks of a move under way
+        if !closed_loop && drives.iter().flatten().all(Drive::idle) {
+            next_tick = Instant::MAX;
+        } else if next_tick == Instant::MAX {
+            next_tick = Instant::now() + tick_interval;
+        }
+    }
+}
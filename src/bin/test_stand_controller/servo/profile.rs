@@ -0,0 +1,134 @@
+//! Trapezoidal servo moves: accelerate, cruise at the rate limit, decelerate
+//! onto the target.
+//!
+//! Positions are tenths of a degree, rates tenths of a degree per second and
+//! accelerations tenths of a degree per second squared.
+
+/// A move between two positions, from rest to rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Profile {
+    from: u16,
+    to: u16,
+    accel: u32,
+    /// Accelerating, and again decelerating
+    ramp_ms: u32,
+    /// Covered by each ramp
+    ramp_distance: u32,
+    /// Between the ramps, at most at the rate limit
+    cruise_ms: u32,
+}
+
+impl Profile {
+    /// `rate` and `accel` are raised to at least 1.
+    pub fn new(from: u16, to: u16, rate: u16, accel: u32) -> Self {
+        let distance = from.abs_diff(to) as u64;
+        let rate = rate.max(1) as u64;
+        let accel = accel.max(1);
+        // A short move turns around before reaching the rate
+        let ramp_ms =
+            (rate * 1000 / accel as u64).min((distance * 1_000_000 / accel as u64).isqrt());
+        let ramp_distance = ramp_distance(accel, ramp_ms);
+        let cruise_ms = ((distance - 2 * ramp_distance) * 1000).div_ceil(rate);
+        Self {
+            from,
+            to,
+            accel,
+            ramp_ms: ramp_ms as u32,
+            ramp_distance: ramp_distance as u32,
+            cruise_ms: cruise_ms as u32,
+        }
+    }
+
+    pub const fn target(&self) -> u16 {
+        self.to
+    }
+
+    pub const fn duration_ms(&self) -> u32 {
+        2 * self.ramp_ms + self.cruise_ms
+    }
+
+    /// Where the servo is `elapsed_ms` into the move.
+    pub fn position(&self, elapsed_ms: u32) -> u16 {
+        let distance = self.from.abs_diff(self.to) as u32;
+        let total_ms = self.duration_ms();
+        let covered = if elapsed_ms >= total_ms {
+            distance
+        } else if elapsed_ms < self.ramp_ms {
+            ramp_distance(self.accel, elapsed_ms as u64) as u32
+        } else if elapsed_ms < self.ramp_ms + self.cruise_ms {
+            let cruise_distance = distance - 2 * self.ramp_distance;
+            let cruised_ms = elapsed_ms - self.ramp_ms;
+            self.ramp_distance
+                + (cruise_distance as u64 * cruised_ms as u64 / self.cruise_ms as u64) as u32
+        } else {
+            distance - ramp_distance(self.accel, (total_ms - elapsed_ms) as u64) as u32
+        };
+        if self.to >= self.from {
+            self.from + covered as u16
+        } else {
+            self.from - covered as u16
+        }
+    }
+}
+
+/// Distance covered in `ms` accelerating from rest.
+fn ramp_distance(accel: u32, ms: u64) -> u64 {
+    accel as u64 * ms * ms / 2_000_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_move_cruises_at_the_rate() {
+        // 90 degrees at 45 deg/s, 90 deg/s2: 0.5 s ramps covering 11.2
+        // degrees each, rounded down, and the rest cruising, rounded up
+        let profile = Profile::new(0, 900, 450, 900);
+        assert_eq!(profile.duration_ms(), 2503);
+        assert_eq!(profile.position(0), 0);
+        assert_eq!(profile.position(500), 112);
+        assert!(profile.position(1251).abs_diff(450) <= 1);
+        assert_eq!(profile.position(2003), 900 - 112);
+        assert_eq!(profile.position(2503), 900);
+        assert_eq!(profile.position(10_000), 900);
+    }
+
+    #[test]
+    fn short_move_turns_around_before_the_rate() {
+        // 10 degrees at 90 deg/s2 only reaches 30 deg/s; the ramps, rounded
+        // down, leave a few ms at the rate limit
+        let profile = Profile::new(0, 100, 900, 900);
+        assert_eq!(profile.duration_ms(), 2 * 333 + 3);
+        assert!(profile.position(333) <= 50);
+        assert_eq!(profile.position(profile.duration_ms()), 100);
+    }
+
+    #[test]
+    fn moves_down() {
+        let profile = Profile::new(900, 0, 450, 900);
+        assert_eq!(profile.target(), 0);
+        assert_eq!(profile.position(500), 900 - 112);
+        assert_eq!(profile.position(2503), 0);
+    }
+
+    #[test]
+    fn position_never_goes_backwards() {
+        let profile = Profile::new(100, 1700, 300, 700);
+        let mut previous = profile.position(0);
+        for elapsed_ms in (0..=profile.duration_ms() + 10).step_by(7) {
+            let position = profile.position(elapsed_ms);
+            assert!(position >= previous, "{elapsed_ms} ms");
+            assert!(position <= 1700);
+            previous = position;
+        }
+        assert_eq!(previous, 1700);
+    }
+
+    #[test]
+    fn no_move() {
+        let profile = Profile::new(500, 500, 0, 0);
+        assert_eq!(profile.duration_ms(), 0);
+        assert_eq!(profile.position(0), 500);
+    }
+}
@@ -1,13 +1,18 @@
-use defmt::{error, info, warn};
+use defmt::{info, warn};
 use embassy_time::{Duration, Instant, Ticker, Timer};
 use esp_hal::peripherals::UART0;
 use esp_hal::uart::Uart;
 
-use crate::config::{TEMP_BATCH_SIZE, TEMP_COLLECTION_INTERVAL_MS, TEMP_UART_BOUDRATE};
+use crate::config::{
+    TASK_DEADLINE_MS, TEMP_BATCH_SIZE, TEMP_COLLECTION_INTERVAL_MS, TEMP_UART_BOUDRATE,
+};
+use crate::interlock;
 use crate::mqtt::publish_temperature_sensor;
 use crate::mqtt::sensors::temp::TempPacket;
 use mainboard::board::{D0Pin, U0RxPin, U0TxPin};
+use mainboard::net_error;
 use mainboard::tmp107::{Tmp107, MAX_SENSORS, ONESHOT_CONVERSION_MS};
+use mainboard::watchdog;
 
 pub struct TemperatureCollectionIo {
     pub uart: UART0<'static>,
@@ -31,10 +36,11 @@ pub async fn temperature_collection_task(io: TemperatureCollectionIo) {
 
     let (rx, tx) = uart.split();
 
+    // The probes are optional: without them the task ends, unsupervised
     let mut driver = match Tmp107::init(tx, rx).await {
         Ok(d) => d,
         Err(e) => {
-            error!("TMP107 init failed: {:?}", e);
+            net_error!("TMP107 init failed, no temperatures: {:?}", e);
             return;
         }
     };
@@ -42,10 +48,11 @@ pub async fn temperature_collection_task(io: TemperatureCollectionIo) {
     let sensor_count = driver.sensor_count() as usize;
 
     if let Err(e) = driver.shutdown().await {
-        error!("TMP107 shutdown failed: {:?}", e);
+        net_error!("TMP107 shutdown failed: {:?}", e);
         return;
     }
 
+    let watch = watchdog::register("temperature", Duration::from_millis(TASK_DEADLINE_MS));
     info!(
         "Temperature collection: {} sensors, {}ms interval, batch {}",
         sensor_count, TEMP_COLLECTION_INTERVAL_MS, TEMP_BATCH_SIZE,
@@ -59,6 +66,7 @@ pub async fn temperature_collection_task(io: TemperatureCollectionIo) {
 
     loop {
         ticker.next().await;
+        watch.check_in();
 
         if let Err(e) = driver.trigger_one_shot().await {
             warn!("TMP107 one-shot trigger failed: {:?}", e);
@@ -87,6 +95,7 @@ pub async fn temperature_collection_task(io: TemperatureCollectionIo) {
 
         for sensor in 0..count {
             batch[sensor][sample_index] = read_buf[sensor];
+            interlock::feed_temperature((sensor + 1) as u8, read_buf[sensor], now);
         }
         sample_index += 1;
 
@@ -0,0 +1,28 @@
+use defmt::{info, warn};
+use mainboard::sntp::SntpHandle;
+
+use crate::mqtt::queue;
+use crate::mqtt::sensors::time::TimeSyncPacket;
+
+/// Publishes the boot-time to UTC mapping on `status/time` whenever the
+/// SNTP service updates it, so sensor `timestamp_ms` values can be
+/// converted to wall-clock time on the receiving side.
+#[embassy_executor::task]
+pub async fn time_sync_publish_task(sntp: SntpHandle) {
+    let mut receiver = sntp.receiver().expect("Failed to get SNTP state receiver");
+    info!("Time sync publisher started");
+
+    loop {
+        let mapping = receiver.changed().await;
+        let packet = TimeSyncPacket {
+            // Sensor packets count milliseconds since boot, same origin as the mapping
+            offset_us: mapping.offset_us,
+            delay_us: mapping.delay_us.clamp(0, u32::MAX as i64) as u32,
+            jitter_us: mapping.jitter_us.clamp(0, u32::MAX as i64) as u32,
+            quality: mapping.quality as u8,
+        };
+        if queue::publish_time_sync(packet).is_err() {
+            warn!("Outbound queue full, dropping time sync status");
+        }
+    }
+}
@@ -14,8 +14,7 @@ use esp_hal::timer::timg::TimerGroup;
 use esp_hal::uart::Uart;
 use mainboard::board::Board;
 use mainboard::create_board;
-use mainboard::tmp107::{Tmp107, Tmp107Error, MAX_SENSORS};
-use panic_rtt_target as _;
+use mainboard::tmp107::{raw_to_millicelsius, Tmp107, Tmp107Error, MAX_SENSORS};
 
 extern crate alloc;
 
@@ -38,6 +37,7 @@ async fn main(_spawner: Spawner) -> ! {
 
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
+    mainboard::crash::init();
 
     esp_alloc::heap_allocator!(#[esp_hal::ram(reclaimed)] size: 32768);
 
@@ -123,7 +123,7 @@ async fn log_temperatures(
     info!("TMP107 captured {} temperature readings", count);
 
     for (index, raw_value) in read_buf[..count].iter().copied().enumerate() {
-        let milli_celsius = raw_temperature_to_millicelsius(raw_value);
+        let milli_celsius = raw_to_millicelsius(raw_value);
         info!(
             "TMP107 sensor {}: raw {:#06x}, {} mC",
             index + 1,
@@ -165,8 +165,3 @@ async fn clear_leds(driver: &mut Tmp107) -> Result<(), Tmp107Error> {
 
     Ok(())
 }
-
-fn raw_temperature_to_millicelsius(raw_value: u16) -> i32 {
-    let raw_units = i16::from_le_bytes(raw_value.to_le_bytes()) >> 2;
-    (i32::from(raw_units) * 1_000) / 64
-}
@@ -8,40 +8,54 @@
 )]
 #![recursion_limit = "256"]
 
-mod adc;
 mod digital_io;
+mod ota_upload;
 mod server;
 mod uart;
 
-use esp_hal::analog::adc::AdcConfig;
+use mainboard::analog::{
+    spawn_analog_service, AnalogChannel, AnalogConfig, AnalogHandle, AnalogIo, ChannelConfig,
+    SamplingMode,
+};
 use mainboard::board::{acquire_i2c_bus, init_i2c_bus, Board};
+use mainboard::config::MDNS_HOSTNAME;
 use mainboard::create_board;
+use mainboard::flash::init_flash;
+use mainboard::netlog::{spawn_syslog_sink, SyslogConfig};
+use mainboard::ota::{spawn_ota_service, OtaConfig, OtaResponse};
 use mainboard::power::PowerControllerIO;
 use mainboard::tasks::{
     spawn_ext_interrupt_task, spawn_power_controller, PowerResponse, PowerStateReceiver,
 };
+use mainboard::watchdog::{self, spawn_watchdog, WatchdogConfig};
 use mainboard::wifi::initialize_wifi_mixed;
+use mainboard::wifi::mdns::{spawn_mdns_responder, MdnsConfig, MdnsService};
 
-use crate::adc::{spawn_adc_task, AdcHandle, VoltageMonitorCalibrationConfig};
 use crate::digital_io::{spawn_digital_io, DigitalPinID};
 use crate::server::ShutdownHandle;
 use crate::uart::spawn_uart_tasks;
 use defmt::info;
 use embassy_executor::Spawner;
-use embassy_futures::select::{select, Either};
+use embassy_futures::select::{select, select3, Either3};
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
 use embassy_time::{Duration, Timer};
 use esp_hal::analog::adc::Attenuation;
 use esp_hal::efuse::{AdcCalibUnit, Efuse};
 use esp_hal::timer::timg::TimerGroup;
 use esp_hal::{clock::CpuClock, rtc_cntl::Rtc};
-use panic_rtt_target as _;
 use static_cell::StaticCell;
 
 // StaticCell for WiFi controller
 static ESP_RADIO_INIT: StaticCell<esp_radio::Controller<'static>> = StaticCell::new();
 static SHUTDOWN_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
+static MDNS_SERVICES: [MdnsService; 1] = [MdnsService {
+    instance: "mainboard web",
+    service: "_http._tcp",
+    port: 80,
+    txt: &["path=/"],
+}];
+
 // This creates a default app-descriptor required by the esp-idf bootloader.
 // For more information see: <https://docs.espressif.com/projects/esp-idf/en/stable/esp32/api-reference/system/app_image_format.html#application-description>
 esp_bootloader_esp_idf::esp_app_desc!();
@@ -58,6 +72,7 @@ async fn main(spawner: Spawner) {
     // Configure and initialize hardware
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
+    mainboard::crash::init();
 
     // Initialize heap allocator
     // Not #[esp_hal::ram(reclaimed)] because its too small XD
@@ -77,6 +92,9 @@ async fn main(spawner: Spawner) {
 
     init_i2c_bus(peripherals.I2C0, board.Sda, board.Scl).expect("Failed to initialize I2C bus");
 
+    init_flash(peripherals.FLASH);
+    let ota = spawn_ota_service(&spawner, OtaConfig::default());
+
     // Initialize RNG for WiFi
     let mut rng = esp_hal::rng::Rng::new();
 
@@ -98,21 +116,18 @@ async fn main(spawner: Spawner) {
         .spawn(log_power_state_changes_task(power_receiver))
         .expect("Failed to spawn log_power_state_changes_task");
 
-    let adc_config = AdcConfig::new();
-    let calibration: VoltageMonitorCalibrationConfig = Default::default();
-    let adc = spawn_adc_task(
-        &spawner,
-        peripherals.ADC1,
-        adc_config,
-        calibration,
-        board.BatVol,
-        board.BoostVol,
-        board.A0,
-        board.A1,
-        board.A2,
-        board.A3,
-        board.A4,
-    );
+    let analog_io = AnalogIo {
+        adc: peripherals.ADC1,
+        a0: board.A0,
+        a1: board.A1,
+        a2: board.A2,
+        a3: board.A3,
+        a4: board.A4,
+        bat_vol: board.BatVol,
+        boost_vol: board.BoostVol,
+        dma: None,
+    };
+    let adc = spawn_analog_service(&spawner, analog_io, analog_config());
     spawner
         .spawn(log_voltage_changes_task(adc))
         .expect("Failed to spawn log_voltage_changes_task");
@@ -138,6 +153,27 @@ async fn main(spawner: Spawner) {
         initialize_wifi_mixed(spawner, radio_init, peripherals.WIFI, &mut rng).await;
     info!("WiFi initialized!");
 
+    // Advertise `<hostname>.local` and the web UI on both interfaces
+    let mdns_config = MdnsConfig {
+        hostname: MDNS_HOSTNAME,
+        services: &MDNS_SERVICES,
+    };
+    spawn_mdns_responder(&spawner, wifi_resources.ap_stack, mdns_config);
+    spawn_mdns_responder(&spawner, wifi_resources.sta_stack, mdns_config);
+
+    if let Some(server) = mainboard::config::SYSLOG_SERVER {
+        spawn_syslog_sink(
+            &spawner,
+            wifi_resources.sta_stack,
+            SyslogConfig {
+                server,
+                hostname: MDNS_HOSTNAME,
+                app_name: "www_test",
+                ..Default::default()
+            },
+        );
+    }
+
     // Initialize simple output
     let digital = spawn_digital_io(&spawner, board.D0, board.D1, board.D2, board.D3, board.D4);
 
@@ -152,26 +188,54 @@ async fn main(spawner: Spawner) {
         digital,
         uart_handle,
         shutdown_handle,
+        ota,
     )
     .await;
     info!("Web server started!");
 
+    // Feed the hardware watchdog only while the main loop keeps running
+    let main_watch = watchdog::register("main", Duration::from_secs(30));
+    let timg1 = TimerGroup::new(peripherals.TIMG1);
+    spawn_watchdog(&spawner, timg1.wdt, WatchdogConfig::default());
+
     // Main loop
+    let mut confirmed = false;
     loop {
-        match select(
+        // The image can serve updates once a browser got the UI or the station
+        // network came up; only then keep it
+        let healthy = async {
+            if confirmed {
+                core::future::pending().await
+            } else {
+                select(
+                    server::UI_CONNECTED.wait(),
+                    wifi_resources.sta_stack.wait_config_up(),
+                )
+                .await
+            }
+        };
+        match select3(
             Timer::after(Duration::from_secs(10)),
             SHUTDOWN_SIGNAL.wait(),
+            healthy,
         )
         .await
         {
-            Either::First(_) => {
+            Either3::Third(_) => {
+                confirmed = true;
+                if let OtaResponse::Err(e) = ota.confirm_healthy().await {
+                    info!("Failed to confirm firmware image: {:?}", e);
+                }
+            }
+            Either3::First(_) => {
+                main_watch.check_in();
                 info!(
                     "Server running... AP IP: {:?}, STA IP: {:?}",
                     wifi_resources.ap_stack.config_v4().map(|c| c.address),
                     wifi_resources.sta_stack.config_v4().map(|c| c.address)
                 );
             }
-            Either::Second(_) => {
+            Either3::Second(_) => {
                 info!("Shutdown signal received");
                 break;
             }
@@ -208,6 +272,28 @@ async fn main(spawner: Spawner) {
     rtc.sleep_deep(&[]);
 }
 
+/// Input dividers of the voltage monitor, calibrated against a multimeter.
+fn analog_config() -> AnalogConfig {
+    let mut config = AnalogConfig {
+        mode: SamplingMode::Oneshot {
+            interval: Duration::from_millis(2),
+        },
+        samples_per_block: 125,
+        ..Default::default()
+    };
+    *config.channel_mut(AnalogChannel::BatVol) = ChannelConfig::volts(5.624);
+    *config.channel_mut(AnalogChannel::BoostVol) = ChannelConfig::volts(13.717);
+    // 39K / 10K -> 4.9
+    *config.channel_mut(AnalogChannel::A0) = ChannelConfig::volts(4.774);
+    // 22K / 10K -> 3.2
+    *config.channel_mut(AnalogChannel::A1) = ChannelConfig::volts(3.100);
+    *config.channel_mut(AnalogChannel::A2) = ChannelConfig::volts(3.129);
+    *config.channel_mut(AnalogChannel::A3) = ChannelConfig::volts(3.136);
+    // 10K / inf -> 1.0 (0.968), with another divider on the connector-main-computer board
+    *config.channel_mut(AnalogChannel::A4) = ChannelConfig::volts(14.316);
+    config
+}
+
 fn dump_adc_efuse_calibration() {
     let (blk_major, blk_minor) = Efuse::block_version();
     info!(
@@ -238,12 +324,13 @@ fn dump_adc_efuse_calibration() {
 }
 
 #[embassy_executor::task]
-async fn log_voltage_changes_task(adc: AdcHandle) {
+async fn log_voltage_changes_task(adc: AnalogHandle) {
     loop {
-        if let Some(state) = adc.state() {
+        if let Some(snapshot) = adc.latest() {
             info!(
-                "Battery voltage: {}mV, Boost voltage: {}mV",
-                state.battery_voltage, state.boost_voltage
+                "Battery voltage: {}V, Boost voltage: {}V",
+                snapshot.value(AnalogChannel::BatVol),
+                snapshot.value(AnalogChannel::BoostVol)
             );
         }
         Timer::after(Duration::from_secs(10)).await;
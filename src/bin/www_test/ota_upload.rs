@@ -0,0 +1,107 @@
+extern crate alloc;
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use mainboard::ota::{parse_sha256_hex, OtaHandle, OtaResponse};
+use picoserve::io::Read;
+use picoserve::response::{IntoResponse, ResponseWriter, StatusCode};
+use picoserve::routing::RequestHandlerService;
+use picoserve::ResponseSent;
+
+/// Chunk size handed to the OTA service; one flash sector.
+const OTA_CHUNK_SIZE: usize = 4096;
+
+/// `POST /ota` with the raw application image as the body and its SHA-256 in
+/// the `X-Sha256` header (64 hex characters), e.g.
+/// `curl --data-binary @app.bin -H "X-Sha256: $(sha256sum app.bin | cut -d' ' -f1)" http://<ip>/ota`.
+/// The device reboots into the new image after a successful upload.
+#[derive(Clone, Copy)]
+pub struct OtaUploadService {
+    pub ota: OtaHandle,
+}
+
+impl OtaUploadService {
+    async fn receive_image<R: Read>(
+        &self,
+        size: usize,
+        sha256: [u8; 32],
+        reader: &mut R,
+    ) -> Result<(), &'static str> {
+        if let OtaResponse::Err(e) = self.ota.begin(size as u32, sha256).await {
+            return Err(e.as_str());
+        }
+
+        let mut offset = 0usize;
+        let mut chunk = Vec::with_capacity(OTA_CHUNK_SIZE);
+        let mut buffer = [0u8; 1024];
+
+        while offset < size {
+            let read = match reader.read(&mut buffer).await {
+                Ok(0) | Err(_) => {
+                    let _ = self.ota.abort().await;
+                    return Err("UPLOAD_INTERRUPTED");
+                }
+                Ok(read) => read,
+            };
+            chunk.extend_from_slice(&buffer[..read]);
+
+            let last = offset + chunk.len() >= size;
+            if chunk.len() >= OTA_CHUNK_SIZE || last {
+                let len = chunk.len();
+                let data = core::mem::replace(&mut chunk, Vec::with_capacity(OTA_CHUNK_SIZE));
+                if let OtaResponse::Err(e) = self.ota.write(offset as u32, data).await {
+                    let _ = self.ota.abort().await;
+                    return Err(e.as_str());
+                }
+                offset += len;
+            }
+        }
+
+        match self.ota.finish().await {
+            OtaResponse::Ok(_) => Ok(()),
+            OtaResponse::Err(e) => Err(e.as_str()),
+        }
+    }
+}
+
+impl<State, PathParameters> RequestHandlerService<State, PathParameters> for OtaUploadService {
+    async fn call_request_handler_service<R: Read, W: ResponseWriter<Error = R::Error>>(
+        &self,
+        _state: &State,
+        _path_parameters: PathParameters,
+        mut request: picoserve::request::Request<'_, R>,
+        response_writer: W,
+    ) -> Result<ResponseSent, W::Error> {
+        let sha256 = request
+            .parts
+            .headers()
+            .get("X-Sha256")
+            .and_then(|value| parse_sha256_hex(value.as_raw()));
+        let size = request.body_connection.content_length();
+
+        let result = match sha256 {
+            None => Err("MISSING_X_SHA256"),
+            Some(_) if size == 0 => Err("EMPTY_BODY"),
+            Some(sha256) => {
+                info!("OTA upload: {} bytes", size);
+                let mut reader = request.body_connection.body().reader();
+                self.receive_image(size, sha256, &mut reader).await
+            }
+        };
+
+        let connection = request.body_connection.finalize().await?;
+        match result {
+            Ok(()) => {
+                info!("OTA upload complete, rebooting");
+                "OK, rebooting\r\n"
+                    .write_to(connection, response_writer)
+                    .await
+            }
+            Err(reason) => {
+                warn!("OTA upload rejected: {}", reason);
+                (StatusCode::BAD_REQUEST, reason)
+                    .write_to(connection, response_writer)
+                    .await
+            }
+        }
+    }
+}
@@ -3,19 +3,23 @@ use embassy_futures::select::{self, Either, Either3, Either4};
 use picoserve::{
     make_static,
     response::{ws, File},
-    routing::{self, get, PathRouter},
+    routing::{self, get, post_service, PathRouter},
     AppBuilder, AppRouter, Router, Server,
 };
 use serde::{Deserialize, Serialize};
 extern crate alloc;
 use crate::digital_io::DigitalIoHandle;
 use crate::digital_io::PinMode;
+use crate::ota_upload::OtaUploadService;
 use crate::uart::UartHandle;
-use crate::AdcHandle;
 use crate::DigitalPinID;
 use alloc::string::String;
 use alloc::vec::Vec;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Instant;
+use mainboard::analog::{AnalogChannel, AnalogHandle, AnalogSnapshot};
+use mainboard::netlog::{Level, LogRecord, ReadResult};
+use mainboard::ota::OtaHandle;
 use mainboard::power::PowerControllerStats;
 use mainboard::tasks::{PowerHandle, PowerResponse};
 
@@ -24,6 +28,9 @@ use mainboard::wifi::WifiResourcesMixed;
 // Define the pool size for web tasks (reduced from 8 to 2 for memory constraints)
 const WEB_TASK_POOL_SIZE: usize = 1;
 
+/// Signalled whenever the web UI opens its WebSocket, i.e. a browser was served
+pub static UI_CONNECTED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
 #[derive(Serialize)]
 struct PinStatesResponse<'a> {
     pin_number: u8,
@@ -72,6 +79,26 @@ pub struct AdcVoltageResponse {
     pub a4: u16,
 }
 
+impl AdcVoltageResponse {
+    fn from_snapshot(snapshot: &AnalogSnapshot) -> Self {
+        let mv = |channel| snapshot.value(channel).map(volts_to_mv).unwrap_or(0);
+        Self {
+            battery_voltage: mv(AnalogChannel::BatVol),
+            boost_voltage: mv(AnalogChannel::BoostVol),
+            a0: mv(AnalogChannel::A0),
+            a1: mv(AnalogChannel::A1),
+            a2: mv(AnalogChannel::A2),
+            a3: mv(AnalogChannel::A3),
+            a4: mv(AnalogChannel::A4),
+        }
+    }
+}
+
+/// The web UI works in millivolts.
+fn volts_to_mv(volts: f32) -> u16 {
+    (volts * 1000.0) as u16
+}
+
 #[derive(Serialize)]
 pub struct AdcBufferResponse {
     pub sequence: u32,
@@ -89,16 +116,17 @@ pub struct AdcBufferResponse {
 struct AppProps {
     power: PowerHandle,
     digital: DigitalIoHandle,
-    adc: AdcHandle,
+    adc: AnalogHandle,
     uart: UartHandle,
     shutdown: ShutdownHandle,
+    ota: OtaHandle,
 }
 
 #[derive(Clone, Copy)]
 struct WebsocketHandler {
     power: PowerHandle,
     digital: DigitalIoHandle,
-    adc: AdcHandle,
+    adc: AnalogHandle,
     uart: UartHandle,
     shutdown: ShutdownHandle,
 }
@@ -139,10 +167,12 @@ impl AppBuilder for AppProps {
                 "/ws",
                 get(
                     move |upgrade: picoserve::response::WebSocketUpgrade| async move {
+                        UI_CONNECTED.signal(());
                         upgrade.on_upgrade(handler)
                     },
                 ),
             )
+            .route("/ota", post_service(OtaUploadService { ota: self.ota }))
     }
 }
 
@@ -233,6 +263,8 @@ enum WebSocketCommand {
     UartSend { bytes: Vec<u8> },
     #[serde(rename = "shutdown")]
     Shutdown,
+    #[serde(rename = "crash_clear")]
+    CrashClear,
 }
 
 #[derive(Serialize)]
@@ -257,6 +289,27 @@ enum OutgoingMessage<'a> {
     },
     #[serde(rename = "uart_receive")]
     UartReceive { bytes: alloc::vec::Vec<u8> },
+    #[serde(rename = "crash_report")]
+    CrashReport(CrashReportResponse<'a>),
+    #[serde(rename = "log")]
+    Log {
+        level: &'static str,
+        uptime_ms: u64,
+        module: &'static str,
+        message: &'a str,
+    },
+}
+
+#[derive(Serialize)]
+struct CrashReportResponse<'a> {
+    kind: &'static str,
+    uptime_ms: u64,
+    boot_reason: &'static str,
+    file: &'a str,
+    line: u32,
+    column: u32,
+    message: &'a str,
+    truncated: bool,
 }
 
 // I2C helper functions
@@ -360,14 +413,14 @@ impl ws::WebSocketCallback for WebsocketHandler {
             let _ = tx.close(Some((1011, "Failed to watch output 5"))).await;
             return Ok(());
         };
-        let Some(mut adc_state_receiver) = self.adc.state_receiver() else {
+        let Some(mut adc_state_receiver) = self.adc.snapshot_receiver() else {
             error!("Failed to get ADC state receiver");
             let _ = tx
                 .close(Some((1011, "Failed to get ADC state receiver")))
                 .await;
             return Ok(());
         };
-        let Some(mut adc_buffer_subscriber) = self.adc.buffer_subscriber() else {
+        let Some(mut adc_buffer_subscriber) = self.adc.block_subscriber() else {
             error!("Failed to get ADC buffer subscriber");
             let _ = tx
                 .close(Some((1011, "Failed to get ADC buffer subscriber")))
@@ -381,6 +434,31 @@ impl ws::WebSocketCallback for WebsocketHandler {
                 .await;
             return Ok(());
         };
+        let Some(mut log_reader) = mainboard::netlog::reader() else {
+            error!("Failed to get network log reader");
+            let _ = tx
+                .close(Some((1011, "Failed to get network log reader")))
+                .await;
+            return Ok(());
+        };
+
+        // The report stays until dismissed, so every new client sees it
+        if let Some(report) = mainboard::crash::pending_report() {
+            tx.send_text(
+                &serde_json::to_string(&OutgoingMessage::CrashReport(CrashReportResponse {
+                    kind: report.kind.as_str(),
+                    uptime_ms: report.uptime_ms,
+                    boot_reason: mainboard::crash::reset_reason_name(report.reset_reason),
+                    file: report.file(),
+                    line: report.line,
+                    column: report.column,
+                    message: report.message(),
+                    truncated: report.truncated,
+                }))
+                .unwrap_or_default(),
+            )
+            .await?;
+        }
 
         let close_reason = loop {
             match select::select(
@@ -398,9 +476,10 @@ impl ws::WebSocketCallback for WebsocketHandler {
                         ),
                     ),
                 ),
-                select::select(
+                select::select3(
                     adc_buffer_subscriber.next_message_pure(),
                     uart_rx_subscriber.next_message_pure(),
+                    log_reader.next(),
                 ),
             )
             .await
@@ -541,6 +620,10 @@ impl ws::WebSocketCallback for WebsocketHandler {
                                         info!("Shutdown requested from Web UI");
                                         self.shutdown.request_shutdown();
                                     }
+                                    WebSocketCommand::CrashClear => {
+                                        info!("Crash report dismissed from Web UI");
+                                        mainboard::crash::clear_report();
+                                    }
                                 }
                             }
                             continue;
@@ -573,16 +656,8 @@ impl ws::WebSocketCallback for WebsocketHandler {
                     )
                     .await?;
                 }
-                Either::First(Either4::Third(adc_state)) => {
-                    let adc_response = AdcVoltageResponse {
-                        battery_voltage: adc_state.battery_voltage,
-                        boost_voltage: adc_state.boost_voltage,
-                        a0: adc_state.a0,
-                        a1: adc_state.a1,
-                        a2: adc_state.a2,
-                        a3: adc_state.a3,
-                        a4: adc_state.a4,
-                    };
+                Either::First(Either4::Third(snapshot)) => {
+                    let adc_response = AdcVoltageResponse::from_snapshot(&snapshot);
                     tx.send_text(
                         &serde_json::to_string(&OutgoingMessage::AdcVoltage(adc_response))
                             .unwrap_or_default(),
@@ -660,16 +735,22 @@ impl ws::WebSocketCallback for WebsocketHandler {
                     },
                 },
                 Either::Second(data_select) => match data_select {
-                    Either::First(buffer_data) => {
+                    Either3::First(buffer_data) => {
+                        let channel_mv = |channel| {
+                            buffer_data
+                                .values(channel)
+                                .map(|values| values.map(volts_to_mv).collect::<Vec<u16>>())
+                                .unwrap_or_default()
+                        };
                         let buffer_response = AdcBufferResponse {
                             sequence: buffer_data.sequence,
-                            battery_voltage: buffer_data.battery_voltage.to_vec(),
-                            boost_voltage: buffer_data.boost_voltage.to_vec(),
-                            a0: buffer_data.a0.to_vec(),
-                            a1: buffer_data.a1.to_vec(),
-                            a2: buffer_data.a2.to_vec(),
-                            a3: buffer_data.a3.to_vec(),
-                            a4: buffer_data.a4.to_vec(),
+                            battery_voltage: channel_mv(AnalogChannel::BatVol),
+                            boost_voltage: channel_mv(AnalogChannel::BoostVol),
+                            a0: channel_mv(AnalogChannel::A0),
+                            a1: channel_mv(AnalogChannel::A1),
+                            a2: channel_mv(AnalogChannel::A2),
+                            a3: channel_mv(AnalogChannel::A3),
+                            a4: channel_mv(AnalogChannel::A4),
                         };
                         tx.send_text(
                             &serde_json::to_string(&OutgoingMessage::AdcBuffer(buffer_response))
@@ -677,7 +758,7 @@ impl ws::WebSocketCallback for WebsocketHandler {
                         )
                         .await?;
                     }
-                    Either::Second(uart_data) => {
+                    Either3::Second(uart_data) => {
                         tx.send_text(
                             &serde_json::to_string(&OutgoingMessage::UartReceive {
                                 bytes: uart_data.bytes,
@@ -686,6 +767,27 @@ impl ws::WebSocketCallback for WebsocketHandler {
                         )
                         .await?;
                     }
+                    Either3::Third(log_result) => {
+                        let record = match log_result {
+                            ReadResult::Record(record) => record,
+                            ReadResult::Dropped(count) => LogRecord::new(
+                                Level::Warn,
+                                Instant::now().as_millis(),
+                                module_path!(),
+                                format_args!("{} log records dropped", count),
+                            ),
+                        };
+                        tx.send_text(
+                            &serde_json::to_string(&OutgoingMessage::Log {
+                                level: record.level.as_str(),
+                                uptime_ms: record.uptime_ms,
+                                module: record.module,
+                                message: record.text(),
+                            })
+                            .unwrap_or_default(),
+                        )
+                        .await?;
+                    }
                 },
             };
         };
@@ -702,10 +804,11 @@ pub async fn run_server(
     spawner: embassy_executor::Spawner,
     wifi_resources: &WifiResourcesMixed,
     power: PowerHandle,
-    adc: AdcHandle,
+    adc: AnalogHandle,
     digital: DigitalIoHandle,
     uart: UartHandle,
     shutdown: ShutdownHandle,
+    ota: OtaHandle,
 ) {
     let WifiResourcesMixed {
         ap_stack,
@@ -721,6 +824,7 @@ pub async fn run_server(
             adc,
             uart,
             shutdown,
+            ota,
         }
         .build_app()
     );
@@ -33,8 +33,12 @@ impl<Req, Resp, const N: usize> RequestResponseChannel<Req, Resp, N> {
         self.resp_channel.receive().await
     }
 
+    /// A response that arrives after its caller stopped waiting (e.g. under
+    /// `with_timeout`) is dropped before the next request is sent, so it is
+    /// not taken for that request's answer.
     pub async fn transact(&self, request: Req) -> Resp {
         let _guard = self.mutex.lock().await;
+        while self.resp_channel.try_receive().is_ok() {}
         self.send_request(request).await;
         self.recv_response().await
     }
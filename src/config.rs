@@ -8,3 +8,15 @@ pub static AP_PASSWORD: &str = match option_env!("AP_PASSWORD") {
     Some(val) => val,
     None => "password123",
 };
+pub static MDNS_HOSTNAME: &str = match option_env!("MDNS_HOSTNAME") {
+    Some(val) => val,
+    None => "esp32-mainboard",
+};
+/// Comma separated SNTP servers, in order of preference.
+pub static NTP_SERVERS: &str = match option_env!("NTP_SERVERS") {
+    Some(val) => val,
+    None => "pool.ntp.org,time.google.com",
+};
+/// Syslog server (host name or IPv4 address) for `mainboard::netlog`; no
+/// syslog sink is started when unset.
+pub static SYSLOG_SERVER: Option<&str> = option_env!("SYSLOG_SERVER");
@@ -0,0 +1,116 @@
+//! Crash reports that survive a reset.
+//!
+//! The panic handler logs the panic over defmt, stores a [`CrashRecord`] in
+//! RTC fast memory and resets the chip. That memory keeps its contents
+//! across software and watchdog resets but not power loss, so on the next
+//! boot the application can fetch the report with [`pending_report`],
+//! publish it and drop it with [`clear_report`].
+//!
+//! The task watchdog (`mainboard::watchdog`) stores a report through
+//! [`store_watchdog_report`] before it lets the hardware watchdog reset the
+//! chip, so a wedged task shows up the same way as a panic.
+//!
+//! Linking this crate installs the panic handler; binaries must not use
+//! another one (e.g. `panic_rtt_target`).
+
+pub mod record;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use defmt::error;
+use embassy_time::Instant;
+use esp_hal::system::Cpu;
+
+use crate::net_warn;
+
+pub use record::{reset_reason_name, CrashKind, CrashRecord, RecordError, RECORD_LEN};
+
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CRASH_RECORD: [u8; RECORD_LEN] = [0; RECORD_LEN];
+
+static BOOT_RESET_REASON: AtomicU8 = AtomicU8::new(record::RESET_REASON_UNKNOWN);
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Capture the reset reason of this boot and log a report left by the
+/// previous run. Call early in `main`.
+pub fn init() {
+    let reason = esp_hal::rtc_cntl::reset_reason(Cpu::ProCpu)
+        .map(|reason| reason as u8)
+        .unwrap_or(record::RESET_REASON_UNKNOWN);
+    BOOT_RESET_REASON.store(reason, Ordering::Relaxed);
+
+    match pending_report() {
+        Some(report) if report.kind == CrashKind::Watchdog => net_warn!(
+            "Previous run was reset by the watchdog after {} ms: task {} {}",
+            report.uptime_ms,
+            report.file(),
+            report.message()
+        ),
+        Some(report) => net_warn!(
+            "Previous run panicked after {} ms at {}:{}: {}",
+            report.uptime_ms,
+            report.file(),
+            report.line,
+            report.message()
+        ),
+        None => {}
+    }
+}
+
+/// Reset reason code of the current boot, see [`reset_reason_name`].
+pub fn boot_reset_reason() -> u8 {
+    BOOT_RESET_REASON.load(Ordering::Relaxed)
+}
+
+/// Crash report left by the previous run, if any.
+pub fn pending_report() -> Option<CrashRecord> {
+    // SAFETY: plain bytes; only written by the panic handler and clear_report
+    let bytes = unsafe { addr_of_mut!(CRASH_RECORD).read_volatile() };
+    CrashRecord::decode(&bytes).ok()
+}
+
+/// Drop the stored report once it has been delivered.
+pub fn clear_report() {
+    // SAFETY: see pending_report
+    unsafe { addr_of_mut!(CRASH_RECORD).write_volatile([0; RECORD_LEN]) }
+}
+
+/// Record that `task` has not checked in for `silent_ms`, replacing an
+/// undelivered report of an earlier run.
+pub fn store_watchdog_report(task: &str, silent_ms: u64, deadline_ms: u64) {
+    let mut report = CrashRecord::new(Instant::now().as_millis(), boot_reset_reason());
+    report.set_task(task);
+    let _ = write!(
+        report,
+        "silent for {} ms (deadline {} ms)",
+        silent_ms, deadline_ms
+    );
+    store_report(&report);
+}
+
+fn store_report(report: &CrashRecord) {
+    // SAFETY: see pending_report
+    unsafe { addr_of_mut!(CRASH_RECORD).write_volatile(report.encode()) }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // A panic while formatting the report must not recurse forever
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        esp_hal::system::software_reset();
+    }
+
+    error!("{}", defmt::Display2Format(info));
+
+    let mut report = CrashRecord::new(Instant::now().as_millis(), boot_reset_reason());
+    if let Some(location) = info.location() {
+        report.set_location(location.file(), location.line(), location.column());
+    }
+    let _ = write!(report, "{}", info.message());
+    store_report(&report);
+
+    esp_hal::system::software_reset();
+}
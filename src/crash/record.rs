@@ -0,0 +1,433 @@
+//! Binary layout of a crash record. Nothing here touches hardware, so the
+//! encoding can be exercised on the host.
+//!
+//! Layout (little-endian, [`RECORD_LEN`] bytes):
+//!
+//! | offset | size | field                                    |
+//! |--------|------|------------------------------------------|
+//! | 0      | 4    | magic [`RECORD_MAGIC`]                   |
+//! | 4      | 1    | version [`RECORD_VERSION`]               |
+//! | 5      | 1    | flags ([`FLAG_TRUNCATED`])               |
+//! | 6      | 1    | reset reason of the crashed run          |
+//! | 7      | 1    | file length                              |
+//! | 8      | 1    | message length                           |
+//! | 9      | 1    | kind ([`CrashKind`], zero for a panic)   |
+//! | 10     | 2    | reserved, zero                           |
+//! | 12     | 4    | line                                     |
+//! | 16     | 4    | column                                   |
+//! | 20     | 8    | uptime in milliseconds                   |
+//! | 28     | 64   | file (panic) or task name (watchdog)     |
+//! | 92     | 120  | message, zero padded                     |
+//! | 212    | 4    | CRC-32 (IEEE) of bytes 0..212            |
+
+use core::fmt;
+
+pub const RECORD_MAGIC: u32 = 0xC4A5_4ED0;
+pub const RECORD_VERSION: u8 = 1;
+pub const RECORD_LEN: usize = 216;
+
+pub const FILE_MAX_LEN: usize = 64;
+pub const MESSAGE_MAX_LEN: usize = 120;
+
+/// Set when the file path or the message did not fit.
+pub const FLAG_TRUNCATED: u8 = 0x01;
+
+/// Reset reason code used when the hardware did not report one.
+pub const RESET_REASON_UNKNOWN: u8 = 0;
+
+const FILE_OFFSET: usize = 28;
+const MESSAGE_OFFSET: usize = FILE_OFFSET + FILE_MAX_LEN;
+const CRC_OFFSET: usize = MESSAGE_OFFSET + MESSAGE_MAX_LEN;
+
+/// What ended the recorded run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CrashKind {
+    Panic,
+    /// A supervised task stopped checking in, see `mainboard::watchdog`.
+    Watchdog,
+}
+
+impl CrashKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            CrashKind::Panic => "panic",
+            CrashKind::Watchdog => "watchdog",
+        }
+    }
+
+    const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(CrashKind::Panic),
+            1 => Some(CrashKind::Watchdog),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RecordError {
+    /// No record stored (magic mismatch), e.g. after power-on.
+    Empty,
+    UnsupportedVersion,
+    /// Checksum or length fields are invalid.
+    Corrupted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashRecord {
+    pub kind: CrashKind,
+    pub uptime_ms: u64,
+    /// Why the crashed run started, see [`reset_reason_name`].
+    pub reset_reason: u8,
+    pub line: u32,
+    pub column: u32,
+    pub truncated: bool,
+    file: [u8; FILE_MAX_LEN],
+    file_len: u8,
+    message: [u8; MESSAGE_MAX_LEN],
+    message_len: u8,
+}
+
+impl CrashRecord {
+    pub const fn new(uptime_ms: u64, reset_reason: u8) -> Self {
+        Self {
+            kind: CrashKind::Panic,
+            uptime_ms,
+            reset_reason,
+            line: 0,
+            column: 0,
+            truncated: false,
+            file: [0; FILE_MAX_LEN],
+            file_len: 0,
+            message: [0; MESSAGE_MAX_LEN],
+            message_len: 0,
+        }
+    }
+
+    /// Store the source location. Long paths keep their tail, which holds
+    /// the file name.
+    pub fn set_location(&mut self, file: &str, line: u32, column: u32) {
+        let mut start = file.len().saturating_sub(FILE_MAX_LEN);
+        while !file.is_char_boundary(start) {
+            start += 1;
+        }
+        if start > 0 {
+            self.truncated = true;
+        }
+
+        let tail = &file.as_bytes()[start..];
+        self.file = [0; FILE_MAX_LEN];
+        self.file[..tail.len()].copy_from_slice(tail);
+        self.file_len = tail.len() as u8;
+        self.line = line;
+        self.column = column;
+    }
+
+    /// Mark the record as a watchdog reset caused by `task`. The task name
+    /// takes the place of the source location.
+    pub fn set_task(&mut self, task: &str) {
+        self.kind = CrashKind::Watchdog;
+        self.set_location(task, 0, 0);
+    }
+
+    /// Task that missed its deadline, for watchdog records.
+    pub fn task(&self) -> Option<&str> {
+        match self.kind {
+            CrashKind::Watchdog => Some(self.file()),
+            CrashKind::Panic => None,
+        }
+    }
+
+    /// Append to the message, dropping whatever does not fit.
+    pub fn push_message(&mut self, text: &str) {
+        let used = self.message_len as usize;
+        let mut len = text.len().min(MESSAGE_MAX_LEN - used);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        if len < text.len() {
+            self.truncated = true;
+        }
+
+        self.message[used..used + len].copy_from_slice(&text.as_bytes()[..len]);
+        self.message_len = (used + len) as u8;
+    }
+
+    pub fn file(&self) -> &str {
+        core::str::from_utf8(&self.file[..self.file_len as usize]).unwrap_or("")
+    }
+
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message[..self.message_len as usize]).unwrap_or("")
+    }
+
+    pub fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut out = [0u8; RECORD_LEN];
+        out[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        out[4] = RECORD_VERSION;
+        out[5] = if self.truncated { FLAG_TRUNCATED } else { 0 };
+        out[6] = self.reset_reason;
+        out[7] = self.file_len;
+        out[8] = self.message_len;
+        out[9] = self.kind as u8;
+        out[12..16].copy_from_slice(&self.line.to_le_bytes());
+        out[16..20].copy_from_slice(&self.column.to_le_bytes());
+        out[20..28].copy_from_slice(&self.uptime_ms.to_le_bytes());
+        out[FILE_OFFSET..MESSAGE_OFFSET].copy_from_slice(&self.file);
+        out[MESSAGE_OFFSET..CRC_OFFSET].copy_from_slice(&self.message);
+
+        let crc = crc32(&out[..CRC_OFFSET]);
+        out[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8; RECORD_LEN]) -> Result<Self, RecordError> {
+        if read_u32(bytes, 0) != RECORD_MAGIC {
+            return Err(RecordError::Empty);
+        }
+        if bytes[4] != RECORD_VERSION {
+            return Err(RecordError::UnsupportedVersion);
+        }
+        if read_u32(bytes, CRC_OFFSET) != crc32(&bytes[..CRC_OFFSET]) {
+            return Err(RecordError::Corrupted);
+        }
+
+        let kind = CrashKind::from_u8(bytes[9]).ok_or(RecordError::Corrupted)?;
+        let file_len = bytes[7];
+        let message_len = bytes[8];
+        if file_len as usize > FILE_MAX_LEN || message_len as usize > MESSAGE_MAX_LEN {
+            return Err(RecordError::Corrupted);
+        }
+
+        let mut file = [0u8; FILE_MAX_LEN];
+        file.copy_from_slice(&bytes[FILE_OFFSET..MESSAGE_OFFSET]);
+        let mut message = [0u8; MESSAGE_MAX_LEN];
+        message.copy_from_slice(&bytes[MESSAGE_OFFSET..CRC_OFFSET]);
+
+        Ok(Self {
+            kind,
+            uptime_ms: u64::from_le_bytes(bytes[20..28].try_into().unwrap()),
+            reset_reason: bytes[6],
+            line: read_u32(bytes, 12),
+            column: read_u32(bytes, 16),
+            truncated: bytes[5] & FLAG_TRUNCATED != 0,
+            file,
+            file_len,
+            message,
+            message_len,
+        })
+    }
+
+    /// One line summary, e.g.
+    /// `panic after 1234 ms (boot: power-on) at src/main.rs:10:5: boom` or
+    /// `watchdog reset after 1234 ms (boot: power-on): task sensors silent for 3000 ms`.
+    pub fn write_summary<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        match self.kind {
+            CrashKind::Panic => write!(
+                out,
+                "panic after {} ms (boot: {}) at {}:{}:{}: {}",
+                self.uptime_ms,
+                reset_reason_name(self.reset_reason),
+                self.file(),
+                self.line,
+                self.column,
+                self.message()
+            )?,
+            CrashKind::Watchdog => write!(
+                out,
+                "watchdog reset after {} ms (boot: {}): task {} {}",
+                self.uptime_ms,
+                reset_reason_name(self.reset_reason),
+                self.file(),
+                self.message()
+            )?,
+        }
+        if self.truncated {
+            out.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for CrashRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_message(s);
+        Ok(())
+    }
+}
+
+/// Human readable name of an ESP32-C6 reset reason code.
+pub const fn reset_reason_name(code: u8) -> &'static str {
+    match code {
+        0x01 => "power-on",
+        0x03 => "software",
+        0x05 => "deep-sleep",
+        0x07 => "mwdt0",
+        0x08 => "mwdt1",
+        0x09 => "rtc-wdt",
+        0x0B => "cpu-mwdt0",
+        0x0C => "cpu-software",
+        0x0D => "cpu-rtc-wdt",
+        0x0F => "brown-out",
+        0x10 => "sys-rtc-wdt",
+        0x11 => "cpu-mwdt1",
+        0x12 => "super-wdt",
+        0x14 => "efuse-crc",
+        0x15 => "usb-uart",
+        0x16 => "usb-jtag",
+        0x18 => "jtag",
+        _ => "unknown",
+    }
+}
+
+/// CRC-32 (IEEE 802.3, reflected, as used by zlib).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn panic_record() -> CrashRecord {
+        let mut record = CrashRecord::new(123_456, 0x0C);
+        record.set_location("src/bin/test_stand_controller/main.rs", 42, 7);
+        record.push_message("index out of bounds");
+        record
+    }
+
+    fn summary(record: &CrashRecord) -> String {
+        let mut out = String::new();
+        record.write_summary(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn panic_round_trips() {
+        let record = panic_record();
+        let decoded = CrashRecord::decode(&record.encode()).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.kind, CrashKind::Panic);
+        assert_eq!(decoded.file(), "src/bin/test_stand_controller/main.rs");
+        assert_eq!(decoded.message(), "index out of bounds");
+        assert_eq!(decoded.task(), None);
+        assert_eq!(
+            summary(&decoded),
+            "panic after 123456 ms (boot: cpu-software) at \
+             src/bin/test_stand_controller/main.rs:42:7: index out of bounds"
+        );
+    }
+
+    #[test]
+    fn watchdog_round_trips() {
+        let mut record = CrashRecord::new(9_000, RESET_REASON_UNKNOWN);
+        record.set_task("sensors");
+        record.push_message("silent for 3000 ms");
+        let decoded = CrashRecord::decode(&record.encode()).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.task(), Some("sensors"));
+        assert_eq!(
+            summary(&decoded),
+            "watchdog reset after 9000 ms (boot: unknown): task sensors silent for 3000 ms"
+        );
+    }
+
+    #[test]
+    fn empty_memory_is_no_record() {
+        assert_eq!(
+            CrashRecord::decode(&[0; RECORD_LEN]),
+            Err(RecordError::Empty)
+        );
+    }
+
+    #[test]
+    fn other_version_rejected() {
+        let mut bytes = panic_record().encode();
+        bytes[4] = RECORD_VERSION + 1;
+        assert_eq!(
+            CrashRecord::decode(&bytes),
+            Err(RecordError::UnsupportedVersion)
+        );
+    }
+
+    #[test]
+    fn crc_mismatch_rejected() {
+        let mut bytes = panic_record().encode();
+        bytes[MESSAGE_OFFSET] ^= 0x01;
+        assert_eq!(CrashRecord::decode(&bytes), Err(RecordError::Corrupted));
+
+        let mut bytes = panic_record().encode();
+        bytes[RECORD_LEN - 1] ^= 0x80;
+        assert_eq!(CrashRecord::decode(&bytes), Err(RecordError::Corrupted));
+    }
+
+    #[test]
+    fn bad_fields_under_valid_crc_rejected() {
+        let reseal = |bytes: &mut [u8; RECORD_LEN]| {
+            let crc = crc32(&bytes[..CRC_OFFSET]);
+            bytes[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        };
+
+        let mut bytes = panic_record().encode();
+        bytes[8] = MESSAGE_MAX_LEN as u8 + 1;
+        reseal(&mut bytes);
+        assert_eq!(CrashRecord::decode(&bytes), Err(RecordError::Corrupted));
+
+        let mut bytes = panic_record().encode();
+        bytes[9] = 2;
+        reseal(&mut bytes);
+        assert_eq!(CrashRecord::decode(&bytes), Err(RecordError::Corrupted));
+    }
+
+    #[test]
+    fn long_message_truncated() {
+        let mut record = CrashRecord::new(0, 0);
+        record.push_message(&"x".repeat(MESSAGE_MAX_LEN - 1));
+        assert!(!record.truncated);
+        // The 2-byte character does not fit in the last byte
+        record.push_message("é");
+        assert!(record.truncated);
+        assert_eq!(record.message().len(), MESSAGE_MAX_LEN - 1);
+        record.push_message("x");
+        assert_eq!(record.message().len(), MESSAGE_MAX_LEN);
+
+        let decoded = CrashRecord::decode(&record.encode()).unwrap();
+        assert!(decoded.truncated);
+        assert_eq!(decoded.message(), record.message());
+        assert!(summary(&decoded).ends_with("x..."));
+    }
+
+    #[test]
+    fn long_path_keeps_file_name() {
+        let path = alloc::format!("{}/main.rs", "dir".repeat(30));
+        let mut record = CrashRecord::new(0, 0);
+        record.set_location(&path, 1, 1);
+        assert!(record.truncated);
+        assert_eq!(record.file().len(), FILE_MAX_LEN);
+        assert!(record.file().ends_with("/main.rs"));
+    }
+
+    #[test]
+    fn crc_matches_zlib() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
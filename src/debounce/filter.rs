@@ -0,0 +1,159 @@
+//! Debounce state of one digital input.
+
+/// A debounced level change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Edge {
+    /// The new level, `true` high
+    pub level: bool,
+    /// When the input last moved to the new level, milliseconds since boot
+    pub timestamp_ms: u64,
+}
+
+/// Takes raw readings of an input and reports a new level once the input
+/// has held it for `stable_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Debouncer {
+    stable_ms: u64,
+    level: bool,
+    /// When the input moved away from `level`, while it stays away
+    changed_at_ms: Option<u64>,
+    chatter: u32,
+}
+
+impl Debouncer {
+    pub const fn new(level: bool, stable_ms: u64) -> Self {
+        Self {
+            stable_ms,
+            level,
+            changed_at_ms: None,
+            chatter: 0,
+        }
+    }
+
+    /// The debounced level.
+    pub const fn level(&self) -> bool {
+        self.level
+    }
+
+    /// Changes that did not hold for the stable time, since creation. A
+    /// lower bound: changes closer than the edge handling are seen as one.
+    pub const fn chatter(&self) -> u32 {
+        self.chatter
+    }
+
+    /// When the pending change will have held for the stable time, `None`
+    /// without one.
+    pub fn settles_at(&self) -> Option<u64> {
+        self.changed_at_ms
+            .map(|changed_at_ms| changed_at_ms + self.stable_ms)
+    }
+
+    /// Feed the level `raw` read at `now_ms` after an edge of the input.
+    pub fn edge(&mut self, raw: bool, now_ms: u64) -> Option<Edge> {
+        // Back at the debounced level, or away again after a return too
+        // quick to see
+        if raw == self.level || self.changed_at_ms.is_some() {
+            self.chatter = self.chatter.wrapping_add(1);
+            self.changed_at_ms = None;
+        }
+        self.poll(raw, now_ms)
+    }
+
+    /// Feed the level `raw` read at `now_ms` without an edge, such as when
+    /// the stable time is up.
+    pub fn poll(&mut self, raw: bool, now_ms: u64) -> Option<Edge> {
+        if raw == self.level {
+            if self.changed_at_ms.take().is_some() {
+                self.chatter = self.chatter.wrapping_add(1);
+            }
+            return None;
+        }
+        let changed_at_ms = *self.changed_at_ms.get_or_insert(now_ms);
+        if now_ms.saturating_sub(changed_at_ms) < self.stable_ms {
+            return None;
+        }
+        self.level = raw;
+        self.changed_at_ms = None;
+        Some(Edge {
+            level: raw,
+            timestamp_ms: changed_at_ms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STABLE_MS: u64 = 30;
+
+    #[test]
+    fn steady_input_reports_nothing() {
+        let mut debouncer = Debouncer::new(false, STABLE_MS);
+        assert_eq!(debouncer.poll(false, 100), None);
+        assert_eq!(debouncer.settles_at(), None);
+        assert!(!debouncer.level());
+        assert_eq!(debouncer.chatter(), 0);
+    }
+
+    #[test]
+    fn clean_change_settles_after_stable_time() {
+        let mut debouncer = Debouncer::new(false, STABLE_MS);
+        assert_eq!(debouncer.edge(true, 100), None);
+        assert_eq!(debouncer.settles_at(), Some(130));
+        assert_eq!(debouncer.poll(true, 129), None);
+
+        let edge = Edge {
+            level: true,
+            timestamp_ms: 100,
+        };
+        assert_eq!(debouncer.poll(true, 130), Some(edge));
+        assert!(debouncer.level());
+        assert_eq!(debouncer.settles_at(), None);
+        assert_eq!(debouncer.chatter(), 0);
+    }
+
+    #[test]
+    fn bounce_restarts_the_stable_time() {
+        let mut debouncer = Debouncer::new(false, STABLE_MS);
+        assert_eq!(debouncer.edge(true, 100), None);
+        assert_eq!(debouncer.edge(false, 105), None);
+        assert_eq!(debouncer.edge(true, 108), None);
+        assert_eq!(debouncer.settles_at(), Some(138));
+        assert_eq!(debouncer.poll(true, 130), None);
+
+        let edge = debouncer.poll(true, 138).unwrap();
+        assert_eq!(edge.timestamp_ms, 108);
+        assert_eq!(debouncer.chatter(), 1);
+    }
+
+    #[test]
+    fn short_pulse_is_chatter() {
+        let mut debouncer = Debouncer::new(true, STABLE_MS);
+        assert_eq!(debouncer.edge(false, 100), None);
+        assert_eq!(debouncer.edge(true, 110), None);
+        assert_eq!(debouncer.settles_at(), None);
+        assert_eq!(debouncer.poll(true, 200), None);
+        assert!(debouncer.level());
+        assert_eq!(debouncer.chatter(), 1);
+    }
+
+    #[test]
+    fn return_seen_only_by_poll_is_chatter() {
+        let mut debouncer = Debouncer::new(false, STABLE_MS);
+        assert_eq!(debouncer.edge(true, 100), None);
+        assert_eq!(debouncer.poll(false, 120), None);
+        assert_eq!(debouncer.settles_at(), None);
+        assert_eq!(debouncer.chatter(), 1);
+    }
+
+    #[test]
+    fn missed_return_counts_as_chatter() {
+        // Two edges to the same level: the return in between was too quick
+        let mut debouncer = Debouncer::new(false, STABLE_MS);
+        assert_eq!(debouncer.edge(true, 100), None);
+        assert_eq!(debouncer.edge(true, 120), None);
+        assert_eq!(debouncer.settles_at(), Some(150));
+        assert_eq!(debouncer.chatter(), 1);
+    }
+}
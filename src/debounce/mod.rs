@@ -0,0 +1,62 @@
+//! Debounced digital input.
+//!
+//! [`DebouncedInput`] wraps a GPIO input and reports a level change only once
+//! the input has held the new level for the stable time, timestamped when
+//! the input reached it. Changes that go back sooner are counted as chatter,
+//! so a worn switch or a loose wire shows up before it causes trouble.
+
+pub mod filter;
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::Input;
+
+pub use filter::{Debouncer, Edge};
+
+pub struct DebouncedInput<'d> {
+    pin: Input<'d>,
+    debouncer: Debouncer,
+}
+
+impl<'d> DebouncedInput<'d> {
+    /// Starts at the level the pin reads now.
+    pub fn new(pin: Input<'d>, stable: Duration) -> Self {
+        let debouncer = Debouncer::new(pin.is_high(), stable.as_millis());
+        Self { pin, debouncer }
+    }
+
+    /// The debounced level, `true` high.
+    pub fn level(&self) -> bool {
+        self.debouncer.level()
+    }
+
+    /// See [`Debouncer::chatter`].
+    pub fn chatter(&self) -> u32 {
+        self.debouncer.chatter()
+    }
+
+    /// Wait for the next debounced level change. Cancel safe: changes made
+    /// while nobody waits are picked up by the next call.
+    pub async fn wait_for_edge(&mut self) -> Edge {
+        loop {
+            if let Some(edge) = self.debouncer.poll(self.pin.is_high(), now_ms()) {
+                return edge;
+            }
+            let settles_at = self
+                .debouncer
+                .settles_at()
+                .map_or(Instant::MAX, Instant::from_millis);
+            if let Either::First(()) =
+                select(self.pin.wait_for_any_edge(), Timer::at(settles_at)).await
+            {
+                if let Some(edge) = self.debouncer.edge(self.pin.is_high(), now_ms()) {
+                    return edge;
+                }
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    Instant::now().as_millis()
+}
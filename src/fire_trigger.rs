@@ -1,30 +1,461 @@
+use embassy_time::{Duration, Instant};
 use embedded_hal::i2c::I2c;
-use pcf857x::Pcf8574;
+use pcf857x::SlaveAddr;
 
+/// Channel N is expander pin PN.
+pub const CHANNELS: usize = 8;
+
+/// PCF8574 address with A2..A0 low
+const BASE_ADDRESS: u8 = 0x20;
+
+#[derive(Debug)]
+pub enum FireTriggerError<E> {
+    Bus(E),
+    /// The outputs read back differ from the ones written
+    Readback {
+        expected: u8,
+        actual: u8,
+    },
+    /// The mask names channels that are not wired
+    UnknownChannel(u8),
+}
+
+/// Igniter channels on a PCF8574, active low. Every write is read back, so a
+/// stuck output or a missing expander is reported instead of assumed away.
+///
+/// No channel stays on longer than `max_on`: [`FireTrigger::expire`] releases
+/// the ones past their time, so the caller has to run it by
+/// [`FireTrigger::next_release`].
+///
+/// A channel counts as on from the moment it is asked to turn on, and as off
+/// only once a write reads it back released. After a failed release it stays
+/// in [`FireTrigger::on_mask`], so the caller must retry until that is 0.
+///
+/// The igniter continuity sense pin is an input: it is kept high (the
+/// expander's weak pull-up) in every write and left out of the read-back, and
+/// reads low while current flows through the igniter.
+///
+/// The port is written and read as single bytes rather than through
+/// `pcf857x`, whose `get` first drives the pins it reads high and so would
+/// release the channels it checks.
 pub struct FireTrigger<I2C: I2c> {
-    expander: Pcf8574<I2C>,
-    trigger_byte: u8,
+    i2c: I2C,
+    address: u8,
+    channels: u8,
+    sense_mask: u8,
+    max_on: Duration,
+    /// Channels that are on
+    on: u8,
+    /// When each channel that is on gets released
+    release_at: [Instant; CHANNELS],
 }
 
 impl<I2C: I2c> FireTrigger<I2C> {
+    /// `channels` is the mask of wired channels, `sense_mask` selects the
+    /// continuity sense pin, 0 if there is none.
     pub fn new(
         i2c: I2C,
-        address: pcf857x::SlaveAddr,
-        trigger_byte: u8,
-    ) -> Result<Self, pcf857x::Error<I2C::Error>> {
-        let mut expander = Pcf8574::new(i2c, address);
-        expander.set(0xFF)?;
-        Ok(Self {
-            expander,
-            trigger_byte,
-        })
+        address: SlaveAddr,
+        channels: u8,
+        sense_mask: u8,
+        max_on: Duration,
+    ) -> Result<Self, FireTriggerError<I2C::Error>> {
+        let mut trigger = Self {
+            i2c,
+            address: match address {
+                SlaveAddr::Default => BASE_ADDRESS,
+                SlaveAddr::Alternative(a2, a1, a0) => {
+                    BASE_ADDRESS | (a2 as u8) << 2 | (a1 as u8) << 1 | a0 as u8
+                }
+            },
+            channels: channels & !sense_mask,
+            sense_mask,
+            max_on,
+            on: 0,
+            release_at: [Instant::MIN; CHANNELS],
+        };
+        trigger.write(0)?;
+        Ok(trigger)
+    }
+
+    pub const fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    pub const fn on_mask(&self) -> u8 {
+        self.on
+    }
+
+    /// Turn on the channels in `mask` for the longest allowed time.
+    pub fn fire(&mut self, mask: u8) -> Result<(), FireTriggerError<I2C::Error>> {
+        self.pulse(mask, self.max_on)
+    }
+
+    /// Turn on the channels in `mask` for `duration`, at most the longest
+    /// allowed time. Channels already on keep their release time if it is
+    /// sooner, so pulsing again cannot hold one past the longest time.
+    pub fn pulse(
+        &mut self,
+        mask: u8,
+        duration: Duration,
+    ) -> Result<(), FireTriggerError<I2C::Error>> {
+        self.check_mask(mask)?;
+        let release_at = Instant::now() + duration.min(self.max_on);
+        for channel in channels_in(mask) {
+            self.release_at[channel] = match self.on & (1 << channel) {
+                0 => release_at,
+                _ => self.release_at[channel].min(release_at),
+            };
+        }
+        self.on |= mask;
+        self.write(self.on)
+    }
+
+    pub fn release(&mut self, mask: u8) -> Result<(), FireTriggerError<I2C::Error>> {
+        self.check_mask(mask)?;
+        self.switch(self.on & !mask)
+    }
+
+    /// Release every channel.
+    pub fn abort(&mut self) -> Result<(), FireTriggerError<I2C::Error>> {
+        self.switch(0)
+    }
+
+    /// Release the channels whose time is up at `now`; returns their mask.
+    pub fn expire(&mut self, now: Instant) -> Result<u8, FireTriggerError<I2C::Error>> {
+        let expired = channels_in(self.on)
+            .filter(|&channel| self.release_at[channel] <= now)
+            .fold(0, |mask, channel| mask | (1 << channel));
+        if expired != 0 {
+            self.switch(self.on & !expired)?;
+        }
+        Ok(expired)
+    }
+
+    /// When [`FireTrigger::expire`] next has a channel to release.
+    pub fn next_release(&self) -> Option<Instant> {
+        channels_in(self.on)
+            .map(|channel| self.release_at[channel])
+            .min()
+    }
+
+    /// Whether the igniter circuit is closed; `None` without a sense pin.
+    pub fn continuity(&mut self) -> Result<Option<bool>, FireTriggerError<I2C::Error>> {
+        if self.sense_mask == 0 {
+            return Ok(None);
+        }
+        let port = self.read()?;
+        Ok(Some(port & self.sense_mask == 0))
+    }
+
+    fn check_mask(&self, mask: u8) -> Result<(), FireTriggerError<I2C::Error>> {
+        match mask & !self.channels {
+            0 => Ok(()),
+            unknown => Err(FireTriggerError::UnknownChannel(unknown)),
+        }
+    }
+
+    /// Write `on`, and take it as the channels that are on once it reads
+    /// back.
+    fn switch(&mut self, on: u8) -> Result<(), FireTriggerError<I2C::Error>> {
+        self.write(on)?;
+        self.on = on;
+        Ok(())
+    }
+
+    fn write(&mut self, on: u8) -> Result<(), FireTriggerError<I2C::Error>> {
+        let value = !on | self.sense_mask;
+        self.i2c
+            .write(self.address, &[value])
+            .map_err(FireTriggerError::Bus)?;
+
+        let output_mask = !self.sense_mask;
+        let actual = self.read()? & output_mask;
+        let expected = value & output_mask;
+        if actual != expected {
+            return Err(FireTriggerError::Readback { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// The level of every pin.
+    fn read(&mut self) -> Result<u8, FireTriggerError<I2C::Error>> {
+        let mut port = [0];
+        self.i2c
+            .read(self.address, &mut port)
+            .map_err(FireTriggerError::Bus)?;
+        Ok(port[0])
+    }
+}
+
+fn channels_in(mask: u8) -> impl Iterator<Item = usize> {
+    (0..CHANNELS).filter(move |&channel| mask & (1 << channel) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use embedded_hal::i2c::{ErrorKind, ErrorType, NoAcknowledgeSource, Operation};
+
+    const SENSE: u8 = 1 << 7;
+
+    /// A PCF8574 on the bus: pins in `pulled_low` read low whatever is
+    /// written, like a shorted output or the sense pin with current through
+    /// the igniter.
+    #[derive(Default)]
+    struct Expander {
+        address: u8,
+        port: u8,
+        pulled_low: u8,
+        offline: bool,
+    }
+
+    struct Bus<'a>(&'a RefCell<Expander>);
+
+    impl ErrorType for Bus<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for Bus<'_> {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            let mut expander = self.0.borrow_mut();
+            if expander.offline {
+                return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+            }
+            expander.address = address;
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => expander.port = *bytes.last().unwrap(),
+                    Operation::Read(bytes) => bytes.fill(expander.port & !expander.pulled_low),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn trigger(
+        expander: &RefCell<Expander>,
+        channels: u8,
+        sense_mask: u8,
+    ) -> Result<FireTrigger<Bus<'_>>, FireTriggerError<ErrorKind>> {
+        FireTrigger::new(
+            Bus(expander),
+            SlaveAddr::Default,
+            channels,
+            sense_mask,
+            Duration::from_millis(1000),
+        )
+    }
+
+    #[test]
+    fn starts_released() {
+        let expander = RefCell::new(Expander::default());
+        let trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        assert_eq!(expander.borrow().address, 0x20);
+        assert_eq!(expander.borrow().port, 0xFF);
+        assert_eq!(trigger.channels(), 0x7F);
+        assert_eq!(trigger.on_mask(), 0);
+    }
+
+    #[test]
+    fn channels_are_active_low() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        trigger.fire(0b101).unwrap();
+        assert_eq!(expander.borrow().port, !0b101);
+        assert_eq!(trigger.on_mask(), 0b101);
+        trigger.release(0b001).unwrap();
+        assert_eq!(expander.borrow().port, !0b100);
+        trigger.abort().unwrap();
+        assert_eq!(expander.borrow().port, 0xFF);
+    }
+
+    #[test]
+    fn pulses_expire() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        let start = Instant::now();
+        trigger.pulse(0b01, Duration::from_millis(100)).unwrap();
+        // Capped at the longest allowed time
+        trigger.pulse(0b10, Duration::from_secs(60)).unwrap();
+        let pulsed = Instant::now();
+        assert!(trigger.next_release().unwrap() <= pulsed + Duration::from_millis(100));
+
+        assert_eq!(
+            trigger.expire(pulsed + Duration::from_millis(100)).unwrap(),
+            0b01
+        );
+        assert_eq!(expander.borrow().port, !0b10);
+        let release_at = trigger.next_release().unwrap();
+        assert!(release_at >= start + Duration::from_millis(1000));
+        assert!(release_at <= pulsed + Duration::from_millis(1000));
+        assert_eq!(trigger.expire(release_at).unwrap(), 0b10);
+        assert_eq!(expander.borrow().port, 0xFF);
+        assert_eq!(trigger.next_release(), None);
+    }
+
+    #[test]
+    fn pulsing_again_keeps_the_first_release() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        trigger.fire(0b01).unwrap();
+        let first = trigger.next_release().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        trigger.fire(0b01).unwrap();
+        trigger.pulse(0b01, Duration::from_secs(60)).unwrap();
+        assert_eq!(trigger.next_release(), Some(first));
+        // A shorter pulse still shortens it
+        trigger.pulse(0b01, Duration::from_millis(10)).unwrap();
+        let shorter = trigger.next_release().unwrap();
+        assert!(shorter < first);
+
+        assert_eq!(trigger.expire(shorter).unwrap(), 0b01);
+        assert_eq!(expander.borrow().port, 0xFF);
+    }
+
+    #[test]
+    fn failed_release_keeps_channels_on() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        trigger.fire(0b11).unwrap();
+
+        expander.borrow_mut().offline = true;
+        assert!(trigger.release(0b01).is_err());
+        assert!(trigger.abort().is_err());
+        assert_eq!(trigger.on_mask(), 0b11);
+
+        expander.borrow_mut().offline = false;
+        trigger.release(0b01).unwrap();
+        assert_eq!(trigger.on_mask(), 0b10);
+    }
+
+    #[test]
+    fn release_counts_only_once_read_back() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        trigger.fire(0b11).unwrap();
+
+        // Channel 0 stays driven low whatever is written
+        expander.borrow_mut().pulled_low = 0b01;
+        assert!(matches!(
+            trigger.abort(),
+            Err(FireTriggerError::Readback { .. })
+        ));
+        assert_eq!(trigger.on_mask(), 0b11);
+
+        expander.borrow_mut().pulled_low = 0;
+        trigger.abort().unwrap();
+        assert_eq!(trigger.on_mask(), 0);
+    }
+
+    #[test]
+    fn failed_expire_is_retried() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        trigger.fire(0b1).unwrap();
+        let release_at = trigger.next_release().unwrap();
+
+        expander.borrow_mut().offline = true;
+        assert!(trigger.expire(release_at).is_err());
+        assert_eq!(trigger.on_mask(), 0b1);
+        // Still due, so the caller comes back to it
+        assert_eq!(trigger.next_release(), Some(release_at));
+
+        expander.borrow_mut().offline = false;
+        assert_eq!(trigger.expire(release_at).unwrap(), 0b1);
+        assert_eq!(expander.borrow().port, 0xFF);
+    }
+
+    #[test]
+    fn rejects_unwired_channels() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0x0F, SENSE).unwrap();
+        assert!(matches!(
+            trigger.fire(0x11),
+            Err(FireTriggerError::UnknownChannel(0x10))
+        ));
+        assert!(matches!(
+            trigger.fire(SENSE),
+            Err(FireTriggerError::UnknownChannel(SENSE))
+        ));
+        assert_eq!(expander.borrow().port, 0xFF);
+    }
+
+    #[test]
+    fn stuck_output_fails_readback() {
+        let expander = RefCell::new(Expander {
+            pulled_low: 1 << 2,
+            ..Expander::default()
+        });
+        match trigger(&expander, 0xFF, SENSE) {
+            Err(FireTriggerError::Readback { expected, actual }) => {
+                assert_eq!((expected, actual), (0x7F, 0x7B));
+            }
+            _ => panic!("stuck output not reported"),
+        }
+    }
+
+    #[test]
+    fn missing_expander_is_a_bus_error() {
+        let expander = RefCell::new(Expander {
+            offline: true,
+            ..Expander::default()
+        });
+        assert!(matches!(
+            trigger(&expander, 0xFF, SENSE),
+            Err(FireTriggerError::Bus(_))
+        ));
+    }
+
+    #[test]
+    fn continuity_reads_the_sense_pin() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        assert_eq!(trigger.continuity().unwrap(), Some(false));
+        // Current through the igniter pulls the sense pin low; it is not a
+        // readback error
+        expander.borrow_mut().pulled_low = SENSE;
+        assert_eq!(trigger.continuity().unwrap(), Some(true));
+        trigger.fire(1).unwrap();
+        assert_eq!(trigger.continuity().unwrap(), Some(true));
+    }
+
+    #[test]
+    fn continuity_leaves_outputs_alone() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, SENSE).unwrap();
+        trigger.fire(0b11).unwrap();
+        trigger.continuity().unwrap();
+        assert_eq!(expander.borrow().port, !0b11);
     }
 
-    pub fn trigger(&mut self) -> Result<(), pcf857x::Error<I2C::Error>> {
-        self.expander.set(self.trigger_byte)
+    #[test]
+    fn no_continuity_without_sense_pin() {
+        let expander = RefCell::new(Expander::default());
+        let mut trigger = trigger(&expander, 0xFF, 0).unwrap();
+        assert_eq!(trigger.channels(), 0xFF);
+        assert_eq!(trigger.continuity().unwrap(), None);
+        trigger.fire(0xFF).unwrap();
+        assert_eq!(expander.borrow().port, 0);
     }
 
-    pub fn abort(&mut self) -> Result<(), pcf857x::Error<I2C::Error>> {
-        self.expander.set(0xFF)
+    #[test]
+    fn alternative_address() {
+        let expander = RefCell::new(Expander::default());
+        FireTrigger::new(
+            Bus(&expander),
+            SlaveAddr::Alternative(true, false, true),
+            0xFF,
+            0,
+            Duration::from_millis(1000),
+        )
+        .unwrap();
+        assert_eq!(expander.borrow().address, 0x25);
     }
 }
@@ -0,0 +1,27 @@
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use esp_hal::peripherals::FLASH;
+use esp_storage::FlashStorage;
+
+use once_cell::sync::OnceCell;
+
+pub type FlashType = FlashStorage<'static>;
+
+pub type FlashGuard = MutexGuard<'static, CriticalSectionRawMutex, FlashType>;
+
+static FLASH_STORAGE: OnceCell<Mutex<CriticalSectionRawMutex, FlashType>> = OnceCell::new();
+
+/// Take ownership of the SPI flash. Must be called once before any flash user
+/// (OTA, persistent storage) is started.
+pub fn init_flash(flash: FLASH<'static>) {
+    let _ = FLASH_STORAGE.set(Mutex::new(FlashStorage::new(flash)));
+}
+
+/// Lock the flash for exclusive access. Hold the guard only for the duration
+/// of one logical operation; flash users run in different tasks.
+pub async fn lock_flash() -> FlashGuard {
+    match FLASH_STORAGE.get() {
+        Some(flash) => flash.lock().await,
+        None => panic!("Flash accessed before initialization"),
+    }
+}
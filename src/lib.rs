@@ -1,14 +1,25 @@
 #![no_std]
 #![feature(impl_trait_in_assoc_type)]
 
+extern crate alloc;
+
+pub mod analog;
 pub mod board;
 pub mod channel;
 pub mod config;
+pub mod crash;
+pub mod debounce;
 pub mod fire_trigger;
+pub mod flash;
+pub mod netlog;
+pub mod ota;
 pub mod power;
 pub mod signal_light;
+pub mod sntp;
+pub mod storage;
 pub mod tasks;
 pub mod tmp107;
+pub mod watchdog;
 pub mod wifi;
 
 pub use board::I2cType;
@@ -0,0 +1,307 @@
+//! Network log transport.
+//!
+//! defmt records are interned and can only be decoded on the host with the
+//! firmware ELF, so they cannot be shipped as text. Messages that should
+//! reach the network are logged with [`net_error!`], [`net_warn!`],
+//! [`net_info!`] or [`net_debug!`] instead: they still go to defmt, and a
+//! formatted copy (level, module, uptime) lands in a RAM ring buffer.
+//!
+//! Sinks consume the ring through a [`LogReader`] at their own pace; a sink
+//! that falls behind gets a drop count instead of blocking the producers.
+//! [`spawn_syslog_sink`] ships records over UDP syslog (RFC 5424); binaries
+//! add their own sinks (MQTT, web socket) on top of [`reader`].
+
+pub mod rate;
+pub mod ring;
+
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::watch;
+use embassy_time::{Duration, Instant, Timer};
+use smoltcp::wire::DnsQueryType;
+
+pub use rate::RateLimiter;
+pub use ring::{Level, LogRecord, LogRing, ReadResult};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Records kept in RAM for sinks that fall behind or connect late.
+pub const RING_CAPACITY: usize = 64;
+/// Maximum number of concurrent [`LogReader`]s.
+pub const MAX_READERS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SyslogConfig {
+    /// Host name or IPv4 address of the syslog server.
+    pub server: &'static str,
+    pub port: u16,
+    /// HOSTNAME field of every message.
+    pub hostname: &'static str,
+    /// APP-NAME field of every message.
+    pub app_name: &'static str,
+    /// Least severe level that is shipped.
+    pub min_level: Level,
+    /// Average records per second; excess records are counted and dropped.
+    pub rate_per_second: u32,
+    pub burst: u32,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            server: "",
+            port: 514,
+            hostname: "-",
+            app_name: "mainboard",
+            min_level: Level::Info,
+            rate_per_second: 20,
+            burst: 40,
+        }
+    }
+}
+
+/// RFC 5424 facility "user-level messages".
+const SYSLOG_FACILITY_USER: u8 = 1;
+const SYSLOG_MAX_LEN: usize = 256;
+const RESOLVE_RETRY: Duration = Duration::from_secs(30);
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static LOG_RING: Mutex<CriticalSectionRawMutex, RefCell<LogRing<RING_CAPACITY>>> =
+    Mutex::new(RefCell::new(LogRing::new()));
+
+/// Carries the next sequence number; readers wait on it for new records.
+static LOG_SEQ: watch::Watch<CriticalSectionRawMutex, u32, MAX_READERS> = watch::Watch::new();
+
+static SYSLOG_STARTED: AtomicBool = AtomicBool::new(false);
+
+// ============================================================================
+// LOGGING
+// ============================================================================
+
+/// Log to defmt and the network ring. Use the `net_*!` macros instead.
+pub fn log(level: Level, module: &'static str, args: fmt::Arguments) {
+    let text = defmt::Display2Format(&args);
+    match level {
+        Level::Error => defmt::error!("[{}] {}", module, text),
+        Level::Warn => defmt::warn!("[{}] {}", module, text),
+        Level::Info => defmt::info!("[{}] {}", module, text),
+        Level::Debug => defmt::debug!("[{}] {}", module, text),
+    }
+
+    let record = LogRecord::new(level, Instant::now().as_millis(), module, args);
+    let seq = LOG_RING.lock(|ring| ring.borrow_mut().push(record));
+    LOG_SEQ.sender().send(seq.wrapping_add(1));
+}
+
+#[macro_export]
+macro_rules! net_error {
+    ($($arg:tt)*) => {
+        $crate::netlog::log($crate::netlog::Level::Error, module_path!(), format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! net_warn {
+    ($($arg:tt)*) => {
+        $crate::netlog::log($crate::netlog::Level::Warn, module_path!(), format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! net_info {
+    ($($arg:tt)*) => {
+        $crate::netlog::log($crate::netlog::Level::Info, module_path!(), format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! net_debug {
+    ($($arg:tt)*) => {
+        $crate::netlog::log($crate::netlog::Level::Debug, module_path!(), format_args!($($arg)*))
+    };
+}
+
+// ============================================================================
+// READER
+// ============================================================================
+
+pub struct LogReader {
+    next_seq: u32,
+    receiver: watch::Receiver<'static, CriticalSectionRawMutex, u32, MAX_READERS>,
+}
+
+/// Start reading at the oldest record still held. Returns `None` when
+/// [`MAX_READERS`] readers already exist.
+pub fn reader() -> Option<LogReader> {
+    let receiver = LOG_SEQ.receiver()?;
+    let next_seq = LOG_RING.lock(|ring| ring.borrow().oldest_seq());
+    Some(LogReader { next_seq, receiver })
+}
+
+impl LogReader {
+    /// Wait for the next record, or for a drop notice if this reader fell
+    /// behind the ring.
+    pub async fn next(&mut self) -> ReadResult {
+        loop {
+            let result = LOG_RING.lock(|ring| ring.borrow().read(self.next_seq));
+            match result {
+                Some(ReadResult::Record(record)) => {
+                    self.next_seq = record.seq.wrapping_add(1);
+                    return ReadResult::Record(record);
+                }
+                Some(ReadResult::Dropped(count)) => {
+                    self.next_seq = self.next_seq.wrapping_add(count);
+                    return ReadResult::Dropped(count);
+                }
+                None => {
+                    self.receiver.changed().await;
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// SPAWN METHOD
+// ============================================================================
+
+/// Ship log records to a syslog server over UDP.
+pub fn spawn_syslog_sink(spawner: &Spawner, stack: Stack<'static>, config: SyslogConfig) {
+    if SYSLOG_STARTED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        panic!("Syslog sink already started");
+    }
+
+    let reader = reader().expect("No log reader left for the syslog sink");
+    spawner
+        .spawn(syslog_task(stack, config, reader))
+        .expect("spawn syslog sink failed");
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Format an RFC 5424 message. The timestamp is left nil; the uptime is part
+/// of the message text.
+pub fn write_syslog<W: Write>(
+    out: &mut W,
+    record: &LogRecord,
+    hostname: &str,
+    app_name: &str,
+) -> fmt::Result {
+    let priority = SYSLOG_FACILITY_USER * 8 + record.level.syslog_severity();
+    write!(out, "<{}>1 - {} {} - - - ", priority, hostname, app_name)?;
+    record.write_line(out)
+}
+
+/// Cuts the message at the buffer end instead of dropping it.
+struct DatagramWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for DatagramWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let take = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        Ok(())
+    }
+}
+
+async fn send_record(
+    socket: &mut UdpSocket<'_>,
+    datagram: &mut [u8; SYSLOG_MAX_LEN],
+    endpoint: IpEndpoint,
+    config: &SyslogConfig,
+    record: &LogRecord,
+) {
+    let mut writer = DatagramWriter {
+        buf: datagram,
+        len: 0,
+    };
+    let _ = write_syslog(&mut writer, record, config.hostname, config.app_name);
+    let len = writer.len;
+    if socket.send_to(&datagram[..len], endpoint).await.is_err() {
+        warn!("Syslog: send failed");
+    }
+}
+
+async fn resolve_server(stack: Stack<'static>, config: &SyslogConfig) -> IpEndpoint {
+    loop {
+        match stack.dns_query(config.server, DnsQueryType::A).await {
+            Ok(addrs) if !addrs.is_empty() => return IpEndpoint::new(addrs[0], config.port),
+            Ok(_) => warn!("Syslog: no address for {}", config.server),
+            Err(e) => warn!("Syslog: failed to resolve {}: {:?}", config.server, e),
+        }
+        Timer::after(RESOLVE_RETRY).await;
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+async fn syslog_task(stack: Stack<'static>, config: SyslogConfig, mut reader: LogReader) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buf = [0u8; 16];
+    let mut tx_buf = [0u8; 4 * SYSLOG_MAX_LEN];
+    let mut datagram = [0u8; SYSLOG_MAX_LEN];
+    let mut limiter = RateLimiter::new(config.rate_per_second, config.burst);
+
+    stack.wait_config_up().await;
+    let endpoint = resolve_server(stack, &config).await;
+    info!("Syslog: shipping logs to {}", endpoint);
+
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
+    if let Err(e) = socket.bind(0) {
+        warn!("Syslog: failed to bind socket: {:?}", e);
+        return;
+    }
+
+    let mut dropped = 0u32;
+    loop {
+        let record = match reader.next().await {
+            ReadResult::Record(record) => record,
+            ReadResult::Dropped(count) => {
+                dropped = dropped.saturating_add(count);
+                continue;
+            }
+        };
+        if record.level > config.min_level || !limiter.allow(Instant::now().as_millis()) {
+            continue;
+        }
+
+        // Report losses once the rate allows sending again
+        dropped = dropped.saturating_add(limiter.take_suppressed());
+        if dropped > 0 {
+            let notice = LogRecord::new(
+                Level::Warn,
+                record.uptime_ms,
+                module_path!(),
+                format_args!("{} log records dropped", dropped),
+            );
+            send_record(&mut socket, &mut datagram, endpoint, &config, &notice).await;
+            dropped = 0;
+        }
+        send_record(&mut socket, &mut datagram, endpoint, &config, &record).await;
+    }
+}
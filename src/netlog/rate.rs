@@ -0,0 +1,47 @@
+//! Token bucket used by the log sinks so a log storm cannot flood the
+//! network or starve other traffic.
+
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    per_second: u32,
+    burst: u32,
+    /// Available tokens, in thousandths.
+    tokens_milli: u64,
+    last_ms: u64,
+    suppressed: u32,
+}
+
+impl RateLimiter {
+    /// Allow `per_second` records on average and up to `burst` at once.
+    pub const fn new(per_second: u32, burst: u32) -> Self {
+        Self {
+            per_second,
+            burst,
+            tokens_milli: burst as u64 * 1000,
+            last_ms: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Take a token at `now_ms`. Refused records are counted, see
+    /// [`Self::take_suppressed`].
+    pub fn allow(&mut self, now_ms: u64) -> bool {
+        let elapsed = now_ms.saturating_sub(self.last_ms);
+        self.last_ms = now_ms;
+        self.tokens_milli =
+            (self.tokens_milli + elapsed * self.per_second as u64).min(self.burst as u64 * 1000);
+
+        if self.tokens_milli >= 1000 {
+            self.tokens_milli -= 1000;
+            true
+        } else {
+            self.suppressed = self.suppressed.saturating_add(1);
+            false
+        }
+    }
+
+    /// Number of records refused since the last call.
+    pub fn take_suppressed(&mut self) -> u32 {
+        core::mem::take(&mut self.suppressed)
+    }
+}
@@ -0,0 +1,163 @@
+//! Formatted log records and the fixed-size ring that holds them.
+
+use core::fmt;
+
+/// Longest message text kept per record; longer text is cut.
+pub const TEXT_MAX_LEN: usize = 96;
+
+/// Log level, ordered from most to least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+
+    /// RFC 5424 severity.
+    pub const fn syslog_severity(self) -> u8 {
+        match self {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogRecord {
+    /// Position in the log, assigned by [`LogRing::push`].
+    pub seq: u32,
+    pub level: Level,
+    pub uptime_ms: u64,
+    pub module: &'static str,
+    pub truncated: bool,
+    text: [u8; TEXT_MAX_LEN],
+    text_len: u8,
+}
+
+impl LogRecord {
+    pub fn new(level: Level, uptime_ms: u64, module: &'static str, args: fmt::Arguments) -> Self {
+        let mut record = Self {
+            seq: 0,
+            level,
+            uptime_ms,
+            module,
+            truncated: false,
+            text: [0; TEXT_MAX_LEN],
+            text_len: 0,
+        };
+        let _ = fmt::write(&mut record, args);
+        record
+    }
+
+    pub fn text(&self) -> &str {
+        core::str::from_utf8(&self.text[..self.text_len as usize]).unwrap_or("")
+    }
+
+    /// `[12.345] WARN module: text`
+    pub fn write_line<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        write!(
+            out,
+            "[{}.{:03}] {} {}: {}",
+            self.uptime_ms / 1000,
+            self.uptime_ms % 1000,
+            self.level.as_str(),
+            self.module,
+            self.text()
+        )?;
+        if self.truncated {
+            out.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for LogRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let used = self.text_len as usize;
+        let mut len = s.len().min(TEXT_MAX_LEN - used);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        if len < s.len() {
+            self.truncated = true;
+        }
+        self.text[used..used + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.text_len = (used + len) as u8;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReadResult {
+    Record(LogRecord),
+    /// The reader fell behind and this many records were overwritten.
+    Dropped(u32),
+}
+
+/// Keeps the last `N` records. Readers track their own sequence number, so
+/// any number of them can consume the ring at their own pace.
+pub struct LogRing<const N: usize> {
+    records: [Option<LogRecord>; N],
+    next_seq: u32,
+}
+
+impl<const N: usize> Default for LogRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LogRing<N> {
+    pub const fn new() -> Self {
+        Self {
+            records: [None; N],
+            next_seq: 0,
+        }
+    }
+
+    /// Store a record, overwriting the oldest one when full. Returns the
+    /// sequence number assigned to it.
+    pub fn push(&mut self, mut record: LogRecord) -> u32 {
+        let seq = self.next_seq;
+        record.seq = seq;
+        self.records[seq as usize % N] = Some(record);
+        self.next_seq = seq.wrapping_add(1);
+        seq
+    }
+
+    /// Sequence number the next record will get.
+    pub const fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    /// Oldest sequence number still held.
+    pub const fn oldest_seq(&self) -> u32 {
+        self.next_seq.saturating_sub(N as u32)
+    }
+
+    /// Record `seq`, a drop notice if it was overwritten, or `None` if it
+    /// has not been written yet.
+    pub fn read(&self, seq: u32) -> Option<ReadResult> {
+        let oldest = self.oldest_seq();
+        if seq < oldest {
+            return Some(ReadResult::Dropped(oldest - seq));
+        }
+        if seq >= self.next_seq {
+            return None;
+        }
+        self.records[seq as usize % N].map(ReadResult::Record)
+    }
+}
@@ -0,0 +1,38 @@
+//! Why an update request failed, as reported to the uploader.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum OtaError {
+    /// An update is already in progress.
+    Busy,
+    /// Write/finish without a preceding begin.
+    NotStarted,
+    /// The running image has not confirmed its health yet; updating now
+    /// would overwrite the rollback target.
+    PendingVerify,
+    InvalidSize,
+    ImageTooLarge,
+    UnexpectedOffset,
+    InvalidImage,
+    SizeMismatch,
+    DigestMismatch,
+    PartitionTable,
+    Flash,
+}
+
+impl OtaError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Busy => "BUSY",
+            Self::NotStarted => "NOT_STARTED",
+            Self::PendingVerify => "PENDING_VERIFY",
+            Self::InvalidSize => "INVALID_SIZE",
+            Self::ImageTooLarge => "IMAGE_TOO_LARGE",
+            Self::UnexpectedOffset => "UNEXPECTED_OFFSET",
+            Self::InvalidImage => "INVALID_IMAGE",
+            Self::SizeMismatch => "SIZE_MISMATCH",
+            Self::DigestMismatch => "DIGEST_MISMATCH",
+            Self::PartitionTable => "PARTITION_TABLE",
+            Self::Flash => "FLASH",
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Transport independent image bookkeeping: enforces sequential writes,
+//! bounds the image by the partition size and checks the SHA-256 digest once
+//! every byte has been received.
+
+use sha2::{Digest, Sha256};
+
+use super::OtaError;
+
+/// First byte of every ESP application image.
+pub const ESP_IMAGE_MAGIC: u8 = 0xE9;
+pub const SHA256_LEN: usize = 32;
+
+pub struct ImageReceiver {
+    size: u32,
+    expected_sha256: [u8; SHA256_LEN],
+    written: u32,
+    hasher: Sha256,
+}
+
+impl ImageReceiver {
+    pub fn new(
+        size: u32,
+        expected_sha256: [u8; SHA256_LEN],
+        capacity: u32,
+    ) -> Result<Self, OtaError> {
+        if size == 0 {
+            return Err(OtaError::InvalidSize);
+        }
+        if size > capacity {
+            return Err(OtaError::ImageTooLarge);
+        }
+
+        Ok(Self {
+            size,
+            expected_sha256,
+            written: 0,
+            hasher: Sha256::new(),
+        })
+    }
+
+    pub const fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub const fn written(&self) -> u32 {
+        self.written
+    }
+
+    /// Validate a chunk before it is written to flash.
+    pub fn check(&self, offset: u32, data: &[u8]) -> Result<(), OtaError> {
+        if offset != self.written {
+            return Err(OtaError::UnexpectedOffset);
+        }
+        if data.is_empty() {
+            return Err(OtaError::InvalidSize);
+        }
+        let end = offset
+            .checked_add(data.len() as u32)
+            .ok_or(OtaError::SizeMismatch)?;
+        if end > self.size {
+            return Err(OtaError::SizeMismatch);
+        }
+        if offset == 0 && data[0] != ESP_IMAGE_MAGIC {
+            return Err(OtaError::InvalidImage);
+        }
+        Ok(())
+    }
+
+    /// Account for a chunk that has been written successfully.
+    pub fn commit(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.written += data.len() as u32;
+    }
+
+    /// Verify that the whole image arrived and matches the expected digest.
+    pub fn finish(self) -> Result<(), OtaError> {
+        if self.written != self.size {
+            return Err(OtaError::SizeMismatch);
+        }
+        let digest = self.hasher.finalize();
+        if digest.as_slice() != self.expected_sha256 {
+            return Err(OtaError::DigestMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a 64 character hex SHA-256 digest (case-insensitive).
+pub fn parse_sha256_hex(hex: &[u8]) -> Option<[u8; SHA256_LEN]> {
+    if hex.len() != SHA256_LEN * 2 {
+        return None;
+    }
+
+    let mut out = [0u8; SHA256_LEN];
+    for (byte, pair) in out.iter_mut().zip(hex.chunks_exact(2)) {
+        *byte = (hex_nibble(pair[0])? << 4) | hex_nibble(pair[1])?;
+    }
+    Some(out)
+}
+
+fn hex_nibble(value: u8) -> Option<u8> {
+    match value {
+        b'0'..=b'9' => Some(value - b'0'),
+        b'a'..=b'f' => Some(value - b'a' + 10),
+        b'A'..=b'F' => Some(value - b'A' + 10),
+        _ => None,
+    }
+}
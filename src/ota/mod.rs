@@ -0,0 +1,406 @@
+//! Over-the-air firmware updates.
+//!
+//! Images are written to the inactive app partition (`ota_0`/`ota_1` in
+//! `partitions.csv`) through the esp-idf bootloader OTA data. A freshly
+//! activated image boots in the "pending verify" state and must call
+//! [`OtaHandle::confirm_healthy`] within [`OtaConfig::health_timeout`];
+//! otherwise, or if it resets more than [`OtaConfig::max_trial_boots`] times
+//! before confirming, the previous image is re-activated and the chip resets.
+
+pub mod error;
+pub mod image;
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{error, info};
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::ota::OtaImageState;
+use esp_bootloader_esp_idf::ota_updater::OtaUpdater;
+use esp_bootloader_esp_idf::partitions::PARTITION_TABLE_MAX_LEN;
+
+use crate::channel::RequestResponseChannel;
+use crate::flash::{lock_flash, FlashType};
+use crate::{net_info, net_warn};
+
+pub use error::OtaError;
+pub use image::{parse_sha256_hex, ImageReceiver, SHA256_LEN};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+pub enum OtaRequest {
+    Begin { size: u32, sha256: [u8; SHA256_LEN] },
+    Write { offset: u32, data: Vec<u8> },
+    Finish,
+    Abort,
+    ConfirmHealthy,
+}
+
+pub enum OtaResponse {
+    Ok(OtaProgress),
+    Err(OtaError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct OtaProgress {
+    pub written: u32,
+    pub size: u32,
+}
+
+impl OtaProgress {
+    const IDLE: Self = Self {
+        written: 0,
+        size: 0,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum BootState {
+    /// Running image is confirmed (or was flashed over the cable).
+    Confirmed,
+    /// Running image was installed over the air and awaits confirmation.
+    PendingVerify,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OtaConfig {
+    pub health_timeout: Duration,
+    pub max_trial_boots: u32,
+    /// Delay between acknowledging `Finish` and resetting into the new image.
+    pub reboot_delay: Duration,
+}
+
+impl Default for OtaConfig {
+    fn default() -> Self {
+        Self {
+            health_timeout: Duration::from_secs(120),
+            max_trial_boots: 3,
+            reboot_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static OTA_CONTROL: RequestResponseChannel<OtaRequest, OtaResponse, 4> =
+    RequestResponseChannel::with_static_channels();
+
+static OTA_STARTED: AtomicBool = AtomicBool::new(false);
+static PENDING_VERIFY: AtomicBool = AtomicBool::new(false);
+
+/// Number of boots of an unconfirmed image; survives software and watchdog
+/// resets but not power loss. Guarded by a magic word since RTC RAM is not
+/// initialized on first boot.
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut TRIAL_BOOTS: [u32; 2] = [0; 2];
+
+const TRIAL_BOOTS_MAGIC: u32 = 0x07A5_B007;
+
+// ============================================================================
+// SPAWN METHOD
+// ============================================================================
+
+/// Start the OTA service. Requires [`crate::flash::init_flash`].
+pub fn spawn_ota_service(spawner: &Spawner, config: OtaConfig) -> OtaHandle {
+    if OTA_STARTED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        panic!("OTA service already started");
+    }
+
+    spawner
+        .spawn(ota_task(config))
+        .expect("spawn OTA service failed");
+
+    OtaHandle { _priv: PhantomData }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn increment_trial_boots() -> u32 {
+    // SAFETY: only accessed from the OTA task
+    unsafe {
+        let slot = addr_of_mut!(TRIAL_BOOTS);
+        let mut value = slot.read_volatile();
+        if value[0] != TRIAL_BOOTS_MAGIC {
+            value = [TRIAL_BOOTS_MAGIC, 0];
+        }
+        value[1] = value[1].saturating_add(1);
+        slot.write_volatile(value);
+        value[1]
+    }
+}
+
+fn clear_trial_boots() {
+    // SAFETY: only accessed from the OTA task
+    unsafe { addr_of_mut!(TRIAL_BOOTS).write_volatile([0; 2]) }
+}
+
+fn updater<'a>(
+    flash: &'a mut FlashType,
+    buffer: &'a mut [u8; PARTITION_TABLE_MAX_LEN],
+) -> Result<OtaUpdater<'a, FlashType>, OtaError> {
+    OtaUpdater::new(flash, buffer).map_err(|e| {
+        error!("OTA: failed to read partition table: {:?}", e);
+        OtaError::PartitionTable
+    })
+}
+
+/// Invalidate the running image and re-activate the other slot.
+fn rollback(ota: &mut OtaUpdater<'_, FlashType>) -> Result<(), OtaError> {
+    ota.set_current_ota_state(OtaImageState::Invalid)
+        .and_then(|_| ota.activate_next_partition())
+        .and_then(|_| ota.set_current_ota_state(OtaImageState::Valid))
+        .map_err(|e| {
+            error!("OTA: rollback failed: {:?}", e);
+            OtaError::Flash
+        })
+}
+
+async fn check_boot_state(
+    buffer: &mut [u8; PARTITION_TABLE_MAX_LEN],
+    config: &OtaConfig,
+) -> Result<BootState, OtaError> {
+    let mut flash = lock_flash().await;
+    let mut ota = updater(&mut flash, buffer)?;
+
+    let state = ota.current_ota_state();
+    info!(
+        "OTA: running {:?}, image state {:?}",
+        ota.selected_partition(),
+        state
+    );
+
+    match state {
+        Ok(OtaImageState::New) | Ok(OtaImageState::PendingVerify) => {
+            let boots = increment_trial_boots();
+            if boots > config.max_trial_boots {
+                error!(
+                    "OTA: image failed to confirm after {} boots, rolling back",
+                    boots - 1
+                );
+                clear_trial_boots();
+                rollback(&mut ota)?;
+                esp_hal::system::software_reset();
+            }
+            if state == Ok(OtaImageState::New) {
+                ota.set_current_ota_state(OtaImageState::PendingVerify)
+                    .map_err(|_| OtaError::Flash)?;
+            }
+            Ok(BootState::PendingVerify)
+        }
+        _ => {
+            clear_trial_boots();
+            Ok(BootState::Confirmed)
+        }
+    }
+}
+
+async fn handle_ota_request(
+    request: OtaRequest,
+    session: &mut Option<ImageReceiver>,
+    buffer: &mut [u8; PARTITION_TABLE_MAX_LEN],
+) -> Result<OtaProgress, OtaError> {
+    match request {
+        OtaRequest::Begin { size, sha256 } => {
+            if session.is_some() {
+                return Err(OtaError::Busy);
+            }
+            if PENDING_VERIFY.load(Ordering::Acquire) {
+                return Err(OtaError::PendingVerify);
+            }
+
+            let mut flash = lock_flash().await;
+            let mut ota = updater(&mut flash, buffer)?;
+            let (region, slot) = ota.next_partition().map_err(|_| OtaError::PartitionTable)?;
+            let receiver = ImageReceiver::new(size, sha256, region.capacity() as u32)?;
+
+            info!("OTA: writing {} bytes to {:?}", size, slot);
+            *session = Some(receiver);
+            Ok(OtaProgress { written: 0, size })
+        }
+        OtaRequest::Write { offset, data } => {
+            let receiver = session.as_mut().ok_or(OtaError::NotStarted)?;
+            receiver.check(offset, &data)?;
+
+            let mut flash = lock_flash().await;
+            let mut ota = updater(&mut flash, buffer)?;
+            let (mut region, _) = ota.next_partition().map_err(|_| OtaError::PartitionTable)?;
+            if let Err(e) = region.write(offset, &data) {
+                error!("OTA: flash write at {} failed: {:?}", offset, e);
+                *session = None;
+                return Err(OtaError::Flash);
+            }
+
+            receiver.commit(&data);
+            Ok(OtaProgress {
+                written: receiver.written(),
+                size: receiver.size(),
+            })
+        }
+        OtaRequest::Finish => {
+            let receiver = session.take().ok_or(OtaError::NotStarted)?;
+            let size = receiver.size();
+            receiver.finish()?;
+
+            let mut flash = lock_flash().await;
+            let mut ota = updater(&mut flash, buffer)?;
+            ota.activate_next_partition()
+                .and_then(|_| ota.set_current_ota_state(OtaImageState::New))
+                .map_err(|e| {
+                    error!("OTA: failed to activate new image: {:?}", e);
+                    OtaError::Flash
+                })?;
+
+            net_info!("OTA: image verified and activated");
+            Ok(OtaProgress {
+                written: size,
+                size,
+            })
+        }
+        OtaRequest::Abort => {
+            if session.take().is_some() {
+                net_warn!("OTA: update aborted");
+            }
+            Ok(OtaProgress::IDLE)
+        }
+        OtaRequest::ConfirmHealthy => {
+            if !PENDING_VERIFY.load(Ordering::Acquire) {
+                return Ok(OtaProgress::IDLE);
+            }
+
+            let mut flash = lock_flash().await;
+            let mut ota = updater(&mut flash, buffer)?;
+            ota.set_current_ota_state(OtaImageState::Valid)
+                .map_err(|_| OtaError::Flash)?;
+
+            clear_trial_boots();
+            PENDING_VERIFY.store(false, Ordering::Release);
+            net_info!("OTA: running image confirmed");
+            Ok(OtaProgress::IDLE)
+        }
+    }
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+async fn ota_task(config: OtaConfig) {
+    let mut buffer = [0u8; PARTITION_TABLE_MAX_LEN];
+    let mut session: Option<ImageReceiver> = None;
+
+    let mut health_deadline = match check_boot_state(&mut buffer, &config).await {
+        Ok(BootState::PendingVerify) => {
+            net_warn!(
+                "OTA: image pending verification, confirm within {} s",
+                config.health_timeout.as_secs()
+            );
+            PENDING_VERIFY.store(true, Ordering::Release);
+            Some(Instant::now() + config.health_timeout)
+        }
+        Ok(BootState::Confirmed) => None,
+        Err(e) => {
+            error!("OTA: failed to read boot state: {:?}", e);
+            None
+        }
+    };
+
+    loop {
+        let request = match health_deadline {
+            Some(deadline) if PENDING_VERIFY.load(Ordering::Acquire) => {
+                match select(OTA_CONTROL.recv_request(), Timer::at(deadline)).await {
+                    Either::First(request) => request,
+                    Either::Second(()) => {
+                        error!("OTA: health not confirmed in time, rolling back");
+                        let mut flash = lock_flash().await;
+                        if let Ok(mut ota) = updater(&mut flash, &mut buffer) {
+                            if rollback(&mut ota).is_ok() {
+                                clear_trial_boots();
+                                esp_hal::system::software_reset();
+                            }
+                        }
+                        health_deadline = None;
+                        continue;
+                    }
+                }
+            }
+            _ => OTA_CONTROL.recv_request().await,
+        };
+
+        let finishing = matches!(request, OtaRequest::Finish);
+        let response = match handle_ota_request(request, &mut session, &mut buffer).await {
+            Ok(progress) => OtaResponse::Ok(progress),
+            Err(e) => {
+                net_warn!("OTA: request failed: {:?}", e);
+                OtaResponse::Err(e)
+            }
+        };
+        let reboot = finishing && matches!(response, OtaResponse::Ok(_));
+        OTA_CONTROL.send_response(response).await;
+
+        if reboot {
+            net_info!("OTA: rebooting into new image");
+            Timer::after(config.reboot_delay).await;
+            esp_hal::system::software_reset();
+        }
+    }
+}
+
+// ============================================================================
+// HANDLE
+// ============================================================================
+
+#[derive(Clone, Copy)]
+pub struct OtaHandle {
+    _priv: PhantomData<()>,
+}
+
+impl OtaHandle {
+    pub async fn transact(&self, req: OtaRequest) -> OtaResponse {
+        OTA_CONTROL.transact(req).await
+    }
+
+    pub async fn begin(&self, size: u32, sha256: [u8; SHA256_LEN]) -> OtaResponse {
+        self.transact(OtaRequest::Begin { size, sha256 }).await
+    }
+
+    pub async fn write(&self, offset: u32, data: Vec<u8>) -> OtaResponse {
+        self.transact(OtaRequest::Write { offset, data }).await
+    }
+
+    pub async fn finish(&self) -> OtaResponse {
+        self.transact(OtaRequest::Finish).await
+    }
+
+    pub async fn abort(&self) -> OtaResponse {
+        self.transact(OtaRequest::Abort).await
+    }
+
+    /// Mark the running image as good. Call once the application has
+    /// verified it is operational (e.g. connected to the network).
+    pub async fn confirm_healthy(&self) -> OtaResponse {
+        self.transact(OtaRequest::ConfirmHealthy).await
+    }
+
+    pub fn boot_state(&self) -> BootState {
+        if PENDING_VERIFY.load(Ordering::Acquire) {
+            BootState::PendingVerify
+        } else {
+            BootState::Confirmed
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! Pure SNTP math: timestamp conversion, offset/delay computation and
+//! sample filtering. Nothing here touches the network or the clock, so it
+//! can be exercised on the host.
+//!
+//! Local time is the monotonic microsecond counter since boot; remote time
+//! is UTC in microseconds since the Unix epoch. The "offset" is therefore
+//! `utc_us - monotonic_us`, i.e. the value that maps boot time onto UTC.
+
+/// Seconds between the NTP era 0 epoch (1900-01-01) and the Unix epoch.
+pub const NTP_UNIX_EPOCH_DELTA_SECS: u64 = 2_208_988_800;
+
+/// Maximum number of samples kept by [`SampleFilter`].
+pub const MAX_SAMPLES: usize = 8;
+
+/// Delay below which a sync is considered [`SyncQuality::Fine`].
+pub const FINE_DELAY_US: i64 = 20_000;
+/// Jitter below which a sync is considered [`SyncQuality::Fine`].
+pub const FINE_JITTER_US: i64 = 5_000;
+/// Age after which the last sync is considered [`SyncQuality::Stale`].
+pub const STALE_AFTER_US: u64 = 2 * 60 * 60 * 1_000_000;
+
+/// Convert a 64-bit NTP timestamp (32.32 fixed point) to Unix
+/// microseconds. Returns `None` for the zero timestamp, which servers use
+/// to mean "unknown".
+///
+/// The seconds wrap in 2036; as in RFC 4330, a timestamp with the top bit
+/// clear is taken to be past the wrap, which covers 1968 to 2104.
+pub fn ntp_to_unix_micros(timestamp: u64) -> Option<i64> {
+    if timestamp == 0 {
+        return None;
+    }
+    let mut seconds = timestamp >> 32;
+    if seconds & 0x8000_0000 == 0 {
+        seconds += 1 << 32;
+    }
+    let fraction = timestamp & 0xFFFF_FFFF;
+    let micros = (fraction * 1_000_000) >> 32;
+    Some((seconds as i64 - NTP_UNIX_EPOCH_DELTA_SECS as i64) * 1_000_000 + micros as i64)
+}
+
+/// Inverse of [`ntp_to_unix_micros`].
+pub fn unix_micros_to_ntp(micros: i64) -> u64 {
+    let seconds = micros.div_euclid(1_000_000) + NTP_UNIX_EPOCH_DELTA_SECS as i64;
+    let sub = micros.rem_euclid(1_000_000) as u64;
+    // Round the fraction up so that converting back yields the same microsecond
+    ((seconds as u64) << 32) | ((sub << 32).div_ceil(1_000_000))
+}
+
+/// One request/response exchange with a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct SyncSample {
+    /// `utc_us - monotonic_us` estimated from this exchange.
+    pub offset_us: i64,
+    /// Round-trip network delay, excluding server processing time.
+    pub delay_us: i64,
+    /// Local monotonic time the response was received.
+    pub taken_at_us: u64,
+}
+
+impl SyncSample {
+    /// Build a sample from the four classic NTP timestamps:
+    ///
+    /// - `t1`: local monotonic time the request was sent
+    /// - `t2`: server UTC time the request was received
+    /// - `t3`: server UTC time the response was sent
+    /// - `t4`: local monotonic time the response was received
+    ///
+    /// offset = ((t2 - t1) + (t3 - t4)) / 2, delay = (t4 - t1) - (t3 - t2).
+    /// Returns `None` if the local timestamps run backwards. A slightly
+    /// negative delay (server clock granularity) is clamped to zero.
+    pub fn from_timestamps(t1: u64, t2: i64, t3: i64, t4: u64) -> Option<Self> {
+        if t4 < t1 {
+            return None;
+        }
+        let t1_i = t1 as i64;
+        let t4_i = t4 as i64;
+
+        let offset_us = ((t2 - t1_i) + (t3 - t4_i)) / 2;
+        let delay_us = ((t4_i - t1_i) - (t3 - t2)).max(0);
+
+        Some(Self {
+            offset_us,
+            delay_us,
+            taken_at_us: t4,
+        })
+    }
+}
+
+/// Sync quality, ordered from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum SyncQuality {
+    /// No time source at all; the mapping is meaningless.
+    Unsynchronized,
+    /// Seeded from a battery-backed RTC, never confirmed over the network.
+    Rtc,
+    /// Network synced, but the last good sync is older than [`STALE_AFTER_US`].
+    Stale,
+    /// Network synced with high delay or jitter.
+    Coarse,
+    /// Network synced with low delay and jitter.
+    Fine,
+}
+
+impl SyncQuality {
+    pub fn classify(delay_us: i64, jitter_us: i64, age_us: u64) -> Self {
+        if age_us > STALE_AFTER_US {
+            SyncQuality::Stale
+        } else if delay_us <= FINE_DELAY_US && jitter_us <= FINE_JITTER_US {
+            SyncQuality::Fine
+        } else {
+            SyncQuality::Coarse
+        }
+    }
+}
+
+/// Keeps the last [`MAX_SAMPLES`] samples and selects the one with the
+/// smallest round-trip delay (the classic NTP clock filter: the shortest
+/// exchange has the least asymmetric queueing error).
+#[derive(Debug, Clone)]
+pub struct SampleFilter {
+    samples: [Option<SyncSample>; MAX_SAMPLES],
+    next: usize,
+}
+
+impl Default for SampleFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SampleFilter {
+    pub const fn new() -> Self {
+        Self {
+            samples: [None; MAX_SAMPLES],
+            next: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples = [None; MAX_SAMPLES];
+        self.next = 0;
+    }
+
+    pub fn push(&mut self, sample: SyncSample) {
+        self.samples[self.next] = Some(sample);
+        self.next = (self.next + 1) % MAX_SAMPLES;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Minimum-delay sample, if any.
+    pub fn best(&self) -> Option<SyncSample> {
+        self.samples
+            .iter()
+            .flatten()
+            .min_by_key(|s| s.delay_us)
+            .copied()
+    }
+
+    /// Largest absolute offset difference between any sample and the best
+    /// one. Zero with fewer than two samples.
+    pub fn jitter_us(&self) -> i64 {
+        let Some(best) = self.best() else {
+            return 0;
+        };
+        self.samples
+            .iter()
+            .flatten()
+            .map(|s| (s.offset_us - best.offset_us).abs())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Mapping from the local monotonic clock to UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct TimeMapping {
+    /// `utc_us - monotonic_us`.
+    pub offset_us: i64,
+    /// Round-trip delay of the sample the offset came from.
+    pub delay_us: i64,
+    /// Offset spread across the filtered samples.
+    pub jitter_us: i64,
+    /// Local monotonic time of the sync.
+    pub synced_at_us: u64,
+    pub quality: SyncQuality,
+}
+
+impl TimeMapping {
+    pub const UNSYNCHRONIZED: Self = Self {
+        offset_us: 0,
+        delay_us: 0,
+        jitter_us: 0,
+        synced_at_us: 0,
+        quality: SyncQuality::Unsynchronized,
+    };
+
+    /// Mapping from a filtered set of network samples.
+    pub fn from_filter(filter: &SampleFilter, now_us: u64) -> Option<Self> {
+        let best = filter.best()?;
+        let jitter_us = filter.jitter_us();
+        Some(Self {
+            offset_us: best.offset_us,
+            delay_us: best.delay_us,
+            jitter_us,
+            synced_at_us: best.taken_at_us,
+            quality: SyncQuality::classify(
+                best.delay_us,
+                jitter_us,
+                now_us.saturating_sub(best.taken_at_us),
+            ),
+        })
+    }
+
+    /// Mapping seeded from an RTC reading of `utc_us` taken at `monotonic_us`.
+    pub fn from_rtc(utc_us: i64, monotonic_us: u64) -> Self {
+        Self {
+            offset_us: utc_us - monotonic_us as i64,
+            delay_us: 0,
+            jitter_us: 0,
+            synced_at_us: monotonic_us,
+            quality: SyncQuality::Rtc,
+        }
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        self.quality != SyncQuality::Unsynchronized
+    }
+
+    /// UTC microseconds since the Unix epoch at local monotonic `monotonic_us`.
+    pub fn utc_micros_at(&self, monotonic_us: u64) -> i64 {
+        monotonic_us as i64 + self.offset_us
+    }
+
+    /// Local monotonic microseconds at UTC `utc_us`, or `None` if that
+    /// instant lies before boot.
+    pub fn monotonic_micros_at(&self, utc_us: i64) -> Option<u64> {
+        u64::try_from(utc_us - self.offset_us).ok()
+    }
+
+    /// Re-evaluate quality for the current time, downgrading to
+    /// [`SyncQuality::Stale`] once the last network sync is too old.
+    pub fn aged(mut self, now_us: u64) -> Self {
+        if matches!(self.quality, SyncQuality::Fine | SyncQuality::Coarse)
+            && now_us.saturating_sub(self.synced_at_us) > STALE_AFTER_US
+        {
+            self.quality = SyncQuality::Stale;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2025-01-01T00:00:00Z
+    const UNIX_2025_US: i64 = 1_735_689_600_000_000;
+    /// 2036-02-07T06:28:16Z, where the NTP seconds wrap
+    const NTP_WRAP_US: i64 = (1 << 32) * 1_000_000 - NTP_UNIX_EPOCH_DELTA_SECS as i64 * 1_000_000;
+
+    fn sample(offset_us: i64, delay_us: i64, taken_at_us: u64) -> SyncSample {
+        SyncSample {
+            offset_us,
+            delay_us,
+            taken_at_us,
+        }
+    }
+
+    #[test]
+    fn ntp_epoch_converts() {
+        let unix_epoch = NTP_UNIX_EPOCH_DELTA_SECS << 32;
+        assert_eq!(ntp_to_unix_micros(unix_epoch), Some(0));
+        assert_eq!(ntp_to_unix_micros(unix_epoch | 0x8000_0000), Some(500_000));
+        assert_eq!(ntp_to_unix_micros(0), None);
+    }
+
+    #[test]
+    fn ntp_round_trips() {
+        for micros in [
+            0,
+            1,
+            999_999,
+            UNIX_2025_US,
+            UNIX_2025_US + 123_457,
+            NTP_WRAP_US - 1,
+        ] {
+            let timestamp = unix_micros_to_ntp(micros);
+            assert_eq!(ntp_to_unix_micros(timestamp), Some(micros), "{micros}");
+        }
+    }
+
+    #[test]
+    fn ntp_seconds_wrap_in_2036() {
+        assert_eq!(ntp_to_unix_micros(1 << 32), Some(NTP_WRAP_US + 1_000_000));
+        assert_eq!(unix_micros_to_ntp(NTP_WRAP_US + 1_000_000), 1 << 32);
+        let after_wrap = NTP_WRAP_US + 86_400_000_000 + 250_000;
+        assert_eq!(
+            ntp_to_unix_micros(unix_micros_to_ntp(after_wrap)),
+            Some(after_wrap)
+        );
+    }
+
+    #[test]
+    fn symmetric_exchange() {
+        // 10 ms each way, 2 ms in the server
+        let offset = UNIX_2025_US;
+        let t1 = 5_000_000;
+        let t2 = t1 as i64 + offset + 10_000;
+        let t3 = t2 + 2_000;
+        let t4 = t1 + 22_000;
+        let sample = SyncSample::from_timestamps(t1, t2, t3, t4).unwrap();
+        assert_eq!(sample.offset_us, offset);
+        assert_eq!(sample.delay_us, 20_000);
+        assert_eq!(sample.taken_at_us, t4);
+    }
+
+    #[test]
+    fn asymmetric_exchange_splits_the_error() {
+        // 30 ms out, 10 ms back: the offset is off by half the difference
+        let t1 = 1_000;
+        let t2 = t1 as i64 + UNIX_2025_US + 30_000;
+        let t3 = t2;
+        let t4 = t1 + 40_000;
+        let sample = SyncSample::from_timestamps(t1, t2, t3, t4).unwrap();
+        assert_eq!(sample.offset_us, UNIX_2025_US + 10_000);
+        assert_eq!(sample.delay_us, 40_000);
+    }
+
+    #[test]
+    fn negative_offset() {
+        // Server time behind the local counter
+        let t1 = 10_000_000;
+        let t2 = 4_000_000;
+        let t3 = 4_000_000;
+        let t4 = 10_002_000;
+        let sample = SyncSample::from_timestamps(t1, t2, t3, t4).unwrap();
+        assert_eq!(sample.offset_us, -6_001_000);
+        assert_eq!(sample.delay_us, 2_000);
+    }
+
+    #[test]
+    fn server_granularity_clamps_delay() {
+        // The server claims to have held the request longer than the round trip
+        let sample = SyncSample::from_timestamps(0, 100, 1_200, 1_000).unwrap();
+        assert_eq!(sample.delay_us, 0);
+    }
+
+    #[test]
+    fn backwards_local_clock_rejected() {
+        assert_eq!(SyncSample::from_timestamps(2_000, 0, 0, 1_999), None);
+    }
+
+    #[test]
+    fn filter_prefers_shortest_exchange() {
+        let mut filter = SampleFilter::new();
+        assert!(filter.is_empty());
+        assert_eq!(filter.best(), None);
+        assert_eq!(filter.jitter_us(), 0);
+
+        filter.push(sample(1_000, 30_000, 1));
+        filter.push(sample(1_200, 8_000, 2));
+        // Queued behind other traffic: far off, and slow
+        filter.push(sample(250_000, 400_000, 3));
+        filter.push(sample(900, 12_000, 4));
+
+        assert_eq!(filter.len(), 4);
+        assert_eq!(filter.best(), Some(sample(1_200, 8_000, 2)));
+        assert_eq!(filter.jitter_us(), 248_800);
+    }
+
+    #[test]
+    fn filter_forgets_oldest() {
+        let mut filter = SampleFilter::new();
+        filter.push(sample(0, 1_000, 0));
+        for taken_at_us in 1..=MAX_SAMPLES as u64 {
+            filter.push(sample(500, 5_000, taken_at_us));
+        }
+        assert_eq!(filter.len(), MAX_SAMPLES);
+        assert_eq!(filter.best().unwrap().offset_us, 500);
+        assert_eq!(filter.jitter_us(), 0);
+
+        filter.clear();
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn mapping_quality() {
+        let mut filter = SampleFilter::new();
+        filter.push(sample(UNIX_2025_US, FINE_DELAY_US, 1_000));
+        let mapping = TimeMapping::from_filter(&filter, 2_000).unwrap();
+        assert_eq!(mapping.quality, SyncQuality::Fine);
+        assert_eq!(mapping.utc_micros_at(1_000), UNIX_2025_US + 1_000);
+        assert_eq!(
+            mapping.monotonic_micros_at(UNIX_2025_US + 1_000),
+            Some(1_000)
+        );
+        assert_eq!(mapping.monotonic_micros_at(UNIX_2025_US - 1), None);
+        assert_eq!(
+            mapping.aged(1_000 + STALE_AFTER_US + 1).quality,
+            SyncQuality::Stale
+        );
+
+        filter.push(sample(UNIX_2025_US + 2 * FINE_JITTER_US, 40_000, 1_500));
+        let mapping = TimeMapping::from_filter(&filter, 2_000).unwrap();
+        assert_eq!(mapping.quality, SyncQuality::Coarse);
+        assert_eq!(mapping.offset_us, UNIX_2025_US);
+    }
+
+    #[test]
+    fn rtc_mapping_never_goes_stale() {
+        let mapping = TimeMapping::from_rtc(UNIX_2025_US, 1_000);
+        assert_eq!(mapping.utc_micros_at(1_000), UNIX_2025_US);
+        assert_eq!(mapping.aged(u64::MAX).quality, SyncQuality::Rtc);
+        assert!(!TimeMapping::UNSYNCHRONIZED.is_synchronized());
+    }
+}
@@ -0,0 +1,350 @@
+//! SNTP time service.
+//!
+//! Maintains a mapping from the monotonic clock (`embassy_time::Instant`) to
+//! UTC. Each sync round sends several requests to one server, keeps the
+//! minimum-delay sample and publishes the result through a `Watch`. Servers
+//! are tried in order; the last server that answered is preferred on the next
+//! round.
+//!
+//! An RTC can be plugged in by seeding the mapping at boot with
+//! [`SntpHandle::seed_from_rtc`] and writing network time back from a
+//! [`TimeReceiver`].
+
+pub mod filter;
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{debug, info, warn};
+use embassy_executor::Spawner;
+use embassy_futures::select::select;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_sync::watch;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use smoltcp::wire::DnsQueryType;
+
+use crate::config::NTP_SERVERS;
+
+pub use filter::{SampleFilter, SyncQuality, SyncSample, TimeMapping};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+#[derive(Debug, Clone, Copy)]
+pub struct SntpConfig {
+    /// Comma separated list of server host names, in order of preference.
+    pub servers: &'static str,
+    /// Interval between successful sync rounds.
+    pub poll_interval: Duration,
+    /// Interval before retrying after every server failed.
+    pub retry_interval: Duration,
+    /// Requests per sync round (capped at [`filter::MAX_SAMPLES`]).
+    pub samples: usize,
+    /// How long to wait for each response.
+    pub response_timeout: Duration,
+}
+
+impl Default for SntpConfig {
+    fn default() -> Self {
+        Self {
+            servers: NTP_SERVERS,
+            poll_interval: Duration::from_secs(1200),
+            retry_interval: Duration::from_secs(30),
+            samples: 4,
+            response_timeout: Duration::from_millis(1500),
+        }
+    }
+}
+
+const NTP_PORT: u16 = 123;
+const NTP_PACKET_LEN: usize = 48;
+/// LI = 0, VN = 4, Mode = 3 (client).
+const NTP_CLIENT_HEADER: u8 = 0x23;
+const NTP_MODE_SERVER: u8 = 4;
+const SAMPLE_SPACING: Duration = Duration::from_millis(250);
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static TIME_STATE: watch::Watch<CriticalSectionRawMutex, TimeMapping, 4> = watch::Watch::new();
+
+pub type TimeReceiver = watch::Receiver<'static, CriticalSectionRawMutex, TimeMapping, 4>;
+
+static SYNC_REQUEST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static SNTP_STARTED: AtomicBool = AtomicBool::new(false);
+
+// ============================================================================
+// SPAWN METHOD
+// ============================================================================
+
+pub fn spawn_sntp_service(
+    spawner: &Spawner,
+    stack: Stack<'static>,
+    config: SntpConfig,
+) -> SntpHandle {
+    if SNTP_STARTED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        panic!("SNTP service already started");
+    }
+
+    spawner
+        .spawn(sntp_task(stack, config))
+        .expect("spawn SNTP service failed");
+
+    SntpHandle { _priv: PhantomData }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn monotonic_us() -> u64 {
+    Instant::now().as_micros()
+}
+
+fn read_timestamp(packet: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&packet[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Build a client request. `cookie` goes into the transmit timestamp and is
+/// echoed back by the server as the originate timestamp.
+fn build_request(cookie: u64) -> [u8; NTP_PACKET_LEN] {
+    let mut packet = [0u8; NTP_PACKET_LEN];
+    packet[0] = NTP_CLIENT_HEADER;
+    packet[40..48].copy_from_slice(&cookie.to_be_bytes());
+    packet
+}
+
+/// Validate a server response and extract `(t2, t3)` in Unix microseconds.
+fn parse_response(packet: &[u8], cookie: u64) -> Option<(i64, i64)> {
+    if packet.len() < NTP_PACKET_LEN {
+        return None;
+    }
+    let mode = packet[0] & 0x07;
+    let leap = packet[0] >> 6;
+    let stratum = packet[1];
+    // Stratum 0 is a kiss-o'-death, leap 3 means the server is unsynchronized
+    if mode != NTP_MODE_SERVER || stratum == 0 || stratum > 15 || leap == 3 {
+        return None;
+    }
+    if read_timestamp(packet, 24) != cookie {
+        return None;
+    }
+    let t2 = filter::ntp_to_unix_micros(read_timestamp(packet, 32))?;
+    let t3 = filter::ntp_to_unix_micros(read_timestamp(packet, 40))?;
+    Some((t2, t3))
+}
+
+async fn exchange(
+    socket: &mut UdpSocket<'_>,
+    endpoint: IpEndpoint,
+    timeout: Duration,
+) -> Option<SyncSample> {
+    let t1 = monotonic_us();
+    // Any unique non-zero value works as the cookie
+    let cookie = t1 | 1;
+    socket
+        .send_to(&build_request(cookie), endpoint)
+        .await
+        .ok()?;
+
+    let mut buf = [0u8; NTP_PACKET_LEN];
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        let (len, meta) = with_timeout(remaining, socket.recv_from(&mut buf))
+            .await
+            .ok()?
+            .ok()?;
+        let t4 = monotonic_us();
+
+        if meta.endpoint != endpoint {
+            continue;
+        }
+        // Anything else is a late reply to an earlier request
+        if let Some((t2, t3)) = parse_response(&buf[..len], cookie) {
+            return SyncSample::from_timestamps(t1, t2, t3, t4);
+        }
+    }
+}
+
+/// Run one sync round against `server`, trying every resolved address.
+async fn sync_with_server(
+    stack: Stack<'static>,
+    socket: &mut UdpSocket<'_>,
+    server: &str,
+    config: &SntpConfig,
+    sample_filter: &mut SampleFilter,
+) -> Option<TimeMapping> {
+    let addrs = match stack.dns_query(server, DnsQueryType::A).await {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            warn!("SNTP: failed to resolve {}: {:?}", server, e);
+            return None;
+        }
+    };
+
+    let samples = config.samples.clamp(1, filter::MAX_SAMPLES);
+    for addr in addrs.iter() {
+        let endpoint = IpEndpoint::new(*addr, NTP_PORT);
+        sample_filter.clear();
+
+        for i in 0..samples {
+            if i != 0 {
+                Timer::after(SAMPLE_SPACING).await;
+            }
+            match exchange(socket, endpoint, config.response_timeout).await {
+                Some(sample) => {
+                    debug!("SNTP: sample {:?}", sample);
+                    sample_filter.push(sample);
+                }
+                None => debug!("SNTP: no valid reply from {}", server),
+            }
+        }
+
+        if let Some(mapping) = TimeMapping::from_filter(sample_filter, monotonic_us()) {
+            return Some(mapping);
+        }
+    }
+
+    None
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+async fn sntp_task(stack: Stack<'static>, config: SntpConfig) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buf = [0u8; 256];
+    let mut tx_buf = [0u8; 256];
+    let mut filter = SampleFilter::new();
+
+    let server_count = config
+        .servers
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .count();
+    if server_count == 0 {
+        warn!("SNTP: no servers configured");
+        return;
+    }
+
+    info!("SNTP: waiting for network");
+    stack.wait_config_up().await;
+
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
+    if let Err(e) = socket.bind(0) {
+        warn!("SNTP: failed to bind socket: {:?}", e);
+        return;
+    }
+
+    let mut preferred = 0;
+    loop {
+        let mut synced = None;
+        for attempt in 0..server_count {
+            let index = (preferred + attempt) % server_count;
+            let Some(server) = config
+                .servers
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .nth(index)
+            else {
+                continue;
+            };
+
+            if let Some(mapping) =
+                sync_with_server(stack, &mut socket, server, &config, &mut filter).await
+            {
+                info!(
+                    "SNTP: synced with {}: offset {} us, delay {} us, jitter {} us, {:?}",
+                    server, mapping.offset_us, mapping.delay_us, mapping.jitter_us, mapping.quality
+                );
+                preferred = index;
+                synced = Some(mapping);
+                break;
+            }
+            warn!("SNTP: server {} failed, trying next", server);
+        }
+
+        let wait = match synced {
+            Some(mapping) => {
+                TIME_STATE.sender().send(mapping);
+                config.poll_interval
+            }
+            None => {
+                warn!("SNTP: all servers failed");
+                if let Some(current) = TIME_STATE.try_get() {
+                    let aged = current.aged(monotonic_us());
+                    if aged != current {
+                        TIME_STATE.sender().send(aged);
+                    }
+                }
+                config.retry_interval
+            }
+        };
+
+        SYNC_REQUEST.reset();
+        select(Timer::after(wait), SYNC_REQUEST.wait()).await;
+    }
+}
+
+// ============================================================================
+// HANDLE
+// ============================================================================
+
+#[derive(Clone, Copy)]
+pub struct SntpHandle {
+    _priv: PhantomData<()>,
+}
+
+impl SntpHandle {
+    /// Start a sync round now instead of waiting for the poll interval.
+    pub fn request_sync(&self) {
+        SYNC_REQUEST.signal(());
+    }
+
+    pub fn receiver(&self) -> Option<TimeReceiver> {
+        TIME_STATE.receiver()
+    }
+
+    /// Current mapping, with quality re-evaluated for the current time.
+    pub fn mapping(&self) -> TimeMapping {
+        TIME_STATE
+            .try_get()
+            .map(|m| m.aged(monotonic_us()))
+            .unwrap_or(TimeMapping::UNSYNCHRONIZED)
+    }
+
+    /// UTC microseconds since the Unix epoch, if any time source is known.
+    pub fn now_utc_micros(&self) -> Option<i64> {
+        let mapping = self.mapping();
+        mapping
+            .is_synchronized()
+            .then(|| mapping.utc_micros_at(monotonic_us()))
+    }
+
+    /// Seed the mapping from an RTC reading. Ignored once a better source
+    /// (a network sync) is available.
+    pub fn seed_from_rtc(&self, utc_us: i64) {
+        if self.mapping().quality > SyncQuality::Rtc {
+            return;
+        }
+        TIME_STATE
+            .sender()
+            .send(TimeMapping::from_rtc(utc_us, monotonic_us()));
+    }
+}
@@ -0,0 +1,162 @@
+//! Small persistent values in the `storage` data partition.
+//!
+//! Values up to [`MAX_VALUE_LEN`] bytes are stored under one of
+//! [`MAX_KEYS`] keys. Each key owns two 4 KiB sectors that are written
+//! alternately (see [`record`]), so a value survives a power loss during
+//! its own update. Meant for settings that change rarely, like calibration;
+//! every store erases a sector.
+//!
+//! Requires [`crate::flash::init_flash`] and a data partition of subtype
+//! `undefined` in `partitions.csv`.
+
+pub mod record;
+
+use defmt::error;
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::partitions::{
+    read_partition_table, DataPartitionSubType, PartitionType, PARTITION_TABLE_MAX_LEN,
+};
+
+use crate::flash::lock_flash;
+
+pub use record::MAX_VALUE_LEN;
+use record::{Slot, RECORD_LEN};
+
+const SECTOR_SIZE: u32 = 4096;
+pub const MAX_KEYS: u8 = 8;
+
+/// Identifies one stored value, below [`MAX_KEYS`]. Binaries define their
+/// own keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct StorageKey(pub u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum StorageError {
+    NotFound,
+    InvalidKey,
+    TooLarge,
+    /// No `storage` partition, or it is too small for the key.
+    Partition,
+    Flash,
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+fn slot_offset(key: StorageKey, slot: Slot) -> u32 {
+    (key.0 as u32 * 2 + slot.index() as u32) * SECTOR_SIZE
+}
+
+/// Run `f` on the storage partition with the flash locked.
+async fn with_partition<R>(
+    f: impl FnOnce(&mut dyn PartitionIo) -> Result<R, StorageError>,
+) -> Result<R, StorageError> {
+    let mut flash = lock_flash().await;
+    let mut buffer = [0u8; PARTITION_TABLE_MAX_LEN];
+
+    let table = read_partition_table(&mut *flash, &mut buffer).map_err(|e| {
+        error!("Storage: failed to read partition table: {:?}", e);
+        StorageError::Partition
+    })?;
+    let entry = table
+        .find_partition(PartitionType::Data(DataPartitionSubType::Undefined))
+        .ok()
+        .flatten()
+        .ok_or(StorageError::Partition)?;
+    let mut region = entry.as_embedded_storage(&mut *flash);
+    f(&mut region)
+}
+
+/// The subset of a flash region the store needs.
+trait PartitionIo {
+    fn capacity(&self) -> usize;
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), StorageError>;
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), StorageError>;
+}
+
+impl<S: ReadStorage + Storage> PartitionIo for S {
+    fn capacity(&self) -> usize {
+        ReadStorage::capacity(self)
+    }
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), StorageError> {
+        ReadStorage::read(self, offset, bytes).map_err(|_| StorageError::Flash)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), StorageError> {
+        Storage::write(self, offset, bytes).map_err(|_| StorageError::Flash)
+    }
+}
+
+fn check_key(io: &dyn PartitionIo, key: StorageKey) -> Result<(), StorageError> {
+    if key.0 >= MAX_KEYS {
+        return Err(StorageError::InvalidKey);
+    }
+    if slot_offset(key, Slot::B) + SECTOR_SIZE > io.capacity() as u32 {
+        return Err(StorageError::Partition);
+    }
+    Ok(())
+}
+
+/// Current slot, its sequence number and its record.
+fn read_current(
+    io: &mut dyn PartitionIo,
+    key: StorageKey,
+) -> Result<Option<(Slot, u32, [u8; RECORD_LEN])>, StorageError> {
+    let mut records = [[0u8; RECORD_LEN]; 2];
+    let mut sequences = [None; 2];
+    for slot in [Slot::A, Slot::B] {
+        let bytes = &mut records[slot.index()];
+        io.read(slot_offset(key, slot), bytes)?;
+        sequences[slot.index()] = record::decode(key.0, bytes).map(|(sequence, _)| sequence);
+    }
+
+    Ok(record::current(sequences[0], sequences[1])
+        .map(|(slot, sequence)| (slot, sequence, records[slot.index()])))
+}
+
+// ============================================================================
+// API
+// ============================================================================
+
+/// Copy the value of `key` into `out`; returns its length.
+pub async fn load(key: StorageKey, out: &mut [u8]) -> Result<usize, StorageError> {
+    with_partition(|io| {
+        check_key(io, key)?;
+        let (_, _, bytes) = read_current(io, key)?.ok_or(StorageError::NotFound)?;
+        let (_, value) = record::decode(key.0, &bytes).ok_or(StorageError::NotFound)?;
+        let out = out.get_mut(..value.len()).ok_or(StorageError::TooLarge)?;
+        out.copy_from_slice(value);
+        Ok(value.len())
+    })
+    .await
+}
+
+pub async fn store(key: StorageKey, value: &[u8]) -> Result<(), StorageError> {
+    with_partition(|io| {
+        check_key(io, key)?;
+        let (slot, sequence) = match read_current(io, key)? {
+            Some((slot, sequence, _)) => (slot.other(), sequence.wrapping_add(1)),
+            None => (Slot::A, 0),
+        };
+
+        let mut bytes = [0u8; RECORD_LEN];
+        let len =
+            record::encode(key.0, sequence, value, &mut bytes).ok_or(StorageError::TooLarge)?;
+        io.write(slot_offset(key, slot), &bytes[..len])
+    })
+    .await
+}
+
+/// Forget the value of `key`.
+pub async fn remove(key: StorageKey) -> Result<(), StorageError> {
+    with_partition(|io| {
+        check_key(io, key)?;
+        for slot in [Slot::A, Slot::B] {
+            io.write(slot_offset(key, slot), &[0u8; record::HEADER_LEN])?;
+        }
+        Ok(())
+    })
+    .await
+}
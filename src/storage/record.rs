@@ -0,0 +1,103 @@
+//! Layout of one stored value and the choice between its two slots.
+//!
+//! Every key owns two flash sectors. A write goes to the slot not holding
+//! the current value, with the next sequence number, so a power loss in the
+//! middle of a write leaves the previous value readable.
+//!
+//! Slot layout (little-endian, [`RECORD_LEN`] bytes):
+//!
+//! | offset | size | field                                        |
+//! |--------|------|----------------------------------------------|
+//! | 0      | 4    | magic [`RECORD_MAGIC`]                       |
+//! | 4      | 1    | key                                          |
+//! | 5      | 1    | reserved, zero                               |
+//! | 6      | 2    | value length                                 |
+//! | 8      | 4    | sequence number                              |
+//! | 12     | 4    | CRC-32 (IEEE) of bytes 0..12 and the value   |
+//! | 16     | ...  | value                                        |
+
+use crate::crash::record::crc32;
+
+pub const RECORD_MAGIC: u32 = 0x5354_4B56;
+pub const HEADER_LEN: usize = 16;
+pub const RECORD_LEN: usize = 256;
+pub const MAX_VALUE_LEN: usize = RECORD_LEN - HEADER_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Slot {
+    pub const fn index(self) -> usize {
+        match self {
+            Slot::A => 0,
+            Slot::B => 1,
+        }
+    }
+
+    pub const fn other(self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+}
+
+/// Returns the record length, or `None` if `value` is too long.
+pub fn encode(key: u8, sequence: u32, value: &[u8], out: &mut [u8; RECORD_LEN]) -> Option<usize> {
+    if value.len() > MAX_VALUE_LEN {
+        return None;
+    }
+
+    out.fill(0);
+    out[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+    out[4] = key;
+    out[6..8].copy_from_slice(&(value.len() as u16).to_le_bytes());
+    out[8..12].copy_from_slice(&sequence.to_le_bytes());
+    let len = HEADER_LEN + value.len();
+    out[HEADER_LEN..len].copy_from_slice(value);
+    let crc = record_crc(out, value.len());
+    out[12..16].copy_from_slice(&crc.to_le_bytes());
+    Some(len)
+}
+
+/// Sequence number and value of a valid record of `key`.
+pub fn decode(key: u8, bytes: &[u8; RECORD_LEN]) -> Option<(u32, &[u8])> {
+    if read_u32(bytes, 0) != RECORD_MAGIC || bytes[4] != key {
+        return None;
+    }
+    let len = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+    if len > MAX_VALUE_LEN || read_u32(bytes, 12) != record_crc(bytes, len) {
+        return None;
+    }
+    Some((read_u32(bytes, 8), &bytes[HEADER_LEN..HEADER_LEN + len]))
+}
+
+/// Slot holding the current value, given the sequence numbers of the valid
+/// records in each slot. Sequence numbers wrap.
+pub fn current(a: Option<u32>, b: Option<u32>) -> Option<(Slot, u32)> {
+    match (a, b) {
+        (Some(a), Some(b)) if (b.wrapping_sub(a) as i32) > 0 => Some((Slot::B, b)),
+        (Some(a), _) => Some((Slot::A, a)),
+        (None, Some(b)) => Some((Slot::B, b)),
+        (None, None) => None,
+    }
+}
+
+fn record_crc(bytes: &[u8; RECORD_LEN], len: usize) -> u32 {
+    let mut covered = [0u8; RECORD_LEN - 4];
+    covered[..12].copy_from_slice(&bytes[..12]);
+    covered[12..12 + len].copy_from_slice(&bytes[HEADER_LEN..HEADER_LEN + len]);
+    crc32(&covered[..12 + len])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
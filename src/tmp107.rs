@@ -6,6 +6,9 @@ use esp_hal::Async;
 /// Maximum sensors in a TMP107 daisy chain (5-bit address space).
 pub const MAX_SENSORS: usize = 31;
 
+/// Address Initialize attempts, 100 ms apart, before [`Tmp107::init`] gives up
+const DISCOVERY_ATTEMPTS: u32 = 30;
+
 /// Sent before every command so sensors can auto-detect baud rate.
 const CALIBRATION_BYTE: u8 = 0x55;
 
@@ -82,6 +85,12 @@ pub enum Tmp107Error {
     NoSensorsFound,
 }
 
+/// Convert a temperature register value to millidegrees Celsius.
+pub fn raw_to_millicelsius(raw_value: u16) -> i32 {
+    let raw_units = i16::from_le_bytes(raw_value.to_le_bytes()) >> 2;
+    (i32::from(raw_units) * 1_000) / 64
+}
+
 pub struct Tmp107 {
     tx: UartTx<'static, Async>,
     rx: UartRx<'static, Async>,
@@ -93,7 +102,8 @@ impl Tmp107 {
     // -- Public API --
 
     /// Create driver, run Address Initialize, return configured driver
-    /// with discovered sensor count.
+    /// with discovered sensor count. Fails when no sensor answers within
+    /// `DISCOVERY_ATTEMPTS` tries.
     pub async fn init(
         tx: UartTx<'static, Async>,
         rx: UartRx<'static, Async>,
@@ -105,18 +115,18 @@ impl Tmp107 {
             config_register: DEFAULT_CONFIG_REGISTER,
         };
 
+        let mut attempt = 1;
         loop {
             match driver.discover_sensors().await {
-                Ok(()) => {
-                    break;
-                }
+                Ok(()) => return Ok(driver),
+                Err(e) if attempt >= DISCOVERY_ATTEMPTS => return Err(e),
                 Err(_) => {
                     info!("No sensors discovered");
+                    attempt += 1;
                     Timer::after_millis(100).await;
                 }
             }
         }
-        Ok(driver)
     }
 
     pub fn sensor_count(&self) -> u8 {
@@ -0,0 +1,183 @@
+//! Task watchdog.
+//!
+//! Critical tasks [`register`] with a deadline and call
+//! [`TaskWatch::check_in`] from their main loop. The supervisor task feeds
+//! the TIMG1 main watchdog (MWDT) only while every registered task is within
+//! its deadline. When one is not, it stores a crash report naming the task
+//! (see [`crate::crash`]) and stops feeding, so the hardware resets the chip.
+//!
+//! Waiting for another task to answer (`transact`, a bus transfer) is not
+//! idling: such waits run under `with_timeout`, shorter than the deadline,
+//! followed by a plain check-in, so a peer that never answers either trips
+//! the timeout or the deadline instead of being slept through.
+//!
+//! A task that blocks the executor (busy loop, blocking driver call) also
+//! starves the supervisor; the MWDT then resets the chip without a report,
+//! and only the `mwdt1` reset reason of the next boot tells what happened.
+
+pub mod registry;
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::peripherals::TIMG1;
+use esp_hal::timer::timg::{MwdtStage, MwdtStageAction, Wdt};
+
+use crate::net_error;
+
+pub use registry::{Overdue, Registry};
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// Maximum number of supervised tasks.
+pub const MAX_TASKS: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WatchdogConfig {
+    /// Hardware timeout; the chip resets this long after the last feed.
+    pub timeout: Duration,
+    /// How often the supervisor checks the tasks and feeds the MWDT.
+    pub poll_interval: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Handle of a supervised task. Dropping it ends the supervision, so a task
+/// that gives up or returns does not reset the chip.
+pub struct TaskWatch {
+    index: usize,
+}
+
+// ============================================================================
+// CHANNELS
+// ============================================================================
+
+static REGISTRY: Mutex<CriticalSectionRawMutex, RefCell<Registry<MAX_TASKS>>> =
+    Mutex::new(RefCell::new(Registry::new()));
+
+static WATCHDOG_STARTED: AtomicBool = AtomicBool::new(false);
+
+// ============================================================================
+// REGISTRATION
+// ============================================================================
+
+/// Supervise the calling task: it must check in at least every `deadline`.
+/// Tasks can register before the supervisor is spawned.
+pub fn register(name: &'static str, deadline: Duration) -> TaskWatch {
+    let now = Instant::now().as_millis();
+    let index = REGISTRY
+        .lock(|registry| {
+            registry
+                .borrow_mut()
+                .register(name, deadline.as_millis(), now)
+        })
+        .expect("Too many tasks registered with the watchdog");
+    TaskWatch { index }
+}
+
+impl TaskWatch {
+    pub fn check_in(&self) {
+        let now = Instant::now().as_millis();
+        REGISTRY.lock(|registry| registry.borrow_mut().check_in(self.index, now));
+    }
+
+    /// Wait for `fut`, checking in every `period` meanwhile. Only for events
+    /// that may legitimately never come (the next command, a timer); a
+    /// deadlock inside `fut` goes unnoticed, so never wrap a request that
+    /// expects an answer in it.
+    pub async fn idle<F: Future>(&self, period: Duration, fut: F) -> F::Output {
+        let mut fut = core::pin::pin!(fut);
+        loop {
+            self.check_in();
+            match select(fut.as_mut(), Timer::after(period)).await {
+                Either::First(output) => {
+                    self.check_in();
+                    return output;
+                }
+                Either::Second(()) => {}
+            }
+        }
+    }
+}
+
+impl Drop for TaskWatch {
+    fn drop(&mut self) {
+        REGISTRY.lock(|registry| registry.borrow_mut().unregister(self.index));
+    }
+}
+
+// ============================================================================
+// SPAWN METHOD
+// ============================================================================
+
+/// Enable the MWDT and start the supervisor.
+pub fn spawn_watchdog(spawner: &Spawner, wdt: Wdt<TIMG1<'static>>, config: WatchdogConfig) {
+    if WATCHDOG_STARTED
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        panic!("Watchdog already started");
+    }
+
+    spawner
+        .spawn(watchdog_task(wdt, config))
+        .expect("spawn watchdog failed");
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task]
+async fn watchdog_task(mut wdt: Wdt<TIMG1<'static>>, config: WatchdogConfig) {
+    wdt.set_timeout(
+        MwdtStage::Stage0,
+        esp_hal::time::Duration::from_millis(config.timeout.as_millis()),
+    );
+    wdt.set_stage_action(MwdtStage::Stage0, MwdtStageAction::ResetSystem);
+    wdt.enable();
+    info!(
+        "Watchdog: supervising {} tasks, {} ms timeout",
+        REGISTRY.lock(|registry| registry.borrow().len()),
+        config.timeout.as_millis()
+    );
+
+    loop {
+        let now = Instant::now().as_millis();
+        if let Some(overdue) = REGISTRY.lock(|registry| registry.borrow().overdue(now)) {
+            net_error!(
+                "Watchdog: task {} silent for {} ms (deadline {} ms), resetting",
+                overdue.name,
+                overdue.silent_ms,
+                overdue.deadline_ms
+            );
+            crate::crash::store_watchdog_report(
+                overdue.name,
+                overdue.silent_ms,
+                overdue.deadline_ms,
+            );
+            // Stop feeding and let the hardware reset the chip; keep `wdt`
+            // alive so dropping it cannot disable the timer
+            core::future::pending::<()>().await;
+        }
+
+        wdt.feed();
+        Timer::after(config.poll_interval).await;
+    }
+}
@@ -0,0 +1,127 @@
+//! Check-in bookkeeping for supervised tasks.
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    name: &'static str,
+    deadline_ms: u64,
+    last_check_in_ms: u64,
+}
+
+/// A task that has not checked in within its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overdue {
+    pub name: &'static str,
+    pub silent_ms: u64,
+    pub deadline_ms: u64,
+}
+
+/// Up to `N` supervised tasks, identified by the index [`Self::register`]
+/// hands out.
+pub struct Registry<const N: usize> {
+    entries: [Option<Entry>; N],
+}
+
+impl<const N: usize> Default for Registry<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Registry<N> {
+    pub const fn new() -> Self {
+        Self { entries: [None; N] }
+    }
+
+    /// Start supervising `name`; it counts as checked in at `now_ms`.
+    /// Returns `None` when all slots are taken.
+    pub fn register(&mut self, name: &'static str, deadline_ms: u64, now_ms: u64) -> Option<usize> {
+        let index = self.entries.iter().position(Option::is_none)?;
+        self.entries[index] = Some(Entry {
+            name,
+            deadline_ms,
+            last_check_in_ms: now_ms,
+        });
+        Some(index)
+    }
+
+    /// Stop supervising the task at `index`, freeing its slot.
+    pub fn unregister(&mut self, index: usize) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            *entry = None;
+        }
+    }
+
+    pub fn check_in(&mut self, index: usize, now_ms: u64) {
+        if let Some(Some(entry)) = self.entries.get_mut(index) {
+            entry.last_check_in_ms = now_ms;
+        }
+    }
+
+    /// The task that is the furthest past its deadline, if any.
+    pub fn overdue(&self, now_ms: u64) -> Option<Overdue> {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|entry| Overdue {
+                name: entry.name,
+                silent_ms: now_ms.saturating_sub(entry.last_check_in_ms),
+                deadline_ms: entry.deadline_ms,
+            })
+            .filter(|overdue| overdue.silent_ms > overdue.deadline_ms)
+            .max_by_key(|overdue| overdue.silent_ms - overdue.deadline_ms)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overdue_after_deadline() {
+        let mut registry = Registry::<4>::new();
+        let index = registry.register("task", 100, 0).unwrap();
+        assert_eq!(registry.overdue(100), None);
+        registry.check_in(index, 50);
+        assert_eq!(registry.overdue(150), None);
+        assert_eq!(
+            registry.overdue(151),
+            Some(Overdue {
+                name: "task",
+                silent_ms: 101,
+                deadline_ms: 100
+            })
+        );
+    }
+
+    #[test]
+    fn reports_the_furthest_past_its_deadline() {
+        let mut registry = Registry::<4>::new();
+        let short = registry.register("short", 100, 0).unwrap();
+        registry.register("long", 1000, 0);
+        assert_eq!(registry.overdue(1050).unwrap().name, "short");
+        registry.check_in(short, 1400);
+        assert_eq!(registry.overdue(1500).unwrap().name, "long");
+    }
+
+    #[test]
+    fn unregistered_task_is_not_overdue() {
+        let mut registry = Registry::<2>::new();
+        let first = registry.register("first", 100, 0).unwrap();
+        registry.register("second", 100, 0).unwrap();
+        assert_eq!(registry.register("third", 100, 0), None);
+
+        registry.unregister(first);
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.overdue(500).unwrap().name, "second");
+        // The slot is free again
+        assert_eq!(registry.register("third", 100, 500), Some(first));
+    }
+}
@@ -0,0 +1,183 @@
+//! Minimal mDNS (RFC 6762) responder and DNS-SD browser.
+//!
+//! The responder answers `A` queries for `<hostname>.local` and advertises
+//! DNS-SD services (`PTR`/`SRV`/`TXT`) on the multicast group 224.0.0.251:5353.
+//! The browser sends a one-shot `PTR` query for a service type (e.g.
+//! `_mqtt._tcp`) and returns the first instance that resolves to an IPv4
+//! address and port.
+//!
+//! Packets are parsed and written in [`packet`]; this module owns the
+//! sockets.
+
+pub mod packet;
+
+use core::net::Ipv4Addr;
+
+use defmt::{debug, info, warn};
+use embassy_executor::Spawner;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, IpEndpoint, Stack};
+use embassy_time::{with_timeout, Duration, Instant};
+
+use packet::{
+    build_announcement, build_query, build_response, parse_browse_response, BrowseState, MAX_PACKET,
+};
+
+pub use packet::{MdnsConfig, MdnsError, MdnsService, MdnsServiceAddress, MDNS_PORT};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+// ============================================================================
+// SPAWN METHOD
+// ============================================================================
+
+/// Spawn the responder on `stack`. Can be called once per network stack
+/// (e.g. both AP and STA in mixed mode).
+pub fn spawn_mdns_responder(spawner: &Spawner, stack: Stack<'static>, config: MdnsConfig) {
+    spawner
+        .spawn(mdns_responder_task(stack, config))
+        .expect("spawn mDNS responder failed");
+}
+
+// ============================================================================
+// TASK
+// ============================================================================
+
+#[embassy_executor::task(pool_size = 2)]
+async fn mdns_responder_task(stack: Stack<'static>, config: MdnsConfig) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buf = [0u8; 1024];
+    let mut tx_buf = [0u8; 1024];
+    let mut packet = [0u8; MAX_PACKET];
+    let mut response = [0u8; MAX_PACKET];
+
+    stack.wait_config_up().await;
+
+    if let Err(e) = stack.join_multicast_group(IpAddress::Ipv4(MDNS_GROUP)) {
+        warn!("mDNS: failed to join multicast group: {:?}", e);
+        return;
+    }
+
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
+    if let Err(e) = socket.bind(MDNS_PORT) {
+        warn!("mDNS: failed to bind port {}: {:?}", MDNS_PORT, e);
+        return;
+    }
+
+    info!("mDNS: responding as {}.local", config.hostname);
+
+    // Announce once so caches pick us up without waiting for a query.
+    if let Some(address) = local_address(&stack) {
+        if let Ok(len) = build_announcement(&config, address, &mut response) {
+            let _ = socket
+                .send_to(
+                    &response[..len],
+                    IpEndpoint::new(MDNS_GROUP.into(), MDNS_PORT),
+                )
+                .await;
+        }
+    }
+
+    loop {
+        let (len, meta) = match socket.recv_from(&mut packet).await {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("mDNS: receive error: {:?}", e);
+                continue;
+            }
+        };
+
+        let Some(address) = local_address(&stack) else {
+            continue;
+        };
+
+        let port = meta.endpoint.port;
+        let answer = match build_response(&config, address, &packet[..len], port, &mut response) {
+            Ok(Some(answer)) => answer,
+            Ok(None) => continue,
+            Err(e) => {
+                debug!("mDNS: ignoring packet: {:?}", e);
+                continue;
+            }
+        };
+
+        // Legacy unicast and QU questions are answered directly; everything
+        // else goes to the multicast group.
+        let destination = if answer.unicast {
+            meta.endpoint
+        } else {
+            IpEndpoint::new(MDNS_GROUP.into(), MDNS_PORT)
+        };
+
+        if let Err(e) = socket.send_to(&response[..answer.len], destination).await {
+            debug!("mDNS: send error: {:?}", e);
+        }
+    }
+}
+
+// ============================================================================
+// BROWSING
+// ============================================================================
+
+/// Query the local link for `service` (e.g. `_mqtt._tcp`) and return the
+/// first instance that resolves to an IPv4 address within `timeout`.
+///
+/// The query is sent from an ephemeral port, so responders reply with
+/// legacy unicast answers and no multicast membership is needed.
+pub async fn browse_service(
+    stack: Stack<'static>,
+    service: &str,
+    timeout: Duration,
+) -> Option<MdnsServiceAddress> {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buf = [0u8; 1024];
+    let mut tx_buf = [0u8; 512];
+    let mut packet = [0u8; MAX_PACKET];
+
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
+    if socket.bind(0).is_err() {
+        return None;
+    }
+
+    let query_len = build_query(service, &mut packet).ok()?;
+    info!("mDNS: browsing for {}.local", service);
+    socket
+        .send_to(
+            &packet[..query_len],
+            IpEndpoint::new(MDNS_GROUP.into(), MDNS_PORT),
+        )
+        .await
+        .ok()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found = BrowseState::new();
+    loop {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        let (len, _) = with_timeout(remaining, socket.recv_from(&mut packet))
+            .await
+            .ok()?
+            .ok()?;
+
+        if parse_browse_response(service, &packet[..len], &mut found).is_err() {
+            continue;
+        }
+
+        if let Some(result) = found.result() {
+            info!(
+                "mDNS: found {} at {}:{}",
+                service, result.address, result.port
+            );
+            return Some(result);
+        }
+    }
+}
+
+fn local_address(stack: &Stack<'static>) -> Option<Ipv4Addr> {
+    stack.config_v4().map(|c| c.address.address())
+}
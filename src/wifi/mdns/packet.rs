@@ -0,0 +1,818 @@
+//! mDNS packet parsing and writing, free of sockets.
+//!
+//! [`build_response`] answers a query for the responder and
+//! [`parse_browse_response`] collects the answers to a browse; names are
+//! matched case-insensitively and may be compressed.
+
+use core::net::Ipv4Addr;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+pub const MDNS_PORT: u16 = 5353;
+
+/// TTL advertised for all records (seconds).
+const RECORD_TTL_SECS: u32 = 120;
+/// TTL of records in legacy unicast responses (RFC 6762 §6.7).
+const LEGACY_TTL_SECS: u32 = 10;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+const CLASS_IN: u16 = 1;
+/// Top bit of the question class: unicast response requested (QU).
+const CLASS_UNICAST_RESPONSE: u16 = 0x8000;
+/// Top bit of the record class: cache flush (unique record).
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+
+const FLAGS_RESPONSE: u16 = 0x8400;
+const FLAGS_RESPONSE_MASK: u16 = 0x8000;
+
+const HEADER_LEN: usize = 12;
+pub const MAX_PACKET: usize = 512;
+const MAX_NAME: usize = 128;
+const MAX_LABEL_JUMPS: usize = 16;
+
+const SERVICES_META_QUERY: &str = "_services._dns-sd._udp.local";
+
+// ============================================================================
+// TYPES
+// ============================================================================
+
+/// A DNS-SD service advertised by the responder.
+///
+/// `service` is the service type without the domain, e.g. `_http._tcp`.
+/// `instance` is the human readable instance label, e.g. `railclock web`.
+#[derive(Debug, Clone, Copy)]
+pub struct MdnsService {
+    pub instance: &'static str,
+    pub service: &'static str,
+    pub port: u16,
+    pub txt: &'static [&'static str],
+}
+
+/// Responder configuration. `hostname` is the bare label (no `.local`).
+#[derive(Debug, Clone, Copy)]
+pub struct MdnsConfig {
+    pub hostname: &'static str,
+    pub services: &'static [MdnsService],
+}
+
+/// Result of a successful service browse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct MdnsServiceAddress {
+    pub address: Ipv4Addr,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum MdnsError {
+    Malformed,
+    BufferTooSmall,
+    NameTooLong,
+}
+
+// ============================================================================
+// NAMES
+// ============================================================================
+
+/// Decoded, lower-cased dotted domain name.
+struct Name {
+    buf: [u8; MAX_NAME],
+    len: usize,
+}
+
+impl Name {
+    const fn new() -> Self {
+        Self {
+            buf: [0; MAX_NAME],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn push_label(&mut self, label: &[u8]) -> Result<(), MdnsError> {
+        let extra = label.len() + usize::from(self.len != 0);
+        if self.len + extra > MAX_NAME {
+            return Err(MdnsError::NameTooLong);
+        }
+        if self.len != 0 {
+            self.buf[self.len] = b'.';
+            self.len += 1;
+        }
+        for byte in label {
+            self.buf[self.len] = byte.to_ascii_lowercase();
+            self.len += 1;
+        }
+        Ok(())
+    }
+
+    /// `true` if this name equals the concatenation of `parts` (case-insensitive).
+    fn matches(&self, parts: &[&str]) -> bool {
+        let mut offset = 0;
+        for part in parts {
+            let part = part.as_bytes();
+            if offset + part.len() > self.len {
+                return false;
+            }
+            if !self.buf[offset..offset + part.len()].eq_ignore_ascii_case(part) {
+                return false;
+            }
+            offset += part.len();
+        }
+        offset == self.len
+    }
+}
+
+/// Read a (possibly compressed) name at `offset`, returning the offset just
+/// past the name in the original position.
+fn read_name(msg: &[u8], mut offset: usize, name: &mut Name) -> Result<usize, MdnsError> {
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *msg.get(offset).ok_or(MdnsError::Malformed)? as usize;
+        if len == 0 {
+            return Ok(end.unwrap_or(offset + 1));
+        }
+
+        if len & 0xC0 == 0xC0 {
+            let low = *msg.get(offset + 1).ok_or(MdnsError::Malformed)? as usize;
+            if end.is_none() {
+                end = Some(offset + 2);
+            }
+            jumps += 1;
+            if jumps > MAX_LABEL_JUMPS {
+                return Err(MdnsError::Malformed);
+            }
+            offset = ((len & 0x3F) << 8) | low;
+            continue;
+        }
+
+        let label = msg
+            .get(offset + 1..offset + 1 + len)
+            .ok_or(MdnsError::Malformed)?;
+        name.push_label(label)?;
+        offset += 1 + len;
+    }
+}
+
+fn read_u16(msg: &[u8], offset: usize) -> Result<u16, MdnsError> {
+    msg.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(MdnsError::Malformed)
+}
+
+// ============================================================================
+// PACKET WRITER
+// ============================================================================
+
+struct PacketWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> PacketWriter<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    fn bytes(&mut self, data: &[u8]) -> Result<(), MdnsError> {
+        let end = self.len + data.len();
+        if end > self.buf.len() {
+            return Err(MdnsError::BufferTooSmall);
+        }
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+
+    fn u16(&mut self, value: u16) -> Result<(), MdnsError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> Result<(), MdnsError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    /// Write the labels of every dotted part in order, then the root label.
+    fn name(&mut self, parts: &[&str]) -> Result<(), MdnsError> {
+        for part in parts {
+            for label in part.split('.').filter(|l| !l.is_empty()) {
+                if label.len() > 63 {
+                    return Err(MdnsError::NameTooLong);
+                }
+                self.bytes(&[label.len() as u8])?;
+                self.bytes(label.as_bytes())?;
+            }
+        }
+        self.bytes(&[0])
+    }
+
+    fn header(
+        &mut self,
+        id: u16,
+        flags: u16,
+        questions: u16,
+        answers: u16,
+    ) -> Result<(), MdnsError> {
+        self.u16(id)?;
+        self.u16(flags)?;
+        self.u16(questions)?;
+        self.u16(answers)?;
+        self.u16(0)?;
+        self.u16(0)
+    }
+
+    fn set_answer_count(&mut self, answers: u16) {
+        self.buf[6..8].copy_from_slice(&answers.to_be_bytes());
+    }
+
+    /// Write a record header followed by a length-prefixed rdata produced by `rdata`.
+    fn record(
+        &mut self,
+        name: &[&str],
+        rtype: u16,
+        class: u16,
+        ttl: u32,
+        rdata: impl FnOnce(&mut Self) -> Result<(), MdnsError>,
+    ) -> Result<(), MdnsError> {
+        self.name(name)?;
+        self.u16(rtype)?;
+        self.u16(class)?;
+        self.u32(ttl)?;
+        let length_at = self.len;
+        self.u16(0)?;
+        rdata(self)?;
+        let rdlen = (self.len - length_at - 2) as u16;
+        self.buf[length_at..length_at + 2].copy_from_slice(&rdlen.to_be_bytes());
+        Ok(())
+    }
+}
+
+// ============================================================================
+// RESPONDER
+// ============================================================================
+
+/// A response written by [`build_response`].
+pub struct Answer {
+    pub len: usize,
+    /// Send to the querier rather than the multicast group
+    pub unicast: bool,
+}
+
+/// Records selected for a response, collected before writing so each record
+/// is emitted at most once.
+#[derive(Default)]
+struct Selection {
+    host_a: bool,
+    services_meta: bool,
+    /// Bit n: answer PTR for services[n].
+    ptr: u32,
+    /// Bit n: answer SRV + TXT for services[n].
+    srv_txt: u32,
+    unicast: bool,
+}
+
+impl Selection {
+    fn is_empty(&self) -> bool {
+        !self.host_a && !self.services_meta && self.ptr == 0 && self.srv_txt == 0
+    }
+}
+
+/// The query a legacy unicast response answers, whose ID and questions it
+/// repeats.
+struct LegacyQuery<'a> {
+    id: u16,
+    count: u16,
+    /// The question section as received
+    questions: &'a [u8],
+}
+
+/// Answer `query`, received from `source_port`, into `out`; `None` when it
+/// asks for nothing of ours.
+pub fn build_response(
+    config: &MdnsConfig,
+    address: Ipv4Addr,
+    query: &[u8],
+    source_port: u16,
+    out: &mut [u8],
+) -> Result<Option<Answer>, MdnsError> {
+    if query.len() < HEADER_LEN {
+        return Err(MdnsError::Malformed);
+    }
+
+    let id = read_u16(query, 0)?;
+    let flags = read_u16(query, 2)?;
+    if flags & FLAGS_RESPONSE_MASK != 0 {
+        return Ok(None);
+    }
+
+    let questions = read_u16(query, 4)?;
+    let mut selection = Selection::default();
+    let mut offset = HEADER_LEN;
+
+    for _ in 0..questions {
+        let mut name = Name::new();
+        offset = read_name(query, offset, &mut name)?;
+        let qtype = read_u16(query, offset)?;
+        let qclass = read_u16(query, offset + 2)?;
+        offset += 4;
+
+        if qclass & !CLASS_UNICAST_RESPONSE != CLASS_IN {
+            continue;
+        }
+        let before = selection.is_empty();
+        select_answers(config, &name, qtype, &mut selection);
+        if before && !selection.is_empty() && qclass & CLASS_UNICAST_RESPONSE != 0 {
+            selection.unicast = true;
+        }
+    }
+
+    if selection.is_empty() {
+        return Ok(None);
+    }
+
+    // A query from a port other than 5353 is a plain DNS resolver's: it gets
+    // its ID and questions back (RFC 6762 §6.7). Other responses carry ID 0.
+    let legacy = (source_port != MDNS_PORT).then(|| LegacyQuery {
+        id,
+        count: questions,
+        questions: &query[HEADER_LEN..offset],
+    });
+    let unicast = selection.unicast || legacy.is_some();
+    let len = write_selection(config, address, &selection, legacy.as_ref(), out)?;
+    Ok(Some(Answer { len, unicast }))
+}
+
+fn select_answers(config: &MdnsConfig, name: &Name, qtype: u16, selection: &mut Selection) {
+    let any = qtype == TYPE_ANY;
+
+    if (qtype == TYPE_A || any) && name.matches(&[config.hostname, ".local"]) {
+        selection.host_a = true;
+    }
+
+    if (qtype == TYPE_PTR || any) && name.matches(&[SERVICES_META_QUERY]) {
+        selection.services_meta = true;
+    }
+
+    for (index, service) in config.services.iter().enumerate().take(32) {
+        let bit = 1u32 << index;
+        if (qtype == TYPE_PTR || any) && name.matches(&[service.service, ".local"]) {
+            selection.ptr |= bit;
+            selection.srv_txt |= bit;
+            selection.host_a = true;
+        }
+        let instance = [service.instance, ".", service.service, ".local"];
+        if (qtype == TYPE_SRV || qtype == TYPE_TXT || any) && name.matches(&instance) {
+            selection.srv_txt |= bit;
+            selection.host_a = true;
+        }
+    }
+}
+
+fn write_selection(
+    config: &MdnsConfig,
+    address: Ipv4Addr,
+    selection: &Selection,
+    legacy: Option<&LegacyQuery>,
+    out: &mut [u8],
+) -> Result<usize, MdnsError> {
+    let mut w = PacketWriter::new(out);
+    match legacy {
+        Some(query) => {
+            w.header(query.id, FLAGS_RESPONSE, query.count, 0)?;
+            // Copied at the same offset, so compression pointers still hold
+            w.bytes(query.questions)?;
+        }
+        None => w.header(0, FLAGS_RESPONSE, 0, 0)?,
+    }
+    // A plain resolver would read the cache-flush bit as part of the class
+    let (unique, ttl) = match legacy {
+        Some(_) => (CLASS_IN, LEGACY_TTL_SECS),
+        None => (CLASS_IN | CLASS_CACHE_FLUSH, RECORD_TTL_SECS),
+    };
+    let mut answers = 0u16;
+
+    if selection.services_meta {
+        for service in config.services {
+            w.record(&[SERVICES_META_QUERY], TYPE_PTR, CLASS_IN, ttl, |w| {
+                w.name(&[service.service, ".local"])
+            })?;
+            answers += 1;
+        }
+    }
+
+    for (index, service) in config.services.iter().enumerate().take(32) {
+        let bit = 1u32 << index;
+        let instance = [service.instance, ".", service.service, ".local"];
+
+        if selection.ptr & bit != 0 {
+            w.record(&[service.service, ".local"], TYPE_PTR, CLASS_IN, ttl, |w| {
+                w.name(&instance)
+            })?;
+            answers += 1;
+        }
+
+        if selection.srv_txt & bit != 0 {
+            w.record(&instance, TYPE_SRV, unique, ttl, |w| {
+                w.u16(0)?;
+                w.u16(0)?;
+                w.u16(service.port)?;
+                w.name(&[config.hostname, ".local"])
+            })?;
+            w.record(&instance, TYPE_TXT, unique, ttl, |w| {
+                if service.txt.is_empty() {
+                    return w.bytes(&[0]);
+                }
+                for entry in service.txt {
+                    if entry.len() > 255 {
+                        return Err(MdnsError::NameTooLong);
+                    }
+                    w.bytes(&[entry.len() as u8])?;
+                    w.bytes(entry.as_bytes())?;
+                }
+                Ok(())
+            })?;
+            answers += 2;
+        }
+    }
+
+    if selection.host_a {
+        w.record(&[config.hostname, ".local"], TYPE_A, unique, ttl, |w| {
+            w.bytes(&address.octets())
+        })?;
+        answers += 1;
+    }
+
+    w.set_answer_count(answers);
+    Ok(w.len)
+}
+
+/// Unsolicited multicast response advertising the host and every service.
+pub fn build_announcement(
+    config: &MdnsConfig,
+    address: Ipv4Addr,
+    out: &mut [u8],
+) -> Result<usize, MdnsError> {
+    let all = if config.services.len() >= 32 {
+        u32::MAX
+    } else {
+        (1u32 << config.services.len()) - 1
+    };
+    let selection = Selection {
+        host_a: true,
+        services_meta: false,
+        ptr: all,
+        srv_txt: all,
+        unicast: false,
+    };
+    write_selection(config, address, &selection, None, out)
+}
+
+// ============================================================================
+// BROWSER
+// ============================================================================
+
+/// `PTR` query for `service` in `.local`.
+pub fn build_query(service: &str, out: &mut [u8]) -> Result<usize, MdnsError> {
+    let mut w = PacketWriter::new(out);
+    // Non-zero ID marks this as a legacy unicast query.
+    w.header(0x4D51, 0, 1, 0)?;
+    w.name(&[service, ".local"])?;
+    w.u16(TYPE_PTR)?;
+    w.u16(CLASS_IN)?;
+    Ok(w.len)
+}
+
+/// Partial results gathered across (possibly several) response packets.
+pub struct BrowseState {
+    instance: Name,
+    target: Name,
+    port: Option<u16>,
+    address: Option<Ipv4Addr>,
+}
+
+impl BrowseState {
+    pub const fn new() -> Self {
+        Self {
+            instance: Name::new(),
+            target: Name::new(),
+            port: None,
+            address: None,
+        }
+    }
+
+    pub fn result(&self) -> Option<MdnsServiceAddress> {
+        Some(MdnsServiceAddress {
+            address: self.address?,
+            port: self.port?,
+        })
+    }
+}
+
+/// Gather the records of a response to a browse for `service` into `state`.
+pub fn parse_browse_response(
+    service: &str,
+    msg: &[u8],
+    state: &mut BrowseState,
+) -> Result<(), MdnsError> {
+    if msg.len() < HEADER_LEN {
+        return Err(MdnsError::Malformed);
+    }
+    if read_u16(msg, 2)? & FLAGS_RESPONSE_MASK == 0 {
+        return Ok(());
+    }
+
+    let questions = read_u16(msg, 4)?;
+    let records =
+        read_u16(msg, 6)? as usize + read_u16(msg, 8)? as usize + read_u16(msg, 10)? as usize;
+
+    let mut offset = HEADER_LEN;
+    for _ in 0..questions {
+        let mut name = Name::new();
+        offset = read_name(msg, offset, &mut name)? + 4;
+    }
+
+    // Records may arrive in any order; remember SRV targets and A records
+    // and match them up at the end.
+    let mut a_records: [(Name, Ipv4Addr); 2] = [
+        (Name::new(), Ipv4Addr::UNSPECIFIED),
+        (Name::new(), Ipv4Addr::UNSPECIFIED),
+    ];
+    let mut a_count = 0;
+
+    for _ in 0..records {
+        let mut name = Name::new();
+        offset = read_name(msg, offset, &mut name)?;
+        let rtype = read_u16(msg, offset)?;
+        let rdlen = read_u16(msg, offset + 8)? as usize;
+        let rdata = offset + 10;
+        if rdata + rdlen > msg.len() {
+            return Err(MdnsError::Malformed);
+        }
+
+        match rtype {
+            TYPE_PTR if name.matches(&[service, ".local"]) && state.instance.len == 0 => {
+                read_name(msg, rdata, &mut state.instance)?;
+            }
+            TYPE_SRV if state.instance.len == 0 || name.as_bytes() == state.instance.as_bytes() => {
+                state.port = Some(read_u16(msg, rdata + 4)?);
+                state.target = Name::new();
+                read_name(msg, rdata + 6, &mut state.target)?;
+            }
+            TYPE_A if rdlen == 4 && a_count < a_records.len() => {
+                let b = &msg[rdata..rdata + 4];
+                a_records[a_count] = (name, Ipv4Addr::new(b[0], b[1], b[2], b[3]));
+                a_count += 1;
+            }
+            _ => {}
+        }
+
+        offset = rdata + rdlen;
+    }
+
+    for (name, address) in a_records.iter().take(a_count) {
+        if state.target.len != 0 && name.as_bytes() == state.target.as_bytes() {
+            state.address = Some(*address);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+    const CONFIG: MdnsConfig = MdnsConfig {
+        hostname: "stand",
+        services: &[MdnsService {
+            instance: "stand mqtt",
+            service: "_mqtt._tcp",
+            port: 1883,
+            txt: &["v=1"],
+        }],
+    };
+    const LEGACY_PORT: u16 = 49152;
+
+    fn name() -> Name {
+        Name::new()
+    }
+
+    /// Query with one question per `(name, type, class)`.
+    fn query(id: u16, questions: &[(&str, u16, u16)]) -> Vec<u8> {
+        let mut buf = [0; MAX_PACKET];
+        let mut w = PacketWriter::new(&mut buf);
+        w.header(id, 0, questions.len() as u16, 0).unwrap();
+        for &(name, qtype, qclass) in questions {
+            w.name(&[name]).unwrap();
+            w.u16(qtype).unwrap();
+            w.u16(qclass).unwrap();
+        }
+        let len = w.len;
+        buf[..len].to_vec()
+    }
+
+    fn respond(query: &[u8], source_port: u16) -> Option<(Vec<u8>, bool)> {
+        let mut out = [0; MAX_PACKET];
+        let answer = build_response(&CONFIG, ADDRESS, query, source_port, &mut out).unwrap()?;
+        Some((out[..answer.len].to_vec(), answer.unicast))
+    }
+
+    /// Type, class and TTL of each answer of `response`.
+    fn records(response: &[u8]) -> Vec<(u16, u16, u32)> {
+        let mut offset = HEADER_LEN;
+        for _ in 0..read_u16(response, 4).unwrap() {
+            offset = read_name(response, offset, &mut Name::new()).unwrap() + 4;
+        }
+        let mut records = Vec::new();
+        for _ in 0..read_u16(response, 6).unwrap() {
+            offset = read_name(response, offset, &mut Name::new()).unwrap();
+            let rtype = read_u16(response, offset).unwrap();
+            let class = read_u16(response, offset + 2).unwrap();
+            let ttl_high = read_u16(response, offset + 4).unwrap() as u32;
+            let ttl = (ttl_high << 16) | read_u16(response, offset + 6).unwrap() as u32;
+            let rdlen = read_u16(response, offset + 8).unwrap() as usize;
+            records.push((rtype, class, ttl));
+            offset += 10 + rdlen;
+        }
+        assert_eq!(offset, response.len());
+        records
+    }
+
+    #[test]
+    fn reads_compressed_names() {
+        // "stand.local" at 12, then "mqtt" + pointer to it
+        let mut msg = [0; HEADER_LEN].to_vec();
+        msg.extend_from_slice(b"\x05Stand\x05local\x00\x04mqtt\xC0\x0C");
+        let mut name = Name::new();
+        assert_eq!(read_name(&msg, 25, &mut name), Ok(32));
+        assert_eq!(name.as_bytes(), b"mqtt.stand.local");
+        assert!(name.matches(&["MQTT.", "stand", ".local"]));
+    }
+
+    #[test]
+    fn rejects_truncated_names() {
+        let mut msg = [0; HEADER_LEN].to_vec();
+        msg.extend_from_slice(b"\x05sta");
+        assert_eq!(
+            read_name(&msg, HEADER_LEN, &mut Name::new()),
+            Err(MdnsError::Malformed)
+        );
+        // Missing root label, and a pointer cut in half
+        let msg = b"\x05stand";
+        assert_eq!(read_name(msg, 0, &mut name()), Err(MdnsError::Malformed));
+        let msg = b"\x05stand\xC0";
+        assert_eq!(read_name(msg, 0, &mut name()), Err(MdnsError::Malformed));
+    }
+
+    #[test]
+    fn rejects_pointer_loops() {
+        let msg = b"\xC0\x00";
+        assert_eq!(read_name(msg, 0, &mut name()), Err(MdnsError::Malformed));
+        let msg = b"\x01a\xC0\x04\x01b\xC0\x00";
+        assert_eq!(read_name(msg, 0, &mut name()), Err(MdnsError::Malformed));
+        // Past the end
+        let msg = b"\xC0\x40";
+        assert_eq!(read_name(msg, 0, &mut name()), Err(MdnsError::Malformed));
+    }
+
+    #[test]
+    fn rejects_long_names() {
+        let mut msg = Vec::new();
+        for _ in 0..3 {
+            msg.push(63);
+            msg.extend_from_slice(&[b'a'; 63]);
+        }
+        msg.push(0);
+        assert_eq!(
+            read_name(&msg, 0, &mut Name::new()),
+            Err(MdnsError::NameTooLong)
+        );
+
+        let mut buf = [0; MAX_PACKET];
+        let label = "a".repeat(64);
+        let result = PacketWriter::new(&mut buf).name(&[&label, ".local"]);
+        assert_eq!(result, Err(MdnsError::NameTooLong));
+    }
+
+    #[test]
+    fn rejects_truncated_queries() {
+        let full = query(0, &[("stand.local", TYPE_A, CLASS_IN)]);
+        let mut out = [0; MAX_PACKET];
+        for len in [0, HEADER_LEN - 1, HEADER_LEN + 3, full.len() - 1] {
+            let result = build_response(&CONFIG, ADDRESS, &full[..len], MDNS_PORT, &mut out);
+            assert_eq!(result.err(), Some(MdnsError::Malformed), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn answers_host_query_on_the_group() {
+        let (response, unicast) =
+            respond(&query(0, &[("Stand.local", TYPE_A, CLASS_IN)]), MDNS_PORT).unwrap();
+        assert!(!unicast);
+        assert_eq!(read_u16(&response, 0), Ok(0));
+        assert_eq!(read_u16(&response, 2), Ok(FLAGS_RESPONSE));
+        assert_eq!(read_u16(&response, 4), Ok(0));
+        let cache_flush = CLASS_IN | CLASS_CACHE_FLUSH;
+        assert_eq!(records(&response), [(TYPE_A, cache_flush, RECORD_TTL_SECS)]);
+        assert_eq!(&response[response.len() - 4..], &ADDRESS.octets());
+    }
+
+    #[test]
+    fn multicast_response_does_not_echo_the_id() {
+        let query = query(0x1234, &[("stand.local", TYPE_A, CLASS_IN)]);
+        let (response, _) = respond(&query, MDNS_PORT).unwrap();
+        assert_eq!(read_u16(&response, 0), Ok(0));
+    }
+
+    #[test]
+    fn answers_qu_question_directly() {
+        let qu = CLASS_IN | CLASS_UNICAST_RESPONSE;
+        let (_, unicast) = respond(&query(0, &[("stand.local", TYPE_A, qu)]), MDNS_PORT).unwrap();
+        assert!(unicast);
+    }
+
+    #[test]
+    fn legacy_unicast_repeats_the_question() {
+        let query = query(0x1234, &[("stand.local", TYPE_A, CLASS_IN)]);
+        let (response, unicast) = respond(&query, LEGACY_PORT).unwrap();
+        assert!(unicast);
+        assert_eq!(read_u16(&response, 0), Ok(0x1234));
+        assert_eq!(read_u16(&response, 4), Ok(1));
+        assert_eq!(&response[HEADER_LEN..query.len()], &query[HEADER_LEN..]);
+        // No cache-flush bit, and a short TTL
+        assert_eq!(records(&response), [(TYPE_A, CLASS_IN, LEGACY_TTL_SECS)]);
+    }
+
+    #[test]
+    fn ignores_other_names_and_responses() {
+        let other = query(0, &[("other.local", TYPE_A, CLASS_IN)]);
+        assert!(respond(&other, MDNS_PORT).is_none());
+
+        let mut response = query(0, &[("stand.local", TYPE_A, CLASS_IN)]);
+        response[2] = 0x84;
+        assert!(respond(&response, MDNS_PORT).is_none());
+    }
+
+    #[test]
+    fn answers_service_browse() {
+        let query = query(0, &[("_mqtt._tcp.local", TYPE_PTR, CLASS_IN)]);
+        let (response, _) = respond(&query, MDNS_PORT).unwrap();
+        let cache_flush = CLASS_IN | CLASS_CACHE_FLUSH;
+        assert_eq!(
+            records(&response),
+            [
+                (TYPE_PTR, CLASS_IN, RECORD_TTL_SECS),
+                (TYPE_SRV, cache_flush, RECORD_TTL_SECS),
+                (TYPE_TXT, cache_flush, RECORD_TTL_SECS),
+                (TYPE_A, cache_flush, RECORD_TTL_SECS),
+            ]
+        );
+    }
+
+    #[test]
+    fn browse_resolves_the_legacy_response() {
+        let mut buf = [0; MAX_PACKET];
+        let len = build_query("_mqtt._tcp", &mut buf).unwrap();
+        let (response, _) = respond(&buf[..len], LEGACY_PORT).unwrap();
+
+        let mut state = BrowseState::new();
+        parse_browse_response("_mqtt._tcp", &response, &mut state).unwrap();
+        assert_eq!(
+            state.result(),
+            Some(MdnsServiceAddress {
+                address: ADDRESS,
+                port: 1883,
+            })
+        );
+
+        // Cut inside the last record
+        let mut state = BrowseState::new();
+        let truncated = &response[..response.len() - 2];
+        let result = parse_browse_response("_mqtt._tcp", truncated, &mut state);
+        assert_eq!(result, Err(MdnsError::Malformed));
+        assert_eq!(state.result(), None);
+    }
+
+    #[test]
+    fn announcement_fits_and_lists_everything() {
+        let mut out = [0; MAX_PACKET];
+        let len = build_announcement(&CONFIG, ADDRESS, &mut out).unwrap();
+        assert_eq!(read_u16(&out, 0), Ok(0));
+        assert_eq!(records(&out[..len]).len(), 4);
+
+        let mut small = [0; 40];
+        let result = build_announcement(&CONFIG, ADDRESS, &mut small);
+        assert_eq!(result, Err(MdnsError::BufferTooSmall));
+    }
+}
@@ -1,3 +1,5 @@
+pub mod mdns;
+
 use core::net::Ipv4Addr;
 
 use defmt::info;